@@ -8,10 +8,14 @@
 // but WITHOUT ANY WARRANTY; without even the implied warranty of
 // MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
 
+mod error;
+mod routes;
+
 use axum::{Json, Router, response::Html, routing::get};
 use listenfd::ListenFd;
 use shared::UserDto;
-use tracing::info;
+use std::time::Duration;
+use tracing::{error, info, warn};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 #[tokio::main]
@@ -23,9 +27,22 @@ async fn main() {
         )
         .with(tracing_subscriber::fmt::layer())
         .init();
+
+    db::init_db()
+        .await
+        .expect("failed to connect to the database");
+
+    tokio::spawn(send_queued_email());
+    tokio::spawn(send_queued_push());
+    tokio::spawn(send_queued_whatsapp());
+    tokio::spawn(remind_event_non_responders());
+    tokio::spawn(expire_lapsed_subscriptions());
+    tokio::spawn(bill_current_terms());
+
     let app = Router::new()
         .route("/", get(get_init))
-        .route("/user", get(get_user));
+        .route("/user", get(get_user))
+        .merge(routes::router());
 
     let mut listenfd = ListenFd::from_env();
     // let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
@@ -46,6 +63,108 @@ async fn main() {
     axum::serve(listener, app).await.unwrap();
 }
 
+/// Send queued announcement email and read bounce reports every few seconds
+async fn send_queued_email() {
+    let mailer = match domain::email::mailer() {
+        Ok(mailer) => mailer,
+        Err(err) => {
+            error!("email is disabled: {err}");
+            return;
+        }
+    };
+    let mut interval = tokio::time::interval(Duration::from_secs(15));
+    loop {
+        interval.tick().await;
+        if let Err(err) = domain::email::run_queue(db::get_db(), mailer, 100).await {
+            warn!("email queue: {err}");
+        }
+    }
+}
+
+/// Send queued push messages every few seconds, dropping expired subscriptions
+async fn send_queued_push() {
+    let client = match domain::push::push_client() {
+        Ok(client) => client,
+        Err(err) => {
+            error!("push is disabled: {err}");
+            return;
+        }
+    };
+    let mut interval = tokio::time::interval(Duration::from_secs(15));
+    loop {
+        interval.tick().await;
+        if let Err(err) = domain::push::run_queue(db::get_db(), client, 100).await {
+            warn!("push queue: {err}");
+        }
+    }
+}
+
+/// Send queued WhatsApp messages every few seconds
+async fn send_queued_whatsapp() {
+    let client = match domain::whatsapp::whatsapp_client() {
+        Ok(client) => client,
+        Err(err) => {
+            error!("WhatsApp is disabled: {err}");
+            return;
+        }
+    };
+    let mut interval = tokio::time::interval(Duration::from_secs(15));
+    loop {
+        interval.tick().await;
+        if let Err(err) = domain::whatsapp::run_queue(db::get_db(), client, 100).await {
+            warn!("WhatsApp queue: {err}");
+        }
+    }
+}
+
+/// Remind people who haven't replied to events whose deadline is near
+async fn remind_event_non_responders() {
+    let mut interval = tokio::time::interval(Duration::from_secs(60 * 60));
+    loop {
+        interval.tick().await;
+        match domain::event::EventService
+            .send_rsvp_reminders(db::get_db())
+            .await
+        {
+            Ok(0) => {}
+            Ok(reminded) => info!("reminded {reminded} people to reply to events"),
+            Err(err) => warn!("event reminders: {err}"),
+        }
+    }
+}
+
+/// Move schools whose grace period after expiry has passed to expired
+async fn expire_lapsed_subscriptions() {
+    let mut interval = tokio::time::interval(Duration::from_secs(60 * 60));
+    loop {
+        interval.tick().await;
+        match domain::subscription::SubscriptionService
+            .expire_lapsed(db::get_db())
+            .await
+        {
+            Ok(0) => {}
+            Ok(expired) => info!("{expired} school subscriptions expired"),
+            Err(err) => warn!("subscription expiry: {err}"),
+        }
+    }
+}
+
+/// Raise each billable school's platform invoice once its term is current
+async fn bill_current_terms() {
+    let mut interval = tokio::time::interval(Duration::from_secs(60 * 60));
+    loop {
+        interval.tick().await;
+        match domain::billing::BillingService
+            .bill_current_terms(db::get_db())
+            .await
+        {
+            Ok(0) => {}
+            Ok(billed) => info!("billed {billed} schools for the current term"),
+            Err(err) => warn!("platform billing: {err}"),
+        }
+    }
+}
+
 async fn get_user() -> Json<UserDto> {
     info!("get_user called");
     Json(UserDto {
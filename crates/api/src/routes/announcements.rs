@@ -0,0 +1,45 @@
+use axum::{
+    Json, Router,
+    extract::Path,
+    http::HeaderMap,
+    routing::{get, post},
+};
+use domain::announcement::AnnouncementService;
+use shared::helpers::api_responses::AnnouncementDeliveryReport;
+
+use super::auth::signed_in;
+use crate::error::ApiResult;
+
+pub fn router() -> Router {
+    Router::new()
+        .route("/announcements/{announcement_id}/publish", post(publish))
+        .route(
+            "/announcements/{announcement_id}/deliveries",
+            get(deliveries),
+        )
+}
+
+/// Publish the announcement and queue its messages on every channel it asks
+/// for; only the school's admins may
+async fn publish(
+    headers: HeaderMap,
+    Path(announcement_id): Path<String>,
+) -> ApiResult<Json<AnnouncementDeliveryReport>> {
+    let user = signed_in(&headers, None).await?;
+    let report = AnnouncementService
+        .publish(db::get_db(), &user, announcement_id)
+        .await?;
+    Ok(Json(report))
+}
+
+/// Delivery counts, spend and failures per channel, for the school's admins
+async fn deliveries(
+    headers: HeaderMap,
+    Path(announcement_id): Path<String>,
+) -> ApiResult<Json<AnnouncementDeliveryReport>> {
+    let user = signed_in(&headers, None).await?;
+    let report = AnnouncementService
+        .report(db::get_db(), &user, announcement_id)
+        .await?;
+    Ok(Json(report))
+}
@@ -0,0 +1,281 @@
+use axum::{
+    Json, Router,
+    body::Bytes,
+    extract::{Path, Query},
+    http::{HeaderMap, header},
+    response::{IntoResponse, Response},
+    routing::{get, post, put},
+};
+use domain::{
+    auth::{STAFF, ensure_role},
+    grading::GradingService,
+    ranking::RankingService,
+    report_card::ReportCardService,
+    scoring::ScoreService,
+    storage::file_store,
+    transcript::TranscriptService,
+    weighting::WeightingService,
+};
+use serde::Deserialize;
+use shared::{
+    helpers::{
+        api_responses::{
+            AssessmentMissingScores, ClassRankingEntry, GradingSchemeWithBands, Transcript,
+        },
+        create_update_dtos::{EnterScoreDto, SaveGradingSchemeDto, SetAssessmentWeightingDto},
+        query_filters::RankingMethod,
+    },
+    models::{
+        ClassLevel, UserType,
+        assessments_nd_grading::{
+            AssessmentWeight, AssessmentWeighting, GradingSchemeVersion, ReportCard, StudentScore,
+        },
+    },
+};
+use surrealdb_types::{Decimal, RecordId};
+
+use super::auth::signed_in;
+use crate::error::ApiResult;
+
+pub fn router() -> Router {
+    Router::new()
+        .route("/assessments/{assessment_id}/unlock", post(unlock_scores))
+        .route(
+            "/assessments/{assessment_id}/scores/{student_id}/override",
+            put(override_score),
+        )
+        .route(
+            "/classes/{class_id}/terms/{term_id}/missing-scores",
+            get(missing_scores),
+        )
+        .route("/classes/{class_id}/terms/{term_id}/ranking", get(ranking))
+        .route(
+            "/classes/{class_id}/terms/{term_id}/report-cards",
+            post(generate_report_cards),
+        )
+        .route(
+            "/schools/{school_id}/assessment-weightings/{class_level}",
+            get(get_weighting).put(set_weighting),
+        )
+        .route(
+            "/schools/{school_id}/grading-schemes",
+            get(grading_schemes).post(save_grading_scheme),
+        )
+        .route("/report-cards/{report_card_id}/publish", post(publish))
+        .route("/students/{student_id}/transcript.pdf", get(transcript_pdf))
+        .route(
+            "/students/{student_id}/transcript.json",
+            get(transcript_json),
+        )
+        .route(
+            "/students/{student_id}/previous-transcript",
+            get(previous_transcript).put(import_previous_transcript),
+        )
+}
+
+#[derive(Debug, Deserialize)]
+struct ScoreOverride {
+    score_obtained: Decimal,
+    remarks: Option<String>,
+    /// Kept in the activity log with the before and after values
+    reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RankingParams {
+    #[serde(default)]
+    method: RankingMethod,
+}
+
+/// Clear verification on an assessment's scores so teachers can edit them
+/// again; returns how many were unlocked
+async fn unlock_scores(
+    headers: HeaderMap,
+    Path(assessment_id): Path<String>,
+) -> ApiResult<Json<usize>> {
+    let user = signed_in(&headers, None).await?;
+    let unlocked = ScoreService
+        .unlock(db::get_db(), &user, assessment_id)
+        .await?;
+    Ok(Json(unlocked))
+}
+
+/// Change a score even when it is verified; admins only
+async fn override_score(
+    headers: HeaderMap,
+    Path((assessment_id, student_id)): Path<(String, String)>,
+    Json(data): Json<ScoreOverride>,
+) -> ApiResult<Json<StudentScore>> {
+    let user = signed_in(&headers, None).await?;
+    let score = ScoreService
+        .override_score(
+            db::get_db(),
+            &user,
+            EnterScoreDto {
+                assessment_id: RecordId::new("assessments", assessment_id.as_str()),
+                student_id: RecordId::new("students", student_id.as_str()),
+                score_obtained: data.score_obtained,
+                remarks: data.remarks,
+            },
+            data.reason,
+        )
+        .await?;
+    Ok(Json(score))
+}
+
+/// Students without a score, grouped by assessment
+async fn missing_scores(
+    headers: HeaderMap,
+    Path((class_id, term_id)): Path<(String, String)>,
+) -> ApiResult<Json<Vec<AssessmentMissingScores>>> {
+    let user = signed_in(&headers, None).await?;
+    let missing = RankingService
+        .missing_scores(db::get_db(), &user, class_id, term_id)
+        .await?;
+    Ok(Json(missing))
+}
+
+async fn ranking(
+    headers: HeaderMap,
+    Path((class_id, term_id)): Path<(String, String)>,
+    Query(params): Query<RankingParams>,
+) -> ApiResult<Json<Vec<ClassRankingEntry>>> {
+    let user = signed_in(&headers, None).await?;
+    let ranking = RankingService
+        .class_ranking(db::get_db(), &user, class_id, term_id, params.method)
+        .await?;
+    Ok(Json(ranking))
+}
+
+/// A class level's weights, for the school's staff
+async fn get_weighting(
+    headers: HeaderMap,
+    Path((school_id, class_level)): Path<(String, ClassLevel)>,
+) -> ApiResult<Json<AssessmentWeighting>> {
+    let user = signed_in(&headers, None).await?;
+    ensure_role(&user, &school_id, STAFF)?;
+    let weighting = WeightingService
+        .get(db::get_db(), school_id, class_level)
+        .await?;
+    Ok(Json(weighting))
+}
+
+/// Replace a class level's weights; they must sum to 100
+async fn set_weighting(
+    headers: HeaderMap,
+    Path((school_id, class_level)): Path<(String, ClassLevel)>,
+    Json(weights): Json<Vec<AssessmentWeight>>,
+) -> ApiResult<Json<AssessmentWeighting>> {
+    let user = signed_in(&headers, None).await?;
+    ensure_role(&user, &school_id, &[UserType::Admin])?;
+    let sdb = db::get_db();
+    WeightingService
+        .save(
+            sdb,
+            SetAssessmentWeightingDto {
+                school_id: RecordId::new("schools", school_id.as_str()),
+                class_level: class_level.clone(),
+                weights,
+            },
+        )
+        .await?;
+    let weighting = WeightingService.get(sdb, school_id, class_level).await?;
+    Ok(Json(weighting))
+}
+
+/// Current version of each named grading scheme, for the school's staff
+async fn grading_schemes(
+    headers: HeaderMap,
+    Path(school_id): Path<String>,
+) -> ApiResult<Json<Vec<GradingSchemeVersion>>> {
+    let user = signed_in(&headers, None).await?;
+    ensure_role(&user, &school_id, STAFF)?;
+    let schemes = GradingService
+        .current_schemes(db::get_db(), school_id)
+        .await?;
+    Ok(Json(schemes))
+}
+
+/// Save a grading scheme as a new version; bands must cover 0-100 without gaps
+async fn save_grading_scheme(
+    headers: HeaderMap,
+    Path(school_id): Path<String>,
+    Json(mut data): Json<SaveGradingSchemeDto>,
+) -> ApiResult<Json<GradingSchemeWithBands>> {
+    let user = signed_in(&headers, None).await?;
+    ensure_role(&user, &school_id, &[UserType::Admin])?;
+    data.school_id = RecordId::new("schools", school_id.as_str());
+    data.created_by = Some(RecordId::new("users", user.user_id.as_str()));
+    let scheme = GradingService.save(db::get_db(), data).await?;
+    Ok(Json(scheme))
+}
+
+/// Compute and store the class's report cards for the term with the
+/// configured weighting
+async fn generate_report_cards(
+    headers: HeaderMap,
+    Path((class_id, term_id)): Path<(String, String)>,
+) -> ApiResult<Json<Vec<ReportCard>>> {
+    let user = signed_in(&headers, None).await?;
+    let cards = ReportCardService
+        .generate(db::get_db(), &user, class_id, term_id)
+        .await?;
+    Ok(Json(cards))
+}
+
+async fn publish(
+    headers: HeaderMap,
+    Path(report_card_id): Path<String>,
+) -> ApiResult<Json<ReportCard>> {
+    let user = signed_in(&headers, None).await?;
+    let card = ReportCardService
+        .publish(db::get_db(), &user, report_card_id)
+        .await?;
+    Ok(Json(card))
+}
+
+/// Every published report card of a student as one printable PDF
+async fn transcript_pdf(headers: HeaderMap, Path(student_id): Path<String>) -> ApiResult<Response> {
+    let user = signed_in(&headers, None).await?;
+    let pdf = TranscriptService
+        .pdf(db::get_db(), &user, student_id)
+        .await?;
+    Ok(([(header::CONTENT_TYPE, "application/pdf")], pdf).into_response())
+}
+
+/// A student's transcript as JSON, to hand over when they transfer
+async fn transcript_json(
+    headers: HeaderMap,
+    Path(student_id): Path<String>,
+) -> ApiResult<Response> {
+    let user = signed_in(&headers, None).await?;
+    let json = TranscriptService
+        .json(db::get_db(), &user, student_id)
+        .await?;
+    Ok(([(header::CONTENT_TYPE, "application/json")], json).into_response())
+}
+
+/// The transcript a student brought from their previous school
+async fn previous_transcript(
+    headers: HeaderMap,
+    Path(student_id): Path<String>,
+) -> ApiResult<Json<Transcript>> {
+    let user = signed_in(&headers, None).await?;
+    let transcript = TranscriptService
+        .previous(db::get_db(), file_store(), &user, student_id)
+        .await?;
+    Ok(Json(transcript))
+}
+
+/// Import a transcript exported by another school as the raw request body
+async fn import_previous_transcript(
+    headers: HeaderMap,
+    Path(student_id): Path<String>,
+    body: Bytes,
+) -> ApiResult<Json<Transcript>> {
+    let user = signed_in(&headers, None).await?;
+    let transcript = TranscriptService
+        .import_previous(db::get_db(), file_store(), &user, student_id, &body)
+        .await?;
+    Ok(Json(transcript))
+}
@@ -0,0 +1,41 @@
+use axum::http::{HeaderMap, header};
+use domain::{auth::AuthService, error::Error};
+use shared::helpers::api_responses::SessionUser;
+
+use crate::error::ApiResult;
+
+/// Cookie the web app keeps its session token in
+const SESSION_COOKIE: &str = "sms_session";
+
+/// The user a request's session token belongs to
+pub(super) async fn signed_in(
+    headers: &HeaderMap,
+    token: Option<String>,
+) -> ApiResult<SessionUser> {
+    let token = session_token(headers)
+        .or(token)
+        .ok_or_else(|| Error::Unauthorized("sign in first".into()))?;
+    let user = AuthService
+        .session_user(db::get_db(), token)
+        .await?
+        .ok_or_else(|| Error::Unauthorized("session has expired".into()))?;
+    Ok(user)
+}
+
+/// Bearer token from the Authorization header, or the web app's session cookie
+fn session_token(headers: &HeaderMap) -> Option<String> {
+    if let Some(token) = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+    {
+        return Some(token.trim().to_string());
+    }
+    let prefix = format!("{SESSION_COOKIE}=");
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|cookies| cookies.split(';'))
+        .find_map(|cookie| cookie.trim().strip_prefix(&prefix).map(str::to_owned))
+}
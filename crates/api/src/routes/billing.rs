@@ -0,0 +1,67 @@
+use axum::{
+    Json, Router,
+    extract::Path,
+    http::HeaderMap,
+    routing::{get, post},
+};
+use domain::billing::BillingService;
+use shared::{
+    helpers::{
+        api_responses::PlatformPaymentReceipt,
+        create_update_dtos::{GeneratePlatformInvoiceDto, RecordPlatformPaymentDto},
+    },
+    models::fee_management::PlatformInvoice,
+};
+
+use super::auth::signed_in;
+use crate::error::ApiResult;
+
+pub fn router() -> Router {
+    Router::new()
+        .route(
+            "/platform/schools/{school_id}/invoices",
+            get(invoices).post(generate),
+        )
+        .route(
+            "/platform/invoices/{invoice_id}/payments",
+            post(record_payment),
+        )
+}
+
+/// Bill a school for a term, the current one unless another is given
+async fn generate(
+    headers: HeaderMap,
+    Path(school_id): Path<String>,
+    Json(data): Json<GeneratePlatformInvoiceDto>,
+) -> ApiResult<Json<PlatformInvoice>> {
+    let user = signed_in(&headers, None).await?;
+    let invoice = BillingService
+        .generate(db::get_db(), user.user_id, school_id, data)
+        .await?;
+    Ok(Json(invoice))
+}
+
+/// A school's platform invoices, newest first
+async fn invoices(
+    headers: HeaderMap,
+    Path(school_id): Path<String>,
+) -> ApiResult<Json<Vec<PlatformInvoice>>> {
+    let user = signed_in(&headers, None).await?;
+    let invoices = BillingService
+        .invoices(db::get_db(), user.user_id, school_id)
+        .await?;
+    Ok(Json(invoices))
+}
+
+/// Record a school's payment towards a platform invoice
+async fn record_payment(
+    headers: HeaderMap,
+    Path(invoice_id): Path<String>,
+    Json(data): Json<RecordPlatformPaymentDto>,
+) -> ApiResult<Json<PlatformPaymentReceipt>> {
+    let user = signed_in(&headers, None).await?;
+    let receipt = BillingService
+        .record_payment(db::get_db(), user.user_id, invoice_id, data)
+        .await?;
+    Ok(Json(receipt))
+}
@@ -0,0 +1,65 @@
+use axum::{
+    Json, Router,
+    extract::Path,
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+};
+use domain::calendar::CalendarService;
+use shared::helpers::api_responses::CalendarFeedLink;
+
+use super::auth::signed_in;
+use crate::error::ApiResult;
+
+pub fn router() -> Router {
+    Router::new()
+        .route("/calendar/feed", post(feed).delete(revoke))
+        .route(
+            "/calendar/school-feed",
+            post(school_feed).delete(revoke_school_feed),
+        )
+        .route("/calendar/{token}/feed.ics", get(ics))
+}
+
+/// The signed-in user's calendar subscription, created on first use
+async fn feed(headers: HeaderMap) -> ApiResult<Json<CalendarFeedLink>> {
+    let user = signed_in(&headers, None).await?;
+    Ok(Json(
+        CalendarService.feed(db::get_db(), user.user_id).await?,
+    ))
+}
+
+async fn revoke(headers: HeaderMap) -> ApiResult<StatusCode> {
+    let user = signed_in(&headers, None).await?;
+    CalendarService.revoke(db::get_db(), user.user_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// The school's whole calendar, for admins to publish
+async fn school_feed(headers: HeaderMap) -> ApiResult<Json<CalendarFeedLink>> {
+    let user = signed_in(&headers, None).await?;
+    Ok(Json(
+        CalendarService
+            .school_feed(db::get_db(), user.user_id)
+            .await?,
+    ))
+}
+
+async fn revoke_school_feed(headers: HeaderMap) -> ApiResult<StatusCode> {
+    let user = signed_in(&headers, None).await?;
+    CalendarService
+        .revoke_school_feed(db::get_db(), user.user_id)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// The feed itself; the token in the URL stands in for signing in, so
+/// calendar apps can subscribe
+async fn ics(Path(token): Path<String>) -> ApiResult<Response> {
+    let calendar = CalendarService.ics(db::get_db(), token).await?;
+    Ok((
+        [(header::CONTENT_TYPE, "text/calendar; charset=utf-8")],
+        calendar,
+    )
+        .into_response())
+}
@@ -0,0 +1,214 @@
+use axum::{
+    Json, Router,
+    body::Bytes,
+    extract::{DefaultBodyLimit, Path, Query},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
+    routing::{get, post, put},
+};
+use domain::{
+    conversation::{ConversationService, MAX_ATTACHMENT_BYTES, UploadedFile},
+    storage::file_store,
+};
+use serde::Deserialize;
+use shared::{
+    helpers::{
+        api_responses::{ConversationSummary, ConversationThread},
+        create_update_dtos::{SendMessageDto, StartConversationDto},
+    },
+    models::communication::{Conversation, Message, MessageAttachment},
+};
+
+use super::auth::signed_in;
+use crate::error::ApiResult;
+
+pub fn router() -> Router {
+    Router::new()
+        .route("/conversations", get(list).post(start))
+        .route("/conversations/{conversation_id}", get(thread))
+        .route("/conversations/{conversation_id}/messages", post(send))
+        .route(
+            "/conversations/{conversation_id}/attachments",
+            post(upload).layer(DefaultBodyLimit::max(MAX_ATTACHMENT_BYTES)),
+        )
+        .route("/conversations/{conversation_id}/typing", post(typing))
+        .route("/conversations/{conversation_id}/read", post(mark_read))
+        .route("/attachments/{attachment_id}", get(download))
+        .route("/moderation/conversations", get(school_conversations))
+        .route("/moderation/conversations/{conversation_id}", get(review))
+        .route(
+            "/moderation/conversations/{conversation_id}/closed",
+            put(set_closed),
+        )
+        .route("/moderation/messages/{message_id}/hide", post(hide_message))
+}
+
+#[derive(Debug, Deserialize)]
+struct UploadParams {
+    file_name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct HideMessage {
+    reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SetClosed {
+    closed: bool,
+}
+
+/// The signed-in user's conversations with last message and unread count
+async fn list(headers: HeaderMap) -> ApiResult<Json<Vec<ConversationSummary>>> {
+    let user = signed_in(&headers, None).await?;
+    let conversations = ConversationService.list(db::get_db(), &user).await?;
+    Ok(Json(conversations))
+}
+
+async fn start(
+    headers: HeaderMap,
+    Json(data): Json<StartConversationDto>,
+) -> ApiResult<(StatusCode, Json<ConversationThread>)> {
+    let user = signed_in(&headers, None).await?;
+    let thread = ConversationService.start(db::get_db(), &user, data).await?;
+    Ok((StatusCode::CREATED, Json(thread)))
+}
+
+/// Messages in a conversation; reading them sends a read receipt
+async fn thread(
+    headers: HeaderMap,
+    Path(conversation_id): Path<String>,
+) -> ApiResult<Json<ConversationThread>> {
+    let user = signed_in(&headers, None).await?;
+    let thread = ConversationService
+        .thread(db::get_db(), &user, conversation_id)
+        .await?;
+    Ok(Json(thread))
+}
+
+async fn send(
+    headers: HeaderMap,
+    Path(conversation_id): Path<String>,
+    Json(data): Json<SendMessageDto>,
+) -> ApiResult<(StatusCode, Json<Message>)> {
+    let user = signed_in(&headers, None).await?;
+    let message = ConversationService
+        .send(db::get_db(), &user, conversation_id, data)
+        .await?;
+    Ok((StatusCode::CREATED, Json(message)))
+}
+
+/// Upload a file as the raw request body; send its id with the next message
+async fn upload(
+    headers: HeaderMap,
+    Path(conversation_id): Path<String>,
+    Query(params): Query<UploadParams>,
+    body: Bytes,
+) -> ApiResult<(StatusCode, Json<MessageAttachment>)> {
+    let user = signed_in(&headers, None).await?;
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .unwrap_or_default()
+        .trim()
+        .to_string();
+    let attachment = ConversationService
+        .upload(
+            db::get_db(),
+            file_store(),
+            &user,
+            conversation_id,
+            UploadedFile {
+                file_name: params.file_name,
+                content_type,
+                bytes: body.to_vec(),
+            },
+        )
+        .await?;
+    Ok((StatusCode::CREATED, Json(attachment)))
+}
+
+async fn download(headers: HeaderMap, Path(attachment_id): Path<String>) -> ApiResult<Response> {
+    let user = signed_in(&headers, None).await?;
+    let (attachment, bytes) = ConversationService
+        .download(db::get_db(), file_store(), &user, attachment_id)
+        .await?;
+    let filename = attachment.file_name.replace(['"', '\\'], "_");
+    Ok((
+        [
+            (header::CONTENT_TYPE, attachment.content_type),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{filename}\""),
+            ),
+        ],
+        bytes,
+    )
+        .into_response())
+}
+
+async fn typing(headers: HeaderMap, Path(conversation_id): Path<String>) -> ApiResult<StatusCode> {
+    let user = signed_in(&headers, None).await?;
+    ConversationService
+        .typing(db::get_db(), &user, conversation_id)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Mark the conversation read without loading it
+async fn mark_read(
+    headers: HeaderMap,
+    Path(conversation_id): Path<String>,
+) -> ApiResult<Json<usize>> {
+    let user = signed_in(&headers, None).await?;
+    let read = ConversationService
+        .mark_read(db::get_db(), &user, conversation_id)
+        .await?;
+    Ok(Json(read))
+}
+
+/// Every conversation in the admin's school
+async fn school_conversations(headers: HeaderMap) -> ApiResult<Json<Vec<ConversationSummary>>> {
+    let admin = signed_in(&headers, None).await?;
+    let conversations = ConversationService
+        .school_conversations(db::get_db(), &admin)
+        .await?;
+    Ok(Json(conversations))
+}
+
+/// A conversation as sent, for safeguarding review; each review is logged
+async fn review(
+    headers: HeaderMap,
+    Path(conversation_id): Path<String>,
+) -> ApiResult<Json<ConversationThread>> {
+    let admin = signed_in(&headers, None).await?;
+    let thread = ConversationService
+        .review(db::get_db(), &admin, conversation_id)
+        .await?;
+    Ok(Json(thread))
+}
+
+async fn set_closed(
+    headers: HeaderMap,
+    Path(conversation_id): Path<String>,
+    Json(data): Json<SetClosed>,
+) -> ApiResult<Json<Conversation>> {
+    let admin = signed_in(&headers, None).await?;
+    let conversation = ConversationService
+        .set_closed(db::get_db(), &admin, conversation_id, data.closed)
+        .await?;
+    Ok(Json(conversation))
+}
+
+async fn hide_message(
+    headers: HeaderMap,
+    Path(message_id): Path<String>,
+    Json(data): Json<HideMessage>,
+) -> ApiResult<Json<Message>> {
+    let admin = signed_in(&headers, None).await?;
+    let message = ConversationService
+        .hide_message(db::get_db(), &admin, message_id, data.reason)
+        .await?;
+    Ok(Json(message))
+}
@@ -0,0 +1,107 @@
+use axum::{
+    Json, Router,
+    body::Bytes,
+    extract::Path,
+    http::{HeaderMap, StatusCode},
+    routing::post,
+};
+use domain::{
+    auth::ensure_self,
+    email::{EmailService, mailer},
+};
+use serde::Deserialize;
+use shared::models::system_and_audit::MessageDelivery;
+
+use super::auth::signed_in;
+use crate::error::ApiResult;
+
+pub fn router() -> Router {
+    Router::new()
+        .route("/payments/{payment_id}/receipt-email", post(send_receipt))
+        .route(
+            "/students/{student_id}/report-card-email",
+            post(send_report_card_link),
+        )
+        .route(
+            "/users/{user_id}/email-verification",
+            post(send_verification_code),
+        )
+        .route(
+            "/users/{user_id}/email-verification/confirm",
+            post(confirm_email),
+        )
+        .route("/email/bounces", post(bounce))
+}
+
+#[derive(Debug, Deserialize)]
+struct ReportCardLink {
+    term_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct VerificationCode {
+    code: String,
+}
+
+/// Email the receipt PDF to the student's parents
+async fn send_receipt(
+    headers: HeaderMap,
+    Path(payment_id): Path<String>,
+) -> ApiResult<Json<Vec<MessageDelivery>>> {
+    let user = signed_in(&headers, None).await?;
+    let deliveries = EmailService
+        .send_receipt(db::get_db(), mailer()?, &user, payment_id)
+        .await?;
+    Ok(Json(deliveries))
+}
+
+/// Email the student's parents a link to the term's report card
+async fn send_report_card_link(
+    headers: HeaderMap,
+    Path(student_id): Path<String>,
+    Json(data): Json<ReportCardLink>,
+) -> ApiResult<Json<Vec<MessageDelivery>>> {
+    let user = signed_in(&headers, None).await?;
+    let deliveries = EmailService
+        .send_report_card_link(db::get_db(), mailer()?, &user, student_id, data.term_id)
+        .await?;
+    Ok(Json(deliveries))
+}
+
+/// Users verify their own address
+async fn send_verification_code(
+    headers: HeaderMap,
+    Path(user_id): Path<String>,
+) -> ApiResult<Json<MessageDelivery>> {
+    let user = signed_in(&headers, None).await?;
+    ensure_self(&user, &user_id)?;
+    let delivery = EmailService
+        .send_verification_code(db::get_db(), mailer()?, user_id)
+        .await?;
+    Ok(Json(delivery))
+}
+
+async fn confirm_email(
+    headers: HeaderMap,
+    Path(user_id): Path<String>,
+    Json(data): Json<VerificationCode>,
+) -> ApiResult<StatusCode> {
+    let user = signed_in(&headers, None).await?;
+    ensure_self(&user, &user_id)?;
+    EmailService
+        .confirm_email(db::get_db(), user_id, data.code)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// A bounce reported by the mail provider, signed with the shared secret;
+/// returns how many deliveries the bounce matched
+async fn bounce(headers: HeaderMap, body: Bytes) -> ApiResult<Json<usize>> {
+    let signature = headers
+        .get("x-webhook-signature")
+        .and_then(|v| v.to_str().ok());
+    let bounced = EmailService
+        .receive_bounce(db::get_db(), mailer()?, signature, &body)
+        .await?;
+    Ok(Json(bounced))
+}
@@ -0,0 +1,80 @@
+use axum::{
+    Json, Router,
+    extract::Path,
+    http::{HeaderMap, header},
+    response::{IntoResponse, Response},
+    routing::{get, post, put},
+};
+use domain::event::EventService;
+use serde::Deserialize;
+use shared::{
+    helpers::{
+        api_responses::{EventCheckIn, EventTicket, RsvpSummary},
+        create_update_dtos::RsvpDto,
+    },
+    models::communication::EventRsvp,
+};
+
+use super::auth::signed_in;
+use crate::error::ApiResult;
+
+pub fn router() -> Router {
+    Router::new()
+        .route("/events/{event_id}/rsvp", put(rsvp))
+        .route("/events/{event_id}/rsvp-summary", get(summary))
+        .route("/rsvps/{rsvp_id}/ticket", get(ticket))
+        .route("/rsvps/{rsvp_id}/ticket.svg", get(ticket_qr))
+        .route("/events/check-in", post(check_in))
+}
+
+#[derive(Debug, Deserialize)]
+struct CheckIn {
+    ticket_code: String,
+}
+
+/// Reply to an event for the signed-in user, or change their reply
+async fn rsvp(
+    headers: HeaderMap,
+    Path(event_id): Path<String>,
+    Json(data): Json<RsvpDto>,
+) -> ApiResult<Json<EventRsvp>> {
+    let user = signed_in(&headers, None).await?;
+    let rsvp = EventService
+        .rsvp(db::get_db(), user.user_id, event_id, data)
+        .await?;
+    Ok(Json(rsvp))
+}
+
+async fn summary(headers: HeaderMap, Path(event_id): Path<String>) -> ApiResult<Json<RsvpSummary>> {
+    let user = signed_in(&headers, None).await?;
+    let summary = EventService
+        .summary(db::get_db(), user.user_id, event_id)
+        .await?;
+    Ok(Json(summary))
+}
+
+async fn ticket(headers: HeaderMap, Path(rsvp_id): Path<String>) -> ApiResult<Json<EventTicket>> {
+    let user = signed_in(&headers, None).await?;
+    let ticket = EventService
+        .ticket(db::get_db(), user.user_id, rsvp_id)
+        .await?;
+    Ok(Json(ticket))
+}
+
+/// Just the QR code, for printing or an `<img>` tag
+async fn ticket_qr(headers: HeaderMap, Path(rsvp_id): Path<String>) -> ApiResult<Response> {
+    let user = signed_in(&headers, None).await?;
+    let ticket = EventService
+        .ticket(db::get_db(), user.user_id, rsvp_id)
+        .await?;
+    Ok(([(header::CONTENT_TYPE, "image/svg+xml")], ticket.qr_svg).into_response())
+}
+
+/// Scan a ticket at the door; staff only, on the day of the event
+async fn check_in(headers: HeaderMap, Json(data): Json<CheckIn>) -> ApiResult<Json<EventCheckIn>> {
+    let staff = signed_in(&headers, None).await?;
+    let check_in = EventService
+        .check_in(db::get_db(), staff.user_id, data.ticket_code)
+        .await?;
+    Ok(Json(check_in))
+}
@@ -0,0 +1,192 @@
+use std::io;
+
+use axum::{
+    Router,
+    body::Body,
+    extract::{Path, Query},
+    http::{HeaderMap, header},
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use chrono::NaiveDate;
+use domain::{auth::ensure_role, export::ExportService};
+use serde::{Deserialize, de::DeserializeOwned};
+use shared::{
+    helpers::{
+        create_update_dtos::{ExportEntity, ExportFormat, ExportRequest},
+        query_filters::{AttendanceFilter, InvoiceFilter, ScoreFilter, StudentFilter},
+        surreal_util::to_surreal_datetime,
+    },
+    models::UserType,
+};
+use surrealdb_types::RecordId;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tracing::error;
+
+use super::auth::signed_in;
+use crate::error::{ApiError, ApiResult};
+
+pub fn router() -> Router {
+    Router::new().route("/schools/{school_id}/exports/{entity}", get(export))
+}
+
+#[derive(Debug, Deserialize)]
+struct ExportParams {
+    format: ExportFormat,
+    /// Comma separated column keys
+    columns: Option<String>,
+    class_id: Option<String>,
+    student_id: Option<String>,
+    term_id: Option<String>,
+    subject_id: Option<String>,
+    assessment_id: Option<String>,
+    status: Option<String>,
+    search: Option<String>,
+    date_from: Option<NaiveDate>,
+    date_to: Option<NaiveDate>,
+}
+
+/// Stream an export of one entity to the school's admins and accountants;
+/// the body is sent page by page as it is read
+async fn export(
+    headers: HeaderMap,
+    Path((school_id, entity)): Path<(String, ExportEntity)>,
+    Query(params): Query<ExportParams>,
+) -> ApiResult<Response> {
+    let user = signed_in(&headers, None).await?;
+    ensure_role(&user, &school_id, &[UserType::Admin, UserType::Accountant])?;
+    let request = to_request(school_id, entity, params)?;
+    let (content_type, extension) = match request.format {
+        ExportFormat::Csv => ("text/csv", "csv"),
+        ExportFormat::Xlsx => (
+            "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+            "xlsx",
+        ),
+        ExportFormat::Json => ("application/json", "json"),
+    };
+    let filename = format!(
+        "{}.{extension}",
+        serde_json::to_value(entity)
+            .ok()
+            .and_then(|v| v.as_str().map(str::to_owned))
+            .unwrap_or_default()
+    );
+
+    // Headers are already sent once the first page goes out, so a failure
+    // part way ends the body with an error and the client sees the download
+    // break off rather than a short file that looks complete
+    let (tx, mut rx) = mpsc::channel::<Vec<u8>>(4);
+    let (body_tx, body_rx) = mpsc::channel::<io::Result<Vec<u8>>>(4);
+    tokio::spawn(async move {
+        let forward = async {
+            while let Some(chunk) = rx.recv().await {
+                if body_tx.send(Ok(chunk)).await.is_err() {
+                    break;
+                }
+            }
+        };
+        let (result, ()) = tokio::join!(
+            ExportService.export(db::get_db(), request, Some(user.user_id), tx),
+            forward
+        );
+        if let Err(err) = result {
+            error!("export failed: {err}");
+            let _ = body_tx.send(Err(io::Error::other(err.to_string()))).await;
+        }
+    });
+    let body = Body::from_stream(ReceiverStream::new(body_rx));
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{filename}\""),
+            ),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+fn to_request(
+    school_id: String,
+    entity: ExportEntity,
+    params: ExportParams,
+) -> ApiResult<ExportRequest> {
+    let columns: Vec<String> = params
+        .columns
+        .map(|c| {
+            c.split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect()
+        })
+        .unwrap_or_default();
+    let available = ExportService.columns(entity);
+    if let Some(unknown) = columns.iter().find(|c| !available.contains(&c.as_str())) {
+        return Err(ApiError::bad_request(format!("Unknown column {unknown}")));
+    }
+
+    let id = |table: &str, key: &Option<String>| key.as_deref().map(|k| RecordId::new(table, k));
+
+    let mut request = ExportRequest {
+        school_id,
+        entity,
+        format: params.format,
+        columns,
+        student_filter: StudentFilter::default(),
+        invoice_filter: InvoiceFilter::default(),
+        attendance_filter: AttendanceFilter::default(),
+        score_filter: ScoreFilter::default(),
+    };
+    match entity {
+        ExportEntity::Students => {
+            request.student_filter = StudentFilter {
+                school_id: None,
+                class_id: id("classes", &params.class_id),
+                status: parse_status(&params.status)?,
+                search: params.search,
+            }
+        }
+        ExportEntity::ClassRegister => {
+            request.attendance_filter = AttendanceFilter {
+                student_id: id("students", &params.student_id),
+                class_id: id("classes", &params.class_id),
+                date_from: params.date_from.map(to_surreal_datetime),
+                date_to: params.date_to.map(to_surreal_datetime),
+                status: parse_status(&params.status)?,
+            }
+        }
+        ExportEntity::ScoreSheet => {
+            request.score_filter = ScoreFilter {
+                class_id: id("classes", &params.class_id),
+                subject_id: id("subjects", &params.subject_id),
+                term_id: id("terms", &params.term_id),
+                assessment_id: id("assessments", &params.assessment_id),
+            }
+        }
+        ExportEntity::Invoices | ExportEntity::Payments => {
+            request.invoice_filter = InvoiceFilter {
+                school_id: None,
+                student_id: id("students", &params.student_id),
+                term_id: id("terms", &params.term_id),
+                status: parse_status(&params.status)?,
+            }
+        }
+        ExportEntity::ActivityLog => {}
+    }
+    Ok(request)
+}
+
+/// Parse a snake_case status string into the entity's status enum
+fn parse_status<T: DeserializeOwned>(status: &Option<String>) -> ApiResult<Option<T>> {
+    status
+        .as_ref()
+        .map(|s| {
+            serde_json::from_value(serde_json::Value::String(s.clone()))
+                .map_err(|_| ApiError::bad_request(format!("Unknown status {s}")))
+        })
+        .transpose()
+}
@@ -0,0 +1,74 @@
+use std::{collections::HashSet, convert::Infallible};
+
+use axum::{
+    Router,
+    extract::Query,
+    http::HeaderMap,
+    response::sse::{Event, KeepAlive, Sse},
+    routing::get,
+};
+use domain::live::{LiveEvent, LiveService, created};
+use serde::Deserialize;
+use tokio_stream::{Stream, StreamExt, wrappers::BroadcastStream};
+
+use super::auth::signed_in;
+use crate::error::ApiResult;
+
+pub fn router() -> Router {
+    Router::new().route("/events", get(events))
+}
+
+#[derive(Debug, Deserialize)]
+struct EventParams {
+    /// Session token, for clients that can't set headers or cookies
+    token: Option<String>,
+    /// Resume after this event when the `Last-Event-ID` header isn't sent
+    last_event_id: Option<String>,
+}
+
+/// Server-sent events carrying the signed-in user's new notifications and
+/// messages, and typing and read signals from their conversations. Each
+/// stored event's id is the record id; a client that reconnects with
+/// `Last-Event-ID` is first sent what it missed.
+async fn events(
+    headers: HeaderMap,
+    Query(params): Query<EventParams>,
+) -> ApiResult<Sse<impl Stream<Item = Result<Event, Infallible>>>> {
+    let sdb = db::get_db();
+    let user = signed_in(&headers, params.token).await?;
+
+    let (notifications, messages) = LiveService.subscribe(sdb, &user).await?;
+    let user_id = user.user_id.clone();
+    let signals = BroadcastStream::new(LiveService.signals()).filter_map(move |signal| {
+        signal
+            .ok()
+            .filter(|s| s.recipient_id == user_id)
+            .map(|s| s.event)
+    });
+    let last_event_id = headers
+        .get("last-event-id")
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_owned)
+        .or(params.last_event_id);
+    let missed = LiveService.missed(sdb, &user, last_event_id).await?;
+
+    // Records created while the backlog was read arrive on both
+    let replayed: HashSet<String> = missed.iter().map(LiveEvent::id).collect();
+    let live = notifications
+        .filter_map(|change| created(change).map(LiveEvent::Notification))
+        .merge(messages.filter_map(|change| created(change).map(LiveEvent::Message)))
+        .filter(move |event| !replayed.contains(&event.id()))
+        .merge(signals);
+    let stream = tokio_stream::iter(missed)
+        .chain(live)
+        .map(|event| Ok(sse_event(&event)));
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+/// Signals carry no id, so they leave the client's `Last-Event-ID` alone
+fn sse_event(event: &LiveEvent) -> Event {
+    let id = event.id();
+    let sse = Event::default().event(event.name());
+    let sse = if id.is_empty() { sse } else { sse.id(id) };
+    sse.json_data(event).unwrap_or_default()
+}
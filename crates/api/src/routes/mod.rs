@@ -0,0 +1,37 @@
+use axum::Router;
+
+mod announcements;
+mod assessments;
+mod auth;
+mod billing;
+mod calendar;
+mod conversations;
+mod email;
+mod events;
+mod exports;
+mod live;
+mod push;
+mod students;
+mod subscription;
+mod templates;
+mod ussd;
+mod whatsapp;
+
+pub fn router() -> Router {
+    Router::new()
+        .merge(announcements::router())
+        .merge(assessments::router())
+        .merge(billing::router())
+        .merge(calendar::router())
+        .merge(conversations::router())
+        .merge(email::router())
+        .merge(events::router())
+        .merge(exports::router())
+        .merge(live::router())
+        .merge(push::router())
+        .merge(students::router())
+        .merge(subscription::router())
+        .merge(templates::router())
+        .merge(ussd::router())
+        .merge(whatsapp::router())
+}
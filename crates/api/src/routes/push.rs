@@ -0,0 +1,65 @@
+use axum::{
+    Json, Router,
+    extract::Query,
+    http::{HeaderMap, StatusCode},
+    routing::{get, post},
+};
+use domain::push::{PushService, push_client};
+use serde::{Deserialize, Serialize};
+use shared::{
+    helpers::create_update_dtos::PushSubscriptionDto, models::system_and_audit::PushSubscription,
+};
+
+use super::auth::signed_in;
+use crate::error::ApiResult;
+
+pub fn router() -> Router {
+    Router::new()
+        .route("/push/public-key", get(public_key))
+        .route(
+            "/push/subscriptions",
+            post(subscribe).get(list).delete(unsubscribe),
+        )
+}
+
+#[derive(Debug, Serialize)]
+struct PublicKey {
+    public_key: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Endpoint {
+    endpoint: String,
+}
+
+/// The VAPID key browsers subscribe with
+async fn public_key() -> ApiResult<Json<PublicKey>> {
+    Ok(Json(PublicKey {
+        public_key: push_client()?.public_key(),
+    }))
+}
+
+/// Subscribe the signed-in user's browser
+async fn subscribe(
+    headers: HeaderMap,
+    Json(data): Json<PushSubscriptionDto>,
+) -> ApiResult<Json<PushSubscription>> {
+    let user = signed_in(&headers, None).await?;
+    let subscription = PushService
+        .subscribe(db::get_db(), user.user_id, data)
+        .await?;
+    Ok(Json(subscription))
+}
+
+async fn list(headers: HeaderMap) -> ApiResult<Json<Vec<PushSubscription>>> {
+    let user = signed_in(&headers, None).await?;
+    Ok(Json(PushService.list(db::get_db(), user.user_id).await?))
+}
+
+async fn unsubscribe(headers: HeaderMap, Query(query): Query<Endpoint>) -> ApiResult<StatusCode> {
+    let user = signed_in(&headers, None).await?;
+    PushService
+        .unsubscribe(db::get_db(), user.user_id, query.endpoint)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,45 @@
+use axum::{Json, Router, body::Bytes, extract::Query, http::HeaderMap, routing::post};
+use domain::import::StudentImportService;
+use serde::Deserialize;
+use shared::helpers::{
+    api_responses::StudentImportReport,
+    create_update_dtos::{ImportFormat, StudentImportMapping},
+};
+
+use super::auth::signed_in;
+use crate::error::ApiResult;
+
+pub fn router() -> Router {
+    Router::new().route("/students/import", post(import))
+}
+
+#[derive(Debug, Deserialize)]
+struct ImportParams {
+    format: ImportFormat,
+    /// Validate and report without creating anything
+    #[serde(default)]
+    dry_run: bool,
+}
+
+/// Import students and parents into the signed-in admin's school from a CSV
+/// or XLSX file sent as the raw request body. Column headers are mapped by
+/// the remaining query parameters.
+async fn import(
+    headers: HeaderMap,
+    Query(params): Query<ImportParams>,
+    Query(mapping): Query<StudentImportMapping>,
+    body: Bytes,
+) -> ApiResult<Json<StudentImportReport>> {
+    let user = signed_in(&headers, None).await?;
+    let report = StudentImportService
+        .import(
+            db::get_db(),
+            &user,
+            params.format,
+            &body,
+            &mapping,
+            params.dry_run,
+        )
+        .await?;
+    Ok(Json(report))
+}
@@ -0,0 +1,19 @@
+use axum::{Json, Router, http::HeaderMap, routing::get};
+use domain::subscription::SubscriptionService;
+use shared::helpers::api_responses::SubscriptionOverview;
+
+use super::auth::signed_in;
+use crate::error::ApiResult;
+
+pub fn router() -> Router {
+    Router::new().route("/subscription", get(overview))
+}
+
+/// The signed-in user's school's plan, its limits and what is used
+async fn overview(headers: HeaderMap) -> ApiResult<Json<SubscriptionOverview>> {
+    let user = signed_in(&headers, None).await?;
+    let overview = SubscriptionService
+        .overview(db::get_db(), user.school_id)
+        .await?;
+    Ok(Json(overview))
+}
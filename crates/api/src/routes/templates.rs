@@ -0,0 +1,103 @@
+use axum::{
+    Json, Router,
+    extract::{Path, Query},
+    http::{HeaderMap, StatusCode},
+    routing::{delete, get, post},
+};
+use domain::{auth::ensure_role, templating::TemplateService};
+use serde::Deserialize;
+use shared::{
+    helpers::{
+        api_responses::TemplatePreview,
+        create_update_dtos::{PreviewTemplateDto, SaveMessageTemplateDto},
+    },
+    i18n::Locale,
+    models::{MessageChannel, UserType, communication::MessageTemplate},
+};
+
+use super::auth::signed_in;
+use crate::error::ApiResult;
+
+pub fn router() -> Router {
+    Router::new()
+        .route(
+            "/schools/{school_id}/message-templates",
+            get(list).put(save),
+        )
+        .route(
+            "/schools/{school_id}/message-templates/preview",
+            post(preview),
+        )
+        .route(
+            "/schools/{school_id}/message-templates/{channel}/{template_key}",
+            delete(reset),
+        )
+}
+
+#[derive(Debug, Deserialize)]
+struct ResetParams {
+    /// Without a locale the template for every language is removed
+    locale: Option<Locale>,
+}
+
+/// Templates the school has customised; anything else uses the built-in wording
+async fn list(
+    headers: HeaderMap,
+    Path(school_id): Path<String>,
+) -> ApiResult<Json<Vec<MessageTemplate>>> {
+    ensure_admin(&headers, &school_id).await?;
+    let templates = TemplateService.list(db::get_db(), school_id).await?;
+    Ok(Json(templates))
+}
+
+/// Create or replace the school's template for one message type and language
+async fn save(
+    headers: HeaderMap,
+    Path(school_id): Path<String>,
+    Json(data): Json<SaveMessageTemplateDto>,
+) -> ApiResult<Json<Vec<MessageTemplate>>> {
+    ensure_admin(&headers, &school_id).await?;
+    let sdb = db::get_db();
+    TemplateService.save(sdb, school_id.clone(), data).await?;
+    let templates = TemplateService.list(sdb, school_id).await?;
+    Ok(Json(templates))
+}
+
+/// Render a draft or saved template, with SMS segment counts
+async fn preview(
+    headers: HeaderMap,
+    Path(school_id): Path<String>,
+    Json(data): Json<PreviewTemplateDto>,
+) -> ApiResult<Json<TemplatePreview>> {
+    ensure_admin(&headers, &school_id).await?;
+    let preview = TemplateService
+        .preview(db::get_db(), school_id, data)
+        .await?;
+    Ok(Json(preview))
+}
+
+/// Go back to the built-in wording
+async fn reset(
+    headers: HeaderMap,
+    Path((school_id, channel, template_key)): Path<(String, MessageChannel, String)>,
+    Query(params): Query<ResetParams>,
+) -> ApiResult<StatusCode> {
+    ensure_admin(&headers, &school_id).await?;
+    TemplateService
+        .reset(
+            db::get_db(),
+            school_id,
+            channel,
+            template_key,
+            params.locale,
+        )
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Templates are managed by the school's admins
+async fn ensure_admin(headers: &HeaderMap, school_id: &str) -> ApiResult<()> {
+    let user = signed_in(headers, None).await?;
+    ensure_role(&user, school_id, &[UserType::Admin])?;
+    Ok(())
+}
@@ -0,0 +1,27 @@
+use axum::{Form, Router, extract::Query, routing::post};
+use domain::ussd::{SchoolRecords, UssdRequest, UssdService};
+use serde::Deserialize;
+
+use crate::error::ApiResult;
+
+pub fn router() -> Router {
+    Router::new().route("/ussd", post(session))
+}
+
+/// The shared secret configured in the gateway's callback URL
+#[derive(Debug, Deserialize)]
+struct GatewayKey {
+    key: Option<String>,
+}
+
+/// The gateway callback; it posts form fields and reads back a plain-text
+/// `CON`/`END` screen
+async fn session(
+    Query(gateway): Query<GatewayKey>,
+    Form(request): Form<UssdRequest>,
+) -> ApiResult<String> {
+    UssdService.authorize(gateway.key.as_deref())?;
+    let records = SchoolRecords { sdb: db::get_db() };
+    let reply = UssdService.handle(&records, request).await?;
+    Ok(reply.to_gateway())
+}
@@ -0,0 +1,154 @@
+use axum::{
+    Json, Router,
+    body::Bytes,
+    extract::{Path, Query},
+    http::{HeaderMap, StatusCode},
+    routing::{delete, get, post},
+};
+use domain::{
+    auth::ensure_role,
+    whatsapp::{WhatsappService, whatsapp_client},
+};
+use serde::Deserialize;
+use shared::{
+    helpers::create_update_dtos::SaveWhatsappTemplateDto,
+    i18n::Locale,
+    models::{UserType, communication::WhatsappTemplate, system_and_audit::MessageDelivery},
+};
+
+use super::auth::signed_in;
+use crate::error::ApiResult;
+
+pub fn router() -> Router {
+    Router::new()
+        .route("/whatsapp/webhook", get(verify).post(receive))
+        .route(
+            "/schools/{school_id}/whatsapp-templates",
+            get(list).put(save),
+        )
+        .route(
+            "/schools/{school_id}/whatsapp-templates/{template_key}",
+            delete(remove),
+        )
+        .route(
+            "/invoices/{invoice_id}/whatsapp-reminder",
+            post(send_fee_reminder),
+        )
+        .route(
+            "/students/{student_id}/whatsapp-results",
+            post(send_result_notification),
+        )
+}
+
+/// Meta's webhook registration check
+#[derive(Debug, Deserialize)]
+struct VerifyParams {
+    #[serde(rename = "hub.mode")]
+    mode: String,
+    #[serde(rename = "hub.verify_token")]
+    verify_token: String,
+    #[serde(rename = "hub.challenge")]
+    challenge: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RemoveParams {
+    /// Without a locale the template for every language is removed
+    locale: Option<Locale>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResultNotification {
+    term_id: String,
+}
+
+async fn verify(Query(params): Query<VerifyParams>) -> ApiResult<(StatusCode, String)> {
+    let challenge = whatsapp_client()?.verify_subscription(
+        &params.mode,
+        &params.verify_token,
+        params.challenge,
+    );
+    Ok(match challenge {
+        Some(challenge) => (StatusCode::OK, challenge),
+        None => (StatusCode::FORBIDDEN, String::new()),
+    })
+}
+
+/// Replies and delivery receipts, signed with the app secret
+async fn receive(headers: HeaderMap, body: Bytes) -> ApiResult<StatusCode> {
+    let signature = headers
+        .get("x-hub-signature-256")
+        .and_then(|v| v.to_str().ok());
+    WhatsappService
+        .receive(db::get_db(), whatsapp_client()?, signature, &body)
+        .await?;
+    Ok(StatusCode::OK)
+}
+
+/// Templates the school has had approved
+async fn list(
+    headers: HeaderMap,
+    Path(school_id): Path<String>,
+) -> ApiResult<Json<Vec<WhatsappTemplate>>> {
+    ensure_admin(&headers, &school_id).await?;
+    let templates = WhatsappService.templates(db::get_db(), school_id).await?;
+    Ok(Json(templates))
+}
+
+async fn save(
+    headers: HeaderMap,
+    Path(school_id): Path<String>,
+    Json(data): Json<SaveWhatsappTemplateDto>,
+) -> ApiResult<Json<Vec<WhatsappTemplate>>> {
+    ensure_admin(&headers, &school_id).await?;
+    let sdb = db::get_db();
+    WhatsappService
+        .save_template(sdb, school_id.clone(), data)
+        .await?;
+    let templates = WhatsappService.templates(sdb, school_id).await?;
+    Ok(Json(templates))
+}
+
+async fn remove(
+    headers: HeaderMap,
+    Path((school_id, template_key)): Path<(String, String)>,
+    Query(params): Query<RemoveParams>,
+) -> ApiResult<StatusCode> {
+    ensure_admin(&headers, &school_id).await?;
+    WhatsappService
+        .delete_template(db::get_db(), school_id, template_key, params.locale)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Remind the student's parents of the invoice's balance
+async fn send_fee_reminder(
+    headers: HeaderMap,
+    Path(invoice_id): Path<String>,
+) -> ApiResult<Json<Vec<MessageDelivery>>> {
+    let user = signed_in(&headers, None).await?;
+    let deliveries = WhatsappService
+        .send_fee_reminder(db::get_db(), &user, invoice_id)
+        .await?;
+    Ok(Json(deliveries))
+}
+
+/// Tell the student's parents the term's results are out
+async fn send_result_notification(
+    headers: HeaderMap,
+    Path(student_id): Path<String>,
+    Json(data): Json<ResultNotification>,
+) -> ApiResult<Json<Vec<MessageDelivery>>> {
+    let user = signed_in(&headers, None).await?;
+    let deliveries = WhatsappService
+        .send_result_notification(db::get_db(), &user, student_id, data.term_id)
+        .await?;
+    Ok(Json(deliveries))
+}
+
+/// WhatsApp templates are managed by the school's admins
+async fn ensure_admin(headers: &HeaderMap, school_id: &str) -> ApiResult<()> {
+    let user = signed_in(headers, None).await?;
+    ensure_role(&user, school_id, &[UserType::Admin])?;
+    Ok(())
+}
@@ -15,3 +15,11 @@ pub enum Error {
     #[error("Duplicate record: {0}")]
     Duplicate(String),
 }
+
+impl Error {
+    /// Whether the database gave up on a transaction that collided with a
+    /// concurrent one, and running it again may succeed
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Error::SurrealDb(err) if err.to_string().contains("can be retried"))
+    }
+}
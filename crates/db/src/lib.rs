@@ -32,6 +32,7 @@ pub async fn init_db() -> Result<()> {
     })
     .await?;
     db.use_ns("app").use_db("main").await?;
+    queries::PlatformInvoiceQ.define_indexes(&db).await?;
 
     // db.query(
     //     "
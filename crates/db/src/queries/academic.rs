@@ -98,6 +98,21 @@ impl TermQ {
         Ok(term)
     }
 
+    /// Get a term by ID
+    pub async fn get_by_id(
+        &self,
+        sdb: &Surreal<Client>,
+        term_id: String,
+    ) -> DbResult<Option<Term>> {
+        let term: Option<Term> = sdb
+            .query("SELECT * FROM type::table($table) WHERE id = type::thing($table, $id)")
+            .bind(("table", TERMS_TABLE))
+            .bind(("id", term_id))
+            .await?
+            .take(0)?;
+        Ok(term)
+    }
+
     /// Get current term for a session
     pub async fn get_current(
         &self,
@@ -174,6 +189,21 @@ impl ClassQ {
         Ok(class)
     }
 
+    /// Get a class by ID
+    pub async fn get_by_id(
+        &self,
+        sdb: &Surreal<Client>,
+        class_id: String,
+    ) -> DbResult<Option<Class>> {
+        let class: Option<Class> = sdb
+            .query("SELECT * FROM type::table($table) WHERE id = type::thing($table, $id)")
+            .bind(("table", CLASS_TABLE))
+            .bind(("id", class_id))
+            .await?
+            .take(0)?;
+        Ok(class)
+    }
+
     /// Get all classes in a school
     pub async fn get_by_school(
         &self,
@@ -304,6 +334,21 @@ impl ClassSubjectQ {
         Ok(cs)
     }
 
+    /// Get a class-subject by ID
+    pub async fn get_by_id(
+        &self,
+        sdb: &Surreal<Client>,
+        class_subject_id: String,
+    ) -> DbResult<Option<ClassSubject>> {
+        let cs: Option<ClassSubject> = sdb
+            .query("SELECT * FROM type::table($table) WHERE id = type::thing($table, $id)")
+            .bind(("table", CLASS_SUBJECT_TABLE))
+            .bind(("id", class_subject_id))
+            .await?
+            .take(0)?;
+        Ok(cs)
+    }
+
     /// Get all subjects taught in a class (with subject and teacher details)
     pub async fn get_by_class(
         &self,
@@ -1,27 +1,51 @@
-use crate::error::DbResult;
+use crate::{error::DbResult, pagination::Page};
 use shared::{
-    helpers::create_update_dtos::EnterScoreDto,
+    helpers::{
+        create_update_dtos::{EnterScoreDto, SaveGradingSchemeDto, SetAssessmentWeightingDto},
+        query_filters::ScoreFilter,
+    },
     models::{
-        Student,
+        ClassLevel, Student,
         assessments_nd_grading::{
-            Assessment, GradingScheme, ReportCard, ReportCardScore, StudentScore,
+            Assessment, AssessmentWeighting, GradingScheme, GradingSchemeVersion, ReportCard,
+            ReportCardScore, StudentScore,
         },
     },
 };
 use surrealdb::{Surreal, engine::remote::ws::Client};
-use surrealdb_types::{Decimal, SurrealValue, Value};
+use surrealdb_types::{Datetime, Decimal, RecordId, SurrealValue, Value};
 
 const ASSESSMENT_TABLE: &str = "assessments";
 const STUDENT_SCORE_TABLE: &str = "student_scores";
 const GRADING_SCHEME_TABLE: &str = "grading_schemes";
+const GRADING_SCHEME_VERSION_TABLE: &str = "grading_scheme_versions";
 const REPORT_CARD_TABLE: &str = "report_cards";
 const REPORT_CARD_SCORE_TABLE: &str = "report_card_scores";
+const STUDENT_TABLE: &str = "students";
+const ASSESSMENT_WEIGHTING_TABLE: &str = "assessment_weightings";
 
 #[derive(Debug, SurrealValue)]
 pub struct AvgResult {
     class_average: Option<f64>,
 }
 
+#[derive(Debug, SurrealValue)]
+struct RankingRow {
+    student: Student,
+    total: Decimal,
+}
+
+/// A report card's overall results across its subjects
+#[derive(Debug, Default)]
+pub struct ReportCardTotals {
+    pub total_score: Option<Decimal>,
+    pub average_percentage: Option<Decimal>,
+    pub class_position: Option<i32>,
+    pub total_students_in_class: Option<i32>,
+    pub class_average: Option<Decimal>,
+    pub generated_by: Option<RecordId>,
+}
+
 pub struct AssessmentQ;
 
 impl AssessmentQ {
@@ -35,6 +59,21 @@ impl AssessmentQ {
         Ok(assessment)
     }
 
+    /// Get an assessment by ID
+    pub async fn get_by_id(
+        &self,
+        sdb: &Surreal<Client>,
+        assessment_id: String,
+    ) -> DbResult<Option<Assessment>> {
+        let assessment: Option<Assessment> = sdb
+            .query("SELECT * FROM type::table($table) WHERE id = type::thing($table, $id)")
+            .bind(("table", ASSESSMENT_TABLE))
+            .bind(("id", assessment_id))
+            .await?
+            .take(0)?;
+        Ok(assessment)
+    }
+
     /// Get all assessments for a class + subject in a term
     pub async fn get_by_class_subject_term(
         &self,
@@ -84,28 +123,256 @@ impl AssessmentQ {
             .take(0)?;
         Ok(assessments)
     }
+
+    /// Get all assessments in a school
+    pub async fn get_by_school(
+        &self,
+        sdb: &Surreal<Client>,
+        school_id: String,
+    ) -> DbResult<Vec<Assessment>> {
+        let assessments: Vec<Assessment> = sdb
+            .query(
+                r#"
+                SELECT * FROM type::table($table)
+                WHERE school_id = type::thing('schools', $school_id)
+                "#,
+            )
+            .bind(("table", ASSESSMENT_TABLE))
+            .bind(("school_id", school_id))
+            .await?
+            .take(0)?;
+        Ok(assessments)
+    }
+}
+
+pub struct AssessmentWeightingQ;
+
+impl AssessmentWeightingQ {
+    /// Set the weighting for a class level (create or replace)
+    pub async fn upsert(
+        &self,
+        sdb: &Surreal<Client>,
+        data: SetAssessmentWeightingDto,
+    ) -> DbResult<()> {
+        sdb.query(
+            r#"
+            IF (SELECT id FROM type::table($table)
+                WHERE school_id = $school_id
+                AND class_level = $class_level)[0] {
+                UPDATE type::table($table) SET
+                    weights = $weights,
+                    updated_at = time::now()
+                WHERE school_id = $school_id
+                AND class_level = $class_level;
+            } ELSE {
+                CREATE type::table($table) CONTENT {
+                    school_id: $school_id,
+                    class_level: $class_level,
+                    weights: $weights,
+                    created_at: time::now(),
+                    updated_at: time::now()
+                };
+            };
+            "#,
+        )
+        .bind(("table", ASSESSMENT_WEIGHTING_TABLE))
+        .bind(("school_id", data.school_id))
+        .bind(("class_level", data.class_level))
+        .bind(("weights", data.weights))
+        .await?;
+        Ok(())
+    }
+
+    /// Get the weighting for a class level
+    pub async fn get(
+        &self,
+        sdb: &Surreal<Client>,
+        school_id: String,
+        class_level: ClassLevel,
+    ) -> DbResult<Option<AssessmentWeighting>> {
+        let weighting: Option<AssessmentWeighting> = sdb
+            .query(
+                r#"
+                SELECT * FROM type::table($table)
+                WHERE school_id = type::thing('schools', $school_id)
+                AND class_level = $class_level
+                LIMIT 1
+                "#,
+            )
+            .bind(("table", ASSESSMENT_WEIGHTING_TABLE))
+            .bind(("school_id", school_id))
+            .bind(("class_level", class_level))
+            .await?
+            .take(0)?;
+        Ok(weighting)
+    }
+
+    /// Get every class level's weighting for a school
+    pub async fn get_by_school(
+        &self,
+        sdb: &Surreal<Client>,
+        school_id: String,
+    ) -> DbResult<Vec<AssessmentWeighting>> {
+        let weightings: Vec<AssessmentWeighting> = sdb
+            .query(
+                r#"
+                SELECT * FROM type::table($table)
+                WHERE school_id = type::thing('schools', $school_id)
+                "#,
+            )
+            .bind(("table", ASSESSMENT_WEIGHTING_TABLE))
+            .bind(("school_id", school_id))
+            .await?
+            .take(0)?;
+        Ok(weightings)
+    }
 }
 
 pub struct StudentScoreQ;
 
 impl StudentScoreQ {
-    /// Enter a score for a student
+    /// Enter a score for a student, recording who entered it
     pub async fn enter(
         &self,
         sdb: &Surreal<Client>,
         data: EnterScoreDto,
+        entered_by: String,
     ) -> DbResult<Option<StudentScore>> {
-        let score: Option<StudentScore> = sdb.create(STUDENT_SCORE_TABLE).content(data).await?;
+        let score: Option<StudentScore> = sdb
+            .query(
+                r#"
+                CREATE type::table($table) CONTENT {
+                    assessment_id: $data.assessment_id,
+                    student_id: $data.student_id,
+                    score_obtained: $data.score_obtained,
+                    remarks: $data.remarks,
+                    entered_by: type::thing('users', $entered_by),
+                    entered_at: time::now(),
+                    updated_at: time::now(),
+                    verified: false
+                }
+                "#,
+            )
+            .bind(("table", STUDENT_SCORE_TABLE))
+            .bind(("data", data))
+            .bind(("entered_by", entered_by))
+            .await?
+            .take(0)?;
+        Ok(score)
+    }
+
+    /// Enter a score unless the student already has one for the assessment
+    pub async fn enter_if_absent(
+        &self,
+        sdb: &Surreal<Client>,
+        data: EnterScoreDto,
+        entered_by: String,
+    ) -> DbResult<Option<StudentScore>> {
+        let mut response = sdb
+            .query(
+                r#"
+                BEGIN TRANSACTION;
+                LET $existing = (SELECT VALUE id FROM type::table($table)
+                    WHERE assessment_id = $data.assessment_id
+                    AND student_id = $data.student_id
+                    LIMIT 1)[0];
+                LET $created = IF $existing = NONE {
+                    CREATE ONLY type::table($table) CONTENT {
+                        assessment_id: $data.assessment_id,
+                        student_id: $data.student_id,
+                        score_obtained: $data.score_obtained,
+                        remarks: $data.remarks,
+                        entered_by: type::thing('users', $entered_by),
+                        entered_at: time::now(),
+                        updated_at: time::now(),
+                        verified: false
+                    }
+                } ELSE {
+                    NONE
+                };
+                RETURN $created;
+                COMMIT TRANSACTION;
+                "#,
+            )
+            .bind(("table", STUDENT_SCORE_TABLE))
+            .bind(("data", data))
+            .bind(("entered_by", entered_by))
+            .await?;
+
+        let last = response.num_statements().saturating_sub(1);
+        let score: Option<StudentScore> = response.take(last)?;
+        Ok(score)
+    }
+
+    /// Update a student's unverified score, provided it is still the version
+    /// last read (`updated_at` unchanged); None if it has moved on
+    pub async fn update_if_unchanged(
+        &self,
+        sdb: &Surreal<Client>,
+        assessment_id: String,
+        student_id: String,
+        new_score: Decimal,
+        seen: Datetime,
+    ) -> DbResult<Option<StudentScore>> {
+        let score: Option<StudentScore> = sdb
+            .query(
+                r#"
+                UPDATE type::table($table) SET
+                    score_obtained = $new_score,
+                    updated_at = time::now()
+                WHERE assessment_id = type::thing('assessments', $assessment_id)
+                AND student_id = type::thing('students', $student_id)
+                AND updated_at = $seen
+                AND verified = false
+                "#,
+            )
+            .bind(("table", STUDENT_SCORE_TABLE))
+            .bind(("assessment_id", assessment_id))
+            .bind(("student_id", student_id))
+            .bind(("new_score", new_score))
+            .bind(("seen", seen))
+            .await?
+            .take(0)?;
         Ok(score)
     }
 
-    /// Update a student's score
+    /// Update a student's score unless it has been verified; None if there is
+    /// no unverified score to change
     pub async fn update(
         &self,
         sdb: &Surreal<Client>,
         assessment_id: String,
         student_id: String,
-        new_score: f64,
+        new_score: Decimal,
+    ) -> DbResult<Option<StudentScore>> {
+        let score: Option<StudentScore> = sdb
+            .query(
+                r#"
+                UPDATE type::table($table) SET
+                    score_obtained = $new_score,
+                    updated_at = time::now()
+                WHERE assessment_id = type::thing('assessments', $assessment_id)
+                AND student_id = type::thing('students', $student_id)
+                AND verified = false
+                "#,
+            )
+            .bind(("table", STUDENT_SCORE_TABLE))
+            .bind(("assessment_id", assessment_id))
+            .bind(("student_id", student_id))
+            .bind(("new_score", new_score))
+            .await?
+            .take(0)?;
+        Ok(score)
+    }
+
+    /// Change a student's score whether or not it has been verified, for
+    /// admin overrides
+    pub async fn override_score(
+        &self,
+        sdb: &Surreal<Client>,
+        assessment_id: String,
+        student_id: String,
+        new_score: Decimal,
     ) -> DbResult<Option<StudentScore>> {
         let score: Option<StudentScore> = sdb
             .query(
@@ -126,6 +393,56 @@ impl StudentScoreQ {
         Ok(score)
     }
 
+    /// Get a student's score for an assessment
+    pub async fn get(
+        &self,
+        sdb: &Surreal<Client>,
+        assessment_id: String,
+        student_id: String,
+    ) -> DbResult<Option<StudentScore>> {
+        let score: Option<StudentScore> = sdb
+            .query(
+                r#"
+                SELECT * FROM type::table($table)
+                WHERE assessment_id = type::thing('assessments', $assessment_id)
+                AND student_id = type::thing('students', $student_id)
+                LIMIT 1
+                "#,
+            )
+            .bind(("table", STUDENT_SCORE_TABLE))
+            .bind(("assessment_id", assessment_id))
+            .bind(("student_id", student_id))
+            .await?
+            .take(0)?;
+        Ok(score)
+    }
+
+    /// Get every score entered for a class + subject's assessments in a term
+    pub async fn get_by_class_subject_term(
+        &self,
+        sdb: &Surreal<Client>,
+        class_id: String,
+        subject_id: String,
+        term_id: String,
+    ) -> DbResult<Vec<StudentScore>> {
+        let scores: Vec<StudentScore> = sdb
+            .query(
+                r#"
+                SELECT * FROM type::table($table)
+                WHERE assessment_id.class_id = type::thing('classes', $class_id)
+                AND assessment_id.subject_id = type::thing('subjects', $subject_id)
+                AND assessment_id.term_id = type::thing('terms', $term_id)
+                "#,
+            )
+            .bind(("table", STUDENT_SCORE_TABLE))
+            .bind(("class_id", class_id))
+            .bind(("subject_id", subject_id))
+            .bind(("term_id", term_id))
+            .await?
+            .take(0)?;
+        Ok(scores)
+    }
+
     /// Verify all scores for an assessment
     pub async fn verify_assessment_scores(
         &self,
@@ -151,6 +468,29 @@ impl StudentScoreQ {
         Ok(scores)
     }
 
+    /// Clear verification on all scores for an assessment so they can be edited again
+    pub async fn unverify_assessment_scores(
+        &self,
+        sdb: &Surreal<Client>,
+        assessment_id: String,
+    ) -> DbResult<Vec<StudentScore>> {
+        let scores: Vec<StudentScore> = sdb
+            .query(
+                r#"
+                UPDATE type::table($table) SET
+                    verified = false,
+                    verified_by = NONE,
+                    verified_at = NONE
+                WHERE assessment_id = type::thing('assessments', $assessment_id)
+                "#,
+            )
+            .bind(("table", STUDENT_SCORE_TABLE))
+            .bind(("assessment_id", assessment_id))
+            .await?
+            .take(0)?;
+        Ok(scores)
+    }
+
     /// Get all scores for a student in a subject (with assessment details)
     pub async fn get_by_student_subject(
         &self,
@@ -176,6 +516,40 @@ impl StudentScoreQ {
         Ok(result)
     }
 
+    /// Get one page of a school's scores matching a filter on the assessment
+    pub async fn get_filtered(
+        &self,
+        sdb: &Surreal<Client>,
+        school_id: String,
+        filter: ScoreFilter,
+        page: Page,
+    ) -> DbResult<Vec<StudentScore>> {
+        let scores: Vec<StudentScore> = sdb
+            .query(
+                r#"
+                SELECT * FROM type::table($table)
+                WHERE assessment_id.school_id = type::thing('schools', $school_id)
+                AND ($class_id = NONE OR assessment_id.class_id = $class_id)
+                AND ($subject_id = NONE OR assessment_id.subject_id = $subject_id)
+                AND ($term_id = NONE OR assessment_id.term_id = $term_id)
+                AND ($assessment_id = NONE OR assessment_id = $assessment_id)
+                ORDER BY student_id, assessment_id
+                LIMIT $limit START $offset
+                "#,
+            )
+            .bind(("table", STUDENT_SCORE_TABLE))
+            .bind(("school_id", school_id))
+            .bind(("class_id", filter.class_id))
+            .bind(("subject_id", filter.subject_id))
+            .bind(("term_id", filter.term_id))
+            .bind(("assessment_id", filter.assessment_id))
+            .bind(("limit", page.limit))
+            .bind(("offset", page.offset))
+            .await?
+            .take(0)?;
+        Ok(scores)
+    }
+
     /// Get class average for an assessment
     pub async fn get_class_average(
         &self,
@@ -242,7 +616,8 @@ impl GradingSchemeQ {
         Ok(scheme)
     }
 
-    /// Get the grade for a given score in a school
+    /// Get the grade for a given score from a school's legacy bands (those not
+    /// part of a named scheme); named schemes are read by version
     pub async fn get_grade_for_score(
         &self,
         sdb: &Surreal<Client>,
@@ -254,6 +629,7 @@ impl GradingSchemeQ {
                 r#"
                 SELECT * FROM type::table($table)
                 WHERE school_id = type::thing('schools', $school_id)
+                AND scheme_version_id = NONE
                 AND $score >= min_score
                 AND $score <= max_score
                 LIMIT 1
@@ -267,7 +643,7 @@ impl GradingSchemeQ {
         Ok(result)
     }
 
-    /// Get full grading scheme for a school
+    /// Get a school's legacy bands (those not part of a named scheme)
     pub async fn get_by_school(
         &self,
         sdb: &Surreal<Client>,
@@ -278,6 +654,7 @@ impl GradingSchemeQ {
                 r#"
                 SELECT * FROM type::table($table)
                 WHERE school_id = type::thing('schools', $school_id)
+                AND scheme_version_id = NONE
                 ORDER BY min_score DESC
                 "#,
             )
@@ -287,6 +664,129 @@ impl GradingSchemeQ {
             .take(0)?;
         Ok(result)
     }
+
+    /// Create a new version of a named scheme
+    pub async fn create_version(
+        &self,
+        sdb: &Surreal<Client>,
+        data: GradingSchemeVersion,
+    ) -> DbResult<Option<GradingSchemeVersion>> {
+        let version: Option<GradingSchemeVersion> = sdb
+            .create(GRADING_SCHEME_VERSION_TABLE)
+            .content(data)
+            .await?;
+        Ok(version)
+    }
+
+    /// Save a named scheme as its next version together with its bands, in
+    /// one transaction so a version is never left without bands
+    pub async fn create_version_with_bands(
+        &self,
+        sdb: &Surreal<Client>,
+        data: SaveGradingSchemeDto,
+    ) -> DbResult<Option<GradingSchemeVersion>> {
+        let mut response = sdb
+            .query(
+                r#"
+                BEGIN TRANSACTION;
+                LET $latest = (SELECT VALUE version FROM type::table($version_table)
+                    WHERE school_id = $school_id
+                    AND scheme_name = $scheme_name
+                    ORDER BY version DESC
+                    LIMIT 1)[0] ?? 0;
+                LET $version = CREATE ONLY type::table($version_table) SET
+                    school_id = $school_id,
+                    scheme_name = $scheme_name,
+                    version = $latest + 1,
+                    class_levels = $class_levels,
+                    created_by = $created_by,
+                    created_at = time::now();
+                FOR $band IN $bands {
+                    CREATE type::table($band_table) SET
+                        school_id = $school_id,
+                        scheme_name = $scheme_name,
+                        min_score = $band.min_score,
+                        max_score = $band.max_score,
+                        grade = $band.grade,
+                        grade_point = $band.grade_point,
+                        remark = $band.remark,
+                        scheme_version_id = $version.id,
+                        created_at = time::now();
+                };
+                RETURN $version;
+                COMMIT TRANSACTION;
+                "#,
+            )
+            .bind(("version_table", GRADING_SCHEME_VERSION_TABLE))
+            .bind(("band_table", GRADING_SCHEME_TABLE))
+            .bind(("school_id", data.school_id))
+            .bind(("scheme_name", data.scheme_name))
+            .bind(("class_levels", data.class_levels))
+            .bind(("created_by", data.created_by))
+            .bind(("bands", data.bands))
+            .await?;
+
+        let last = response.num_statements().saturating_sub(1);
+        let version: Option<GradingSchemeVersion> = response.take(last)?;
+        Ok(version)
+    }
+
+    /// Get a scheme version by ID
+    pub async fn get_version(
+        &self,
+        sdb: &Surreal<Client>,
+        version_id: String,
+    ) -> DbResult<Option<GradingSchemeVersion>> {
+        let version: Option<GradingSchemeVersion> = sdb
+            .query("SELECT * FROM type::table($table) WHERE id = type::thing($table, $id)")
+            .bind(("table", GRADING_SCHEME_VERSION_TABLE))
+            .bind(("id", version_id))
+            .await?
+            .take(0)?;
+        Ok(version)
+    }
+
+    /// Get every version of every named scheme in a school, newest first per name
+    pub async fn get_versions(
+        &self,
+        sdb: &Surreal<Client>,
+        school_id: String,
+    ) -> DbResult<Vec<GradingSchemeVersion>> {
+        let versions: Vec<GradingSchemeVersion> = sdb
+            .query(
+                r#"
+                SELECT * FROM type::table($table)
+                WHERE school_id = type::thing('schools', $school_id)
+                ORDER BY scheme_name, version DESC
+                "#,
+            )
+            .bind(("table", GRADING_SCHEME_VERSION_TABLE))
+            .bind(("school_id", school_id))
+            .await?
+            .take(0)?;
+        Ok(versions)
+    }
+
+    /// Get the bands of a scheme version
+    pub async fn get_bands(
+        &self,
+        sdb: &Surreal<Client>,
+        version_id: String,
+    ) -> DbResult<Vec<GradingScheme>> {
+        let bands: Vec<GradingScheme> = sdb
+            .query(
+                r#"
+                SELECT * FROM type::table($table)
+                WHERE scheme_version_id = type::thing('grading_scheme_versions', $version_id)
+                ORDER BY min_score DESC
+                "#,
+            )
+            .bind(("table", GRADING_SCHEME_TABLE))
+            .bind(("version_id", version_id))
+            .await?
+            .take(0)?;
+        Ok(bands)
+    }
 }
 
 pub struct ReportCardQ;
@@ -302,6 +802,43 @@ impl ReportCardQ {
         Ok(card)
     }
 
+    /// Get a report card by ID
+    pub async fn get_by_id(
+        &self,
+        sdb: &Surreal<Client>,
+        report_card_id: String,
+    ) -> DbResult<Option<ReportCard>> {
+        let card: Option<ReportCard> = sdb
+            .query("SELECT * FROM type::table($table) WHERE id = type::thing($table, $id)")
+            .bind(("table", REPORT_CARD_TABLE))
+            .bind(("id", report_card_id))
+            .await?
+            .take(0)?;
+        Ok(card)
+    }
+
+    /// Pin the grading scheme version a report card is graded with
+    pub async fn set_grading_scheme(
+        &self,
+        sdb: &Surreal<Client>,
+        report_card_id: String,
+        version_id: String,
+    ) -> DbResult<Option<ReportCard>> {
+        let card: Option<ReportCard> = sdb
+            .query(
+                r#"
+                UPDATE type::thing($table, $id) SET
+                    grading_scheme_version_id = type::thing('grading_scheme_versions', $version_id)
+                "#,
+            )
+            .bind(("table", REPORT_CARD_TABLE))
+            .bind(("id", report_card_id))
+            .bind(("version_id", version_id))
+            .await?
+            .take(0)?;
+        Ok(card)
+    }
+
     /// Add subject scores to a report card
     pub async fn add_score(
         &self,
@@ -313,6 +850,57 @@ impl ReportCardQ {
         Ok(score)
     }
 
+    /// Remove a report card's subject scores before they are computed again
+    pub async fn clear_scores(
+        &self,
+        sdb: &Surreal<Client>,
+        report_card_id: String,
+    ) -> DbResult<()> {
+        sdb.query(
+            r#"
+            DELETE type::table($table)
+            WHERE report_card_id = type::thing('report_cards', $report_card_id)
+            "#,
+        )
+        .bind(("table", REPORT_CARD_SCORE_TABLE))
+        .bind(("report_card_id", report_card_id))
+        .await?;
+        Ok(())
+    }
+
+    /// Set a report card's overall results
+    pub async fn set_totals(
+        &self,
+        sdb: &Surreal<Client>,
+        report_card_id: String,
+        totals: ReportCardTotals,
+    ) -> DbResult<Option<ReportCard>> {
+        let card: Option<ReportCard> = sdb
+            .query(
+                r#"
+                UPDATE type::thing($table, $id) SET
+                    total_score = $total_score,
+                    average_percentage = $average_percentage,
+                    class_position = $class_position,
+                    total_students_in_class = $total_students_in_class,
+                    class_average = $class_average,
+                    generated_by = $generated_by,
+                    generated_at = time::now()
+                "#,
+            )
+            .bind(("table", REPORT_CARD_TABLE))
+            .bind(("id", report_card_id))
+            .bind(("total_score", totals.total_score))
+            .bind(("average_percentage", totals.average_percentage))
+            .bind(("class_position", totals.class_position))
+            .bind(("total_students_in_class", totals.total_students_in_class))
+            .bind(("class_average", totals.class_average))
+            .bind(("generated_by", totals.generated_by))
+            .await?
+            .take(0)?;
+        Ok(card)
+    }
+
     /// Publish a report card
     pub async fn publish(
         &self,
@@ -385,6 +973,95 @@ impl ReportCardQ {
         Ok(result)
     }
 
+    /// Get all report cards for a student across every term
+    pub async fn get_by_student(
+        &self,
+        sdb: &Surreal<Client>,
+        student_id: String,
+    ) -> DbResult<Vec<ReportCard>> {
+        let cards: Vec<ReportCard> = sdb
+            .query(
+                r#"
+                SELECT * FROM type::table($table)
+                WHERE student_id = type::thing('students', $student_id)
+                ORDER BY generated_at ASC
+                "#,
+            )
+            .bind(("table", REPORT_CARD_TABLE))
+            .bind(("student_id", student_id))
+            .await?
+            .take(0)?;
+        Ok(cards)
+    }
+
+    /// Get all report cards for a class in a term
+    pub async fn get_by_class_term(
+        &self,
+        sdb: &Surreal<Client>,
+        class_id: String,
+        term_id: String,
+    ) -> DbResult<Vec<ReportCard>> {
+        let cards: Vec<ReportCard> = sdb
+            .query(
+                r#"
+                SELECT * FROM type::table($table)
+                WHERE class_id = type::thing('classes', $class_id)
+                AND term_id = type::thing('terms', $term_id)
+                "#,
+            )
+            .bind(("table", REPORT_CARD_TABLE))
+            .bind(("class_id", class_id))
+            .bind(("term_id", term_id))
+            .await?
+            .take(0)?;
+        Ok(cards)
+    }
+
+    /// Get the subject scores on a report card
+    pub async fn get_scores(
+        &self,
+        sdb: &Surreal<Client>,
+        report_card_id: String,
+    ) -> DbResult<Vec<ReportCardScore>> {
+        let scores: Vec<ReportCardScore> = sdb
+            .query(
+                r#"
+                SELECT * FROM type::table($table)
+                WHERE report_card_id = type::thing('report_cards', $report_card_id)
+                "#,
+            )
+            .bind(("table", REPORT_CARD_SCORE_TABLE))
+            .bind(("report_card_id", report_card_id))
+            .await?
+            .take(0)?;
+        Ok(scores)
+    }
+
+    /// Check whether any report card for a class in a term has been published
+    pub async fn is_published(
+        &self,
+        sdb: &Surreal<Client>,
+        class_id: String,
+        term_id: String,
+    ) -> DbResult<bool> {
+        let ids: Vec<RecordId> = sdb
+            .query(
+                r#"
+                SELECT VALUE id FROM type::table($table)
+                WHERE class_id = type::thing('classes', $class_id)
+                AND term_id = type::thing('terms', $term_id)
+                AND published = true
+                LIMIT 1
+                "#,
+            )
+            .bind(("table", REPORT_CARD_TABLE))
+            .bind(("class_id", class_id))
+            .bind(("term_id", term_id))
+            .await?
+            .take(0)?;
+        Ok(!ids.is_empty())
+    }
+
     /// Get all published report cards for a term
     pub async fn get_published_by_term(
         &self,
@@ -464,7 +1141,22 @@ impl ReportCardQ {
         assessment_id: String,
         student_id: String,
     ) -> DbResult<bool> {
-        todo!()
+        let ids: Vec<RecordId> = sdb
+            .query(
+                r#"
+                SELECT VALUE id FROM type::table($table)
+                WHERE assessment_id = type::thing('assessments', $assessment_id)
+                AND student_id = type::thing('students', $student_id)
+                AND score_obtained != NONE
+                LIMIT 1
+                "#,
+            )
+            .bind(("table", STUDENT_SCORE_TABLE))
+            .bind(("assessment_id", assessment_id))
+            .bind(("student_id", student_id))
+            .await?
+            .take(0)?;
+        Ok(!ids.is_empty())
     }
 
     // Get all students who have NOT yet been scored for an assessment
@@ -474,16 +1166,54 @@ impl ReportCardQ {
         assessment_id: String,
         class_id: String,
     ) -> DbResult<Vec<Student>> {
-        todo!()
+        let students: Vec<Student> = sdb
+            .query(
+                r#"
+                LET $scored = SELECT VALUE student_id FROM type::table($score_table)
+                    WHERE assessment_id = type::thing('assessments', $assessment_id)
+                    AND score_obtained != NONE;
+                SELECT * FROM type::table($student_table)
+                WHERE current_class_id = type::thing('classes', $class_id)
+                AND status = 'active'
+                AND id NOTINSIDE $scored
+                ORDER BY last_name, first_name
+                "#,
+            )
+            .bind(("score_table", STUDENT_SCORE_TABLE))
+            .bind(("student_table", STUDENT_TABLE))
+            .bind(("assessment_id", assessment_id))
+            .bind(("class_id", class_id))
+            .await?
+            .take(1)?;
+        Ok(students)
     }
 
-    // Get class ranking for a term
+    // Get class ranking for a term: each student's raw scores summed, highest
+    // first, for classes whose level has no assessment weighting
     pub async fn get_class_ranking(
         &self,
         sdb: &Surreal<Client>,
         class_id: String,
         term_id: String,
     ) -> DbResult<Vec<(Student, Decimal)>> {
-        todo!()
+        let rows: Vec<RankingRow> = sdb
+            .query(
+                r#"
+                LET $totals = SELECT student_id, math::sum(score_obtained) AS total
+                    FROM type::table($table)
+                    WHERE assessment_id.class_id = type::thing('classes', $class_id)
+                    AND assessment_id.term_id = type::thing('terms', $term_id)
+                    AND score_obtained != NONE
+                    GROUP BY student_id;
+                SELECT student_id.* AS student, total FROM $totals
+                ORDER BY total DESC
+                "#,
+            )
+            .bind(("table", STUDENT_SCORE_TABLE))
+            .bind(("class_id", class_id))
+            .bind(("term_id", term_id))
+            .await?
+            .take(1)?;
+        Ok(rows.into_iter().map(|r| (r.student, r.total)).collect())
     }
 }
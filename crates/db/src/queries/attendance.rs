@@ -1,6 +1,6 @@
-use crate::error::DbResult;
+use crate::{error::DbResult, pagination::Page};
 use shared::{
-    helpers::create_update_dtos::MarkAttendanceDto,
+    helpers::{create_update_dtos::MarkAttendanceDto, query_filters::AttendanceFilter},
     models::{
         AttendanceStatus,
         attendance::{Attendance, AttendanceSummary},
@@ -150,6 +150,42 @@ impl AttendanceQ {
         Ok(result)
     }
 
+    /// Get one page of a school's attendance records matching a filter
+    pub async fn get_filtered(
+        &self,
+        sdb: &Surreal<Client>,
+        school_id: String,
+        filter: AttendanceFilter,
+        page: Page,
+    ) -> DbResult<Vec<Attendance>> {
+        let attendance: Vec<Attendance> = sdb
+            .query(
+                r#"
+                SELECT * FROM type::table($table)
+                WHERE class_id.school_id = type::thing('schools', $school_id)
+                AND ($student_id = NONE OR student_id = $student_id)
+                AND ($class_id = NONE OR class_id = $class_id)
+                AND ($date_from = NONE OR date >= $date_from)
+                AND ($date_to = NONE OR date <= $date_to)
+                AND ($status = NONE OR status = $status)
+                ORDER BY date, class_id, student_id
+                LIMIT $limit START $offset
+                "#,
+            )
+            .bind(("table", ATTENDANCE_TABLE))
+            .bind(("school_id", school_id))
+            .bind(("student_id", filter.student_id))
+            .bind(("class_id", filter.class_id))
+            .bind(("date_from", filter.date_from))
+            .bind(("date_to", filter.date_to))
+            .bind(("status", filter.status))
+            .bind(("limit", page.limit))
+            .bind(("offset", page.offset))
+            .await?
+            .take(0)?;
+        Ok(attendance)
+    }
+
     /// Update an attendance record
     pub async fn update_status(
         &self,
@@ -1,5 +1,13 @@
+use shared::models::system_and_audit::{EmailVerification, UserSession};
+use surrealdb::{Surreal, engine::remote::ws::Client};
+use surrealdb_types::RecordId;
+
 use crate::error::DbResult;
 
+const STUDENT_TABLE: &str = "students";
+const SESSION_TABLE: &str = "user_sessions";
+const EMAIL_VERIFICATION_TABLE: &str = "email_verifications";
+
 pub struct AuthQ;
 
 impl AuthQ {
@@ -13,12 +21,27 @@ impl AuthQ {
         todo!()
     }
 
-    /// Check if admission number is already taken in a school
+    /// Check if admission number is already taken in a school, ignoring case
     pub async fn admission_number_exists(
+        sdb: &Surreal<Client>,
         school_id: String,
         admission_number: String,
     ) -> DbResult<bool> {
-        todo!()
+        let ids: Vec<RecordId> = sdb
+            .query(
+                r#"
+                SELECT VALUE id FROM type::table($table)
+                WHERE school_id = type::thing('schools', $school_id)
+                AND string::lowercase(admission_number) = string::lowercase($admission_number)
+                LIMIT 1
+                "#,
+            )
+            .bind(("table", STUDENT_TABLE))
+            .bind(("school_id", school_id))
+            .bind(("admission_number", admission_number))
+            .await?
+            .take(0)?;
+        Ok(!ids.is_empty())
     }
 
     /// Verify user password hash (fetch hash for comparison)
@@ -41,3 +64,115 @@ impl AuthQ {
         todo!()
     }
 }
+
+pub struct SessionQ;
+
+impl SessionQ {
+    /// Create a session
+    pub async fn create(
+        &self,
+        sdb: &Surreal<Client>,
+        data: UserSession,
+    ) -> DbResult<Option<UserSession>> {
+        let session: Option<UserSession> = sdb.create(SESSION_TABLE).content(data).await?;
+        Ok(session)
+    }
+
+    /// Get an unexpired session by its token
+    pub async fn get_by_token(
+        &self,
+        sdb: &Surreal<Client>,
+        token: String,
+    ) -> DbResult<Option<UserSession>> {
+        let session: Option<UserSession> = sdb
+            .query(
+                r#"
+                SELECT * FROM type::table($table)
+                WHERE token = $token
+                AND expires_at > time::now()
+                LIMIT 1
+                "#,
+            )
+            .bind(("table", SESSION_TABLE))
+            .bind(("token", token))
+            .await?
+            .take(0)?;
+        Ok(session)
+    }
+
+    /// Delete a session (logout)
+    pub async fn delete_by_token(&self, sdb: &Surreal<Client>, token: String) -> DbResult<()> {
+        sdb.query("DELETE type::table($table) WHERE token = $token")
+            .bind(("table", SESSION_TABLE))
+            .bind(("token", token))
+            .await?;
+        Ok(())
+    }
+
+    /// Remove sessions that have expired
+    pub async fn delete_expired(&self, sdb: &Surreal<Client>) -> DbResult<()> {
+        sdb.query("DELETE type::table($table) WHERE expires_at <= time::now()")
+            .bind(("table", SESSION_TABLE))
+            .await?;
+        Ok(())
+    }
+}
+
+pub struct EmailVerificationQ;
+
+impl EmailVerificationQ {
+    /// Store a new code, replacing any the user was sent before
+    pub async fn replace(
+        &self,
+        sdb: &Surreal<Client>,
+        data: EmailVerification,
+    ) -> DbResult<Option<EmailVerification>> {
+        sdb.query("DELETE type::table($table) WHERE user_id = $user_id")
+            .bind(("table", EMAIL_VERIFICATION_TABLE))
+            .bind(("user_id", data.user_id.clone()))
+            .await?;
+        let verification: Option<EmailVerification> =
+            sdb.create(EMAIL_VERIFICATION_TABLE).content(data).await?;
+        Ok(verification)
+    }
+
+    /// The user's unexpired code, if any
+    pub async fn get_pending(
+        &self,
+        sdb: &Surreal<Client>,
+        user_id: String,
+    ) -> DbResult<Option<EmailVerification>> {
+        let verification: Option<EmailVerification> = sdb
+            .query(
+                r#"
+                SELECT * FROM type::table($table)
+                WHERE user_id = type::thing('users', $user_id)
+                AND expires_at > time::now()
+                LIMIT 1
+                "#,
+            )
+            .bind(("table", EMAIL_VERIFICATION_TABLE))
+            .bind(("user_id", user_id))
+            .await?
+            .take(0)?;
+        Ok(verification)
+    }
+
+    /// Count a wrong guess against a code
+    pub async fn record_attempt(&self, sdb: &Surreal<Client>, id: String) -> DbResult<()> {
+        sdb.query("UPDATE type::thing($table, $id) SET attempts += 1")
+            .bind(("table", EMAIL_VERIFICATION_TABLE))
+            .bind(("id", id))
+            .await?;
+        Ok(())
+    }
+
+    /// Remove the user's codes once one has been used
+    pub async fn delete_for_user(&self, sdb: &Surreal<Client>, user_id: String) -> DbResult<()> {
+        sdb.query("DELETE type::table($table) WHERE user_id = type::thing('users', $user_id)")
+            .bind(("table", EMAIL_VERIFICATION_TABLE))
+            .bind(("user_id", user_id))
+            .await?;
+        Ok(())
+    }
+}
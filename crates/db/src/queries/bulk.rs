@@ -6,18 +6,73 @@ use shared::{
     },
 };
 
+use surrealdb::{Surreal, engine::remote::ws::Client};
+
 use crate::error::DbResult;
 
+const ATTENDANCE_TABLE: &str = "attendance";
+const NOTIFICATION_TABLE: &str = "notifications";
+
 pub struct BulkQ;
 
 impl BulkQ {
-    /// Bulk mark attendance for a whole class in one query
+    /// Bulk mark attendance for a whole class in one query, replacing any
+    /// marks already recorded for the same students on that date
     pub async fn mark_class_attendance(
+        &self,
+        sdb: &Surreal<Client>,
         class_id: String,
-        date: String,
+        date: String, // "YYYY-MM-DD"
         records: Vec<MarkAttendanceDto>,
+        marked_by: String,
     ) -> DbResult<Vec<Attendance>> {
-        todo!()
+        let attendance: Vec<Attendance> = sdb
+            .query(
+                r#"
+                LET $day = type::datetime($date);
+                LET $class = type::thing('classes', $class_id);
+                LET $marker = type::thing('users', $marked_by);
+                FOR $record IN $records {
+                    IF (SELECT id FROM type::table($table)
+                        WHERE student_id = $record.student_id
+                        AND date = $day)[0] {
+                        UPDATE type::table($table) SET
+                            class_id = $class,
+                            status = $record.status,
+                            arrival_time = $record.arrival_time,
+                            reason = $record.reason,
+                            marked_by = $marker,
+                            updated_at = time::now()
+                        WHERE student_id = $record.student_id
+                        AND date = $day;
+                    } ELSE {
+                        CREATE type::table($table) CONTENT {
+                            student_id: $record.student_id,
+                            class_id: $class,
+                            date: $day,
+                            status: $record.status,
+                            arrival_time: $record.arrival_time,
+                            reason: $record.reason,
+                            marked_by: $marker,
+                            marked_at: time::now(),
+                            updated_at: time::now()
+                        };
+                    };
+                };
+                SELECT * FROM type::table($table)
+                WHERE class_id = $class
+                AND date = $day
+                ORDER BY student_id;
+                "#,
+            )
+            .bind(("table", ATTENDANCE_TABLE))
+            .bind(("class_id", class_id))
+            .bind(("date", date))
+            .bind(("records", records))
+            .bind(("marked_by", marked_by))
+            .await?
+            .take(4)?;
+        Ok(attendance)
     }
 
     /// Bulk enter scores for all students in an assessment
@@ -45,8 +100,14 @@ impl BulkQ {
 
     /// Bulk send notifications to users
     pub async fn create_notifications_batch(
+        &self,
+        sdb: &Surreal<Client>,
         notifications: Vec<Notification>,
     ) -> DbResult<Vec<Notification>> {
-        todo!()
+        let created: Vec<Notification> = sdb
+            .insert(NOTIFICATION_TABLE)
+            .content(notifications)
+            .await?;
+        Ok(created)
     }
 }
@@ -1,10 +1,18 @@
 use crate::error::DbResult;
-use shared::models::{
-    communication::{Announcement, Event, EventRsvp, Message},
-    system_and_audit::{Notification, SmsLog},
+use shared::{
+    helpers::create_update_dtos::{SaveMessageTemplateDto, SaveWhatsappTemplateDto},
+    i18n::Locale,
+    models::{
+        DeliveryStatus, MessageChannel,
+        communication::{
+            Announcement, Conversation, Event, EventRsvp, Message, MessageAttachment,
+            MessageTemplate, WhatsappTemplate,
+        },
+        system_and_audit::{MessageDelivery, Notification, PushSubscription, SmsLog},
+    },
 };
-use surrealdb::{Surreal, engine::remote::ws::Client};
-use surrealdb_types::{SurrealValue, Value};
+use surrealdb::{Surreal, engine::remote::ws::Client, method::QueryStream};
+use surrealdb_types::{Datetime, Decimal, RecordId, SurrealValue, Value};
 
 const ANNOUNCEMENT_TABLE: &str = "announcements";
 const MESSAGE_TABLE: &str = "messages";
@@ -12,12 +20,36 @@ const EVENT_TABLE: &str = "events";
 const EVENT_RSVP_TABLE: &str = "event_rsvps";
 const NOTIFICATION_TABLE: &str = "notifications";
 const SMS_LOG_TABLE: &str = "sms_log";
+const MESSAGE_TEMPLATE_TABLE: &str = "message_templates";
+const MESSAGE_DELIVERY_TABLE: &str = "message_deliveries";
+const PUSH_SUBSCRIPTION_TABLE: &str = "push_subscriptions";
+const WHATSAPP_TEMPLATE_TABLE: &str = "whatsapp_templates";
+const CONVERSATION_TABLE: &str = "conversations";
+const MESSAGE_ATTACHMENT_TABLE: &str = "message_attachments";
+
+/// Changes to records matching a `LIVE SELECT`, as they happen
+pub type LiveStream<T> = QueryStream<surrealdb::Notification<T>>;
+
+/// How many missed records a reconnecting client is sent at most
+const REPLAY_LIMIT: u32 = 200;
 
 #[derive(Debug, SurrealValue)]
 struct CountTotal {
     total: Option<u64>,
 }
 
+#[derive(Debug, SurrealValue)]
+struct SmsUsage {
+    total: Option<u64>,
+    cost: Option<Decimal>,
+}
+
+#[derive(Debug, SurrealValue)]
+struct ConversationUnread {
+    conversation_id: RecordId,
+    total: u64,
+}
+
 #[derive(Debug, SurrealValue)]
 struct MonthlyCost {
     total_cost: Option<f64>,
@@ -37,6 +69,21 @@ impl AnnouncementQ {
         Ok(announcement)
     }
 
+    /// Get announcement by ID
+    pub async fn get_by_id(
+        &self,
+        sdb: &Surreal<Client>,
+        announcement_id: String,
+    ) -> DbResult<Option<Announcement>> {
+        let announcement: Option<Announcement> = sdb
+            .query("SELECT * FROM type::table($table) WHERE id = type::thing($table, $id)")
+            .bind(("table", ANNOUNCEMENT_TABLE))
+            .bind(("id", announcement_id))
+            .await?
+            .take(0)?;
+        Ok(announcement)
+    }
+
     /// Get published announcements for a school
     pub async fn get_published(
         &self,
@@ -167,206 +214,1032 @@ impl MessageQ {
         Ok(messages)
     }
 
-    /// Count unread messages for a user
-    pub async fn count_unread(&self, sdb: &Surreal<Client>, user_id: String) -> DbResult<u64> {
-        let mut response = sdb
-            .query(
-                r#"
-                SELECT count() AS total
-                FROM type::table($table)
-                WHERE recipient_id = type::thing('users', $user_id)
-                AND read = false
-                "#,
-            )
+    /// Get a message by ID
+    pub async fn get_by_id(
+        &self,
+        sdb: &Surreal<Client>,
+        message_id: String,
+    ) -> DbResult<Option<Message>> {
+        let message: Option<Message> = sdb
+            .query("SELECT * FROM type::table($table) WHERE id = type::thing($table, $id)")
             .bind(("table", MESSAGE_TABLE))
-            .bind(("user_id", user_id))
-            .await?;
-
-        let result: Option<CountTotal> = response.take(0)?;
-        Ok(result.and_then(|r| r.total).unwrap_or(0))
-    }
-}
-
-pub struct EventQ;
-
-impl EventQ {
-    /// Create an event
-    pub async fn create(&self, sdb: &Surreal<Client>, data: Event) -> DbResult<Option<Event>> {
-        let event: Option<Event> = sdb.create(EVENT_TABLE).content(data).await?;
-        Ok(event)
+            .bind(("id", message_id))
+            .await?
+            .take(0)?;
+        Ok(message)
     }
 
-    /// Get upcoming events for a school
-    pub async fn get_upcoming(
+    /// Messages a user received in a school since a moment, oldest first
+    pub async fn get_received_since(
         &self,
         sdb: &Surreal<Client>,
         school_id: String,
-    ) -> DbResult<Vec<Event>> {
-        let events: Vec<Event> = sdb
+        user_id: String,
+        since: Datetime,
+    ) -> DbResult<Vec<Message>> {
+        let messages: Vec<Message> = sdb
             .query(
                 r#"
                 SELECT * FROM type::table($table)
                 WHERE school_id = type::thing('schools', $school_id)
-                AND event_date >= time::today()
-                ORDER BY event_date ASC
+                AND recipient_id = type::thing('users', $user_id)
+                AND sent_at >= $since
+                ORDER BY sent_at ASC
+                LIMIT $limit
                 "#,
             )
-            .bind(("table", EVENT_TABLE))
+            .bind(("table", MESSAGE_TABLE))
             .bind(("school_id", school_id))
+            .bind(("user_id", user_id))
+            .bind(("since", since))
+            .bind(("limit", REPLAY_LIMIT))
             .await?
             .take(0)?;
-        Ok(events)
+        Ok(messages)
     }
 
-    /// RSVP to an event
-    pub async fn rsvp(
+    /// Watch for messages sent to a user in a school
+    pub async fn live_received(
         &self,
         sdb: &Surreal<Client>,
-        data: EventRsvp,
-    ) -> DbResult<Option<EventRsvp>> {
-        let rsvp: Option<EventRsvp> = sdb.create(EVENT_RSVP_TABLE).content(data).await?;
-        Ok(rsvp)
+        school_id: String,
+        user_id: String,
+    ) -> DbResult<LiveStream<Message>> {
+        let stream = sdb
+            .query(format!(
+                "LIVE SELECT * FROM {MESSAGE_TABLE} \
+                 WHERE school_id = $school_id AND recipient_id = $user_id"
+            ))
+            .bind(("school_id", RecordId::new("schools", school_id)))
+            .bind(("user_id", RecordId::new("users", user_id)))
+            .await?
+            .stream(0)?;
+        Ok(stream)
     }
 
-    /// Get RSVP summary for an event
-    pub async fn get_rsvp_summary(
+    /// The latest message between two users, either way
+    pub async fn get_latest_between(
         &self,
         sdb: &Surreal<Client>,
-        event_id: String,
-    ) -> DbResult<Vec<Value>> {
-        let result: Vec<Value> = sdb
+        user1_id: String,
+        user2_id: String,
+    ) -> DbResult<Option<Message>> {
+        let message: Option<Message> = sdb
             .query(
                 r#"
-                SELECT response, count() AS count
-                FROM type::table($table)
-                WHERE event_id = type::thing('events', $event_id)
-                GROUP BY response
+                SELECT * FROM type::table($table)
+                WHERE (
+                    sender_id = type::thing('users', $user1_id)
+                    AND recipient_id = type::thing('users', $user2_id)
+                ) OR (
+                    sender_id = type::thing('users', $user2_id)
+                    AND recipient_id = type::thing('users', $user1_id)
+                )
+                ORDER BY sent_at DESC
+                LIMIT 1
                 "#,
             )
-            .bind(("table", EVENT_RSVP_TABLE))
-            .bind(("event_id", event_id))
+            .bind(("table", MESSAGE_TABLE))
+            .bind(("user1_id", user1_id))
+            .bind(("user2_id", user2_id))
             .await?
             .take(0)?;
-        Ok(result)
+        Ok(message)
     }
-}
 
-pub struct NotificationQ;
+    /// The latest message a user sent or received
+    pub async fn get_latest_for(
+        &self,
+        sdb: &Surreal<Client>,
+        user_id: String,
+    ) -> DbResult<Option<Message>> {
+        let message: Option<Message> = sdb
+            .query(
+                r#"
+                SELECT * FROM type::table($table)
+                WHERE sender_id = type::thing('users', $user_id)
+                OR recipient_id = type::thing('users', $user_id)
+                ORDER BY sent_at DESC
+                LIMIT 1
+                "#,
+            )
+            .bind(("table", MESSAGE_TABLE))
+            .bind(("user_id", user_id))
+            .await?
+            .take(0)?;
+        Ok(message)
+    }
 
-impl NotificationQ {
-    /// Create a notification
-    pub async fn create(
+    /// Mark a message as answered
+    pub async fn mark_replied(&self, sdb: &Surreal<Client>, message_id: String) -> DbResult<()> {
+        sdb.query("UPDATE type::thing($table, $id) SET replied = true")
+            .bind(("table", MESSAGE_TABLE))
+            .bind(("id", message_id))
+            .await?;
+        Ok(())
+    }
+
+    /// A conversation's messages, oldest first
+    pub async fn get_by_conversation(
         &self,
         sdb: &Surreal<Client>,
-        data: Notification,
-    ) -> DbResult<Option<Notification>> {
-        let notification: Option<Notification> =
-            sdb.create(NOTIFICATION_TABLE).content(data).await?;
-        Ok(notification)
+        conversation_id: String,
+    ) -> DbResult<Vec<Message>> {
+        let messages: Vec<Message> = sdb
+            .query(
+                r#"
+                SELECT * FROM type::table($table)
+                WHERE conversation_id = type::thing('conversations', $conversation_id)
+                ORDER BY sent_at ASC
+                "#,
+            )
+            .bind(("table", MESSAGE_TABLE))
+            .bind(("conversation_id", conversation_id))
+            .await?
+            .take(0)?;
+        Ok(messages)
     }
 
-    /// Get unread notifications for a user
-    pub async fn get_unread(
+    /// The latest message in a conversation
+    pub async fn get_latest_in(
         &self,
         sdb: &Surreal<Client>,
-        user_id: String,
-    ) -> DbResult<Vec<Notification>> {
-        let notifications: Vec<Notification> = sdb
+        conversation_id: String,
+    ) -> DbResult<Option<Message>> {
+        let message: Option<Message> = sdb
             .query(
                 r#"
                 SELECT * FROM type::table($table)
-                WHERE user_id = type::thing('users', $user_id)
-                AND read = false
+                WHERE conversation_id = type::thing('conversations', $conversation_id)
                 ORDER BY sent_at DESC
+                LIMIT 1
                 "#,
             )
-            .bind(("table", NOTIFICATION_TABLE))
-            .bind(("user_id", user_id))
+            .bind(("table", MESSAGE_TABLE))
+            .bind(("conversation_id", conversation_id))
             .await?
             .take(0)?;
-        Ok(notifications)
+        Ok(message)
     }
 
-    /// Mark a notification as read
-    pub async fn mark_read(
+    /// Unread messages a user has in each of their conversations
+    pub async fn count_unread_by_conversation(
         &self,
         sdb: &Surreal<Client>,
-        notification_id: String,
-    ) -> DbResult<Option<Notification>> {
-        let notification: Option<Notification> = sdb
+        user_id: String,
+    ) -> DbResult<Vec<(RecordId, u64)>> {
+        let counts: Vec<ConversationUnread> = sdb
             .query(
                 r#"
-                UPDATE type::thing($table, $id) SET
-                    read = true,
-                    read_at = time::now()
+                SELECT conversation_id, count() AS total
+                FROM type::table($table)
+                WHERE recipient_id = type::thing('users', $user_id)
+                AND read = false
+                AND conversation_id != NONE
+                GROUP BY conversation_id
                 "#,
             )
-            .bind(("table", NOTIFICATION_TABLE))
-            .bind(("id", notification_id))
+            .bind(("table", MESSAGE_TABLE))
+            .bind(("user_id", user_id))
             .await?
             .take(0)?;
-        Ok(notification)
+        Ok(counts
+            .into_iter()
+            .map(|c| (c.conversation_id, c.total))
+            .collect())
     }
 
-    /// Mark all notifications as read for a user
-    pub async fn mark_all_read(
+    /// Mark everything a user was sent in a conversation as read, returning
+    /// the messages that were unread
+    pub async fn mark_conversation_read(
         &self,
         sdb: &Surreal<Client>,
+        conversation_id: String,
         user_id: String,
-    ) -> DbResult<Vec<Notification>> {
-        let notifications: Vec<Notification> = sdb
+    ) -> DbResult<Vec<Message>> {
+        let messages: Vec<Message> = sdb
             .query(
                 r#"
                 UPDATE type::table($table) SET
                     read = true,
                     read_at = time::now()
-                WHERE user_id = type::thing('users', $user_id)
+                WHERE conversation_id = type::thing('conversations', $conversation_id)
+                AND recipient_id = type::thing('users', $user_id)
                 AND read = false
                 "#,
             )
-            .bind(("table", NOTIFICATION_TABLE))
+            .bind(("table", MESSAGE_TABLE))
+            .bind(("conversation_id", conversation_id))
             .bind(("user_id", user_id))
             .await?
             .take(0)?;
-        Ok(notifications)
-    }
-}
-
-pub struct SmsLogQ;
-
-impl SmsLogQ {
-    /// Log an SMS
-    pub async fn log(&self, sdb: &Surreal<Client>, data: SmsLog) -> DbResult<Option<SmsLog>> {
-        let log: Option<SmsLog> = sdb.create(SMS_LOG_TABLE).content(data).await?;
-        Ok(log)
+        Ok(messages)
     }
 
-    /// Get total SMS cost for a school in the current month
-    pub async fn get_monthly_cost(
+    /// Withdraw a message from its conversation on an admin's behalf
+    pub async fn hide(
         &self,
         sdb: &Surreal<Client>,
-        school_id: String,
-    ) -> DbResult<Option<f64>> {
+        message_id: String,
+        admin_id: String,
+    ) -> DbResult<Option<Message>> {
+        let message: Option<Message> = sdb
+            .query(
+                r#"
+                UPDATE type::thing($table, $id) SET
+                    hidden_by = type::thing('users', $admin_id),
+                    hidden_at = time::now()
+                "#,
+            )
+            .bind(("table", MESSAGE_TABLE))
+            .bind(("id", message_id))
+            .bind(("admin_id", admin_id))
+            .await?
+            .take(0)?;
+        Ok(message)
+    }
+
+    /// Count unread messages for a user
+    pub async fn count_unread(&self, sdb: &Surreal<Client>, user_id: String) -> DbResult<u64> {
         let mut response = sdb
             .query(
                 r#"
-                SELECT math::sum(cost) AS total_cost
+                SELECT count() AS total
                 FROM type::table($table)
-                WHERE school_id = type::thing('schools', $school_id)
-                AND sent_at >= time::floor(time::now(), 1M)
-                AND sent_at < time::now()
+                WHERE recipient_id = type::thing('users', $user_id)
+                AND read = false
                 "#,
             )
-            .bind(("table", SMS_LOG_TABLE))
-            .bind(("school_id", school_id))
+            .bind(("table", MESSAGE_TABLE))
+            .bind(("user_id", user_id))
             .await?;
 
-        let result: Option<MonthlyCost> = response.take(0)?;
-        Ok(result.and_then(|r| r.total_cost))
+        let result: Option<CountTotal> = response.take(0)?;
+        Ok(result.and_then(|r| r.total).unwrap_or(0))
     }
+}
 
-    /// Get SMS logs for a school
-    pub async fn get_by_school(
+pub struct EventQ;
+
+impl EventQ {
+    /// Create an event
+    pub async fn create(&self, sdb: &Surreal<Client>, data: Event) -> DbResult<Option<Event>> {
+        let event: Option<Event> = sdb.create(EVENT_TABLE).content(data).await?;
+        Ok(event)
+    }
+
+    /// Get upcoming events for a school
+    pub async fn get_upcoming(
+        &self,
+        sdb: &Surreal<Client>,
+        school_id: String,
+    ) -> DbResult<Vec<Event>> {
+        let events: Vec<Event> = sdb
+            .query(
+                r#"
+                SELECT * FROM type::table($table)
+                WHERE school_id = type::thing('schools', $school_id)
+                AND event_date >= time::today()
+                ORDER BY event_date ASC
+                "#,
+            )
+            .bind(("table", EVENT_TABLE))
+            .bind(("school_id", school_id))
+            .await?
+            .take(0)?;
+        Ok(events)
+    }
+
+    /// A school's events on or after a date, soonest first
+    pub async fn get_since(
+        &self,
+        sdb: &Surreal<Client>,
+        school_id: String,
+        since: Value,
+    ) -> DbResult<Vec<Event>> {
+        let events: Vec<Event> = sdb
+            .query(
+                r#"
+                SELECT * FROM type::table($table)
+                WHERE school_id = type::thing('schools', $school_id)
+                AND event_date >= $since
+                ORDER BY event_date ASC
+                "#,
+            )
+            .bind(("table", EVENT_TABLE))
+            .bind(("school_id", school_id))
+            .bind(("since", since))
+            .await?
+            .take(0)?;
+        Ok(events)
+    }
+
+    /// Get an event by ID
+    pub async fn get_by_id(
+        &self,
+        sdb: &Surreal<Client>,
+        event_id: String,
+    ) -> DbResult<Option<Event>> {
+        let event: Option<Event> = sdb
+            .query("SELECT * FROM type::table($table) WHERE id = type::thing($table, $id)")
+            .bind(("table", EVENT_TABLE))
+            .bind(("id", event_id))
+            .await?
+            .take(0)?;
+        Ok(event)
+    }
+
+    /// Upcoming events taking replies whose deadline falls within the next
+    /// few days, and whose non-responders haven't been reminded
+    pub async fn get_needing_reminders(
+        &self,
+        sdb: &Surreal<Client>,
+        days_ahead: i64,
+    ) -> DbResult<Vec<Event>> {
+        let events: Vec<Event> = sdb
+            .query(
+                r#"
+                SELECT * FROM type::table($table)
+                WHERE requires_rsvp = true
+                AND rsvp_reminder_sent_at = NONE
+                AND event_date >= time::today()
+                AND rsvp_deadline >= time::today()
+                AND rsvp_deadline <= time::today() + duration::from::days($days)
+                "#,
+            )
+            .bind(("table", EVENT_TABLE))
+            .bind(("days", days_ahead))
+            .await?
+            .take(0)?;
+        Ok(events)
+    }
+
+    /// Record that non-responders were reminded
+    pub async fn mark_reminded(&self, sdb: &Surreal<Client>, event_id: String) -> DbResult<()> {
+        sdb.query("UPDATE type::thing($table, $id) SET rsvp_reminder_sent_at = time::now()")
+            .bind(("table", EVENT_TABLE))
+            .bind(("id", event_id))
+            .await?;
+        Ok(())
+    }
+
+    /// RSVP to an event
+    pub async fn rsvp(
+        &self,
+        sdb: &Surreal<Client>,
+        data: EventRsvp,
+    ) -> DbResult<Option<EventRsvp>> {
+        let rsvp: Option<EventRsvp> = sdb.create(EVENT_RSVP_TABLE).content(data).await?;
+        Ok(rsvp)
+    }
+
+    /// Change a reply
+    pub async fn update_rsvp(
+        &self,
+        sdb: &Surreal<Client>,
+        rsvp_id: String,
+        data: EventRsvp,
+    ) -> DbResult<Option<EventRsvp>> {
+        let rsvp: Option<EventRsvp> = sdb
+            .query(
+                r#"
+                UPDATE type::thing($table, $id) MERGE {
+                    response: $data.response,
+                    number_of_guests: $data.number_of_guests,
+                    ticket_code: $data.ticket_code,
+                    responded_at: $data.responded_at
+                }
+                "#,
+            )
+            .bind(("table", EVENT_RSVP_TABLE))
+            .bind(("id", rsvp_id))
+            .bind(("data", data))
+            .await?
+            .take(0)?;
+        Ok(rsvp)
+    }
+
+    /// Every reply to an event
+    pub async fn get_rsvps(
+        &self,
+        sdb: &Surreal<Client>,
+        event_id: String,
+    ) -> DbResult<Vec<EventRsvp>> {
+        let rsvps: Vec<EventRsvp> = sdb
+            .query(
+                r#"
+                SELECT * FROM type::table($table)
+                WHERE event_id = type::thing('events', $event_id)
+                ORDER BY responded_at ASC
+                "#,
+            )
+            .bind(("table", EVENT_RSVP_TABLE))
+            .bind(("event_id", event_id))
+            .await?
+            .take(0)?;
+        Ok(rsvps)
+    }
+
+    /// Get a reply by ID
+    pub async fn get_rsvp_by_id(
+        &self,
+        sdb: &Surreal<Client>,
+        rsvp_id: String,
+    ) -> DbResult<Option<EventRsvp>> {
+        let rsvp: Option<EventRsvp> = sdb
+            .query("SELECT * FROM type::table($table) WHERE id = type::thing($table, $id)")
+            .bind(("table", EVENT_RSVP_TABLE))
+            .bind(("id", rsvp_id))
+            .await?
+            .take(0)?;
+        Ok(rsvp)
+    }
+
+    /// The reply a ticket was issued for
+    pub async fn get_rsvp_by_ticket(
+        &self,
+        sdb: &Surreal<Client>,
+        ticket_code: String,
+    ) -> DbResult<Option<EventRsvp>> {
+        let rsvp: Option<EventRsvp> = sdb
+            .query("SELECT * FROM type::table($table) WHERE ticket_code = $code LIMIT 1")
+            .bind(("table", EVENT_RSVP_TABLE))
+            .bind(("code", ticket_code))
+            .await?
+            .take(0)?;
+        Ok(rsvp)
+    }
+
+    /// Admit a ticket, unless it was already scanned
+    pub async fn check_in(
+        &self,
+        sdb: &Surreal<Client>,
+        rsvp_id: String,
+        staff_id: String,
+    ) -> DbResult<Option<EventRsvp>> {
+        let rsvp: Option<EventRsvp> = sdb
+            .query(
+                r#"
+                UPDATE type::thing($table, $id) SET
+                    checked_in_at = time::now(),
+                    checked_in_by = type::thing('users', $staff_id)
+                WHERE checked_in_at = NONE
+                "#,
+            )
+            .bind(("table", EVENT_RSVP_TABLE))
+            .bind(("id", rsvp_id))
+            .bind(("staff_id", staff_id))
+            .await?
+            .take(0)?;
+        Ok(rsvp)
+    }
+
+    /// Get RSVP summary for an event
+    pub async fn get_rsvp_summary(
+        &self,
+        sdb: &Surreal<Client>,
+        event_id: String,
+    ) -> DbResult<Vec<Value>> {
+        let result: Vec<Value> = sdb
+            .query(
+                r#"
+                SELECT response, count() AS count
+                FROM type::table($table)
+                WHERE event_id = type::thing('events', $event_id)
+                GROUP BY response
+                "#,
+            )
+            .bind(("table", EVENT_RSVP_TABLE))
+            .bind(("event_id", event_id))
+            .await?
+            .take(0)?;
+        Ok(result)
+    }
+}
+
+pub struct NotificationQ;
+
+impl NotificationQ {
+    /// Create a notification
+    pub async fn create(
+        &self,
+        sdb: &Surreal<Client>,
+        data: Notification,
+    ) -> DbResult<Option<Notification>> {
+        let notification: Option<Notification> =
+            sdb.create(NOTIFICATION_TABLE).content(data).await?;
+        Ok(notification)
+    }
+
+    /// Get unread notifications for a user
+    pub async fn get_unread(
+        &self,
+        sdb: &Surreal<Client>,
+        user_id: String,
+    ) -> DbResult<Vec<Notification>> {
+        let notifications: Vec<Notification> = sdb
+            .query(
+                r#"
+                SELECT * FROM type::table($table)
+                WHERE user_id = type::thing('users', $user_id)
+                AND read = false
+                ORDER BY sent_at DESC
+                "#,
+            )
+            .bind(("table", NOTIFICATION_TABLE))
+            .bind(("user_id", user_id))
+            .await?
+            .take(0)?;
+        Ok(notifications)
+    }
+
+    /// Mark a notification as read
+    pub async fn mark_read(
+        &self,
+        sdb: &Surreal<Client>,
+        notification_id: String,
+    ) -> DbResult<Option<Notification>> {
+        let notification: Option<Notification> = sdb
+            .query(
+                r#"
+                UPDATE type::thing($table, $id) SET
+                    read = true,
+                    read_at = time::now()
+                "#,
+            )
+            .bind(("table", NOTIFICATION_TABLE))
+            .bind(("id", notification_id))
+            .await?
+            .take(0)?;
+        Ok(notification)
+    }
+
+    /// Get a notification by ID
+    pub async fn get_by_id(
+        &self,
+        sdb: &Surreal<Client>,
+        notification_id: String,
+    ) -> DbResult<Option<Notification>> {
+        let notification: Option<Notification> = sdb
+            .query("SELECT * FROM type::table($table) WHERE id = type::thing($table, $id)")
+            .bind(("table", NOTIFICATION_TABLE))
+            .bind(("id", notification_id))
+            .await?
+            .take(0)?;
+        Ok(notification)
+    }
+
+    /// Notifications for a user since a moment, oldest first
+    pub async fn get_since(
+        &self,
+        sdb: &Surreal<Client>,
+        user_id: String,
+        since: Datetime,
+    ) -> DbResult<Vec<Notification>> {
+        let notifications: Vec<Notification> = sdb
+            .query(
+                r#"
+                SELECT * FROM type::table($table)
+                WHERE user_id = type::thing('users', $user_id)
+                AND sent_at >= $since
+                ORDER BY sent_at ASC
+                LIMIT $limit
+                "#,
+            )
+            .bind(("table", NOTIFICATION_TABLE))
+            .bind(("user_id", user_id))
+            .bind(("since", since))
+            .bind(("limit", REPLAY_LIMIT))
+            .await?
+            .take(0)?;
+        Ok(notifications)
+    }
+
+    /// Watch for notifications to a user
+    pub async fn live(
+        &self,
+        sdb: &Surreal<Client>,
+        user_id: String,
+    ) -> DbResult<LiveStream<Notification>> {
+        let stream = sdb
+            .query(format!(
+                "LIVE SELECT * FROM {NOTIFICATION_TABLE} WHERE user_id = $user_id"
+            ))
+            .bind(("user_id", RecordId::new("users", user_id)))
+            .await?
+            .stream(0)?;
+        Ok(stream)
+    }
+
+    /// Mark all notifications as read for a user
+    pub async fn mark_all_read(
+        &self,
+        sdb: &Surreal<Client>,
+        user_id: String,
+    ) -> DbResult<Vec<Notification>> {
+        let notifications: Vec<Notification> = sdb
+            .query(
+                r#"
+                UPDATE type::table($table) SET
+                    read = true,
+                    read_at = time::now()
+                WHERE user_id = type::thing('users', $user_id)
+                AND read = false
+                "#,
+            )
+            .bind(("table", NOTIFICATION_TABLE))
+            .bind(("user_id", user_id))
+            .await?
+            .take(0)?;
+        Ok(notifications)
+    }
+}
+
+pub struct MessageTemplateQ;
+
+impl MessageTemplateQ {
+    /// A school's templates for one kind of message, in every language
+    pub async fn find(
+        &self,
+        sdb: &Surreal<Client>,
+        school_id: String,
+        channel: MessageChannel,
+        template_key: String,
+    ) -> DbResult<Vec<MessageTemplate>> {
+        let templates: Vec<MessageTemplate> = sdb
+            .query(
+                r#"
+                SELECT * FROM type::table($table)
+                WHERE school_id = type::thing('schools', $school_id)
+                AND channel = $channel
+                AND template_key = $template_key
+                "#,
+            )
+            .bind(("table", MESSAGE_TEMPLATE_TABLE))
+            .bind(("school_id", school_id))
+            .bind(("channel", channel))
+            .bind(("template_key", template_key))
+            .await?
+            .take(0)?;
+        Ok(templates)
+    }
+
+    /// Every template a school has customised
+    pub async fn get_by_school(
+        &self,
+        sdb: &Surreal<Client>,
+        school_id: String,
+    ) -> DbResult<Vec<MessageTemplate>> {
+        let templates: Vec<MessageTemplate> = sdb
+            .query(
+                r#"
+                SELECT * FROM type::table($table)
+                WHERE school_id = type::thing('schools', $school_id)
+                ORDER BY channel, template_key, locale
+                "#,
+            )
+            .bind(("table", MESSAGE_TEMPLATE_TABLE))
+            .bind(("school_id", school_id))
+            .await?
+            .take(0)?;
+        Ok(templates)
+    }
+
+    /// Create or replace a school's template for one kind of message and language
+    pub async fn upsert(
+        &self,
+        sdb: &Surreal<Client>,
+        school_id: String,
+        data: SaveMessageTemplateDto,
+    ) -> DbResult<()> {
+        sdb.query(
+            r#"
+            IF (SELECT id FROM type::table($table)
+                WHERE school_id = type::thing('schools', $school_id)
+                AND channel = $channel
+                AND template_key = $template_key
+                AND locale = $locale)[0] {
+                UPDATE type::table($table) SET
+                    subject = $subject,
+                    body = $body,
+                    updated_at = time::now()
+                WHERE school_id = type::thing('schools', $school_id)
+                AND channel = $channel
+                AND template_key = $template_key
+                AND locale = $locale;
+            } ELSE {
+                CREATE type::table($table) CONTENT {
+                    school_id: type::thing('schools', $school_id),
+                    channel: $channel,
+                    template_key: $template_key,
+                    locale: $locale,
+                    subject: $subject,
+                    body: $body,
+                    created_at: time::now(),
+                    updated_at: time::now()
+                };
+            };
+            "#,
+        )
+        .bind(("table", MESSAGE_TEMPLATE_TABLE))
+        .bind(("school_id", school_id))
+        .bind(("channel", data.channel))
+        .bind(("template_key", data.template_key))
+        .bind(("locale", data.locale))
+        .bind(("subject", data.subject))
+        .bind(("body", data.body))
+        .await?;
+        Ok(())
+    }
+
+    /// Remove a school's template so the built-in wording is used again
+    pub async fn delete(
+        &self,
+        sdb: &Surreal<Client>,
+        school_id: String,
+        channel: MessageChannel,
+        template_key: String,
+        locale: Option<Locale>,
+    ) -> DbResult<()> {
+        sdb.query(
+            r#"
+            DELETE type::table($table)
+            WHERE school_id = type::thing('schools', $school_id)
+            AND channel = $channel
+            AND template_key = $template_key
+            AND locale = $locale
+            "#,
+        )
+        .bind(("table", MESSAGE_TEMPLATE_TABLE))
+        .bind(("school_id", school_id))
+        .bind(("channel", channel))
+        .bind(("template_key", template_key))
+        .bind(("locale", locale))
+        .await?;
+        Ok(())
+    }
+}
+
+pub struct MessageDeliveryQ;
+
+impl MessageDeliveryQ {
+    /// Queue deliveries in one statement
+    pub async fn create_many(
+        &self,
+        sdb: &Surreal<Client>,
+        deliveries: Vec<MessageDelivery>,
+    ) -> DbResult<Vec<MessageDelivery>> {
+        let created: Vec<MessageDelivery> = sdb
+            .insert(MESSAGE_DELIVERY_TABLE)
+            .content(deliveries)
+            .await?;
+        Ok(created)
+    }
+
+    /// Oldest queued deliveries for a channel
+    pub async fn get_queued(
+        &self,
+        sdb: &Surreal<Client>,
+        channel: MessageChannel,
+        limit: u32,
+    ) -> DbResult<Vec<MessageDelivery>> {
+        let deliveries: Vec<MessageDelivery> = sdb
+            .query(
+                r#"
+                SELECT * FROM type::table($table)
+                WHERE channel = $channel
+                AND status = 'queued'
+                ORDER BY created_at ASC
+                LIMIT $limit
+                "#,
+            )
+            .bind(("table", MESSAGE_DELIVERY_TABLE))
+            .bind(("channel", channel))
+            .bind(("limit", limit))
+            .await?
+            .take(0)?;
+        Ok(deliveries)
+    }
+
+    /// Record that the provider accepted a delivery
+    pub async fn mark_sent(
+        &self,
+        sdb: &Surreal<Client>,
+        delivery_id: String,
+        provider: String,
+        provider_message_id: Option<String>,
+        cost: Option<Decimal>,
+    ) -> DbResult<Option<MessageDelivery>> {
+        let delivery: Option<MessageDelivery> = sdb
+            .query(
+                r#"
+                UPDATE type::thing($table, $id) SET
+                    status = 'sent',
+                    attempts += 1,
+                    provider = $provider,
+                    provider_message_id = $provider_message_id,
+                    cost = $cost,
+                    last_error = NONE,
+                    sent_at = time::now(),
+                    updated_at = time::now()
+                "#,
+            )
+            .bind(("table", MESSAGE_DELIVERY_TABLE))
+            .bind(("id", delivery_id))
+            .bind(("provider", provider))
+            .bind(("provider_message_id", provider_message_id))
+            .bind(("cost", cost))
+            .await?
+            .take(0)?;
+        Ok(delivery)
+    }
+
+    /// Record a failed attempt; the delivery stays queued for another try
+    /// unless `status` says otherwise
+    pub async fn record_failure(
+        &self,
+        sdb: &Surreal<Client>,
+        delivery_id: String,
+        error: String,
+        status: DeliveryStatus,
+    ) -> DbResult<Option<MessageDelivery>> {
+        let delivery: Option<MessageDelivery> = sdb
+            .query(
+                r#"
+                UPDATE type::thing($table, $id) SET
+                    status = $status,
+                    attempts += 1,
+                    last_error = $error,
+                    updated_at = time::now()
+                "#,
+            )
+            .bind(("table", MESSAGE_DELIVERY_TABLE))
+            .bind(("id", delivery_id))
+            .bind(("error", error))
+            .bind(("status", status))
+            .await?
+            .take(0)?;
+        Ok(delivery)
+    }
+
+    /// Apply a provider's delivery receipt
+    pub async fn update_status_by_provider_id(
+        &self,
+        sdb: &Surreal<Client>,
+        provider_message_id: String,
+        status: DeliveryStatus,
+        error: Option<String>,
+    ) -> DbResult<Vec<MessageDelivery>> {
+        let deliveries: Vec<MessageDelivery> = sdb
+            .query(
+                r#"
+                UPDATE type::table($table) SET
+                    status = $status,
+                    last_error = $error ?? last_error,
+                    delivered_at = IF $status = 'delivered' THEN time::now() ELSE delivered_at END,
+                    updated_at = time::now()
+                WHERE provider_message_id = $provider_message_id
+                "#,
+            )
+            .bind(("table", MESSAGE_DELIVERY_TABLE))
+            .bind(("provider_message_id", provider_message_id))
+            .bind(("status", status))
+            .bind(("error", error))
+            .await?
+            .take(0)?;
+        Ok(deliveries)
+    }
+
+    /// The delivery a provider knows by this id
+    pub async fn get_by_provider_id(
+        &self,
+        sdb: &Surreal<Client>,
+        provider_message_id: String,
+    ) -> DbResult<Option<MessageDelivery>> {
+        let delivery: Option<MessageDelivery> = sdb
+            .query(
+                r#"
+                SELECT * FROM type::table($table)
+                WHERE provider_message_id = $provider_message_id
+                LIMIT 1
+                "#,
+            )
+            .bind(("table", MESSAGE_DELIVERY_TABLE))
+            .bind(("provider_message_id", provider_message_id))
+            .await?
+            .take(0)?;
+        Ok(delivery)
+    }
+
+    /// Every delivery queued for an announcement
+    pub async fn get_by_announcement(
+        &self,
+        sdb: &Surreal<Client>,
+        announcement_id: String,
+    ) -> DbResult<Vec<MessageDelivery>> {
+        let deliveries: Vec<MessageDelivery> = sdb
+            .query(
+                r#"
+                SELECT * FROM type::table($table)
+                WHERE announcement_id = type::thing('announcements', $announcement_id)
+                ORDER BY created_at ASC
+                "#,
+            )
+            .bind(("table", MESSAGE_DELIVERY_TABLE))
+            .bind(("announcement_id", announcement_id))
+            .await?
+            .take(0)?;
+        Ok(deliveries)
+    }
+}
+
+pub struct SmsLogQ;
+
+impl SmsLogQ {
+    /// Log an SMS
+    pub async fn log(&self, sdb: &Surreal<Client>, data: SmsLog) -> DbResult<Option<SmsLog>> {
+        let log: Option<SmsLog> = sdb.create(SMS_LOG_TABLE).content(data).await?;
+        Ok(log)
+    }
+
+    /// Get total SMS cost for a school in the current month
+    pub async fn get_monthly_cost(
+        &self,
+        sdb: &Surreal<Client>,
+        school_id: String,
+    ) -> DbResult<Option<f64>> {
+        let mut response = sdb
+            .query(
+                r#"
+                SELECT math::sum(cost) AS total_cost
+                FROM type::table($table)
+                WHERE school_id = type::thing('schools', $school_id)
+                AND sent_at >= time::floor(time::now(), 1M)
+                AND sent_at < time::now()
+                "#,
+            )
+            .bind(("table", SMS_LOG_TABLE))
+            .bind(("school_id", school_id))
+            .await?;
+
+        let result: Option<MonthlyCost> = response.take(0)?;
+        Ok(result.and_then(|r| r.total_cost))
+    }
+
+    /// Count the SMS a school has sent in the current month
+    pub async fn count_this_month(
+        &self,
+        sdb: &Surreal<Client>,
+        school_id: String,
+    ) -> DbResult<u64> {
+        let mut response = sdb
+            .query(
+                r#"
+                SELECT count() AS total
+                FROM type::table($table)
+                WHERE school_id = type::thing('schools', $school_id)
+                AND sent_at >= time::floor(time::now(), 1M)
+                GROUP ALL
+                "#,
+            )
+            .bind(("table", SMS_LOG_TABLE))
+            .bind(("school_id", school_id))
+            .await?;
+
+        let result: Option<CountTotal> = response.take(0)?;
+        Ok(result.and_then(|r| r.total).unwrap_or(0))
+    }
+
+    /// How many SMS a school has sent since a moment, and what they cost
+    pub async fn get_usage_since(
+        &self,
+        sdb: &Surreal<Client>,
+        school_id: String,
+        since: Datetime,
+    ) -> DbResult<(u64, Decimal)> {
+        let mut response = sdb
+            .query(
+                r#"
+                SELECT count() AS total, math::sum(cost ?? 0dec) AS cost
+                FROM type::table($table)
+                WHERE school_id = type::thing('schools', $school_id)
+                AND sent_at >= $since
+                GROUP ALL
+                "#,
+            )
+            .bind(("table", SMS_LOG_TABLE))
+            .bind(("school_id", school_id))
+            .bind(("since", since))
+            .await?;
+
+        let usage: Option<SmsUsage> = response.take(0)?;
+        Ok(usage
+            .map(|u| (u.total.unwrap_or(0), u.cost.unwrap_or_default()))
+            .unwrap_or_default())
+    }
+
+    /// Get SMS logs for a school
+    pub async fn get_by_school(
         &self,
         sdb: &Surreal<Client>,
         school_id: String,
@@ -390,3 +1263,436 @@ impl SmsLogQ {
         Ok(logs)
     }
 }
+
+pub struct PushSubscriptionQ;
+
+impl PushSubscriptionQ {
+    /// Register a subscription, or update the one with the same endpoint
+    /// (a browser re-subscribing, possibly for another user)
+    pub async fn upsert(
+        &self,
+        sdb: &Surreal<Client>,
+        data: PushSubscription,
+    ) -> DbResult<Option<PushSubscription>> {
+        let subscription: Option<PushSubscription> = sdb
+            .query(
+                r#"
+                LET $existing = (SELECT VALUE id FROM type::table($table)
+                    WHERE endpoint = $data.endpoint LIMIT 1)[0];
+                IF $existing {
+                    UPDATE ONLY $existing MERGE {
+                        school_id: $data.school_id,
+                        user_id: $data.user_id,
+                        p256dh: $data.p256dh,
+                        auth: $data.auth,
+                        user_agent: $data.user_agent
+                    }
+                } ELSE {
+                    CREATE ONLY type::table($table) CONTENT $data
+                };
+                "#,
+            )
+            .bind(("table", PUSH_SUBSCRIPTION_TABLE))
+            .bind(("data", data))
+            .await?
+            .take(1)?;
+        Ok(subscription)
+    }
+
+    /// Every device a user receives push on
+    pub async fn get_by_user(
+        &self,
+        sdb: &Surreal<Client>,
+        user_id: String,
+    ) -> DbResult<Vec<PushSubscription>> {
+        let subscriptions: Vec<PushSubscription> = sdb
+            .query(
+                r#"
+                SELECT * FROM type::table($table)
+                WHERE user_id = type::thing('users', $user_id)
+                ORDER BY created_at ASC
+                "#,
+            )
+            .bind(("table", PUSH_SUBSCRIPTION_TABLE))
+            .bind(("user_id", user_id))
+            .await?
+            .take(0)?;
+        Ok(subscriptions)
+    }
+
+    /// Record a successful push to a subscription
+    pub async fn touch(&self, sdb: &Surreal<Client>, subscription_id: String) -> DbResult<()> {
+        sdb.query("UPDATE type::thing($table, $id) SET last_used_at = time::now()")
+            .bind(("table", PUSH_SUBSCRIPTION_TABLE))
+            .bind(("id", subscription_id))
+            .await?;
+        Ok(())
+    }
+
+    /// Remove a subscription the push service says has expired
+    pub async fn delete(&self, sdb: &Surreal<Client>, subscription_id: String) -> DbResult<()> {
+        sdb.query("DELETE type::thing($table, $id)")
+            .bind(("table", PUSH_SUBSCRIPTION_TABLE))
+            .bind(("id", subscription_id))
+            .await?;
+        Ok(())
+    }
+
+    /// Remove a user's subscription for one endpoint (the browser unsubscribed)
+    pub async fn delete_by_endpoint(
+        &self,
+        sdb: &Surreal<Client>,
+        user_id: String,
+        endpoint: String,
+    ) -> DbResult<Vec<PushSubscription>> {
+        let deleted: Vec<PushSubscription> = sdb
+            .query(
+                r#"
+                DELETE type::table($table)
+                WHERE user_id = type::thing('users', $user_id)
+                AND endpoint = $endpoint
+                RETURN BEFORE
+                "#,
+            )
+            .bind(("table", PUSH_SUBSCRIPTION_TABLE))
+            .bind(("user_id", user_id))
+            .bind(("endpoint", endpoint))
+            .await?
+            .take(0)?;
+        Ok(deleted)
+    }
+}
+
+pub struct WhatsappTemplateQ;
+
+impl WhatsappTemplateQ {
+    /// A school's approved templates for one kind of message, in every language
+    pub async fn find(
+        &self,
+        sdb: &Surreal<Client>,
+        school_id: String,
+        template_key: String,
+    ) -> DbResult<Vec<WhatsappTemplate>> {
+        let templates: Vec<WhatsappTemplate> = sdb
+            .query(
+                r#"
+                SELECT * FROM type::table($table)
+                WHERE school_id = type::thing('schools', $school_id)
+                AND template_key = $template_key
+                "#,
+            )
+            .bind(("table", WHATSAPP_TEMPLATE_TABLE))
+            .bind(("school_id", school_id))
+            .bind(("template_key", template_key))
+            .await?
+            .take(0)?;
+        Ok(templates)
+    }
+
+    /// Every approved template a school has registered
+    pub async fn get_by_school(
+        &self,
+        sdb: &Surreal<Client>,
+        school_id: String,
+    ) -> DbResult<Vec<WhatsappTemplate>> {
+        let templates: Vec<WhatsappTemplate> = sdb
+            .query(
+                r#"
+                SELECT * FROM type::table($table)
+                WHERE school_id = type::thing('schools', $school_id)
+                ORDER BY template_key, locale
+                "#,
+            )
+            .bind(("table", WHATSAPP_TEMPLATE_TABLE))
+            .bind(("school_id", school_id))
+            .await?
+            .take(0)?;
+        Ok(templates)
+    }
+
+    /// Register or replace a school's template for one kind of message and language
+    pub async fn upsert(
+        &self,
+        sdb: &Surreal<Client>,
+        school_id: String,
+        data: SaveWhatsappTemplateDto,
+    ) -> DbResult<()> {
+        sdb.query(
+            r#"
+            IF (SELECT id FROM type::table($table)
+                WHERE school_id = type::thing('schools', $school_id)
+                AND template_key = $template_key
+                AND locale = $locale)[0] {
+                UPDATE type::table($table) SET
+                    name = $name,
+                    language = $language,
+                    parameters = $parameters,
+                    updated_at = time::now()
+                WHERE school_id = type::thing('schools', $school_id)
+                AND template_key = $template_key
+                AND locale = $locale;
+            } ELSE {
+                CREATE type::table($table) CONTENT {
+                    school_id: type::thing('schools', $school_id),
+                    template_key: $template_key,
+                    locale: $locale,
+                    name: $name,
+                    language: $language,
+                    parameters: $parameters,
+                    created_at: time::now(),
+                    updated_at: time::now()
+                };
+            };
+            "#,
+        )
+        .bind(("table", WHATSAPP_TEMPLATE_TABLE))
+        .bind(("school_id", school_id))
+        .bind(("template_key", data.template_key))
+        .bind(("locale", data.locale))
+        .bind(("name", data.name))
+        .bind(("language", data.language))
+        .bind(("parameters", data.parameters))
+        .await?;
+        Ok(())
+    }
+
+    /// Stop using a template, e.g. one Meta has paused or disabled
+    pub async fn delete(
+        &self,
+        sdb: &Surreal<Client>,
+        school_id: String,
+        template_key: String,
+        locale: Option<Locale>,
+    ) -> DbResult<()> {
+        sdb.query(
+            r#"
+            DELETE type::table($table)
+            WHERE school_id = type::thing('schools', $school_id)
+            AND template_key = $template_key
+            AND locale = $locale
+            "#,
+        )
+        .bind(("table", WHATSAPP_TEMPLATE_TABLE))
+        .bind(("school_id", school_id))
+        .bind(("template_key", template_key))
+        .bind(("locale", locale))
+        .await?;
+        Ok(())
+    }
+}
+
+pub struct ConversationQ;
+
+impl ConversationQ {
+    /// Start a conversation
+    pub async fn create(
+        &self,
+        sdb: &Surreal<Client>,
+        data: Conversation,
+    ) -> DbResult<Option<Conversation>> {
+        let conversation: Option<Conversation> =
+            sdb.create(CONVERSATION_TABLE).content(data).await?;
+        Ok(conversation)
+    }
+
+    /// Get a conversation by ID
+    pub async fn get_by_id(
+        &self,
+        sdb: &Surreal<Client>,
+        conversation_id: String,
+    ) -> DbResult<Option<Conversation>> {
+        let conversation: Option<Conversation> = sdb
+            .query("SELECT * FROM type::table($table) WHERE id = type::thing($table, $id)")
+            .bind(("table", CONVERSATION_TABLE))
+            .bind(("id", conversation_id))
+            .await?
+            .take(0)?;
+        Ok(conversation)
+    }
+
+    /// The open conversation two users have about a child (or about no child
+    /// in particular)
+    pub async fn find_open_between(
+        &self,
+        sdb: &Surreal<Client>,
+        school_id: RecordId,
+        participants: Vec<RecordId>,
+        student_id: Option<RecordId>,
+    ) -> DbResult<Option<Conversation>> {
+        let conversation: Option<Conversation> = sdb
+            .query(
+                r#"
+                SELECT * FROM type::table($table)
+                WHERE school_id = $school_id
+                AND participants CONTAINSALL $participants
+                AND student_id = $student_id
+                AND closed = false
+                ORDER BY last_message_at DESC
+                LIMIT 1
+                "#,
+            )
+            .bind(("table", CONVERSATION_TABLE))
+            .bind(("school_id", school_id))
+            .bind(("participants", participants))
+            .bind(("student_id", student_id))
+            .await?
+            .take(0)?;
+        Ok(conversation)
+    }
+
+    /// A user's conversations, most recently active first
+    pub async fn get_for_user(
+        &self,
+        sdb: &Surreal<Client>,
+        user_id: String,
+    ) -> DbResult<Vec<Conversation>> {
+        let conversations: Vec<Conversation> = sdb
+            .query(
+                r#"
+                SELECT * FROM type::table($table)
+                WHERE participants CONTAINS type::thing('users', $user_id)
+                ORDER BY last_message_at DESC
+                "#,
+            )
+            .bind(("table", CONVERSATION_TABLE))
+            .bind(("user_id", user_id))
+            .await?
+            .take(0)?;
+        Ok(conversations)
+    }
+
+    /// Every conversation in a school, most recently active first
+    pub async fn get_by_school(
+        &self,
+        sdb: &Surreal<Client>,
+        school_id: String,
+    ) -> DbResult<Vec<Conversation>> {
+        let conversations: Vec<Conversation> = sdb
+            .query(
+                r#"
+                SELECT * FROM type::table($table)
+                WHERE school_id = type::thing('schools', $school_id)
+                ORDER BY last_message_at DESC
+                "#,
+            )
+            .bind(("table", CONVERSATION_TABLE))
+            .bind(("school_id", school_id))
+            .await?
+            .take(0)?;
+        Ok(conversations)
+    }
+
+    /// Record that a message was just sent
+    pub async fn touch(
+        &self,
+        sdb: &Surreal<Client>,
+        conversation_id: String,
+        at: Datetime,
+    ) -> DbResult<()> {
+        sdb.query(
+            "UPDATE type::thing($table, $id) SET last_message_at = $at, updated_at = time::now()",
+        )
+        .bind(("table", CONVERSATION_TABLE))
+        .bind(("id", conversation_id))
+        .bind(("at", at))
+        .await?;
+        Ok(())
+    }
+
+    /// Close or reopen a conversation
+    pub async fn set_closed(
+        &self,
+        sdb: &Surreal<Client>,
+        conversation_id: String,
+        closed: bool,
+    ) -> DbResult<Option<Conversation>> {
+        let conversation: Option<Conversation> = sdb
+            .query("UPDATE type::thing($table, $id) SET closed = $closed, updated_at = time::now()")
+            .bind(("table", CONVERSATION_TABLE))
+            .bind(("id", conversation_id))
+            .bind(("closed", closed))
+            .await?
+            .take(0)?;
+        Ok(conversation)
+    }
+}
+
+pub struct MessageAttachmentQ;
+
+impl MessageAttachmentQ {
+    /// Record an uploaded file
+    pub async fn create(
+        &self,
+        sdb: &Surreal<Client>,
+        data: MessageAttachment,
+    ) -> DbResult<Option<MessageAttachment>> {
+        let attachment: Option<MessageAttachment> =
+            sdb.create(MESSAGE_ATTACHMENT_TABLE).content(data).await?;
+        Ok(attachment)
+    }
+
+    /// Get an attachment by ID
+    pub async fn get_by_id(
+        &self,
+        sdb: &Surreal<Client>,
+        attachment_id: String,
+    ) -> DbResult<Option<MessageAttachment>> {
+        let attachment: Option<MessageAttachment> = sdb
+            .query("SELECT * FROM type::table($table) WHERE id = type::thing($table, $id)")
+            .bind(("table", MESSAGE_ATTACHMENT_TABLE))
+            .bind(("id", attachment_id))
+            .await?
+            .take(0)?;
+        Ok(attachment)
+    }
+
+    /// Attachments sent in a conversation, oldest first
+    pub async fn get_by_conversation(
+        &self,
+        sdb: &Surreal<Client>,
+        conversation_id: String,
+    ) -> DbResult<Vec<MessageAttachment>> {
+        let attachments: Vec<MessageAttachment> = sdb
+            .query(
+                r#"
+                SELECT * FROM type::table($table)
+                WHERE conversation_id = type::thing('conversations', $conversation_id)
+                AND message_id != NONE
+                ORDER BY created_at ASC
+                "#,
+            )
+            .bind(("table", MESSAGE_ATTACHMENT_TABLE))
+            .bind(("conversation_id", conversation_id))
+            .await?
+            .take(0)?;
+        Ok(attachments)
+    }
+
+    /// Link a user's unsent uploads in a conversation to the message they
+    /// went out with, returning the ones linked
+    pub async fn attach(
+        &self,
+        sdb: &Surreal<Client>,
+        attachment_ids: Vec<RecordId>,
+        message: &Message,
+    ) -> DbResult<Vec<MessageAttachment>> {
+        let attachments: Vec<MessageAttachment> = sdb
+            .query(
+                r#"
+                UPDATE type::table($table) SET message_id = $message_id
+                WHERE id IN $ids
+                AND conversation_id = $conversation_id
+                AND uploaded_by = $sender_id
+                AND message_id = NONE
+                "#,
+            )
+            .bind(("table", MESSAGE_ATTACHMENT_TABLE))
+            .bind(("ids", attachment_ids))
+            .bind(("message_id", message.id.clone()))
+            .bind(("conversation_id", message.conversation_id.clone()))
+            .bind(("sender_id", message.sender_id.clone()))
+            .await?
+            .take(0)?;
+        Ok(attachments)
+    }
+}
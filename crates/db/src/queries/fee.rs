@@ -1,16 +1,30 @@
-use crate::error::DbResult;
-use shared::models::{
-    InvoiceStatus,
-    fee_management::{FeeStructure, Invoice, InvoiceItem, Payment, PaymentReminder},
+use crate::{
+    error::{DbResult, Error},
+    pagination::Page,
+};
+use shared::{
+    helpers::query_filters::InvoiceFilter,
+    models::{
+        InvoiceStatus,
+        fee_management::{
+            FeeStructure, Invoice, InvoiceItem, Payment, PaymentReminder, PlatformInvoice,
+            PlatformPayment,
+        },
+    },
 };
 use surrealdb::{Surreal, engine::remote::ws::Client};
-use surrealdb_types::Value;
+use surrealdb_types::{Datetime, Decimal, Value};
 
 const FEE_STRUCTURE_TABLE: &str = "fee_structures";
 const INVOICE_TABLE: &str = "invoices";
 const INVOICE_ITEM_TABLE: &str = "invoice_items";
 const PAYMENT_TABLE: &str = "payments";
 const PAYMENT_REMINDER_TABLE: &str = "payment_reminders";
+const PLATFORM_INVOICE_TABLE: &str = "platform_invoices";
+const PLATFORM_PAYMENT_TABLE: &str = "platform_payments";
+const COUNTER_TABLE: &str = "counters";
+/// Unique index that bills a school at most once per term
+const PLATFORM_INVOICE_TERM_INDEX: &str = "platform_invoice_school_term";
 
 pub struct FeeStructureQ;
 
@@ -116,6 +130,21 @@ impl InvoiceQ {
         Ok(invoice)
     }
 
+    /// Get invoice by ID
+    pub async fn get_by_id(
+        &self,
+        sdb: &Surreal<Client>,
+        invoice_id: String,
+    ) -> DbResult<Option<Invoice>> {
+        let invoice: Option<Invoice> = sdb
+            .query("SELECT * FROM type::table($table) WHERE id = type::thing($table, $id)")
+            .bind(("table", INVOICE_TABLE))
+            .bind(("id", invoice_id))
+            .await?
+            .take(0)?;
+        Ok(invoice)
+    }
+
     /// Get invoice with items
     pub async fn get_with_items(
         &self,
@@ -186,6 +215,37 @@ impl InvoiceQ {
         Ok(invoices)
     }
 
+    /// Get one page of invoices matching a filter (every field optional)
+    pub async fn get_filtered(
+        &self,
+        sdb: &Surreal<Client>,
+        filter: InvoiceFilter,
+        page: Page,
+    ) -> DbResult<Vec<Invoice>> {
+        let invoices: Vec<Invoice> = sdb
+            .query(
+                r#"
+                SELECT * FROM type::table($table)
+                WHERE ($school_id = NONE OR school_id = $school_id)
+                AND ($student_id = NONE OR student_id = $student_id)
+                AND ($term_id = NONE OR term_id = $term_id)
+                AND ($status = NONE OR status = $status)
+                ORDER BY invoice_number
+                LIMIT $limit START $offset
+                "#,
+            )
+            .bind(("table", INVOICE_TABLE))
+            .bind(("school_id", filter.school_id))
+            .bind(("student_id", filter.student_id))
+            .bind(("term_id", filter.term_id))
+            .bind(("status", filter.status))
+            .bind(("limit", page.limit))
+            .bind(("offset", page.offset))
+            .await?
+            .take(0)?;
+        Ok(invoices)
+    }
+
     /// Update invoice balances after payment
     pub async fn apply_payment(
         &self,
@@ -299,6 +359,21 @@ impl PaymentQ {
         Ok(payment)
     }
 
+    /// Get payment by ID
+    pub async fn get_by_id(
+        &self,
+        sdb: &Surreal<Client>,
+        payment_id: String,
+    ) -> DbResult<Option<Payment>> {
+        let payment: Option<Payment> = sdb
+            .query("SELECT * FROM type::table($table) WHERE id = type::thing($table, $id)")
+            .bind(("table", PAYMENT_TABLE))
+            .bind(("id", payment_id))
+            .await?
+            .take(0)?;
+        Ok(payment)
+    }
+
     /// Get payment history for a student
     pub async fn get_by_student(
         &self,
@@ -320,6 +395,37 @@ impl PaymentQ {
         Ok(payments)
     }
 
+    /// Get one page of payments whose invoice matches a filter (every field optional)
+    pub async fn get_filtered(
+        &self,
+        sdb: &Surreal<Client>,
+        filter: InvoiceFilter,
+        page: Page,
+    ) -> DbResult<Vec<Payment>> {
+        let payments: Vec<Payment> = sdb
+            .query(
+                r#"
+                SELECT * FROM type::table($table)
+                WHERE ($school_id = NONE OR invoice_id.school_id = $school_id)
+                AND ($student_id = NONE OR student_id = $student_id)
+                AND ($term_id = NONE OR invoice_id.term_id = $term_id)
+                AND ($status = NONE OR invoice_id.status = $status)
+                ORDER BY payment_date DESC
+                LIMIT $limit START $offset
+                "#,
+            )
+            .bind(("table", PAYMENT_TABLE))
+            .bind(("school_id", filter.school_id))
+            .bind(("student_id", filter.student_id))
+            .bind(("term_id", filter.term_id))
+            .bind(("status", filter.status))
+            .bind(("limit", page.limit))
+            .bind(("offset", page.offset))
+            .await?
+            .take(0)?;
+        Ok(payments)
+    }
+
     /// Get payments for an invoice
     pub async fn get_by_invoice(
         &self,
@@ -424,3 +530,181 @@ impl PaymentReminderQ {
         todo!()
     }
 }
+
+pub struct PlatformInvoiceQ;
+
+impl PlatformInvoiceQ {
+    /// Make sure a school can only be billed once per term
+    pub async fn define_indexes(&self, sdb: &Surreal<Client>) -> DbResult<()> {
+        // Index and table names can't be bound as parameters
+        sdb.query(format!(
+            "DEFINE INDEX IF NOT EXISTS {PLATFORM_INVOICE_TERM_INDEX} \
+             ON TABLE {PLATFORM_INVOICE_TABLE} FIELDS school_id, term_id UNIQUE"
+        ))
+        .await?
+        .check()?;
+        Ok(())
+    }
+
+    /// Create an invoice; Duplicate if the school was already billed for the term
+    pub async fn create(
+        &self,
+        sdb: &Surreal<Client>,
+        data: PlatformInvoice,
+    ) -> DbResult<Option<PlatformInvoice>> {
+        let created: Result<Option<PlatformInvoice>, _> =
+            sdb.create(PLATFORM_INVOICE_TABLE).content(data).await;
+        match created {
+            Err(err) if err.to_string().contains(PLATFORM_INVOICE_TERM_INDEX) => Err(
+                Error::Duplicate("platform invoice for this school and term".into()),
+            ),
+            created => Ok(created?),
+        }
+    }
+
+    pub async fn get_by_id(
+        &self,
+        sdb: &Surreal<Client>,
+        invoice_id: String,
+    ) -> DbResult<Option<PlatformInvoice>> {
+        let invoice: Option<PlatformInvoice> = sdb
+            .query("SELECT * FROM type::table($table) WHERE id = type::thing($table, $id)")
+            .bind(("table", PLATFORM_INVOICE_TABLE))
+            .bind(("id", invoice_id))
+            .await?
+            .take(0)?;
+        Ok(invoice)
+    }
+
+    /// A school's invoice for a term, if it has been billed
+    pub async fn get_by_school_term(
+        &self,
+        sdb: &Surreal<Client>,
+        school_id: String,
+        term_id: String,
+    ) -> DbResult<Option<PlatformInvoice>> {
+        let invoice: Option<PlatformInvoice> = sdb
+            .query(
+                r#"
+                SELECT * FROM type::table($table)
+                WHERE school_id = type::thing('schools', $school_id)
+                AND term_id = type::thing('terms', $term_id)
+                LIMIT 1
+                "#,
+            )
+            .bind(("table", PLATFORM_INVOICE_TABLE))
+            .bind(("school_id", school_id))
+            .bind(("term_id", term_id))
+            .await?
+            .take(0)?;
+        Ok(invoice)
+    }
+
+    /// A school's invoices, newest first
+    pub async fn get_by_school(
+        &self,
+        sdb: &Surreal<Client>,
+        school_id: String,
+    ) -> DbResult<Vec<PlatformInvoice>> {
+        let invoices: Vec<PlatformInvoice> = sdb
+            .query(
+                r#"
+                SELECT * FROM type::table($table)
+                WHERE school_id = type::thing('schools', $school_id)
+                ORDER BY created_at DESC
+                "#,
+            )
+            .bind(("table", PLATFORM_INVOICE_TABLE))
+            .bind(("school_id", school_id))
+            .await?
+            .take(0)?;
+        Ok(invoices)
+    }
+
+    /// Take the next platform invoice number. The counter record is bumped
+    /// in one statement, so concurrent billing runs never share a number;
+    /// it starts after the invoices raised before it existed.
+    pub async fn next_sequence(&self, sdb: &Surreal<Client>) -> DbResult<u64> {
+        let sequence: Option<u64> = sdb
+            .query(
+                r#"
+                UPSERT ONLY type::thing($counters, $table)
+                SET value = (value ?? count((SELECT VALUE id FROM type::table($table)))) + 1
+                RETURN VALUE value
+                "#,
+            )
+            .bind(("counters", COUNTER_TABLE))
+            .bind(("table", PLATFORM_INVOICE_TABLE))
+            .await?
+            .take(0)?;
+        sequence.ok_or_else(|| Error::NotFound("platform invoice counter".into()))
+    }
+
+    /// Record a payment's effect on an invoice, provided nothing was paid
+    /// towards it since `previously_paid` was read
+    pub async fn settle(
+        &self,
+        sdb: &Surreal<Client>,
+        invoice_id: String,
+        previously_paid: Decimal,
+        amount_paid: Decimal,
+        status: InvoiceStatus,
+        paid_at: Option<Datetime>,
+    ) -> DbResult<Option<PlatformInvoice>> {
+        let invoice: Option<PlatformInvoice> = sdb
+            .query(
+                r#"
+                UPDATE type::thing($table, $id) SET
+                    amount_paid = $amount_paid,
+                    balance = total_amount - $amount_paid,
+                    status = $status,
+                    paid_at = $paid_at,
+                    updated_at = time::now()
+                WHERE amount_paid = $previously_paid
+                "#,
+            )
+            .bind(("table", PLATFORM_INVOICE_TABLE))
+            .bind(("id", invoice_id))
+            .bind(("previously_paid", previously_paid))
+            .bind(("amount_paid", amount_paid))
+            .bind(("status", status))
+            .bind(("paid_at", paid_at))
+            .await?
+            .take(0)?;
+        Ok(invoice)
+    }
+}
+
+pub struct PlatformPaymentQ;
+
+impl PlatformPaymentQ {
+    pub async fn record(
+        &self,
+        sdb: &Surreal<Client>,
+        data: PlatformPayment,
+    ) -> DbResult<Option<PlatformPayment>> {
+        let payment: Option<PlatformPayment> =
+            sdb.create(PLATFORM_PAYMENT_TABLE).content(data).await?;
+        Ok(payment)
+    }
+
+    pub async fn get_by_invoice(
+        &self,
+        sdb: &Surreal<Client>,
+        invoice_id: String,
+    ) -> DbResult<Vec<PlatformPayment>> {
+        let payments: Vec<PlatformPayment> = sdb
+            .query(
+                r#"
+                SELECT * FROM type::table($table)
+                WHERE invoice_id = type::thing('platform_invoices', $invoice_id)
+                ORDER BY paid_at ASC
+                "#,
+            )
+            .bind(("table", PLATFORM_PAYMENT_TABLE))
+            .bind(("invoice_id", invoice_id))
+            .await?
+            .take(0)?;
+        Ok(payments)
+    }
+}
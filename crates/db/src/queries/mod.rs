@@ -11,11 +11,23 @@ pub mod system;
 pub mod user;
 
 pub use academic::{AcademicSessionQ, ClassQ, ClassSubjectQ, SubjectQ, TermQ};
-pub use assessment::{AssessmentQ, GradingSchemeQ, ReportCardQ, StudentScoreQ};
+pub use assessment::{
+    AssessmentQ, AssessmentWeightingQ, GradingSchemeQ, ReportCardQ, ReportCardTotals, StudentScoreQ,
+};
 pub use attendance::{AttendanceQ, AttendanceSummaryQ};
-pub use communication::{AnnouncementQ, EventQ, MessageQ, NotificationQ, SmsLogQ};
-pub use fee::{FeeStructureQ, InvoiceQ, PaymentQ, PaymentReminderQ};
+pub use auth::{EmailVerificationQ, SessionQ};
+pub use bulk::BulkQ;
+pub use communication::{
+    AnnouncementQ, ConversationQ, EventQ, LiveStream, MessageAttachmentQ, MessageDeliveryQ,
+    MessageQ, MessageTemplateQ, NotificationQ, PushSubscriptionQ, SmsLogQ, WhatsappTemplateQ,
+};
+pub use fee::{
+    FeeStructureQ, InvoiceQ, PaymentQ, PaymentReminderQ, PlatformInvoiceQ, PlatformPaymentQ,
+};
 pub use school::SchoolQ;
-pub use student::{ParentQ, StudentQ};
-pub use system::{ActivityLogQ, AnalyticsQ, ReportCardTemplateQ, SchoolSettingQ};
+pub use student::{ImportedParent, ParentQ, StudentQ};
+pub use system::{
+    ActivityLogQ, AnalyticsQ, CalendarFeedQ, IdempotencyQ, ReportCardTemplateQ, SchoolSettingQ,
+    UssdSessionQ,
+};
 pub use user::UserQ;
@@ -4,6 +4,7 @@ use shared::{
     models::{School, SubscriptionStatus},
 };
 use surrealdb::{Surreal, engine::remote::ws::Client};
+use surrealdb_types::Datetime;
 
 const SCHOOL_TABLE: &str = "schools";
 
@@ -98,6 +99,69 @@ impl SchoolQ {
         Ok(schools)
     }
 
+    /// Active and trial schools whose subscription ran out more than
+    /// `grace_days` ago
+    pub async fn get_lapsed(
+        &self,
+        sdb: &Surreal<Client>,
+        grace_days: u32,
+    ) -> DbResult<Vec<School>> {
+        let schools: Vec<School> = sdb
+            .query(
+                r#"
+                SELECT * FROM type::table($table)
+                WHERE subscription_status IN ['active', 'trial']
+                AND subscription_expiry_date < time::now() - type::duration($days)
+                "#,
+            )
+            .bind(("table", SCHOOL_TABLE))
+            .bind(("days", format!("{}d", grace_days)))
+            .await?
+            .take(0)?;
+        Ok(schools)
+    }
+
+    /// Schools the platform bills: active ones and those on trial
+    pub async fn get_billable(&self, sdb: &Surreal<Client>) -> DbResult<Vec<School>> {
+        let schools: Vec<School> = sdb
+            .query(
+                r#"
+                SELECT * FROM type::table($table)
+                WHERE subscription_status IN ['active', 'trial']
+                "#,
+            )
+            .bind(("table", SCHOOL_TABLE))
+            .await?
+            .take(0)?;
+        Ok(schools)
+    }
+
+    /// Set a school's subscription status and when it runs out
+    pub async fn renew_subscription(
+        &self,
+        sdb: &Surreal<Client>,
+        school_id: String,
+        status: SubscriptionStatus,
+        expiry_date: Datetime,
+    ) -> DbResult<Option<School>> {
+        let school: Option<School> = sdb
+            .query(
+                r#"
+                UPDATE type::thing($table, $id) SET
+                    subscription_status = $status,
+                    subscription_expiry_date = $expiry_date,
+                    updated_at = time::now()
+                "#,
+            )
+            .bind(("table", SCHOOL_TABLE))
+            .bind(("id", school_id))
+            .bind(("status", status))
+            .bind(("expiry_date", expiry_date))
+            .await?
+            .take(0)?;
+        Ok(school)
+    }
+
     /// Update subscription status
     pub async fn update_subscription_status(
         &self,
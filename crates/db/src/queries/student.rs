@@ -1,15 +1,37 @@
-use crate::error::DbResult;
+use crate::{error::DbResult, pagination::Page};
 use shared::{
-    helpers::create_update_dtos::CreateStudentDto,
-    models::{Parent, Student, StudentParent, StudentStatus},
+    helpers::{create_update_dtos::CreateStudentDto, query_filters::StudentFilter},
+    models::{Parent, RelationshipType, Student, StudentParent, StudentStatus, User},
 };
 use surrealdb::{Surreal, engine::remote::ws::Client};
-use surrealdb_types::Value;
+use surrealdb_types::{RecordId, SurrealValue, Value};
 
 const STUDENT_TABLE: &str = "students";
 const PARENT_TABLE: &str = "parents";
 const STUDENT_PARENTS_TABLE: &str = "student_parents";
 
+#[derive(Debug, SurrealValue)]
+struct CountTotal {
+    total: Option<u64>,
+}
+
+/// The parent an imported student is linked to
+#[derive(Debug)]
+pub enum ImportedParent {
+    /// A parent already on file, e.g. a sibling's
+    Existing(RecordId),
+    /// A parent profile for an existing parent account
+    ForUser {
+        user_id: RecordId,
+        relationship_type: RelationshipType,
+    },
+    /// A new parent account and profile
+    New {
+        user: Box<User>,
+        relationship_type: RelationshipType,
+    },
+}
+
 pub struct StudentQ;
 
 impl StudentQ {
@@ -23,6 +45,107 @@ impl StudentQ {
         Ok(student)
     }
 
+    /// Create a student, count them into their class and link them to their
+    /// parent, creating the parent's account and profile as needed, in one
+    /// transaction so a failure leaves nothing behind
+    pub async fn create_with_parent(
+        &self,
+        sdb: &Surreal<Client>,
+        data: CreateStudentDto,
+        parent: Option<ImportedParent>,
+    ) -> DbResult<Option<Student>> {
+        let (parent_id, user_id, new_user, relationship_type) = match parent {
+            None => (None, None, None, None),
+            Some(ImportedParent::Existing(parent_id)) => (Some(parent_id), None, None, None),
+            Some(ImportedParent::ForUser {
+                user_id,
+                relationship_type,
+            }) => (None, Some(user_id), None, Some(relationship_type)),
+            Some(ImportedParent::New {
+                user,
+                relationship_type,
+            }) => (None, None, Some(user), Some(relationship_type)),
+        };
+        let mut response = sdb
+            .query(
+                r#"
+                BEGIN TRANSACTION;
+                LET $student = CREATE ONLY type::table($table) CONTENT $data;
+                IF $class_id != NONE {
+                    UPDATE $class_id SET
+                        current_enrollment = current_enrollment + 1,
+                        updated_at = time::now();
+                };
+                LET $account = IF $new_user != NONE {
+                    (CREATE ONLY users CONTENT $new_user).id
+                } ELSE {
+                    $user_id
+                };
+                LET $parent = IF $parent_id != NONE {
+                    $parent_id
+                } ELSE IF $account != NONE {
+                    (CREATE ONLY type::table($parent_table) SET
+                        user_id = $account,
+                        relationship_type = $relationship_type,
+                        created_at = time::now()).id
+                } ELSE {
+                    NONE
+                };
+                IF $parent != NONE {
+                    RELATE ($student.id)->student_parents->($parent) CONTENT {
+                        primary_contact: true,
+                        created_at: time::now()
+                    };
+                };
+                RETURN $student;
+                COMMIT TRANSACTION;
+                "#,
+            )
+            .bind(("table", STUDENT_TABLE))
+            .bind(("parent_table", PARENT_TABLE))
+            .bind(("class_id", data.current_class_id.clone()))
+            .bind(("data", data))
+            .bind(("parent_id", parent_id))
+            .bind(("user_id", user_id))
+            .bind(("new_user", new_user))
+            .bind(("relationship_type", relationship_type))
+            .await?;
+
+        let last = response.num_statements().saturating_sub(1);
+        let student: Option<Student> = response.take(last)?;
+        Ok(student)
+    }
+
+    /// Get student by ID
+    pub async fn get_by_id(
+        &self,
+        sdb: &Surreal<Client>,
+        student_id: String,
+    ) -> DbResult<Option<Student>> {
+        let student: Option<Student> = sdb
+            .query("SELECT * FROM type::table($table) WHERE id = type::thing($table, $id)")
+            .bind(("table", STUDENT_TABLE))
+            .bind(("id", student_id))
+            .await?
+            .take(0)?;
+        Ok(student)
+    }
+
+    /// Get several students in one query; unknown ids are skipped
+    pub async fn get_by_ids(
+        &self,
+        sdb: &Surreal<Client>,
+        student_ids: Vec<RecordId>,
+    ) -> DbResult<Vec<Student>> {
+        let students: Vec<Student> = sdb
+            .query("SELECT * FROM type::table($table) WHERE id IN $ids")
+            .bind(("table", STUDENT_TABLE))
+            .bind(("ids", student_ids))
+            .await?
+            .take(0)?;
+        Ok(students)
+    }
+
     /// Get student by admission number
     pub async fn get_by_admission_number(
         &self,
@@ -66,6 +189,26 @@ impl StudentQ {
         Ok(students)
     }
 
+    /// Count the active students in a school
+    pub async fn count_active(&self, sdb: &Surreal<Client>, school_id: String) -> DbResult<u64> {
+        let mut response = sdb
+            .query(
+                r#"
+                SELECT count() AS total
+                FROM type::table($table)
+                WHERE school_id = type::thing('schools', $school_id)
+                AND status = 'active'
+                GROUP ALL
+                "#,
+            )
+            .bind(("table", STUDENT_TABLE))
+            .bind(("school_id", school_id))
+            .await?;
+
+        let result: Option<CountTotal> = response.take(0)?;
+        Ok(result.and_then(|r| r.total).unwrap_or(0))
+    }
+
     /// Get students in a specific class
     pub async fn get_by_class(
         &self,
@@ -141,6 +284,41 @@ impl StudentQ {
         Ok(students)
     }
 
+    /// Get one page of students matching a filter (every field optional)
+    pub async fn get_filtered(
+        &self,
+        sdb: &Surreal<Client>,
+        filter: StudentFilter,
+        page: Page,
+    ) -> DbResult<Vec<Student>> {
+        let students: Vec<Student> = sdb
+            .query(
+                r#"
+                SELECT * FROM type::table($table)
+                WHERE ($school_id = NONE OR school_id = $school_id)
+                AND ($class_id = NONE OR current_class_id = $class_id)
+                AND ($status = NONE OR status = $status)
+                AND ($search = NONE
+                    OR string::lowercase(first_name) CONTAINS string::lowercase($search)
+                    OR string::lowercase(last_name) CONTAINS string::lowercase($search)
+                    OR string::lowercase(admission_number) CONTAINS string::lowercase($search)
+                )
+                ORDER BY last_name, first_name
+                LIMIT $limit START $offset
+                "#,
+            )
+            .bind(("table", STUDENT_TABLE))
+            .bind(("school_id", filter.school_id))
+            .bind(("class_id", filter.class_id))
+            .bind(("status", filter.status))
+            .bind(("search", filter.search))
+            .bind(("limit", page.limit))
+            .bind(("offset", page.offset))
+            .await?
+            .take(0)?;
+        Ok(students)
+    }
+
     /// Promote student to a new class
     pub async fn promote(
         &self,
@@ -223,6 +401,27 @@ impl ParentQ {
         Ok(parent)
     }
 
+    /// Get the parent profile attached to a user account
+    pub async fn get_by_user(
+        &self,
+        sdb: &Surreal<Client>,
+        user_id: String,
+    ) -> DbResult<Option<Parent>> {
+        let parent: Option<Parent> = sdb
+            .query(
+                r#"
+                SELECT * FROM type::table($table)
+                WHERE user_id = type::thing('users', $user_id)
+                LIMIT 1
+                "#,
+            )
+            .bind(("table", PARENT_TABLE))
+            .bind(("user_id", user_id))
+            .await?
+            .take(0)?;
+        Ok(parent)
+    }
+
     /// Link parent to student using a graph relation
     pub async fn link_to_student(
         &self,
@@ -260,7 +459,7 @@ impl ParentQ {
         let result: Option<Value> = sdb
             .query(
                 r#"
-                SELECT *, ->student_parents->students.* AS children
+                SELECT *, <-student_parents<-students.* AS children
                 FROM type::thing($table, $id)
                 "#,
             )
@@ -1,11 +1,11 @@
-use crate::error::DbResult;
+use crate::{error::DbResult, pagination::Page};
 
 use shared::{
     helpers::api_responses::DashboardMetrics,
     models::{
         SettingType,
         settings_and_configuration::{ReportCardTemplate, SchoolSetting},
-        system_and_audit::ActivityLog,
+        system_and_audit::{ActivityLog, CalendarFeed, IdempotencyKey, UssdSession},
     },
 };
 use surrealdb::{Surreal, engine::remote::ws::Client};
@@ -14,6 +14,9 @@ use surrealdb_types::{Decimal, SurrealValue, Value};
 const ACTIVITY_LOG_TABLE: &str = "activity_logs";
 const SCHOOL_SETTING_TABLE: &str = "school_settings";
 const REPORT_CARD_TEMPLATE_TABLE: &str = "report_card_templates";
+const IDEMPOTENCY_KEY_TABLE: &str = "idempotency_keys";
+const USSD_SESSION_TABLE: &str = "ussd_sessions";
+const CALENDAR_FEED_TABLE: &str = "calendar_feeds";
 
 #[derive(Debug, SurrealValue)]
 struct GetSettings {
@@ -58,6 +61,31 @@ impl ActivityLogQ {
         Ok(logs)
     }
 
+    /// Get one page of a school's activity, most recent first
+    pub async fn get_page(
+        &self,
+        sdb: &Surreal<Client>,
+        school_id: String,
+        page: Page,
+    ) -> DbResult<Vec<ActivityLog>> {
+        let logs: Vec<ActivityLog> = sdb
+            .query(
+                r#"
+                SELECT * FROM type::table($table)
+                WHERE school_id = type::thing('schools', $school_id)
+                ORDER BY created_at DESC
+                LIMIT $limit START $offset
+                "#,
+            )
+            .bind(("table", ACTIVITY_LOG_TABLE))
+            .bind(("school_id", school_id))
+            .bind(("limit", page.limit))
+            .bind(("offset", page.offset))
+            .await?
+            .take(0)?;
+        Ok(logs)
+    }
+
     /// Get activity for a specific user
     pub async fn get_by_user(
         &self,
@@ -212,6 +240,31 @@ impl SchoolSettingQ {
         Ok(())
     }
 
+    /// The school whose setting `key` has this value, e.g. the one a
+    /// provider's account id belongs to
+    pub async fn find_school(
+        &self,
+        sdb: &Surreal<Client>,
+        key: String,
+        value: String,
+    ) -> DbResult<Option<SchoolSetting>> {
+        let setting: Option<SchoolSetting> = sdb
+            .query(
+                r#"
+                SELECT * FROM type::table($table)
+                WHERE setting_key = $key
+                AND setting_value = $value
+                LIMIT 1
+                "#,
+            )
+            .bind(("table", SCHOOL_SETTING_TABLE))
+            .bind(("key", key))
+            .bind(("value", value))
+            .await?
+            .take(0)?;
+        Ok(setting)
+    }
+
     /// Get all settings for a school
     pub async fn get_all(
         &self,
@@ -295,6 +348,219 @@ impl ReportCardTemplateQ {
     }
 }
 
+pub struct IdempotencyQ;
+
+impl IdempotencyQ {
+    /// Record a key for a user; false if it was already recorded
+    pub async fn claim(
+        &self,
+        sdb: &Surreal<Client>,
+        id: String,
+        user_id: String,
+    ) -> DbResult<bool> {
+        let claimed: Option<bool> = sdb
+            .query(
+                r#"
+                LET $existing = (SELECT VALUE id FROM type::thing($table, $id));
+                IF array::len($existing) = 0 {
+                    CREATE type::thing($table, $id) CONTENT {
+                        user_id: type::thing('users', $user_id),
+                        response: NONE,
+                        created_at: time::now()
+                    };
+                };
+                RETURN array::len($existing) = 0;
+                "#,
+            )
+            .bind(("table", IDEMPOTENCY_KEY_TABLE))
+            .bind(("id", id))
+            .bind(("user_id", user_id))
+            .await?
+            .take(2)?;
+        Ok(claimed.unwrap_or(false))
+    }
+
+    /// Get a recorded key
+    pub async fn get(&self, sdb: &Surreal<Client>, id: String) -> DbResult<Option<IdempotencyKey>> {
+        let key: Option<IdempotencyKey> = sdb
+            .query("SELECT * FROM type::thing($table, $id)")
+            .bind(("table", IDEMPOTENCY_KEY_TABLE))
+            .bind(("id", id))
+            .await?
+            .take(0)?;
+        Ok(key)
+    }
+
+    /// Store the response to replay for a key
+    pub async fn complete(
+        &self,
+        sdb: &Surreal<Client>,
+        id: String,
+        response: String,
+    ) -> DbResult<()> {
+        sdb.query("UPDATE type::thing($table, $id) SET response = $response")
+            .bind(("table", IDEMPOTENCY_KEY_TABLE))
+            .bind(("id", id))
+            .bind(("response", response))
+            .await?;
+        Ok(())
+    }
+
+    /// Forget a key whose request failed, so it can be retried
+    pub async fn release(&self, sdb: &Surreal<Client>, id: String) -> DbResult<()> {
+        sdb.query("DELETE type::thing($table, $id)")
+            .bind(("table", IDEMPOTENCY_KEY_TABLE))
+            .bind(("id", id))
+            .await?;
+        Ok(())
+    }
+
+    /// Remove keys older than a week; devices are not expected to stay offline longer
+    pub async fn delete_expired(&self, sdb: &Surreal<Client>) -> DbResult<()> {
+        sdb.query("DELETE type::table($table) WHERE created_at < time::now() - 7d")
+            .bind(("table", IDEMPOTENCY_KEY_TABLE))
+            .await?;
+        Ok(())
+    }
+}
+
+pub struct UssdSessionQ;
+
+impl UssdSessionQ {
+    /// A session that hasn't expired
+    pub async fn get(
+        &self,
+        sdb: &Surreal<Client>,
+        session_id: String,
+    ) -> DbResult<Option<UssdSession>> {
+        let session: Option<UssdSession> = sdb
+            .query("SELECT * FROM type::thing($table, $id) WHERE expires_at > time::now()")
+            .bind(("table", USSD_SESSION_TABLE))
+            .bind(("id", session_id))
+            .await?
+            .take(0)?;
+        Ok(session)
+    }
+
+    /// Create or replace a session
+    pub async fn save(
+        &self,
+        sdb: &Surreal<Client>,
+        session_id: String,
+        data: UssdSession,
+    ) -> DbResult<Option<UssdSession>> {
+        let session: Option<UssdSession> = sdb
+            .upsert((USSD_SESSION_TABLE, session_id))
+            .content(data)
+            .await?;
+        Ok(session)
+    }
+
+    pub async fn delete(&self, sdb: &Surreal<Client>, session_id: String) -> DbResult<()> {
+        sdb.query("DELETE type::thing($table, $id)")
+            .bind(("table", USSD_SESSION_TABLE))
+            .bind(("id", session_id))
+            .await?;
+        Ok(())
+    }
+
+    /// Remove sessions callers abandoned
+    pub async fn delete_expired(&self, sdb: &Surreal<Client>) -> DbResult<()> {
+        sdb.query("DELETE type::table($table) WHERE expires_at <= time::now()")
+            .bind(("table", USSD_SESSION_TABLE))
+            .await?;
+        Ok(())
+    }
+}
+
+pub struct CalendarFeedQ;
+
+impl CalendarFeedQ {
+    pub async fn create(
+        &self,
+        sdb: &Surreal<Client>,
+        data: CalendarFeed,
+    ) -> DbResult<Option<CalendarFeed>> {
+        let feed: Option<CalendarFeed> = sdb.create(CALENDAR_FEED_TABLE).content(data).await?;
+        Ok(feed)
+    }
+
+    /// The feed a subscription URL points at
+    pub async fn get_by_token(
+        &self,
+        sdb: &Surreal<Client>,
+        token: String,
+    ) -> DbResult<Option<CalendarFeed>> {
+        let feed: Option<CalendarFeed> = sdb
+            .query("SELECT * FROM type::table($table) WHERE token = $token LIMIT 1")
+            .bind(("table", CALENDAR_FEED_TABLE))
+            .bind(("token", token))
+            .await?
+            .take(0)?;
+        Ok(feed)
+    }
+
+    /// A user's own feed
+    pub async fn get_by_user(
+        &self,
+        sdb: &Surreal<Client>,
+        user_id: String,
+    ) -> DbResult<Option<CalendarFeed>> {
+        let feed: Option<CalendarFeed> = sdb
+            .query(
+                r#"
+                SELECT * FROM type::table($table)
+                WHERE user_id = type::thing('users', $user_id)
+                LIMIT 1
+                "#,
+            )
+            .bind(("table", CALENDAR_FEED_TABLE))
+            .bind(("user_id", user_id))
+            .await?
+            .take(0)?;
+        Ok(feed)
+    }
+
+    /// The school-wide feed, which belongs to no user
+    pub async fn get_by_school(
+        &self,
+        sdb: &Surreal<Client>,
+        school_id: String,
+    ) -> DbResult<Option<CalendarFeed>> {
+        let feed: Option<CalendarFeed> = sdb
+            .query(
+                r#"
+                SELECT * FROM type::table($table)
+                WHERE school_id = type::thing('schools', $school_id)
+                AND user_id = NONE
+                LIMIT 1
+                "#,
+            )
+            .bind(("table", CALENDAR_FEED_TABLE))
+            .bind(("school_id", school_id))
+            .await?
+            .take(0)?;
+        Ok(feed)
+    }
+
+    /// Record a calendar app fetching the feed
+    pub async fn touch(&self, sdb: &Surreal<Client>, feed_id: String) -> DbResult<()> {
+        sdb.query("UPDATE type::thing($table, $id) SET last_fetched_at = time::now()")
+            .bind(("table", CALENDAR_FEED_TABLE))
+            .bind(("id", feed_id))
+            .await?;
+        Ok(())
+    }
+
+    pub async fn delete(&self, sdb: &Surreal<Client>, feed_id: String) -> DbResult<()> {
+        sdb.query("DELETE type::thing($table, $id)")
+            .bind(("table", CALENDAR_FEED_TABLE))
+            .bind(("id", feed_id))
+            .await?;
+        Ok(())
+    }
+}
+
 pub struct AnalyticsQ;
 
 impl AnalyticsQ {
@@ -312,13 +578,16 @@ impl AnalyticsQ {
                 r#"
                 LET $sid = type::thing('schools', $school_id);
                 LET $tid = type::thing('terms', $term_id);
+                LET $term = (SELECT start_date, end_date FROM ONLY $tid);
 
                 SELECT
-                    (SELECT count() FROM students WHERE school_id = $sid AND status = 'active')[0].count AS total_students,
-                    (SELECT count() FROM users WHERE school_id = $sid AND user_type = 'teacher' AND status = 'active')[0].count AS total_teachers,
-                    (SELECT count() FROM classes WHERE school_id = $sid)[0].count AS total_classes,
-                    (SELECT math::sum(amount_paid) FROM invoices WHERE school_id = $sid AND term_id = $tid)[0]['math::sum'] AS total_collected,
-                    (SELECT math::sum(total_amount) FROM invoices WHERE school_id = $sid AND term_id = $tid)[0]['math::sum'] AS total_expected
+                    (SELECT count() FROM students WHERE school_id = $sid AND status = 'active' GROUP ALL)[0].count AS total_students,
+                    (SELECT count() FROM users WHERE school_id = $sid AND user_type = 'teacher' AND status = 'active' GROUP ALL)[0].count AS total_teachers,
+                    (SELECT count() FROM classes WHERE school_id = $sid GROUP ALL)[0].count AS total_classes,
+                    (SELECT math::sum(amount_paid) AS total FROM invoices WHERE school_id = $sid AND term_id = $tid GROUP ALL)[0].total AS total_collected,
+                    (SELECT math::sum(total_amount) AS total FROM invoices WHERE school_id = $sid AND term_id = $tid GROUP ALL)[0].total AS total_expected,
+                    (SELECT count() FROM attendance WHERE class_id.school_id = $sid AND date >= $term.start_date AND date <= $term.end_date GROUP ALL)[0].count AS attendance_marked,
+                    (SELECT count() FROM attendance WHERE class_id.school_id = $sid AND date >= $term.start_date AND date <= $term.end_date AND status IN ['present', 'late'] GROUP ALL)[0].count AS attendance_present
                 FROM ONLY $sid;
                 "#,
             )
@@ -326,7 +595,7 @@ impl AnalyticsQ {
             .bind(("term_id", term_id))
             .await?;
 
-        let raw: Option<Value> = response.take(2)?;
+        let raw: Option<Value> = response.take(3)?;
 
         // Parse and map into DashboardMetrics
         let metrics = if let Some(v) = raw {
@@ -367,6 +636,21 @@ impl AnalyticsQ {
                             (collected / expected) * Decimal::from(100)
                         }
                     },
+                    attendance_rate: {
+                        let marked = obj
+                            .get("attendance_marked")
+                            .and_then(|x| x.as_i64().copied())
+                            .unwrap_or(0);
+                        let present = obj
+                            .get("attendance_present")
+                            .and_then(|x| x.as_i64().copied())
+                            .unwrap_or(0);
+                        if marked == 0 {
+                            Decimal::ZERO
+                        } else {
+                            (Decimal::from(present) / Decimal::from(marked)) * Decimal::from(100)
+                        }
+                    },
                 }
             } else {
                 DashboardMetrics {
@@ -376,6 +660,7 @@ impl AnalyticsQ {
                     total_collected: Decimal::ZERO,
                     total_expected: Decimal::ZERO,
                     collection_rate: Decimal::ZERO,
+                    attendance_rate: Decimal::ZERO,
                 }
             }
         } else {
@@ -386,6 +671,7 @@ impl AnalyticsQ {
                 total_collected: Decimal::ZERO,
                 total_expected: Decimal::ZERO,
                 collection_rate: Decimal::ZERO,
+                attendance_rate: Decimal::ZERO,
             }
         };
 
@@ -2,7 +2,10 @@ use crate::{
     error::DbResult,
     pagination::{Page, PaginatedResult},
 };
-use shared::models::{User, UserStatus, UserType};
+use shared::{
+    i18n::Locale,
+    models::{User, UserStatus, UserType},
+};
 use surrealdb::{Surreal, engine::remote::ws::Client};
 use surrealdb_types::Value;
 
@@ -47,6 +50,53 @@ impl UserQ {
         Ok(user)
     }
 
+    /// Find a school's user by any of the ways their phone number may be written
+    pub async fn find_in_school_by_phone(
+        &self,
+        sdb: &Surreal<Client>,
+        school_id: String,
+        phones: Vec<String>,
+    ) -> DbResult<Option<User>> {
+        let user: Option<User> = sdb
+            .query(
+                r#"
+                SELECT * FROM type::table($table)
+                WHERE school_id = type::thing('schools', $school_id)
+                AND phone_number IN $phones
+                LIMIT 1
+                "#,
+            )
+            .bind(("table", USER_TABLE))
+            .bind(("school_id", school_id))
+            .bind(("phones", phones))
+            .await?
+            .take(0)?;
+        Ok(user)
+    }
+
+    /// Active parent accounts with a phone number, in any school
+    pub async fn find_parents_by_phone(
+        &self,
+        sdb: &Surreal<Client>,
+        phones: Vec<String>,
+    ) -> DbResult<Vec<User>> {
+        let users: Vec<User> = sdb
+            .query(
+                r#"
+                SELECT * FROM type::table($table)
+                WHERE phone_number IN $phones
+                AND user_type = 'parent'
+                AND status = 'active'
+                ORDER BY created_at ASC
+                "#,
+            )
+            .bind(("table", USER_TABLE))
+            .bind(("phones", phones))
+            .await?
+            .take(0)?;
+        Ok(users)
+    }
+
     /// Get all active teachers in a school
     pub async fn get_teachers(
         &self,
@@ -92,6 +142,76 @@ impl UserQ {
         Ok(users)
     }
 
+    /// Active users of every type in a school
+    pub async fn get_active_by_school(
+        &self,
+        sdb: &Surreal<Client>,
+        school_id: String,
+    ) -> DbResult<Vec<User>> {
+        let users: Vec<User> = sdb
+            .query(
+                r#"
+                SELECT * FROM type::table($table)
+                WHERE school_id = type::thing('schools', $school_id)
+                AND status = 'active'
+                "#,
+            )
+            .bind(("table", USER_TABLE))
+            .bind(("school_id", school_id))
+            .await?
+            .take(0)?;
+        Ok(users)
+    }
+
+    /// Active parent accounts linked to an active student in a class
+    pub async fn get_class_parents(
+        &self,
+        sdb: &Surreal<Client>,
+        class_id: String,
+    ) -> DbResult<Vec<User>> {
+        let users: Vec<User> = sdb
+            .query(
+                r#"
+                LET $parents = array::distinct(array::flatten(
+                    SELECT VALUE ->student_parents->parents.user_id
+                    FROM students
+                    WHERE current_class_id = type::thing('classes', $class_id)
+                    AND status = 'active'
+                ));
+                SELECT * FROM type::table($table)
+                WHERE id IN $parents
+                AND status = 'active'
+                "#,
+            )
+            .bind(("table", USER_TABLE))
+            .bind(("class_id", class_id))
+            .await?
+            .take(1)?;
+        Ok(users)
+    }
+
+    /// Active parent accounts linked to a student
+    pub async fn get_student_parents(
+        &self,
+        sdb: &Surreal<Client>,
+        student_id: String,
+    ) -> DbResult<Vec<User>> {
+        let users: Vec<User> = sdb
+            .query(
+                r#"
+                LET $parents = type::thing('students', $student_id)->student_parents->parents.user_id;
+                SELECT * FROM type::table($table)
+                WHERE id IN $parents
+                AND status = 'active'
+                "#,
+            )
+            .bind(("table", USER_TABLE))
+            .bind(("student_id", student_id))
+            .await?
+            .take(1)?;
+        Ok(users)
+    }
+
     /// Update last login timestamp
     pub async fn update_last_login(
         &self,
@@ -136,6 +256,52 @@ impl UserQ {
         Ok(user)
     }
 
+    /// Set the user's language; None follows the school's default
+    pub async fn update_locale(
+        &self,
+        sdb: &Surreal<Client>,
+        user_id: String,
+        locale: Option<Locale>,
+    ) -> DbResult<Option<User>> {
+        let user: Option<User> = sdb
+            .query(
+                r#"
+                UPDATE type::thing($table, $id) SET
+                    locale = $locale,
+                    updated_at = time::now()
+                "#,
+            )
+            .bind(("table", USER_TABLE))
+            .bind(("id", user_id))
+            .bind(("locale", locale))
+            .await?
+            .take(0)?;
+        Ok(user)
+    }
+
+    /// Record whether the user has proved they own their email address
+    pub async fn set_email_verified(
+        &self,
+        sdb: &Surreal<Client>,
+        user_id: String,
+        verified: bool,
+    ) -> DbResult<Option<User>> {
+        let user: Option<User> = sdb
+            .query(
+                r#"
+                UPDATE type::thing($table, $id) SET
+                    email_verified = $verified,
+                    updated_at = time::now()
+                "#,
+            )
+            .bind(("table", USER_TABLE))
+            .bind(("id", user_id))
+            .bind(("verified", verified))
+            .await?
+            .take(0)?;
+        Ok(user)
+    }
+
     /// Get user with school info (fetch)
     pub async fn get_with_school(
         &self,
@@ -161,7 +327,13 @@ impl UserQ {
         sdb: &Surreal<Client>,
         user_id: String,
     ) -> DbResult<Option<User>> {
-        todo!()
+        let user: Option<User> = sdb
+            .query("SELECT * FROM type::table($table) WHERE id = type::thing($table, $id)")
+            .bind(("table", USER_TABLE))
+            .bind(("id", user_id))
+            .await?
+            .take(0)?;
+        Ok(user)
     }
 
     // Get all users in a school (paginated)
@@ -0,0 +1,367 @@
+use db::queries::{AnnouncementQ, BulkQ, MessageDeliveryQ, UserQ};
+use shared::{
+    helpers::{
+        api_responses::{AnnouncementDeliveryReport, SessionUser},
+        surreal_util::record_key,
+    },
+    i18n::{Localizer, Translations},
+    models::{
+        DeliveryStatus, MessageChannel, NotificationType, SmsType, TargetAudience, User,
+        UserStatus, UserType,
+        communication::Announcement,
+        system_and_audit::{MessageDelivery, Notification},
+    },
+};
+use surrealdb::{Surreal, engine::remote::ws::Client};
+use surrealdb_types::{Datetime, RecordId};
+
+use crate::{
+    auth::ensure_role,
+    delivery::summarize,
+    error::{DomainResult, Error},
+    locale::LocaleService,
+    subscription::SubscriptionService,
+    templating::{RenderedMessage, TemplateCache, TemplateContext, TemplateKey},
+};
+
+/// Publishing announcements to the people they are meant for
+pub struct AnnouncementService;
+
+impl AnnouncementService {
+    /// Publish an announcement: every recipient gets an in-app notification,
+    /// and SMS, email and push deliveries are queued for the channels it asks
+    /// for, each worded in the recipient's language
+    pub async fn publish(
+        &self,
+        sdb: &Surreal<Client>,
+        admin: &SessionUser,
+        announcement_id: String,
+    ) -> DomainResult<AnnouncementDeliveryReport> {
+        let announcement = self.administered(sdb, admin, &announcement_id).await?;
+        if announcement.published {
+            return Err(Error::Conflict("announcement is already published".into()));
+        }
+        let school_id = record_key(&announcement.school_id);
+        let school = SubscriptionService.ensure_writable(sdb, &school_id).await?;
+        let recipients = self.recipients(sdb, &announcement).await?;
+        let (school_locale, currency) = LocaleService
+            .school_defaults(sdb, school_id.clone())
+            .await?;
+
+        let mut templates = TemplateCache::new(school_id);
+        let mut notifications = Vec::new();
+        let mut deliveries = Vec::new();
+        for user in &recipients {
+            let i18n = Localizer::new(
+                Translations::global(),
+                user.locale.unwrap_or(school_locale),
+                currency,
+            );
+            let mut context = TemplateContext::new();
+            context
+                .school(&school)
+                .recipient(user)
+                .announcement(&announcement);
+            for channel in channels(&announcement) {
+                let key = match channel {
+                    MessageChannel::Sms | MessageChannel::Whatsapp => {
+                        TemplateKey::Sms(SmsType::Announcement)
+                    }
+                    MessageChannel::Email => TemplateKey::Email(NotificationType::Announcement),
+                    MessageChannel::Notification | MessageChannel::Push => {
+                        TemplateKey::Notification(NotificationType::Announcement)
+                    }
+                };
+                let message = templates.render(sdb, &key, &i18n, &context).await?;
+                if channel == MessageChannel::Notification {
+                    notifications.push(notification(&announcement, user, &message));
+                }
+                deliveries.push(delivery(&announcement, user, channel, message));
+            }
+        }
+
+        AnnouncementQ.publish(sdb, announcement_id.clone()).await?;
+        BulkQ.create_notifications_batch(sdb, notifications).await?;
+        let deliveries = MessageDeliveryQ.create_many(sdb, deliveries).await?;
+        Ok(summarize(announcement_id, &deliveries))
+    }
+
+    /// Where a published announcement's messages have got to
+    pub async fn report(
+        &self,
+        sdb: &Surreal<Client>,
+        admin: &SessionUser,
+        announcement_id: String,
+    ) -> DomainResult<AnnouncementDeliveryReport> {
+        self.administered(sdb, admin, &announcement_id).await?;
+        let deliveries = MessageDeliveryQ
+            .get_by_announcement(sdb, announcement_id.clone())
+            .await?;
+        Ok(summarize(announcement_id, &deliveries))
+    }
+
+    /// The announcement, provided the user is an admin of its school
+    async fn administered(
+        &self,
+        sdb: &Surreal<Client>,
+        admin: &SessionUser,
+        announcement_id: &str,
+    ) -> DomainResult<Announcement> {
+        let announcement = AnnouncementQ
+            .get_by_id(sdb, announcement_id.to_string())
+            .await?
+            .ok_or_else(|| Error::NotFound(format!("announcement {announcement_id}")))?;
+        ensure_role(
+            admin,
+            &record_key(&announcement.school_id),
+            &[UserType::Admin],
+        )?;
+        Ok(announcement)
+    }
+
+    /// Active users the announcement's audience covers
+    async fn recipients(
+        &self,
+        sdb: &Surreal<Client>,
+        announcement: &Announcement,
+    ) -> DomainResult<Vec<User>> {
+        audience(
+            sdb,
+            record_key(&announcement.school_id),
+            &announcement.target_audience,
+            announcement.class_id.as_ref(),
+        )
+        .await
+    }
+}
+
+/// Active users in a school an audience covers; a class audience is the
+/// class's parents
+pub(crate) async fn audience(
+    sdb: &Surreal<Client>,
+    school_id: String,
+    target_audience: &TargetAudience,
+    class_id: Option<&RecordId>,
+) -> DomainResult<Vec<User>> {
+    let users = match target_audience {
+        TargetAudience::All => UserQ.get_active_by_school(sdb, school_id).await?,
+        TargetAudience::Parents => UserQ.get_by_type(sdb, school_id, UserType::Parent).await?,
+        TargetAudience::Teachers => UserQ.get_by_type(sdb, school_id, UserType::Teacher).await?,
+        TargetAudience::Students => UserQ.get_by_type(sdb, school_id, UserType::Student).await?,
+        TargetAudience::SpecificClass => {
+            let class_id = class_id
+                .ok_or_else(|| Error::Validation("a class audience needs a class".into()))?;
+            UserQ.get_class_parents(sdb, record_key(class_id)).await?
+        }
+    };
+    Ok(users
+        .into_iter()
+        .filter(|user| user.id.is_some() && user.status == UserStatus::Active)
+        .collect())
+}
+
+/// In-app always, plus each channel the announcement asks for
+fn channels(announcement: &Announcement) -> Vec<MessageChannel> {
+    let mut channels = vec![MessageChannel::Notification];
+    if announcement.send_sms {
+        channels.push(MessageChannel::Sms);
+    }
+    if announcement.send_email {
+        channels.push(MessageChannel::Email);
+    }
+    if announcement.send_push {
+        channels.push(MessageChannel::Push);
+    }
+    channels
+}
+
+fn notification(
+    announcement: &Announcement,
+    user: &User,
+    message: &RenderedMessage,
+) -> Notification {
+    Notification {
+        id: None,
+        user_id: user.id.clone().expect("recipients have ids"),
+        notification_type: NotificationType::Announcement,
+        title: message
+            .subject
+            .clone()
+            .unwrap_or_else(|| announcement.title.clone()),
+        message: message.body.clone(),
+        link_url: None,
+        read: false,
+        read_at: None,
+        sent_at: Datetime::now(),
+        created_at: Datetime::now(),
+    }
+}
+
+/// One recipient's delivery on one channel. In-app notifications are
+/// delivered as soon as they are written; a recipient with no phone number
+/// or email address for the channel is recorded as failed rather than skipped.
+fn delivery(
+    announcement: &Announcement,
+    user: &User,
+    channel: MessageChannel,
+    message: RenderedMessage,
+) -> MessageDelivery {
+    let (address, missing) = match channel {
+        MessageChannel::Sms | MessageChannel::Whatsapp => {
+            (user.phone_number.clone(), "no phone number")
+        }
+        MessageChannel::Email => (user.email.clone(), "no email address"),
+        MessageChannel::Notification | MessageChannel::Push => (None, ""),
+    };
+    let address = address.filter(|a| !a.trim().is_empty());
+    let (status, last_error, delivered_at) = match channel {
+        MessageChannel::Notification => (DeliveryStatus::Delivered, None, Some(Datetime::now())),
+        MessageChannel::Sms | MessageChannel::Email | MessageChannel::Whatsapp
+            if address.is_none() =>
+        {
+            (DeliveryStatus::Failed, Some(missing.to_string()), None)
+        }
+        _ => (DeliveryStatus::Queued, None, None),
+    };
+    MessageDelivery {
+        id: None,
+        school_id: announcement.school_id.clone(),
+        announcement_id: announcement.id.clone(),
+        recipient_user_id: user.id.clone().expect("recipients have ids"),
+        channel,
+        address,
+        subject: message.subject,
+        body: message.body,
+        status,
+        attempts: 0,
+        last_error,
+        provider: None,
+        provider_message_id: None,
+        cost: None,
+        sent_at: None,
+        delivered_at,
+        template: None,
+        created_at: Datetime::now(),
+        updated_at: Datetime::now(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shared::models::Priority;
+    use surrealdb_types::Decimal;
+
+    fn announcement() -> Announcement {
+        Announcement {
+            id: Some(RecordId::new("announcements", "a1")),
+            school_id: RecordId::new("schools", "s1"),
+            title: "Mid-term break".into(),
+            message: "School closes on Friday.".into(),
+            target_audience: TargetAudience::Parents,
+            class_id: None,
+            priority: Priority::Normal,
+            send_sms: true,
+            send_email: true,
+            send_push: false,
+            published: false,
+            published_at: None,
+            created_by: None,
+            created_at: Datetime::now(),
+            updated_at: Datetime::now(),
+        }
+    }
+
+    fn user(key: &str, phone: Option<&str>, email: Option<&str>) -> User {
+        User {
+            id: Some(RecordId::new("users", key)),
+            school_id: RecordId::new("schools", "s1"),
+            user_type: UserType::Parent,
+            first_name: "Ngozi".into(),
+            last_name: "Okafor".into(),
+            email: email.map(Into::into),
+            phone_number: phone.map(Into::into),
+            password_hash: String::new(),
+            status: UserStatus::Active,
+            last_login: None,
+            email_verified: false,
+            phone_verified: false,
+            locale: None,
+            created_at: Datetime::now(),
+            updated_at: Datetime::now(),
+        }
+    }
+
+    fn message() -> RenderedMessage {
+        RenderedMessage {
+            subject: None,
+            body: "Mid-term break".into(),
+        }
+    }
+
+    #[test]
+    fn queues_reachable_channels_and_fails_the_rest() {
+        let announcement = announcement();
+        assert_eq!(
+            channels(&announcement),
+            vec![
+                MessageChannel::Notification,
+                MessageChannel::Sms,
+                MessageChannel::Email
+            ]
+        );
+
+        let parent = user("u1", Some("+2348012345678"), None);
+        let sms = delivery(&announcement, &parent, MessageChannel::Sms, message());
+        assert_eq!(sms.status, DeliveryStatus::Queued);
+        assert_eq!(sms.address.as_deref(), Some("+2348012345678"));
+
+        let email = delivery(&announcement, &parent, MessageChannel::Email, message());
+        assert_eq!(email.status, DeliveryStatus::Failed);
+        assert_eq!(email.last_error.as_deref(), Some("no email address"));
+
+        let in_app = delivery(
+            &announcement,
+            &parent,
+            MessageChannel::Notification,
+            message(),
+        );
+        assert_eq!(in_app.status, DeliveryStatus::Delivered);
+    }
+
+    #[test]
+    fn summarizes_deliveries_per_channel() {
+        let announcement = announcement();
+        let first = user("u1", Some("+2348012345678"), Some("ngozi@example.com"));
+        let second = user("u2", None, Some("tunde@example.com"));
+        let mut deliveries = Vec::new();
+        for parent in [&first, &second] {
+            for channel in channels(&announcement) {
+                deliveries.push(delivery(&announcement, parent, channel, message()));
+            }
+        }
+        deliveries[1].status = DeliveryStatus::Sent;
+        deliveries[1].cost = Some(Decimal::new(450, 2));
+        deliveries[2].status = DeliveryStatus::Bounced;
+        deliveries[2].last_error = Some("mailbox full".into());
+
+        let report = summarize("a1".into(), &deliveries);
+        assert_eq!(report.recipients, 2);
+        assert_eq!(report.total_cost, Decimal::new(450, 2));
+        let sms = report
+            .channels
+            .iter()
+            .find(|c| c.channel == MessageChannel::Sms)
+            .unwrap();
+        assert_eq!((sms.sent, sms.failed), (1, 1));
+        let email = report
+            .channels
+            .iter()
+            .find(|c| c.channel == MessageChannel::Email)
+            .unwrap();
+        assert_eq!((email.queued, email.bounced), (1, 1));
+        assert_eq!(report.failures.len(), 2);
+        assert_eq!(report.failures[0].error, "mailbox full");
+        assert_eq!(report.failures[1].error, "no phone number");
+    }
+}
@@ -0,0 +1,229 @@
+use argon2::{
+    Argon2, PasswordHash, PasswordHasher, PasswordVerifier,
+    password_hash::{
+        SaltString,
+        rand_core::{OsRng, RngCore},
+    },
+};
+use chrono::{Duration, Utc};
+use db::queries::{ActivityLogQ, SessionQ, UserQ};
+use shared::{
+    helpers::{api_responses::SessionUser, surreal_util::record_key},
+    i18n::Localizer,
+    models::{
+        ActionType, User, UserStatus, UserType,
+        system_and_audit::{ActivityLog, UserSession},
+    },
+};
+use surrealdb::{Surreal, engine::remote::ws::Client};
+use surrealdb_types::Datetime;
+
+use crate::{
+    error::{DomainResult, Error},
+    locale::LocaleService,
+};
+
+const SESSION_DAYS: i64 = 7;
+
+/// Everyone who works at a school
+pub const STAFF: &[UserType] = &[
+    UserType::SuperAdmin,
+    UserType::Admin,
+    UserType::Teacher,
+    UserType::Accountant,
+];
+
+/// The school's own admins
+pub const ADMINS: &[UserType] = &[UserType::SuperAdmin, UserType::Admin];
+
+pub struct AuthService;
+
+impl AuthService {
+    pub fn hash_password(&self, password: &str) -> DomainResult<String> {
+        let salt = SaltString::generate(&mut OsRng);
+        Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+            .map_err(|e| Error::Validation(e.to_string()))
+    }
+
+    pub fn verify_password(&self, password: &str, hash: &str) -> bool {
+        PasswordHash::new(hash)
+            .map(|parsed| {
+                Argon2::default()
+                    .verify_password(password.as_bytes(), &parsed)
+                    .is_ok()
+            })
+            .unwrap_or(false)
+    }
+
+    /// Check credentials (email or phone number) and open a session
+    pub async fn login(
+        &self,
+        sdb: &Surreal<Client>,
+        identifier: String,
+        password: String,
+        ip_address: Option<String>,
+    ) -> DomainResult<(UserSession, SessionUser)> {
+        let identifier = identifier.trim().to_string();
+        let user = if identifier.contains('@') {
+            UserQ.find_by_email(sdb, identifier.to_lowercase()).await?
+        } else {
+            UserQ.find_by_phone(sdb, identifier).await?
+        };
+        let invalid = || Error::Unauthorized("invalid login details".into());
+        let user = user
+            .filter(|u| u.status == UserStatus::Active)
+            .ok_or_else(invalid)?;
+        if !self.verify_password(&password, &user.password_hash) {
+            return Err(invalid());
+        }
+        let user_id = user.id.clone().ok_or_else(invalid)?;
+
+        let mut token = [0u8; 32];
+        OsRng.fill_bytes(&mut token);
+        let token: String = token.iter().map(|b| format!("{b:02x}")).collect();
+        let session = SessionQ
+            .create(
+                sdb,
+                UserSession {
+                    id: None,
+                    user_id: user_id.clone(),
+                    token,
+                    expires_at: Datetime::from(Utc::now() + Duration::days(SESSION_DAYS)),
+                    created_at: Datetime::now(),
+                },
+            )
+            .await?
+            .ok_or_else(|| Error::NotFound("session".into()))?;
+
+        UserQ.update_last_login(sdb, record_key(&user_id)).await?;
+        ActivityLogQ
+            .log(
+                sdb,
+                ActivityLog {
+                    id: None,
+                    school_id: user.school_id.clone(),
+                    user_id: Some(user_id),
+                    action: ActionType::Login,
+                    entity_type: None,
+                    entity_id: None,
+                    description: None,
+                    ip_address,
+                    metadata: None,
+                    created_at: Datetime::now(),
+                },
+            )
+            .await?;
+
+        let localizer = LocaleService.for_user(sdb, &user).await?;
+        Ok((session, session_user(&user, &localizer)))
+    }
+
+    /// The user behind a session token, if the session is still valid
+    pub async fn session_user(
+        &self,
+        sdb: &Surreal<Client>,
+        token: String,
+    ) -> DomainResult<Option<SessionUser>> {
+        let Some(session) = SessionQ.get_by_token(sdb, token).await? else {
+            return Ok(None);
+        };
+        let user = UserQ
+            .get_by_id(sdb, record_key(&session.user_id))
+            .await?
+            .filter(|u| u.status == UserStatus::Active);
+        match user {
+            Some(user) => {
+                let localizer = LocaleService.for_user(sdb, &user).await?;
+                Ok(Some(session_user(&user, &localizer)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub async fn logout(&self, sdb: &Surreal<Client>, token: String) -> DomainResult<()> {
+        SessionQ.delete_by_token(sdb, token).await?;
+        Ok(())
+    }
+}
+
+/// Fails unless the signed-in user belongs to the school as one of `roles`.
+/// Super admins may do whatever admins may.
+pub fn ensure_role(user: &SessionUser, school_id: &str, roles: &[UserType]) -> DomainResult<()> {
+    if user.school_id != school_id {
+        return Err(Error::Forbidden("not a member of this school".into()));
+    }
+    let allowed = roles.contains(&user.user_type)
+        || (user.user_type == UserType::SuperAdmin && roles.contains(&UserType::Admin));
+    if allowed {
+        Ok(())
+    } else {
+        Err(Error::Forbidden(
+            "your account isn't allowed to do this".into(),
+        ))
+    }
+}
+
+/// Fails unless `user_id` is the signed-in user's own account
+pub fn ensure_self(user: &SessionUser, user_id: &str) -> DomainResult<()> {
+    if user.user_id != user_id {
+        return Err(Error::Forbidden("this is someone else's account".into()));
+    }
+    Ok(())
+}
+
+fn session_user(user: &User, localizer: &Localizer) -> SessionUser {
+    SessionUser {
+        user_id: user.id.as_ref().map(record_key).unwrap_or_default(),
+        school_id: record_key(&user.school_id),
+        first_name: user.first_name.clone(),
+        last_name: user.last_name.clone(),
+        user_type: user.user_type.clone(),
+        locale: localizer.locale,
+        currency: localizer.currency,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verifies_hashed_password() {
+        let hash = AuthService.hash_password("s3cret-pass").unwrap();
+        assert!(AuthService.verify_password("s3cret-pass", &hash));
+        assert!(!AuthService.verify_password("wrong", &hash));
+        assert!(!AuthService.verify_password("s3cret-pass", "not-a-hash"));
+    }
+
+    #[test]
+    fn checks_school_and_role() {
+        let user = |user_type| SessionUser {
+            user_id: "u1".into(),
+            school_id: "s1".into(),
+            first_name: "Ada".into(),
+            last_name: "Obi".into(),
+            user_type,
+            locale: Default::default(),
+            currency: Default::default(),
+        };
+        let admins = &[UserType::Admin];
+
+        assert!(ensure_role(&user(UserType::Admin), "s1", admins).is_ok());
+        assert!(ensure_role(&user(UserType::SuperAdmin), "s1", admins).is_ok());
+        assert!(ensure_role(&user(UserType::Teacher), "s1", STAFF).is_ok());
+        assert!(matches!(
+            ensure_role(&user(UserType::Teacher), "s1", admins),
+            Err(Error::Forbidden(_))
+        ));
+        assert!(matches!(
+            ensure_role(&user(UserType::Admin), "s2", admins),
+            Err(Error::Forbidden(_))
+        ));
+        assert!(matches!(
+            ensure_role(&user(UserType::Parent), "s1", STAFF),
+            Err(Error::Forbidden(_))
+        ));
+    }
+}
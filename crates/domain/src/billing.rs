@@ -0,0 +1,553 @@
+use std::env;
+
+use chrono::{DateTime, Datelike, Duration, NaiveTime, Utc};
+use db::queries::{
+    AcademicSessionQ, PlatformInvoiceQ, PlatformPaymentQ, SchoolQ, SmsLogQ, TermQ, UserQ,
+};
+use shared::{
+    helpers::{
+        api_responses::PlatformPaymentReceipt,
+        create_update_dtos::{GeneratePlatformInvoiceDto, RecordPlatformPaymentDto},
+        surreal_util::{record_key, to_naive_date},
+    },
+    models::{
+        InvoiceStatus, School, SubscriptionPlan, SubscriptionStatus, User, UserStatus, UserType,
+        academic::Term,
+        fee_management::{PlatformInvoice, PlatformPayment},
+    },
+};
+use surrealdb::{Surreal, engine::remote::ws::Client};
+use surrealdb_types::{Datetime, Decimal, RecordId};
+
+use crate::error::{DomainResult, Error};
+
+/// Days a school has to pay a platform invoice
+pub const PAYMENT_TERMS_DAYS: i64 = 30;
+/// Tries at taking an invoice number while other billing runs hold the counter
+const NUMBERING_ATTEMPTS: u32 = 5;
+/// Comma separated ids of the user accounts that run the platform. Being
+/// set here is the only way to become a platform operator; no role a school
+/// gives its own staff does it.
+pub const PLATFORM_OPERATORS_VAR: &str = "PLATFORM_OPERATORS";
+
+/// What the platform charges per student on roll for a term, by plan
+pub fn rate_per_student(plan: &SubscriptionPlan) -> Decimal {
+    match plan {
+        SubscriptionPlan::Starter => Decimal::new(300, 0),
+        SubscriptionPlan::Basic => Decimal::new(400, 0),
+        SubscriptionPlan::Standard => Decimal::new(500, 0),
+        SubscriptionPlan::Premium => Decimal::new(750, 0),
+        SubscriptionPlan::Enterprise => Decimal::new(1000, 0),
+    }
+}
+
+/// Bills schools once a term for their plan and the SMS they sent, and
+/// renews their subscription when an invoice is paid in full
+pub struct BillingService;
+
+impl BillingService {
+    /// Bill a school for a term, by default the current one. A school is
+    /// billed once per term.
+    pub async fn generate(
+        &self,
+        sdb: &Surreal<Client>,
+        staff_id: String,
+        school_id: String,
+        dto: GeneratePlatformInvoiceDto,
+    ) -> DomainResult<PlatformInvoice> {
+        self.platform_operator(sdb, &staff_id).await?;
+        let school = self.school(sdb, &school_id).await?;
+        let term = match dto.term_id {
+            Some(term_id) => {
+                let sessions = AcademicSessionQ
+                    .get_all_by_school(sdb, school_id.clone())
+                    .await?;
+                TermQ
+                    .get_by_id(sdb, term_id.clone())
+                    .await?
+                    .filter(|term| {
+                        sessions
+                            .iter()
+                            .any(|session| session.id.as_ref() == Some(&term.session_id))
+                    })
+                    .ok_or_else(|| Error::NotFound(format!("term {term_id}")))?
+            }
+            None => self
+                .current_term(sdb, &school_id)
+                .await?
+                .ok_or_else(|| Error::NotFound(format!("current term for school {school_id}")))?,
+        };
+        self.bill(sdb, &school, &term).await
+    }
+
+    /// Bill every active and trial school for its current term, if it hasn't
+    /// been already, returning how many invoices were raised
+    pub async fn bill_current_terms(&self, sdb: &Surreal<Client>) -> DomainResult<usize> {
+        let mut billed = 0;
+        for school in SchoolQ.get_billable(sdb).await? {
+            let Some(school_id) = school.id.as_ref().map(record_key) else {
+                continue;
+            };
+            let Some(term) = self.current_term(sdb, &school_id).await? else {
+                continue;
+            };
+            let Some(term_id) = term.id.as_ref().map(record_key) else {
+                continue;
+            };
+            if PlatformInvoiceQ
+                .get_by_school_term(sdb, school_id, term_id)
+                .await?
+                .is_some()
+            {
+                continue;
+            }
+            match self.bill(sdb, &school, &term).await {
+                Ok(_) => billed += 1,
+                // Another run billed the school first
+                Err(Error::Conflict(_)) => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(billed)
+    }
+
+    /// A school's platform invoices, newest first, for platform operators
+    /// and the school's own admins
+    pub async fn invoices(
+        &self,
+        sdb: &Surreal<Client>,
+        user_id: String,
+        school_id: String,
+    ) -> DomainResult<Vec<PlatformInvoice>> {
+        let user = self.user(sdb, &user_id).await?;
+        let own_admin = matches!(user.user_type, UserType::SuperAdmin | UserType::Admin)
+            && record_key(&user.school_id) == school_id;
+        if !own_admin && !is_operator(&operators(), &user_id) {
+            return Err(Error::Forbidden(
+                "only the school's admins can see its platform invoices".into(),
+            ));
+        }
+        Ok(PlatformInvoiceQ.get_by_school(sdb, school_id).await?)
+    }
+
+    /// Record money a school paid towards a platform invoice. Paying it in
+    /// full makes the school active until the end of the term it covers.
+    pub async fn record_payment(
+        &self,
+        sdb: &Surreal<Client>,
+        staff_id: String,
+        invoice_id: String,
+        dto: RecordPlatformPaymentDto,
+    ) -> DomainResult<PlatformPaymentReceipt> {
+        self.platform_operator(sdb, &staff_id).await?;
+        let invoice = PlatformInvoiceQ
+            .get_by_id(sdb, invoice_id.clone())
+            .await?
+            .ok_or_else(|| Error::NotFound(format!("platform invoice {invoice_id}")))?;
+        check_payment(&invoice, dto.amount)?;
+
+        let now = Utc::now();
+        let (amount_paid, status, paid_at) = settlement(&invoice, dto.amount, now);
+        let invoice = PlatformInvoiceQ
+            .settle(
+                sdb,
+                invoice_id.clone(),
+                invoice.amount_paid,
+                amount_paid,
+                status,
+                paid_at,
+            )
+            .await?
+            .ok_or_else(|| {
+                Error::Conflict(format!(
+                    "platform invoice {invoice_id} changed while the payment was recorded; try again"
+                ))
+            })?;
+
+        let payment = PlatformPaymentQ
+            .record(
+                sdb,
+                PlatformPayment {
+                    id: None,
+                    school_id: invoice.school_id.clone(),
+                    invoice_id: RecordId::new("platform_invoices", invoice_id.as_str()),
+                    amount: dto.amount,
+                    payment_method: dto.payment_method,
+                    reference: dto
+                        .reference
+                        .map(|r| r.trim().to_string())
+                        .filter(|r| !r.is_empty()),
+                    recorded_by: Some(RecordId::new("users", staff_id.as_str())),
+                    paid_at: Datetime::from(now),
+                    created_at: Datetime::now(),
+                },
+            )
+            .await?
+            .ok_or_else(|| Error::Validation("payment was not recorded".into()))?;
+
+        let school = if invoice.status == InvoiceStatus::Paid {
+            self.renew(sdb, &invoice).await?
+        } else {
+            self.school(sdb, &record_key(&invoice.school_id)).await?
+        };
+
+        Ok(PlatformPaymentReceipt {
+            payment,
+            invoice,
+            subscription_status: school.subscription_status,
+            subscription_expiry_date: school.subscription_expiry_date,
+        })
+    }
+
+    async fn bill(
+        &self,
+        sdb: &Surreal<Client>,
+        school: &School,
+        term: &Term,
+    ) -> DomainResult<PlatformInvoice> {
+        let school_rid = school
+            .id
+            .clone()
+            .ok_or_else(|| Error::Validation("school has no id".into()))?;
+        let term_rid = term
+            .id
+            .clone()
+            .ok_or_else(|| Error::Validation("term has no id".into()))?;
+        let school_id = record_key(&school_rid);
+        let term_id = record_key(&term_rid);
+
+        let already_billed = || {
+            Error::Conflict(format!(
+                "{} has already been billed for this term",
+                school.school_name
+            ))
+        };
+        if PlatformInvoiceQ
+            .get_by_school_term(sdb, school_id.clone(), term_id.clone())
+            .await?
+            .is_some()
+        {
+            return Err(already_billed());
+        }
+        let period_end = to_naive_date(&term.end_date)
+            .map(|date| date.and_time(NaiveTime::MIN).and_utc() + Duration::days(1))
+            .ok_or_else(|| Error::Validation(format!("term {term_id} has no end date")))?;
+
+        // SMS are metered from where the school's last invoice left off
+        let sms_from = PlatformInvoiceQ
+            .get_by_school(sdb, school_id.clone())
+            .await?
+            .into_iter()
+            .next()
+            .map(|previous| previous.created_at)
+            .unwrap_or(school.created_at);
+        let (sms_count, sms_amount) = SmsLogQ.get_usage_since(sdb, school_id, sms_from).await?;
+
+        let now = Utc::now();
+        let sequence = self.next_sequence(sdb).await?;
+        let charges = charges(&school.subscription_plan, school.total_students, sms_amount);
+        let settled = charges.total <= Decimal::ZERO;
+
+        // A concurrent run may bill the school between the check above and
+        // here; the unique index on school and term turns that into Duplicate
+        let invoice = PlatformInvoiceQ
+            .create(
+                sdb,
+                PlatformInvoice {
+                    id: None,
+                    school_id: school_rid,
+                    term_id: term_rid,
+                    invoice_number: invoice_number(now.year(), sequence),
+                    plan: school.subscription_plan.clone(),
+                    student_count: school.total_students,
+                    rate_per_student: charges.rate,
+                    subscription_amount: charges.subscription,
+                    sms_count: sms_count as i64,
+                    sms_amount,
+                    sms_from,
+                    total_amount: charges.total,
+                    amount_paid: Decimal::ZERO,
+                    balance: charges.total,
+                    status: if settled {
+                        InvoiceStatus::Paid
+                    } else {
+                        InvoiceStatus::Unpaid
+                    },
+                    period_end: Datetime::from(period_end),
+                    due_date: Datetime::from(now + Duration::days(PAYMENT_TERMS_DAYS)),
+                    paid_at: settled.then(|| Datetime::from(now)),
+                    created_at: Datetime::from(now),
+                    updated_at: Datetime::from(now),
+                },
+            )
+            .await
+            .map_err(|err| match err {
+                db::error::Error::Duplicate(_) => already_billed(),
+                err => err.into(),
+            })?
+            .ok_or_else(|| Error::Validation("platform invoice was not created".into()))?;
+
+        // Nothing owed, so the term is paid for already
+        if settled {
+            self.renew(sdb, &invoice).await?;
+        }
+        Ok(invoice)
+    }
+
+    /// The next invoice number, retried if a concurrent run held the counter
+    async fn next_sequence(&self, sdb: &Surreal<Client>) -> DomainResult<u64> {
+        let mut attempt = 1;
+        loop {
+            match PlatformInvoiceQ.next_sequence(sdb).await {
+                Err(err) if err.is_retryable() && attempt < NUMBERING_ATTEMPTS => attempt += 1,
+                result => return Ok(result?),
+            }
+        }
+    }
+
+    /// Make the invoice's school active until at least the end of the term
+    /// the invoice covers
+    async fn renew(
+        &self,
+        sdb: &Surreal<Client>,
+        invoice: &PlatformInvoice,
+    ) -> DomainResult<School> {
+        let school_id = record_key(&invoice.school_id);
+        let school = self.school(sdb, &school_id).await?;
+        let expiry = renewed_expiry(
+            school.subscription_expiry_date.as_deref(),
+            &invoice.period_end,
+        );
+        SchoolQ
+            .renew_subscription(
+                sdb,
+                school_id.clone(),
+                SubscriptionStatus::Active,
+                Datetime::from(expiry),
+            )
+            .await?
+            .ok_or_else(|| Error::NotFound(format!("school {school_id}")))
+    }
+
+    async fn current_term(
+        &self,
+        sdb: &Surreal<Client>,
+        school_id: &str,
+    ) -> DomainResult<Option<Term>> {
+        let Some(session_id) = AcademicSessionQ
+            .get_current(sdb, school_id.to_string())
+            .await?
+            .and_then(|session| session.id)
+        else {
+            return Ok(None);
+        };
+        Ok(TermQ.get_current(sdb, record_key(&session_id)).await?)
+    }
+
+    async fn school(&self, sdb: &Surreal<Client>, school_id: &str) -> DomainResult<School> {
+        SchoolQ
+            .get_by_id(sdb, school_id.to_string())
+            .await?
+            .ok_or_else(|| Error::NotFound(format!("school {school_id}")))
+    }
+
+    async fn user(&self, sdb: &Surreal<Client>, user_id: &str) -> DomainResult<User> {
+        UserQ
+            .get_by_id(sdb, user_id.to_string())
+            .await?
+            .filter(|u| u.status == UserStatus::Active)
+            .ok_or_else(|| Error::NotFound(format!("user {user_id}")))
+    }
+
+    async fn platform_operator(&self, sdb: &Surreal<Client>, user_id: &str) -> DomainResult<User> {
+        if !is_operator(&operators(), user_id) {
+            return Err(Error::Forbidden(
+                "only platform operators can bill schools".into(),
+            ));
+        }
+        self.user(sdb, user_id).await
+    }
+}
+
+fn operators() -> String {
+    env::var(PLATFORM_OPERATORS_VAR).unwrap_or_default()
+}
+
+/// Whether the user is on the configured operator list
+fn is_operator(operators: &str, user_id: &str) -> bool {
+    operators
+        .split(',')
+        .map(str::trim)
+        .any(|id| !id.is_empty() && id == user_id)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Charges {
+    rate: Decimal,
+    subscription: Decimal,
+    total: Decimal,
+}
+
+fn charges(plan: &SubscriptionPlan, students: i32, sms_amount: Decimal) -> Charges {
+    let rate = rate_per_student(plan);
+    let subscription = rate * Decimal::from(students.max(0));
+    Charges {
+        rate,
+        subscription,
+        total: subscription + sms_amount,
+    }
+}
+
+/// PLT-{YEAR}-{6 digit padded sequence}
+fn invoice_number(year: i32, sequence: u64) -> String {
+    format!("PLT-{year}-{sequence:06}")
+}
+
+fn check_payment(invoice: &PlatformInvoice, amount: Decimal) -> DomainResult<()> {
+    if matches!(
+        invoice.status,
+        InvoiceStatus::Paid | InvoiceStatus::Cancelled
+    ) {
+        return Err(Error::Conflict(format!(
+            "invoice {} is not open for payment",
+            invoice.invoice_number
+        )));
+    }
+    if amount <= Decimal::ZERO {
+        return Err(Error::Validation("payment amount must be positive".into()));
+    }
+    if amount > invoice.balance {
+        return Err(Error::Validation(format!(
+            "payment of {} is more than the {} owed on invoice {}",
+            amount.normalize(),
+            invoice.balance.normalize(),
+            invoice.invoice_number
+        )));
+    }
+    Ok(())
+}
+
+/// What the invoice has been paid, its status and when it was paid off,
+/// after a payment of `amount`
+fn settlement(
+    invoice: &PlatformInvoice,
+    amount: Decimal,
+    now: DateTime<Utc>,
+) -> (Decimal, InvoiceStatus, Option<Datetime>) {
+    let amount_paid = invoice.amount_paid + amount;
+    if amount_paid >= invoice.total_amount {
+        (amount_paid, InvoiceStatus::Paid, Some(Datetime::from(now)))
+    } else {
+        (amount_paid, InvoiceStatus::Partial, None)
+    }
+}
+
+/// Paying for a term never shortens time a school already has
+fn renewed_expiry(current: Option<&DateTime<Utc>>, period_end: &DateTime<Utc>) -> DateTime<Utc> {
+    match current {
+        Some(current) if current > period_end => *current,
+        _ => *period_end,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invoice(total: i64, paid: i64, status: InvoiceStatus) -> PlatformInvoice {
+        PlatformInvoice {
+            id: None,
+            school_id: RecordId::new("schools", "s1"),
+            term_id: RecordId::new("terms", "t1"),
+            invoice_number: "PLT-2026-000001".into(),
+            plan: SubscriptionPlan::Basic,
+            student_count: 100,
+            rate_per_student: Decimal::new(400, 0),
+            subscription_amount: Decimal::new(total, 0),
+            sms_count: 0,
+            sms_amount: Decimal::ZERO,
+            sms_from: Datetime::now(),
+            total_amount: Decimal::new(total, 0),
+            amount_paid: Decimal::new(paid, 0),
+            balance: Decimal::new(total - paid, 0),
+            status,
+            period_end: Datetime::now(),
+            due_date: Datetime::now(),
+            paid_at: None,
+            created_at: Datetime::now(),
+            updated_at: Datetime::now(),
+        }
+    }
+
+    fn at(rfc3339: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(rfc3339)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn charges_per_student_plus_metered_sms() {
+        assert_eq!(
+            charges(&SubscriptionPlan::Basic, 250, Decimal::new(123450, 2)),
+            Charges {
+                rate: Decimal::new(400, 0),
+                subscription: Decimal::new(100_000, 0),
+                total: Decimal::new(10_123_450, 2),
+            }
+        );
+        assert_eq!(
+            charges(&SubscriptionPlan::Starter, 0, Decimal::ZERO).total,
+            Decimal::ZERO
+        );
+        assert_eq!(invoice_number(2026, 42), "PLT-2026-000042");
+    }
+
+    #[test]
+    fn payments_settle_invoices() {
+        let open = invoice(40_000, 10_000, InvoiceStatus::Partial);
+        assert!(check_payment(&open, Decimal::new(30_000, 0)).is_ok());
+        assert!(matches!(
+            check_payment(&open, Decimal::new(30_001, 0)),
+            Err(Error::Validation(_))
+        ));
+        assert!(matches!(
+            check_payment(&open, Decimal::ZERO),
+            Err(Error::Validation(_))
+        ));
+        assert!(matches!(
+            check_payment(&invoice(40_000, 40_000, InvoiceStatus::Paid), Decimal::ONE),
+            Err(Error::Conflict(_))
+        ));
+
+        let now = at("2026-10-19T12:00:00Z");
+        let (paid, status, paid_at) = settlement(&open, Decimal::new(5_000, 0), now);
+        assert_eq!(
+            (paid, status, paid_at),
+            (Decimal::new(15_000, 0), InvoiceStatus::Partial, None)
+        );
+        let (paid, status, paid_at) = settlement(&open, Decimal::new(30_000, 0), now);
+        assert_eq!(paid, Decimal::new(40_000, 0));
+        assert_eq!(status, InvoiceStatus::Paid);
+        assert_eq!(paid_at.as_deref(), Some(&now));
+    }
+
+    #[test]
+    fn renewal_runs_to_the_end_of_the_term() {
+        let term_end = at("2026-12-19T00:00:00Z");
+        assert_eq!(renewed_expiry(None, &term_end), term_end);
+        assert_eq!(
+            renewed_expiry(Some(&at("2026-10-01T00:00:00Z")), &term_end),
+            term_end
+        );
+        let later = at("2027-04-01T00:00:00Z");
+        assert_eq!(renewed_expiry(Some(&later), &term_end), later);
+    }
+
+    #[test]
+    fn only_listed_accounts_operate_the_platform() {
+        assert!(is_operator("ops1, ops2", "ops2"));
+        assert!(!is_operator("ops1, ops2", "admin1"));
+        assert!(!is_operator("", ""));
+        assert!(!is_operator("ops1,,", ""));
+    }
+}
@@ -0,0 +1,606 @@
+use std::collections::HashMap;
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use chrono::{DateTime, Duration, NaiveDate, NaiveTime, Utc};
+use db::queries::{
+    AcademicSessionQ, AssessmentQ, CalendarFeedQ, ClassQ, ClassSubjectQ, EventQ, SchoolQ, SubjectQ,
+    TermQ, UserQ,
+};
+use shared::{
+    helpers::{
+        api_responses::CalendarFeedLink,
+        surreal_util::{record_key, to_naive_date, to_surreal_datetime},
+    },
+    i18n::{Localizer, Translations},
+    models::{
+        TargetAudience, User, UserStatus, UserType,
+        academic::{AcademicSession, Term},
+        assessments_nd_grading::Assessment,
+        communication::Event,
+        system_and_audit::CalendarFeed,
+    },
+};
+use surrealdb::{Surreal, engine::remote::ws::Client};
+use surrealdb_types::{Datetime, RecordId, SurrealValue};
+
+use crate::{
+    error::{DomainResult, Error},
+    locale::LocaleService,
+    parent_portal::ParentPortalService,
+};
+
+/// How far back feeds still carry past dates
+const PAST_DAYS: i64 = 90;
+const PRODUCT_ID: &str = "-//School Management//School Calendar//EN";
+/// iCalendar lines longer than this many bytes are folded
+const LINE_LIMIT: usize = 75;
+
+/// Calendar subscriptions for phones and desktop calendar apps
+pub struct CalendarService;
+
+impl CalendarService {
+    /// The user's own feed, created the first time it's asked for
+    pub async fn feed(
+        &self,
+        sdb: &Surreal<Client>,
+        user_id: String,
+    ) -> DomainResult<CalendarFeedLink> {
+        let user = self.user(sdb, &user_id).await?;
+        let feed = match CalendarFeedQ.get_by_user(sdb, user_id).await? {
+            Some(feed) => feed,
+            None => self.create(sdb, user.school_id, user.id).await?,
+        };
+        Ok(link(feed))
+    }
+
+    /// The feed of everything on the school's calendar; admins only
+    pub async fn school_feed(
+        &self,
+        sdb: &Surreal<Client>,
+        user_id: String,
+    ) -> DomainResult<CalendarFeedLink> {
+        let admin = self.admin(sdb, &user_id).await?;
+        let school_id = record_key(&admin.school_id);
+        let feed = match CalendarFeedQ.get_by_school(sdb, school_id).await? {
+            Some(feed) => feed,
+            None => self.create(sdb, admin.school_id, None).await?,
+        };
+        Ok(link(feed))
+    }
+
+    /// Stop the user's feed URL working; asking for the feed again gives a
+    /// new one
+    pub async fn revoke(&self, sdb: &Surreal<Client>, user_id: String) -> DomainResult<()> {
+        if let Some(id) = CalendarFeedQ
+            .get_by_user(sdb, user_id)
+            .await?
+            .and_then(|feed| feed.id)
+        {
+            CalendarFeedQ.delete(sdb, record_key(&id)).await?;
+        }
+        Ok(())
+    }
+
+    /// Stop the school feed's URL working; admins only
+    pub async fn revoke_school_feed(
+        &self,
+        sdb: &Surreal<Client>,
+        user_id: String,
+    ) -> DomainResult<()> {
+        let admin = self.admin(sdb, &user_id).await?;
+        if let Some(id) = CalendarFeedQ
+            .get_by_school(sdb, record_key(&admin.school_id))
+            .await?
+            .and_then(|feed| feed.id)
+        {
+            CalendarFeedQ.delete(sdb, record_key(&id)).await?;
+        }
+        Ok(())
+    }
+
+    /// The iCalendar document behind a feed URL: sessions and terms, events
+    /// and dated assessments. A user's feed keeps to the events meant for
+    /// them and, for parents and teachers, their classes' assessments.
+    pub async fn ics(&self, sdb: &Surreal<Client>, token: String) -> DomainResult<String> {
+        let not_found = || Error::NotFound("calendar feed".into());
+        let feed = CalendarFeedQ
+            .get_by_token(sdb, token)
+            .await?
+            .ok_or_else(not_found)?;
+        let school_id = record_key(&feed.school_id);
+        let school_name = SchoolQ
+            .get_by_id(sdb, school_id.clone())
+            .await?
+            .map(|s| s.school_name)
+            .unwrap_or_default();
+        let (school_locale, currency) = LocaleService
+            .school_defaults(sdb, school_id.clone())
+            .await?;
+
+        let (scope, locale, name) = match &feed.user_id {
+            None => (Scope::School, school_locale, school_name.clone()),
+            Some(user_id) => {
+                let user = UserQ
+                    .get_by_id(sdb, record_key(user_id))
+                    .await?
+                    .filter(|u| u.status == UserStatus::Active && u.school_id == feed.school_id)
+                    .ok_or_else(not_found)?;
+                let scope = self.scope(sdb, &user).await?;
+                let name = format!("{} {}", user.first_name, user.last_name);
+                (scope, user.locale.unwrap_or(school_locale), name)
+            }
+        };
+        let i18n = Localizer::new(Translations::global(), locale, currency);
+        let name = match feed.user_id {
+            Some(_) => i18n.format(
+                "calendar.personal",
+                &[("school", &school_name), ("name", &name)],
+            ),
+            None => name,
+        };
+
+        let since = Utc::now().date_naive() - Duration::days(PAST_DAYS);
+        let mut entries = Vec::new();
+        for session in AcademicSessionQ
+            .get_all_by_school(sdb, school_id.clone())
+            .await?
+        {
+            entries.extend(session_entries(&session, &i18n));
+            if let Some(session_id) = &session.id {
+                for term in TermQ.get_by_session(sdb, record_key(session_id)).await? {
+                    entries.extend(term_entries(&term, &i18n));
+                }
+            }
+        }
+        entries.extend(
+            EventQ
+                .get_since(sdb, school_id.clone(), to_surreal_datetime(since))
+                .await?
+                .iter()
+                .filter(|event| scope.shows_event(event))
+                .filter_map(event_entry),
+        );
+
+        let names = |rows: Vec<(Option<RecordId>, String)>| -> HashMap<String, String> {
+            rows.into_iter()
+                .filter_map(|(id, name)| Some((record_key(&id?), name)))
+                .collect()
+        };
+        let subjects = names(
+            SubjectQ
+                .get_by_school(sdb, school_id.clone())
+                .await?
+                .into_iter()
+                .map(|s| (s.id, s.subject_name))
+                .collect(),
+        );
+        let classes = names(
+            ClassQ
+                .get_by_school(sdb, school_id.clone())
+                .await?
+                .into_iter()
+                .map(|c| (c.id, c.class_name))
+                .collect(),
+        );
+        entries.extend(
+            AssessmentQ
+                .get_by_school(sdb, school_id)
+                .await?
+                .iter()
+                .filter(|assessment| scope.shows_assessment(assessment))
+                .filter_map(|assessment| assessment_entry(assessment, &subjects, &classes, &i18n)),
+        );
+
+        entries.retain(|entry| entry.date >= since);
+        entries.sort_by_key(|entry| (entry.date, entry.time));
+        if let Some(id) = &feed.id {
+            CalendarFeedQ.touch(sdb, record_key(id)).await?;
+        }
+        Ok(render(&name, &entries, Utc::now()))
+    }
+
+    async fn create(
+        &self,
+        sdb: &Surreal<Client>,
+        school_id: RecordId,
+        user_id: Option<RecordId>,
+    ) -> DomainResult<CalendarFeed> {
+        let mut token = [0u8; 32];
+        OsRng.fill_bytes(&mut token);
+        CalendarFeedQ
+            .create(
+                sdb,
+                CalendarFeed {
+                    id: None,
+                    school_id,
+                    user_id,
+                    token: token.iter().map(|b| format!("{b:02x}")).collect(),
+                    last_fetched_at: None,
+                    created_at: Datetime::now(),
+                },
+            )
+            .await?
+            .ok_or_else(|| Error::NotFound("calendar feed".into()))
+    }
+
+    /// Which of the school's dates concern a user
+    async fn scope(&self, sdb: &Surreal<Client>, user: &User) -> DomainResult<Scope> {
+        let user_id = user.id.as_ref().map(record_key).unwrap_or_default();
+        let (audience, classes) = match user.user_type {
+            UserType::SuperAdmin | UserType::Admin | UserType::Accountant => {
+                return Ok(Scope::School);
+            }
+            UserType::Parent => (
+                TargetAudience::Parents,
+                ParentPortalService
+                    .children(sdb, user_id)
+                    .await?
+                    .into_iter()
+                    .filter_map(|child| child.current_class_id)
+                    .collect(),
+            ),
+            UserType::Teacher => {
+                let mut classes: Vec<RecordId> = ClassQ
+                    .get_by_class_teacher(sdb, user_id.clone())
+                    .await?
+                    .into_iter()
+                    .filter_map(|class| class.id)
+                    .collect();
+                for row in ClassSubjectQ.get_by_teacher(sdb, user_id).await? {
+                    if let Ok(class_id) = RecordId::from_value(row.get("class_id").clone())
+                        && !classes.contains(&class_id)
+                    {
+                        classes.push(class_id);
+                    }
+                }
+                (TargetAudience::Teachers, classes)
+            }
+            // Student accounts aren't linked to a student record, so there
+            // are no classes to follow
+            UserType::Student => (TargetAudience::Students, Vec::new()),
+        };
+        Ok(Scope::Member { audience, classes })
+    }
+
+    async fn user(&self, sdb: &Surreal<Client>, user_id: &str) -> DomainResult<User> {
+        UserQ
+            .get_by_id(sdb, user_id.to_string())
+            .await?
+            .filter(|u| u.status == UserStatus::Active)
+            .ok_or_else(|| Error::NotFound(format!("user {user_id}")))
+    }
+
+    async fn admin(&self, sdb: &Surreal<Client>, user_id: &str) -> DomainResult<User> {
+        let user = self.user(sdb, user_id).await?;
+        if matches!(user.user_type, UserType::Admin | UserType::SuperAdmin) {
+            Ok(user)
+        } else {
+            Err(Error::Forbidden(
+                "only admins can manage the school calendar feed".into(),
+            ))
+        }
+    }
+}
+
+/// Which of a school's dates a feed carries
+#[derive(Debug)]
+enum Scope {
+    School,
+    /// Events for everyone, for the user's kind of account or for one of
+    /// their classes, and those classes' assessments
+    Member {
+        audience: TargetAudience,
+        classes: Vec<RecordId>,
+    },
+}
+
+impl Scope {
+    fn shows_event(&self, event: &Event) -> bool {
+        match self {
+            Scope::School => true,
+            Scope::Member { audience, classes } => match event.target_audience {
+                TargetAudience::All => true,
+                TargetAudience::SpecificClass => event
+                    .class_id
+                    .as_ref()
+                    .is_some_and(|class_id| classes.contains(class_id)),
+                ref target => target == audience,
+            },
+        }
+    }
+
+    fn shows_assessment(&self, assessment: &Assessment) -> bool {
+        match self {
+            Scope::School => true,
+            Scope::Member { classes, .. } => classes.contains(&assessment.class_id),
+        }
+    }
+}
+
+/// One date on the calendar, all day unless it has a time
+#[derive(Debug, Clone, PartialEq)]
+struct Entry {
+    uid: String,
+    date: NaiveDate,
+    time: Option<NaiveTime>,
+    summary: String,
+    description: Option<String>,
+    location: Option<String>,
+}
+
+impl Entry {
+    fn new(id: &RecordId, part: &str, date: NaiveDate, summary: String) -> Self {
+        Self {
+            uid: format!("{}{part}", record_key(id)),
+            date,
+            time: None,
+            summary,
+            description: None,
+            location: None,
+        }
+    }
+}
+
+fn link(feed: CalendarFeed) -> CalendarFeedLink {
+    CalendarFeedLink {
+        path: format!("/calendar/{}/feed.ics", feed.token),
+        feed,
+    }
+}
+
+fn session_entries(session: &AcademicSession, i18n: &Localizer) -> Vec<Entry> {
+    let Some(id) = &session.id else {
+        return Vec::new();
+    };
+    let name = [("session", session.session_name.as_str())];
+    [
+        (
+            "session-start",
+            &session.start_date,
+            "calendar.session_starts",
+        ),
+        ("session-end", &session.end_date, "calendar.session_ends"),
+    ]
+    .into_iter()
+    .filter_map(|(part, date, key)| {
+        Some(Entry::new(
+            id,
+            &format!("-{part}"),
+            to_naive_date(date)?,
+            i18n.format(key, &name),
+        ))
+    })
+    .collect()
+}
+
+fn term_entries(term: &Term, i18n: &Localizer) -> Vec<Entry> {
+    let Some(id) = &term.id else {
+        return Vec::new();
+    };
+    let term_name = term.term_name.clone().unwrap_or_else(|| {
+        i18n.format(
+            "calendar.term",
+            &[("number", &term.term_number.to_string())],
+        )
+    });
+    let name = [("term", term_name.as_str())];
+    [
+        ("term-start", &term.start_date, "calendar.term_starts"),
+        ("term-end", &term.end_date, "calendar.term_ends"),
+    ]
+    .into_iter()
+    .filter_map(|(part, date, key)| {
+        Some(Entry::new(
+            id,
+            &format!("-{part}"),
+            to_naive_date(date)?,
+            i18n.format(key, &name),
+        ))
+    })
+    .collect()
+}
+
+fn event_entry(event: &Event) -> Option<Entry> {
+    let mut entry = Entry::new(
+        event.id.as_ref()?,
+        "-event",
+        to_naive_date(&event.event_date)?,
+        event.event_name.clone(),
+    );
+    entry.time = event.event_time.as_deref().and_then(parse_time);
+    entry.description = event.event_description.clone();
+    entry.location = event.event_location.clone();
+    Some(entry)
+}
+
+fn assessment_entry(
+    assessment: &Assessment,
+    subjects: &HashMap<String, String>,
+    classes: &HashMap<String, String>,
+    i18n: &Localizer,
+) -> Option<Entry> {
+    let date = to_naive_date(assessment.assessment_date.as_ref()?)?;
+    let name = assessment.assessment_name.clone().unwrap_or_else(|| {
+        serde_json::to_value(&assessment.assessment_type)
+            .ok()
+            .and_then(|v| v.as_str().map(|t| i18n.t(&format!("assessment_type.{t}"))))
+            .unwrap_or_default()
+    });
+    let lookup = |names: &HashMap<String, String>, id: &RecordId| {
+        names.get(&record_key(id)).cloned().unwrap_or_default()
+    };
+    let summary = i18n.format(
+        "calendar.assessment",
+        &[
+            ("assessment", &name),
+            ("subject", &lookup(subjects, &assessment.subject_id)),
+            ("class", &lookup(classes, &assessment.class_id)),
+        ],
+    );
+    Some(Entry::new(
+        assessment.id.as_ref()?,
+        "-assessment",
+        date,
+        summary,
+    ))
+}
+
+/// Event times as schools type them: "14:00", "14:00:00" or "2:00 PM"
+fn parse_time(time: &str) -> Option<NaiveTime> {
+    let time = time.trim();
+    ["%H:%M", "%H:%M:%S", "%I:%M %p", "%I:%M%p"]
+        .iter()
+        .find_map(|format| NaiveTime::parse_from_str(time, format).ok())
+}
+
+/// An iCalendar (RFC 5545) document. Timed events are left in floating local
+/// time, so they show at the school's hour wherever the phone is set.
+fn render(name: &str, entries: &[Entry], now: DateTime<Utc>) -> String {
+    let stamp = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".into(),
+        format!("PRODID:{PRODUCT_ID}"),
+        "CALSCALE:GREGORIAN".into(),
+        "METHOD:PUBLISH".into(),
+        format!("X-WR-CALNAME:{}", escape(name)),
+        "REFRESH-INTERVAL;VALUE=DURATION:PT6H".into(),
+        "X-PUBLISHED-TTL:PT6H".into(),
+    ];
+    for entry in entries {
+        lines.push("BEGIN:VEVENT".into());
+        lines.push(format!("UID:{}", entry.uid));
+        lines.push(format!("DTSTAMP:{stamp}"));
+        match entry.time {
+            Some(time) => lines.push(format!(
+                "DTSTART:{}",
+                entry.date.and_time(time).format("%Y%m%dT%H%M%S")
+            )),
+            None => {
+                lines.push(format!(
+                    "DTSTART;VALUE=DATE:{}",
+                    entry.date.format("%Y%m%d")
+                ));
+                if let Some(next) = entry.date.succ_opt() {
+                    lines.push(format!("DTEND;VALUE=DATE:{}", next.format("%Y%m%d")));
+                }
+            }
+        }
+        lines.push(format!("SUMMARY:{}", escape(&entry.summary)));
+        if let Some(description) = &entry.description {
+            lines.push(format!("DESCRIPTION:{}", escape(description)));
+        }
+        if let Some(location) = &entry.location {
+            lines.push(format!("LOCATION:{}", escape(location)));
+        }
+        lines.push("END:VEVENT".into());
+    }
+    lines.push("END:VCALENDAR".into());
+    lines.iter().map(|line| fold(line) + "\r\n").collect()
+}
+
+/// Escape a text value's commas, semicolons, backslashes and line breaks
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Split a line into 75-byte pieces, each continuation starting with a
+/// space, without breaking a character in two
+fn fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len() + line.len() / LINE_LIMIT * 3);
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > LINE_LIMIT {
+            folded.push_str("\r\n ");
+            width = 1;
+        }
+        folded.push(c);
+        width += c.len_utf8();
+    }
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use surrealdb_types::Value;
+
+    fn event(target_audience: TargetAudience, class: Option<&str>) -> Event {
+        Event {
+            id: Some(RecordId::new("events", "e1")),
+            school_id: RecordId::new("schools", "s1"),
+            event_name: "Sports day".into(),
+            event_description: None,
+            event_date: Value::String("2026-10-20".into()),
+            event_time: None,
+            event_location: None,
+            target_audience,
+            class_id: class.map(|c| RecordId::new("classes", c)),
+            requires_rsvp: false,
+            rsvp_deadline: None,
+            capacity: None,
+            rsvp_reminder_sent_at: None,
+            created_by: None,
+            created_at: Datetime::now(),
+            updated_at: Datetime::now(),
+        }
+    }
+
+    #[test]
+    fn keeps_member_feeds_to_their_audience_and_classes() {
+        let parent = Scope::Member {
+            audience: TargetAudience::Parents,
+            classes: vec![RecordId::new("classes", "jss1")],
+        };
+        assert!(parent.shows_event(&event(TargetAudience::All, None)));
+        assert!(parent.shows_event(&event(TargetAudience::Parents, None)));
+        assert!(parent.shows_event(&event(TargetAudience::SpecificClass, Some("jss1"))));
+        assert!(!parent.shows_event(&event(TargetAudience::SpecificClass, Some("jss2"))));
+        assert!(!parent.shows_event(&event(TargetAudience::Teachers, None)));
+        assert!(Scope::School.shows_event(&event(TargetAudience::Teachers, None)));
+    }
+
+    #[test]
+    fn renders_all_day_and_timed_events() {
+        let mut sports = event_entry(&event(TargetAudience::All, None)).unwrap();
+        sports.description = Some("Bring kits, water; and hats\nParents welcome".into());
+        let mut concert = event_entry(&event(TargetAudience::All, None)).unwrap();
+        concert.uid = "e2-event".into();
+        concert.time = parse_time("2:30 PM");
+
+        let now = DateTime::parse_from_rfc3339("2026-10-01T08:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let ics = render("Greenfield College", &[sports, concert], now);
+
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+        assert!(ics.ends_with("END:VCALENDAR\r\n"));
+        assert!(ics.contains("X-WR-CALNAME:Greenfield College\r\n"));
+        assert!(ics.contains("UID:e1-event\r\nDTSTAMP:20261001T080000Z\r\n"));
+        assert!(ics.contains("DTSTART;VALUE=DATE:20261020\r\nDTEND;VALUE=DATE:20261021\r\n"));
+        assert!(ics.contains("DTSTART:20261020T143000\r\n"));
+        assert!(ics.contains("DESCRIPTION:Bring kits\\, water\\; and hats\\nParents welcome\r\n"));
+    }
+
+    #[test]
+    fn folds_long_lines_between_characters() {
+        let line = format!("SUMMARY:{}", "é".repeat(60));
+        let folded = fold(&line);
+        let pieces: Vec<&str> = folded.split("\r\n").collect();
+        assert!(pieces.len() > 1);
+        assert!(pieces.iter().all(|piece| piece.len() <= LINE_LIMIT));
+        assert!(pieces[1..].iter().all(|piece| piece.starts_with(' ')));
+        assert_eq!(folded.replace("\r\n ", ""), line);
+    }
+}
@@ -0,0 +1,924 @@
+use std::collections::HashMap;
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use db::queries::{ActivityLogQ, ConversationQ, MessageAttachmentQ, MessageQ, StudentQ, UserQ};
+use shared::{
+    helpers::{
+        api_responses::{Contact, ConversationSummary, ConversationThread, SessionUser},
+        create_update_dtos::{SendMessageDto, StartConversationDto},
+        surreal_util::record_key,
+    },
+    models::{
+        ActionType, User, UserStatus, UserType,
+        communication::{Conversation, Message, MessageAttachment},
+        system_and_audit::ActivityLog,
+    },
+};
+use surrealdb::{Surreal, engine::remote::ws::Client};
+use surrealdb_types::{Datetime, RecordId};
+
+use crate::{
+    error::{DomainResult, Error},
+    live::{ConversationSignal, LiveEvent, send_signal},
+    parent_portal::ParentPortalService,
+    storage::FileStore,
+    subscription::SubscriptionService,
+};
+
+pub const MAX_MESSAGE_LEN: usize = 2000;
+/// Largest file that can be attached to a message
+pub const MAX_ATTACHMENT_BYTES: usize = 10 * 1024 * 1024;
+const MAX_ATTACHMENTS_PER_MESSAGE: usize = 5;
+/// What parents and staff share: letters, forms, photos of homework
+const ATTACHMENT_TYPES: &[&str] = &[
+    "application/pdf",
+    "image/jpeg",
+    "image/png",
+    "image/webp",
+    "text/plain",
+    "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+];
+
+/// A file as it arrived from the client
+pub struct UploadedFile {
+    pub file_name: String,
+    pub content_type: String,
+    pub bytes: Vec<u8>,
+}
+
+/// Conversations between parents and staff, with attachments, typing and
+/// read receipts, and admin oversight for safeguarding
+pub struct ConversationService;
+
+impl ConversationService {
+    /// Message someone in the school, continuing the open conversation about
+    /// the same child if there is one
+    pub async fn start(
+        &self,
+        sdb: &Surreal<Client>,
+        user: &SessionUser,
+        data: StartConversationDto,
+    ) -> DomainResult<ConversationThread> {
+        let body = message_body(&data.body)?;
+        let sender = self.member(sdb, &user.user_id, &user.school_id).await?;
+        let recipient = self
+            .member(sdb, &data.recipient_id, &user.school_id)
+            .await
+            .map_err(|_| Error::NotFound("recipient".into()))?;
+        if !may_converse(&sender.user_type, &recipient.user_type) {
+            return Err(Error::Forbidden(
+                "conversations are between parents and staff".into(),
+            ));
+        }
+        let student_id = match data.student_id {
+            Some(student_id) => Some(
+                self.about(sdb, user, &sender, &recipient, student_id)
+                    .await?,
+            ),
+            None => None,
+        };
+        let sender_id = sender.id.expect("loaded users have ids");
+        let recipient_id = recipient.id.expect("loaded users have ids");
+
+        let conversation = self
+            .open(
+                sdb,
+                RecordId::new("schools", user.school_id.as_str()),
+                [sender_id.clone(), recipient_id],
+                student_id,
+                data.subject,
+            )
+            .await?;
+        self.post(sdb, &conversation, sender_id, body, data.attachment_ids)
+            .await?;
+        self.thread(
+            sdb,
+            user,
+            conversation.id.as_ref().map(record_key).unwrap_or_default(),
+        )
+        .await
+    }
+
+    /// The user's conversations in their school, most recently active first
+    pub async fn list(
+        &self,
+        sdb: &Surreal<Client>,
+        user: &SessionUser,
+    ) -> DomainResult<Vec<ConversationSummary>> {
+        let school_id = RecordId::new("schools", user.school_id.as_str());
+        let conversations: Vec<Conversation> = ConversationQ
+            .get_for_user(sdb, user.user_id.clone())
+            .await?
+            .into_iter()
+            .filter(|c| c.school_id == school_id)
+            .collect();
+        let unread: HashMap<String, u64> = MessageQ
+            .count_unread_by_conversation(sdb, user.user_id.clone())
+            .await?
+            .into_iter()
+            .map(|(id, count)| (record_key(&id), count))
+            .collect();
+        self.summaries(sdb, conversations, &unread, true).await
+    }
+
+    /// A conversation's messages, marking what the user was sent as read
+    pub async fn thread(
+        &self,
+        sdb: &Surreal<Client>,
+        user: &SessionUser,
+        conversation_id: String,
+    ) -> DomainResult<ConversationThread> {
+        let conversation = self.joined(sdb, user, &conversation_id).await?;
+        self.read(sdb, user, &conversation).await?;
+        self.full_thread(sdb, conversation, true).await
+    }
+
+    /// Reply in a conversation
+    pub async fn send(
+        &self,
+        sdb: &Surreal<Client>,
+        user: &SessionUser,
+        conversation_id: String,
+        data: SendMessageDto,
+    ) -> DomainResult<Message> {
+        let body = message_body(&data.body)?;
+        let conversation = self.joined(sdb, user, &conversation_id).await?;
+        self.post(
+            sdb,
+            &conversation,
+            RecordId::new("users", user.user_id.as_str()),
+            body,
+            data.attachment_ids,
+        )
+        .await
+    }
+
+    /// Keep a file for a message the user is about to send
+    pub async fn upload(
+        &self,
+        sdb: &Surreal<Client>,
+        store: &impl FileStore,
+        user: &SessionUser,
+        conversation_id: String,
+        file: UploadedFile,
+    ) -> DomainResult<MessageAttachment> {
+        let UploadedFile {
+            file_name,
+            content_type,
+            bytes,
+        } = file;
+        let conversation = self.joined(sdb, user, &conversation_id).await?;
+        if conversation.closed {
+            return Err(Error::Locked("conversation is closed".into()));
+        }
+        SubscriptionService
+            .ensure_writable(sdb, &record_key(&conversation.school_id))
+            .await?;
+        check_attachment(&file_name, &content_type, bytes.len())?;
+
+        let storage_key = storage_key(&user.school_id, &conversation_id, &file_name);
+        store.put(&storage_key, &bytes).await?;
+        MessageAttachmentQ
+            .create(
+                sdb,
+                MessageAttachment {
+                    id: None,
+                    school_id: conversation.school_id,
+                    conversation_id: RecordId::new("conversations", conversation_id.as_str()),
+                    message_id: None,
+                    uploaded_by: RecordId::new("users", user.user_id.as_str()),
+                    file_name,
+                    content_type,
+                    size_bytes: bytes.len() as i64,
+                    storage_key,
+                    created_at: Datetime::now(),
+                },
+            )
+            .await?
+            .ok_or_else(|| Error::NotFound("attachment".into()))
+    }
+
+    /// An attachment and its bytes, for the conversation's participants or
+    /// the school's admins
+    pub async fn download(
+        &self,
+        sdb: &Surreal<Client>,
+        store: &impl FileStore,
+        user: &SessionUser,
+        attachment_id: String,
+    ) -> DomainResult<(MessageAttachment, Vec<u8>)> {
+        let attachment = MessageAttachmentQ
+            .get_by_id(sdb, attachment_id.clone())
+            .await?
+            .ok_or_else(|| Error::NotFound("attachment".into()))?;
+        let user_id = RecordId::new("users", user.user_id.as_str());
+        if is_admin(&user.user_type) {
+            self.moderating(sdb, user, &record_key(&attachment.conversation_id))
+                .await?;
+            audit(
+                sdb,
+                user,
+                ActionType::View,
+                "message_attachment",
+                attachment_id,
+                format!("Opened attachment {}", attachment.file_name),
+                None,
+            )
+            .await?;
+        } else {
+            self.joined(sdb, user, &record_key(&attachment.conversation_id))
+                .await?;
+            let visible = match &attachment.message_id {
+                Some(message_id) => MessageQ
+                    .get_by_id(sdb, record_key(message_id))
+                    .await?
+                    .is_some_and(|m| m.hidden_at.is_none()),
+                None => attachment.uploaded_by == user_id,
+            };
+            if !visible {
+                return Err(Error::NotFound("attachment".into()));
+            }
+        }
+        let bytes = store.get(&attachment.storage_key).await?;
+        Ok((attachment, bytes))
+    }
+
+    /// Tell the other side the user is typing
+    pub async fn typing(
+        &self,
+        sdb: &Surreal<Client>,
+        user: &SessionUser,
+        conversation_id: String,
+    ) -> DomainResult<()> {
+        let conversation = self.joined(sdb, user, &conversation_id).await?;
+        if conversation.closed {
+            return Err(Error::Locked("conversation is closed".into()));
+        }
+        let signal = ConversationSignal {
+            conversation_id,
+            user_id: user.user_id.clone(),
+            at: Datetime::now(),
+        };
+        for other in others(&conversation, &user.user_id) {
+            send_signal(other, LiveEvent::Typing(signal.clone()));
+        }
+        Ok(())
+    }
+
+    /// Mark what the user was sent in a conversation as read
+    pub async fn mark_read(
+        &self,
+        sdb: &Surreal<Client>,
+        user: &SessionUser,
+        conversation_id: String,
+    ) -> DomainResult<usize> {
+        let conversation = self.joined(sdb, user, &conversation_id).await?;
+        self.read(sdb, user, &conversation).await
+    }
+
+    /// Every conversation in the admin's school
+    pub async fn school_conversations(
+        &self,
+        sdb: &Surreal<Client>,
+        admin: &SessionUser,
+    ) -> DomainResult<Vec<ConversationSummary>> {
+        ensure_admin(admin)?;
+        let conversations = ConversationQ
+            .get_by_school(sdb, admin.school_id.clone())
+            .await?;
+        self.summaries(sdb, conversations, &HashMap::new(), false)
+            .await
+    }
+
+    /// A conversation as it was sent, withdrawn messages included, without
+    /// marking anything read. Each review is logged.
+    pub async fn review(
+        &self,
+        sdb: &Surreal<Client>,
+        admin: &SessionUser,
+        conversation_id: String,
+    ) -> DomainResult<ConversationThread> {
+        let conversation = self.moderating(sdb, admin, &conversation_id).await?;
+        audit(
+            sdb,
+            admin,
+            ActionType::View,
+            "conversation",
+            conversation_id,
+            "Reviewed a conversation".into(),
+            None,
+        )
+        .await?;
+        self.full_thread(sdb, conversation, false).await
+    }
+
+    /// Withdraw a message; participants see that it was removed
+    pub async fn hide_message(
+        &self,
+        sdb: &Surreal<Client>,
+        admin: &SessionUser,
+        message_id: String,
+        reason: Option<String>,
+    ) -> DomainResult<Message> {
+        let message = MessageQ
+            .get_by_id(sdb, message_id.clone())
+            .await?
+            .ok_or_else(|| Error::NotFound("message".into()))?;
+        let conversation_id = message
+            .conversation_id
+            .as_ref()
+            .map(record_key)
+            .ok_or_else(|| Error::NotFound("conversation".into()))?;
+        self.moderating(sdb, admin, &conversation_id).await?;
+        let message = MessageQ
+            .hide(sdb, message_id.clone(), admin.user_id.clone())
+            .await?
+            .ok_or_else(|| Error::NotFound("message".into()))?;
+        audit(
+            sdb,
+            admin,
+            ActionType::Update,
+            "message",
+            message_id,
+            "Withdrew a message".into(),
+            Some(serde_json::json!({
+                "conversation_id": conversation_id,
+                "reason": reason,
+            })),
+        )
+        .await?;
+        Ok(message)
+    }
+
+    /// Stop, or allow again, new messages in a conversation
+    pub async fn set_closed(
+        &self,
+        sdb: &Surreal<Client>,
+        admin: &SessionUser,
+        conversation_id: String,
+        closed: bool,
+    ) -> DomainResult<Conversation> {
+        self.moderating(sdb, admin, &conversation_id).await?;
+        let conversation = ConversationQ
+            .set_closed(sdb, conversation_id.clone(), closed)
+            .await?
+            .ok_or_else(|| Error::NotFound("conversation".into()))?;
+        let description = if closed {
+            "Closed a conversation"
+        } else {
+            "Reopened a conversation"
+        };
+        audit(
+            sdb,
+            admin,
+            ActionType::Update,
+            "conversation",
+            conversation_id,
+            description.into(),
+            None,
+        )
+        .await?;
+        Ok(conversation)
+    }
+
+    /// The open conversation between two users about a child, started if
+    /// there isn't one
+    pub(crate) async fn open(
+        &self,
+        sdb: &Surreal<Client>,
+        school_id: RecordId,
+        participants: [RecordId; 2],
+        student_id: Option<RecordId>,
+        subject: Option<String>,
+    ) -> DomainResult<Conversation> {
+        if let Some(conversation) = ConversationQ
+            .find_open_between(
+                sdb,
+                school_id.clone(),
+                participants.to_vec(),
+                student_id.clone(),
+            )
+            .await?
+        {
+            return Ok(conversation);
+        }
+        ConversationQ
+            .create(
+                sdb,
+                Conversation {
+                    id: None,
+                    school_id,
+                    participants: participants.to_vec(),
+                    student_id,
+                    subject: subject
+                        .map(|s| s.trim().to_string())
+                        .filter(|s| !s.is_empty()),
+                    closed: false,
+                    last_message_at: Datetime::now(),
+                    created_at: Datetime::now(),
+                    updated_at: Datetime::now(),
+                },
+            )
+            .await?
+            .ok_or_else(|| Error::NotFound("conversation".into()))
+    }
+
+    /// Add a message to a conversation from one of its participants, as a
+    /// reply to the latest one
+    pub(crate) async fn post(
+        &self,
+        sdb: &Surreal<Client>,
+        conversation: &Conversation,
+        sender_id: RecordId,
+        body: String,
+        attachment_ids: Vec<String>,
+    ) -> DomainResult<Message> {
+        if conversation.closed {
+            return Err(Error::Locked("conversation is closed".into()));
+        }
+        let conversation_id = conversation
+            .id
+            .clone()
+            .expect("loaded conversations have ids");
+        let recipient_id = conversation
+            .participants
+            .iter()
+            .find(|p| **p != sender_id)
+            .cloned()
+            .ok_or_else(|| Error::Validation("conversation has no one to reply to".into()))?;
+        let attachment_ids = self
+            .pending_uploads(sdb, &conversation_id, &sender_id, attachment_ids)
+            .await?;
+
+        let previous = MessageQ
+            .get_latest_in(sdb, record_key(&conversation_id))
+            .await?;
+        if let Some(previous) = previous.as_ref().filter(|m| m.recipient_id == sender_id) {
+            MessageQ
+                .mark_replied(
+                    sdb,
+                    previous.id.as_ref().map(record_key).unwrap_or_default(),
+                )
+                .await?;
+        }
+        let sent_at = Datetime::now();
+        let message = MessageQ
+            .send(
+                sdb,
+                Message {
+                    id: None,
+                    school_id: conversation.school_id.clone(),
+                    sender_id,
+                    recipient_id,
+                    subject: conversation.subject.clone(),
+                    message_body: body,
+                    read: false,
+                    read_at: None,
+                    replied: false,
+                    parent_message_id: previous.and_then(|m| m.id),
+                    conversation_id: Some(conversation_id.clone()),
+                    hidden_by: None,
+                    hidden_at: None,
+                    sent_at,
+                    created_at: Datetime::now(),
+                },
+            )
+            .await?
+            .ok_or_else(|| Error::NotFound("message".into()))?;
+        if !attachment_ids.is_empty() {
+            MessageAttachmentQ
+                .attach(sdb, attachment_ids, &message)
+                .await?;
+        }
+        ConversationQ
+            .touch(sdb, record_key(&conversation_id), sent_at)
+            .await?;
+        Ok(message)
+    }
+
+    /// The sender's unsent uploads in this conversation, by id
+    async fn pending_uploads(
+        &self,
+        sdb: &Surreal<Client>,
+        conversation_id: &RecordId,
+        sender_id: &RecordId,
+        attachment_ids: Vec<String>,
+    ) -> DomainResult<Vec<RecordId>> {
+        if attachment_ids.len() > MAX_ATTACHMENTS_PER_MESSAGE {
+            return Err(Error::Validation(format!(
+                "at most {MAX_ATTACHMENTS_PER_MESSAGE} attachments per message"
+            )));
+        }
+        let mut ids = Vec::new();
+        for attachment_id in attachment_ids {
+            let attachment = MessageAttachmentQ
+                .get_by_id(sdb, attachment_id.clone())
+                .await?
+                .filter(|a| {
+                    &a.conversation_id == conversation_id
+                        && &a.uploaded_by == sender_id
+                        && a.message_id.is_none()
+                })
+                .ok_or_else(|| Error::NotFound(format!("attachment {attachment_id}")))?;
+            ids.extend(attachment.id);
+        }
+        Ok(ids)
+    }
+
+    async fn read(
+        &self,
+        sdb: &Surreal<Client>,
+        user: &SessionUser,
+        conversation: &Conversation,
+    ) -> DomainResult<usize> {
+        let conversation_id = conversation.id.as_ref().map(record_key).unwrap_or_default();
+        let read = MessageQ
+            .mark_conversation_read(sdb, conversation_id.clone(), user.user_id.clone())
+            .await?;
+        if !read.is_empty() {
+            let signal = ConversationSignal {
+                conversation_id,
+                user_id: user.user_id.clone(),
+                at: Datetime::now(),
+            };
+            for other in others(conversation, &user.user_id) {
+                send_signal(other, LiveEvent::Read(signal.clone()));
+            }
+        }
+        Ok(read.len())
+    }
+
+    async fn full_thread(
+        &self,
+        sdb: &Surreal<Client>,
+        conversation: Conversation,
+        redacted: bool,
+    ) -> DomainResult<ConversationThread> {
+        let conversation_id = conversation.id.as_ref().map(record_key).unwrap_or_default();
+        let mut messages = MessageQ
+            .get_by_conversation(sdb, conversation_id.clone())
+            .await?;
+        let mut attachments = MessageAttachmentQ
+            .get_by_conversation(sdb, conversation_id)
+            .await?;
+        if redacted {
+            redact(&mut messages, &mut attachments);
+        }
+        let participants = self
+            .contacts(sdb, &conversation.participants, &mut HashMap::new())
+            .await?;
+        Ok(ConversationThread {
+            conversation,
+            participants,
+            messages,
+            attachments,
+        })
+    }
+
+    async fn summaries(
+        &self,
+        sdb: &Surreal<Client>,
+        conversations: Vec<Conversation>,
+        unread: &HashMap<String, u64>,
+        redacted: bool,
+    ) -> DomainResult<Vec<ConversationSummary>> {
+        let mut names = HashMap::new();
+        let mut summaries = Vec::with_capacity(conversations.len());
+        for conversation in conversations {
+            let Some(id) = conversation.id.as_ref().map(record_key) else {
+                continue;
+            };
+            let mut last_message = MessageQ.get_latest_in(sdb, id.clone()).await?;
+            if redacted {
+                last_message.iter_mut().for_each(withdraw);
+            }
+            let participants = self
+                .contacts(sdb, &conversation.participants, &mut names)
+                .await?;
+            summaries.push(ConversationSummary {
+                conversation,
+                participants,
+                last_message,
+                unread_count: unread.get(&id).copied().unwrap_or(0),
+            });
+        }
+        Ok(summaries)
+    }
+
+    /// Names for users, looked up once each
+    async fn contacts(
+        &self,
+        sdb: &Surreal<Client>,
+        user_ids: &[RecordId],
+        names: &mut HashMap<String, String>,
+    ) -> DomainResult<Vec<Contact>> {
+        let mut contacts = Vec::with_capacity(user_ids.len());
+        for user_id in user_ids.iter().map(record_key) {
+            if !names.contains_key(&user_id) {
+                let name = UserQ
+                    .get_by_id(sdb, user_id.clone())
+                    .await?
+                    .map(|u| format!("{} {}", u.first_name, u.last_name))
+                    .unwrap_or_default();
+                names.insert(user_id.clone(), name);
+            }
+            contacts.push(Contact {
+                name: names[&user_id].clone(),
+                user_id,
+            });
+        }
+        Ok(contacts)
+    }
+
+    /// An active user in the school
+    async fn member(
+        &self,
+        sdb: &Surreal<Client>,
+        user_id: &str,
+        school_id: &str,
+    ) -> DomainResult<User> {
+        UserQ
+            .get_by_id(sdb, user_id.to_string())
+            .await?
+            .filter(|u| {
+                u.id.is_some()
+                    && record_key(&u.school_id) == school_id
+                    && u.status == UserStatus::Active
+            })
+            .ok_or_else(|| Error::NotFound("user".into()))
+    }
+
+    /// The child a new conversation is about: in the school, and linked to
+    /// whichever side is the parent
+    async fn about(
+        &self,
+        sdb: &Surreal<Client>,
+        user: &SessionUser,
+        sender: &User,
+        recipient: &User,
+        student_id: String,
+    ) -> DomainResult<RecordId> {
+        let student = StudentQ
+            .get_by_id(sdb, student_id)
+            .await?
+            .filter(|s| record_key(&s.school_id) == user.school_id)
+            .ok_or_else(|| Error::NotFound("student".into()))?;
+        let parent = [sender, recipient]
+            .into_iter()
+            .find(|u| u.user_type == UserType::Parent);
+        if let Some(parent) = parent {
+            let children = ParentPortalService
+                .children(sdb, parent.id.as_ref().map(record_key).unwrap_or_default())
+                .await?;
+            if !children.iter().any(|c| c.id == student.id) {
+                return Err(Error::Forbidden("not this parent's child".into()));
+            }
+        }
+        student.id.ok_or_else(|| Error::NotFound("student".into()))
+    }
+
+    /// A conversation the user takes part in
+    async fn joined(
+        &self,
+        sdb: &Surreal<Client>,
+        user: &SessionUser,
+        conversation_id: &str,
+    ) -> DomainResult<Conversation> {
+        let user_id = RecordId::new("users", user.user_id.as_str());
+        ConversationQ
+            .get_by_id(sdb, conversation_id.to_string())
+            .await?
+            .filter(|c| c.participants.contains(&user_id))
+            .ok_or_else(|| Error::NotFound("conversation".into()))
+    }
+
+    /// A conversation in the admin's school
+    async fn moderating(
+        &self,
+        sdb: &Surreal<Client>,
+        admin: &SessionUser,
+        conversation_id: &str,
+    ) -> DomainResult<Conversation> {
+        ensure_admin(admin)?;
+        ConversationQ
+            .get_by_id(sdb, conversation_id.to_string())
+            .await?
+            .filter(|c| record_key(&c.school_id) == admin.school_id)
+            .ok_or_else(|| Error::NotFound("conversation".into()))
+    }
+}
+
+/// Record an admin's moderation in the activity log
+async fn audit(
+    sdb: &Surreal<Client>,
+    admin: &SessionUser,
+    action: ActionType,
+    entity_type: &str,
+    entity_id: String,
+    description: String,
+    metadata: Option<serde_json::Value>,
+) -> DomainResult<()> {
+    ActivityLogQ
+        .log(
+            sdb,
+            ActivityLog {
+                id: None,
+                school_id: RecordId::new("schools", admin.school_id.as_str()),
+                user_id: Some(RecordId::new("users", admin.user_id.as_str())),
+                action,
+                entity_type: Some(entity_type.into()),
+                entity_id: Some(entity_id),
+                description: Some(description),
+                ip_address: None,
+                metadata,
+                created_at: Datetime::now(),
+            },
+        )
+        .await?;
+    Ok(())
+}
+
+/// A message body, trimmed, that isn't empty or too long
+pub(crate) fn message_body(body: &str) -> DomainResult<String> {
+    let body = body.trim().to_string();
+    if body.is_empty() {
+        return Err(Error::Validation("message is empty".into()));
+    }
+    if body.chars().count() > MAX_MESSAGE_LEN {
+        return Err(Error::Validation(format!(
+            "messages are limited to {MAX_MESSAGE_LEN} characters"
+        )));
+    }
+    Ok(body)
+}
+
+/// Parents talk to staff and staff to each other; students and
+/// parent-to-parent conversations aren't supported
+fn may_converse(a: &UserType, b: &UserType) -> bool {
+    let staff = |t: &UserType| !matches!(t, UserType::Parent | UserType::Student);
+    match (a, b) {
+        (UserType::Student, _) | (_, UserType::Student) => false,
+        (a, b) => staff(a) || staff(b),
+    }
+}
+
+fn is_admin(user_type: &UserType) -> bool {
+    matches!(user_type, UserType::Admin | UserType::SuperAdmin)
+}
+
+fn ensure_admin(user: &SessionUser) -> DomainResult<()> {
+    if is_admin(&user.user_type) {
+        Ok(())
+    } else {
+        Err(Error::Forbidden(
+            "only admins can moderate conversations".into(),
+        ))
+    }
+}
+
+/// The participants other than this user
+fn others(conversation: &Conversation, user_id: &str) -> Vec<String> {
+    conversation
+        .participants
+        .iter()
+        .map(record_key)
+        .filter(|p| p != user_id)
+        .collect()
+}
+
+fn check_attachment(file_name: &str, content_type: &str, size: usize) -> DomainResult<()> {
+    if file_name.trim().is_empty() {
+        return Err(Error::Validation("attachment needs a file name".into()));
+    }
+    if size == 0 {
+        return Err(Error::Validation("attachment is empty".into()));
+    }
+    if size > MAX_ATTACHMENT_BYTES {
+        return Err(Error::Validation(format!(
+            "attachments are limited to {} MB",
+            MAX_ATTACHMENT_BYTES / (1024 * 1024)
+        )));
+    }
+    if !ATTACHMENT_TYPES.contains(&content_type) {
+        return Err(Error::Validation(format!(
+            "{content_type} files can't be attached"
+        )));
+    }
+    Ok(())
+}
+
+/// Where an upload is kept: under its school and conversation, with a random
+/// prefix so names never collide
+fn storage_key(school_id: &str, conversation_id: &str, file_name: &str) -> String {
+    let mut bytes = [0u8; 8];
+    OsRng.fill_bytes(&mut bytes);
+    let prefix: String = bytes.iter().map(|b| format!("{b:02x}")).collect();
+    let name: String = file_name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let name = name.trim_start_matches('.');
+    format!("schools/{school_id}/messages/{conversation_id}/{prefix}-{name}")
+}
+
+/// Drop the body of a withdrawn message
+fn withdraw(message: &mut Message) {
+    if message.hidden_at.is_some() {
+        message.message_body.clear();
+    }
+}
+
+/// What participants see: withdrawn messages without their text or files
+fn redact(messages: &mut [Message], attachments: &mut Vec<MessageAttachment>) {
+    messages.iter_mut().for_each(withdraw);
+    let hidden: Vec<&RecordId> = messages
+        .iter()
+        .filter(|m| m.hidden_at.is_some())
+        .filter_map(|m| m.id.as_ref())
+        .collect();
+    attachments.retain(|a| a.message_id.as_ref().is_none_or(|id| !hidden.contains(&id)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(key: &str, hidden: bool) -> Message {
+        Message {
+            id: Some(RecordId::new("messages", key)),
+            school_id: RecordId::new("schools", "s1"),
+            sender_id: RecordId::new("users", "p1"),
+            recipient_id: RecordId::new("users", "t1"),
+            subject: None,
+            message_body: "Chidi will be late tomorrow.".into(),
+            read: false,
+            read_at: None,
+            replied: false,
+            parent_message_id: None,
+            conversation_id: Some(RecordId::new("conversations", "c1")),
+            hidden_by: hidden.then(|| RecordId::new("users", "a1")),
+            hidden_at: hidden.then(Datetime::now),
+            sent_at: Datetime::now(),
+            created_at: Datetime::now(),
+        }
+    }
+
+    fn attachment(message_key: &str) -> MessageAttachment {
+        MessageAttachment {
+            id: None,
+            school_id: RecordId::new("schools", "s1"),
+            conversation_id: RecordId::new("conversations", "c1"),
+            message_id: Some(RecordId::new("messages", message_key)),
+            uploaded_by: RecordId::new("users", "p1"),
+            file_name: "note.pdf".into(),
+            content_type: "application/pdf".into(),
+            size_bytes: 8,
+            storage_key: "schools/s1/messages/c1/note.pdf".into(),
+            created_at: Datetime::now(),
+        }
+    }
+
+    #[test]
+    fn parents_talk_to_staff_only() {
+        assert!(may_converse(&UserType::Parent, &UserType::Teacher));
+        assert!(may_converse(&UserType::Accountant, &UserType::Parent));
+        assert!(may_converse(&UserType::Teacher, &UserType::Admin));
+        assert!(!may_converse(&UserType::Parent, &UserType::Parent));
+        assert!(!may_converse(&UserType::Student, &UserType::Teacher));
+    }
+
+    #[test]
+    fn checks_attachments_and_keeps_keys_inside_the_conversation() {
+        assert!(check_attachment("homework.jpg", "image/jpeg", 2048).is_ok());
+        assert!(check_attachment("setup.exe", "application/x-msdownload", 2048).is_err());
+        assert!(check_attachment("scan.pdf", "application/pdf", MAX_ATTACHMENT_BYTES + 1).is_err());
+        assert!(check_attachment("empty.txt", "text/plain", 0).is_err());
+
+        let key = storage_key("s1", "c1", "../../Fee receipt (1).pdf");
+        let (dir, name) = key.rsplit_once('/').unwrap();
+        assert_eq!(dir, "schools/s1/messages/c1");
+        assert!(name.ends_with("-_.._Fee_receipt__1_.pdf"));
+        assert!(!name.contains('/'));
+    }
+
+    #[test]
+    fn withdrawn_messages_lose_their_text_and_files() {
+        let mut messages = vec![message("m1", false), message("m2", true)];
+        let mut attachments = vec![attachment("m1"), attachment("m2")];
+        redact(&mut messages, &mut attachments);
+
+        assert_eq!(messages[0].message_body, "Chidi will be late tomorrow.");
+        assert!(messages[1].message_body.is_empty());
+        assert_eq!(attachments.len(), 1);
+        assert_eq!(
+            attachments[0].message_id,
+            Some(RecordId::new("messages", "m1"))
+        );
+    }
+}
@@ -0,0 +1,238 @@
+use std::{collections::HashMap, future::Future};
+
+use db::queries::{MessageDeliveryQ, SmsLogQ};
+use shared::{
+    helpers::{
+        api_responses::{AnnouncementDeliveryReport, ChannelDeliverySummary, DeliveryFailure},
+        surreal_util::record_key,
+    },
+    models::{
+        DeliveryStatus, MessageChannel, SmsType,
+        system_and_audit::{MessageDelivery, SmsLog},
+    },
+};
+use surrealdb::{Surreal, engine::remote::ws::Client};
+use surrealdb_types::{Datetime, Decimal};
+
+use crate::{
+    error::{DomainResult, Error},
+    subscription::SubscriptionService,
+};
+
+/// Attempts before a delivery the provider keeps refusing is marked failed
+pub const MAX_ATTEMPTS: i32 = 3;
+
+/// What a provider reports for a message it accepted
+#[derive(Debug, Clone, PartialEq)]
+pub struct SentMessage {
+    pub provider: String,
+    pub provider_message_id: Option<String>,
+    pub cost: Option<Decimal>,
+}
+
+/// Why a provider didn't take a message
+#[derive(Debug, Clone, PartialEq)]
+pub enum SendError {
+    /// Worth trying again later, e.g. the server could not be reached
+    Temporary(String),
+    /// Refused for good, e.g. a mailbox that does not exist
+    Rejected(String),
+}
+
+/// Hands one channel's messages to an SMS gateway, mail server or push service
+pub trait DeliveryProvider {
+    fn channel(&self) -> MessageChannel;
+
+    fn send(
+        &self,
+        delivery: &MessageDelivery,
+    ) -> impl Future<Output = Result<SentMessage, SendError>> + Send;
+}
+
+/// Works through the queue of outgoing messages
+pub struct DeliveryService;
+
+impl DeliveryService {
+    /// Send up to `limit` queued deliveries on the provider's channel, oldest
+    /// first, and return how many it accepted. Refused deliveries are retried
+    /// on later runs until they have had [`MAX_ATTEMPTS`]; rejected email is
+    /// recorded as bounced. SMS past the school's monthly quota fail without
+    /// being sent.
+    pub async fn dispatch<P: DeliveryProvider>(
+        &self,
+        sdb: &Surreal<Client>,
+        provider: &P,
+        limit: u32,
+    ) -> DomainResult<usize> {
+        let queued = MessageDeliveryQ
+            .get_queued(sdb, provider.channel(), limit)
+            .await?;
+        let mut accepted = 0;
+        // SMS each school may still send this month; None is unlimited
+        let mut sms_left: HashMap<String, Option<u64>> = HashMap::new();
+        for delivery in queued {
+            let Some(id) = delivery.id.as_ref().map(record_key) else {
+                continue;
+            };
+            let school_id = record_key(&delivery.school_id);
+            if delivery.channel == MessageChannel::Sms {
+                let left = match sms_left.get(&school_id) {
+                    Some(left) => *left,
+                    None => {
+                        let left = SubscriptionService.sms_left(sdb, &school_id).await?;
+                        sms_left.insert(school_id.clone(), left);
+                        left
+                    }
+                };
+                if left == Some(0) {
+                    MessageDeliveryQ
+                        .record_failure(
+                            sdb,
+                            id,
+                            "the school's SMS quota for this month has been used".into(),
+                            DeliveryStatus::Failed,
+                        )
+                        .await?;
+                    continue;
+                }
+            }
+            match provider.send(&delivery).await {
+                Ok(sent) => {
+                    if delivery.channel == MessageChannel::Sms {
+                        SmsLogQ.log(sdb, sms_log(&delivery, &sent)).await?;
+                        if let Some(Some(left)) = sms_left.get_mut(&school_id) {
+                            *left -= 1;
+                        }
+                    }
+                    MessageDeliveryQ
+                        .mark_sent(sdb, id, sent.provider, sent.provider_message_id, sent.cost)
+                        .await?;
+                    accepted += 1;
+                }
+                Err(err) => {
+                    let (status, err) = failure_status(&delivery, err);
+                    MessageDeliveryQ
+                        .record_failure(sdb, id, err, status)
+                        .await?;
+                }
+            }
+        }
+        Ok(accepted)
+    }
+
+    /// Apply a provider's delivery receipt, bounce or failure report
+    pub async fn receipt(
+        &self,
+        sdb: &Surreal<Client>,
+        provider_message_id: String,
+        status: DeliveryStatus,
+        error: Option<String>,
+    ) -> DomainResult<usize> {
+        if status == DeliveryStatus::Queued {
+            return Err(Error::Validation(
+                "a receipt can't put a message back in the queue".into(),
+            ));
+        }
+        let updated = MessageDeliveryQ
+            .update_status_by_provider_id(sdb, provider_message_id, status, error)
+            .await?;
+        Ok(updated.len())
+    }
+}
+
+/// Where a refused delivery goes next, and the error to record
+fn failure_status(delivery: &MessageDelivery, err: SendError) -> (DeliveryStatus, String) {
+    match err {
+        SendError::Rejected(err) if delivery.channel == MessageChannel::Email => {
+            (DeliveryStatus::Bounced, err)
+        }
+        SendError::Rejected(err) => (DeliveryStatus::Failed, err),
+        SendError::Temporary(err) if delivery.attempts + 1 >= MAX_ATTEMPTS => {
+            (DeliveryStatus::Failed, err)
+        }
+        SendError::Temporary(err) => (DeliveryStatus::Queued, err),
+    }
+}
+
+fn sms_log(delivery: &MessageDelivery, sent: &SentMessage) -> SmsLog {
+    SmsLog {
+        id: None,
+        school_id: delivery.school_id.clone(),
+        recipient_phone: delivery.address.clone().unwrap_or_default(),
+        recipient_user_id: Some(delivery.recipient_user_id.clone()),
+        message: delivery.body.clone(),
+        sms_type: if delivery.announcement_id.is_some() {
+            SmsType::Announcement
+        } else {
+            SmsType::Other
+        },
+        provider: Some(sent.provider.clone()),
+        provider_message_id: sent.provider_message_id.clone(),
+        status: DeliveryStatus::Sent,
+        cost: sent.cost,
+        sent_at: Some(Datetime::now()),
+        delivered_at: None,
+        created_at: Datetime::now(),
+    }
+}
+
+/// Counts, spend and failures per channel for an announcement's deliveries
+pub fn summarize(
+    announcement_id: String,
+    deliveries: &[MessageDelivery],
+) -> AnnouncementDeliveryReport {
+    let mut channels: Vec<ChannelDeliverySummary> = Vec::new();
+    let mut recipients: Vec<String> = Vec::new();
+    let mut failures = Vec::new();
+    for delivery in deliveries {
+        let recipient = record_key(&delivery.recipient_user_id);
+        if !recipients.contains(&recipient) {
+            recipients.push(recipient.clone());
+        }
+
+        let summary = match channels.iter().position(|c| c.channel == delivery.channel) {
+            Some(index) => &mut channels[index],
+            None => {
+                channels.push(ChannelDeliverySummary {
+                    channel: delivery.channel.clone(),
+                    queued: 0,
+                    sent: 0,
+                    delivered: 0,
+                    failed: 0,
+                    bounced: 0,
+                    cost: Decimal::ZERO,
+                });
+                channels.last_mut().expect("just pushed")
+            }
+        };
+        match delivery.status {
+            DeliveryStatus::Queued => summary.queued += 1,
+            DeliveryStatus::Sent => summary.sent += 1,
+            DeliveryStatus::Delivered => summary.delivered += 1,
+            DeliveryStatus::Failed => summary.failed += 1,
+            DeliveryStatus::Bounced => summary.bounced += 1,
+        }
+        summary.cost += delivery.cost.unwrap_or_default();
+
+        if matches!(
+            delivery.status,
+            DeliveryStatus::Failed | DeliveryStatus::Bounced
+        ) {
+            failures.push(DeliveryFailure {
+                recipient_user_id: recipient,
+                channel: delivery.channel.clone(),
+                error: delivery
+                    .last_error
+                    .clone()
+                    .unwrap_or_else(|| "not delivered".into()),
+            });
+        }
+    }
+    AnnouncementDeliveryReport {
+        announcement_id,
+        recipients: recipients.len(),
+        total_cost: channels.iter().map(|c| c.cost).sum(),
+        channels,
+        failures,
+    }
+}
@@ -0,0 +1,34 @@
+use thiserror::Error;
+
+pub type DomainResult<T> = Result<T, Error>;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error(transparent)]
+    Db(#[from] db::error::Error),
+
+    #[error("Record not found: {0}")]
+    NotFound(String),
+
+    #[error("Validation failed: {0}")]
+    Validation(String),
+
+    #[error("Locked: {0}")]
+    Locked(String),
+
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+
+    #[error("Not allowed: {0}")]
+    Forbidden(String),
+
+    /// The school's plan doesn't allow it, or its subscription has run out
+    #[error("Subscription: {0}")]
+    Subscription(String),
+
+    #[error("Export failed: {0}")]
+    Export(String),
+}
@@ -0,0 +1,571 @@
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use chrono::{NaiveDate, Utc};
+use db::queries::{BulkQ, EventQ, MessageDeliveryQ, SchoolQ, UserQ};
+use qrcode::{QrCode, render::svg};
+use shared::{
+    helpers::{
+        api_responses::{Contact, EventCheckIn, EventTicket, RsvpSummary},
+        create_update_dtos::RsvpDto,
+        surreal_util::{record_key, to_naive_date},
+    },
+    i18n::{Localizer, Translations},
+    models::{
+        DeliveryStatus, MessageChannel, NotificationType, RsvpResponse, User, UserStatus, UserType,
+        communication::{Event, EventRsvp},
+        system_and_audit::{MessageDelivery, Notification},
+    },
+};
+use surrealdb::{Surreal, engine::remote::ws::Client};
+use surrealdb_types::{Datetime, RecordId};
+
+use crate::{
+    announcement::audience,
+    error::{DomainResult, Error},
+    locale::LocaleService,
+    parent_portal::ParentPortalService,
+    subscription::SubscriptionService,
+};
+
+/// Most guests one reply can bring
+const MAX_GUESTS: i32 = 10;
+/// Non-responders are reminded once the deadline is this close
+const REMINDER_DAYS_AHEAD: i64 = 3;
+/// Ticket codes avoid characters that are easy to misread when typed in
+const TICKET_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+const TICKET_LEN: usize = 10;
+
+/// Replies to school events, tickets and check-in at the door
+pub struct EventService;
+
+impl EventService {
+    /// Reply to an event, or change an earlier reply. Replies close at the
+    /// event's deadline and can't take it past capacity.
+    pub async fn rsvp(
+        &self,
+        sdb: &Surreal<Client>,
+        user_id: String,
+        event_id: String,
+        data: RsvpDto,
+    ) -> DomainResult<EventRsvp> {
+        let event = self.event(sdb, &event_id).await?;
+        if !event.requires_rsvp {
+            return Err(Error::Validation("this event doesn't take replies".into()));
+        }
+        if !rsvp_open(&event, Utc::now().date_naive()) {
+            return Err(Error::Locked("replies for this event have closed".into()));
+        }
+        if !(0..=MAX_GUESTS).contains(&data.number_of_guests) {
+            return Err(Error::Validation(format!(
+                "bring between 0 and {MAX_GUESTS} guests"
+            )));
+        }
+        let user_id = RecordId::new("users", user_id.as_str());
+        let invited = audience(
+            sdb,
+            record_key(&event.school_id),
+            &event.target_audience,
+            event.class_id.as_ref(),
+        )
+        .await?;
+        if !invited.iter().any(|u| u.id.as_ref() == Some(&user_id)) {
+            return Err(Error::Forbidden("this event isn't for you".into()));
+        }
+        let student_id = match data.student_id {
+            Some(student_id) => Some(self.child(sdb, &user_id, student_id).await?),
+            None => None,
+        };
+        SubscriptionService
+            .ensure_writable(sdb, &record_key(&event.school_id))
+            .await?;
+
+        let rsvps = EventQ.get_rsvps(sdb, event_id.clone()).await?;
+        let existing = rsvps
+            .iter()
+            .find(|r| r.user_id == user_id && r.student_id == student_id)
+            .cloned();
+        let mut rsvp = EventRsvp {
+            id: None,
+            event_id: event.id.clone().expect("loaded events have ids"),
+            user_id,
+            student_id,
+            response: data.response,
+            number_of_guests: data.number_of_guests,
+            ticket_code: None,
+            checked_in_at: None,
+            checked_in_by: None,
+            responded_at: Datetime::now(),
+            created_at: Datetime::now(),
+        };
+        if let Some(left) = places_left(event.capacity, &rsvps, existing.as_ref())
+            && places(&rsvp) > left
+        {
+            return Err(Error::Conflict(format!(
+                "only {left} places are left for this event"
+            )));
+        }
+        if rsvp.response == RsvpResponse::Attending {
+            rsvp.ticket_code = existing
+                .as_ref()
+                .and_then(|r| r.ticket_code.clone())
+                .or_else(|| Some(ticket_code()));
+        }
+
+        let saved = match existing.and_then(|r| r.id) {
+            Some(id) => EventQ.update_rsvp(sdb, record_key(&id), rsvp).await?,
+            None => EventQ.rsvp(sdb, rsvp).await?,
+        };
+        saved.ok_or_else(|| Error::NotFound("rsvp".into()))
+    }
+
+    /// Replies to an event and the places left, for the school's staff
+    pub async fn summary(
+        &self,
+        sdb: &Surreal<Client>,
+        user_id: String,
+        event_id: String,
+    ) -> DomainResult<RsvpSummary> {
+        let event = self.event(sdb, &event_id).await?;
+        self.staff(sdb, &user_id, &event).await?;
+        let rsvps = EventQ.get_rsvps(sdb, event_id.clone()).await?;
+        Ok(summarize(event_id, &event, &rsvps))
+    }
+
+    /// The ticket for an attending reply, for the person who replied or the
+    /// school's staff
+    pub async fn ticket(
+        &self,
+        sdb: &Surreal<Client>,
+        user_id: String,
+        rsvp_id: String,
+    ) -> DomainResult<EventTicket> {
+        let rsvp = EventQ
+            .get_rsvp_by_id(sdb, rsvp_id)
+            .await?
+            .ok_or_else(|| Error::NotFound("rsvp".into()))?;
+        let event = self.event(sdb, &record_key(&rsvp.event_id)).await?;
+        if record_key(&rsvp.user_id) != user_id {
+            self.staff(sdb, &user_id, &event).await?;
+        }
+        let code = rsvp
+            .ticket_code
+            .clone()
+            .filter(|_| rsvp.response == RsvpResponse::Attending)
+            .ok_or_else(|| Error::NotFound("ticket for this reply".into()))?;
+        Ok(EventTicket {
+            qr_svg: qr_svg(&code)?,
+            event,
+            rsvp,
+        })
+    }
+
+    /// Admit the holder of a scanned ticket on the day of the event
+    pub async fn check_in(
+        &self,
+        sdb: &Surreal<Client>,
+        staff_id: String,
+        ticket_code: String,
+    ) -> DomainResult<EventCheckIn> {
+        let ticket_code = ticket_code.trim().to_ascii_uppercase();
+        let rsvp = EventQ
+            .get_rsvp_by_ticket(sdb, ticket_code)
+            .await?
+            .filter(|r| r.response == RsvpResponse::Attending)
+            .ok_or_else(|| Error::NotFound("ticket".into()))?;
+        let event = self.event(sdb, &record_key(&rsvp.event_id)).await?;
+        self.staff(sdb, &staff_id, &event).await?;
+        if let Some(date) = to_naive_date(&event.event_date)
+            && date != Utc::now().date_naive()
+        {
+            return Err(Error::Validation(format!("this ticket is for {date}")));
+        }
+        if let Some(at) = rsvp.checked_in_at {
+            return Err(Error::Conflict(format!("ticket was already used at {at}")));
+        }
+        SubscriptionService
+            .ensure_writable(sdb, &record_key(&event.school_id))
+            .await?;
+
+        let rsvp_id = rsvp.id.as_ref().map(record_key).unwrap_or_default();
+        let rsvp = EventQ
+            .check_in(sdb, rsvp_id, staff_id)
+            .await?
+            .ok_or_else(|| Error::Conflict("ticket was already used".into()))?;
+        let attendee = UserQ
+            .get_by_id(sdb, record_key(&rsvp.user_id))
+            .await?
+            .map(|u| format!("{} {}", u.first_name, u.last_name))
+            .unwrap_or_default();
+        Ok(EventCheckIn {
+            places: places(&rsvp),
+            attendee: Contact {
+                user_id: record_key(&rsvp.user_id),
+                name: attendee,
+            },
+            event,
+            rsvp,
+        })
+    }
+
+    /// Remind everyone an event is for who hasn't replied, once, as its
+    /// deadline nears: in-app, and by SMS where there is a phone number.
+    /// Returns how many people were reminded.
+    pub async fn send_rsvp_reminders(&self, sdb: &Surreal<Client>) -> DomainResult<usize> {
+        let mut reminded = 0;
+        for event in EventQ
+            .get_needing_reminders(sdb, REMINDER_DAYS_AHEAD)
+            .await?
+        {
+            let event_id = event.id.as_ref().map(record_key).unwrap_or_default();
+            let school_id = record_key(&event.school_id);
+            let school_name = SchoolQ
+                .get_by_id(sdb, school_id.clone())
+                .await?
+                .map(|s| s.school_name)
+                .unwrap_or_default();
+            let invited = audience(
+                sdb,
+                school_id.clone(),
+                &event.target_audience,
+                event.class_id.as_ref(),
+            )
+            .await?;
+            let rsvps = EventQ.get_rsvps(sdb, event_id.clone()).await?;
+            let (school_locale, currency) = LocaleService.school_defaults(sdb, school_id).await?;
+
+            let mut notifications = Vec::new();
+            let mut deliveries = Vec::new();
+            for user in non_responders(invited, &rsvps) {
+                let i18n = Localizer::new(
+                    Translations::global(),
+                    user.locale.unwrap_or(school_locale),
+                    currency,
+                );
+                let (notification, sms) = reminder(&event, &user, &school_name, &i18n);
+                notifications.push(notification);
+                deliveries.extend(sms);
+                reminded += 1;
+            }
+            BulkQ.create_notifications_batch(sdb, notifications).await?;
+            MessageDeliveryQ.create_many(sdb, deliveries).await?;
+            EventQ.mark_reminded(sdb, event_id).await?;
+        }
+        Ok(reminded)
+    }
+
+    async fn event(&self, sdb: &Surreal<Client>, event_id: &str) -> DomainResult<Event> {
+        EventQ
+            .get_by_id(sdb, event_id.to_string())
+            .await?
+            .ok_or_else(|| Error::NotFound(format!("event {event_id}")))
+    }
+
+    /// The student, provided they are the parent's child
+    async fn child(
+        &self,
+        sdb: &Surreal<Client>,
+        user_id: &RecordId,
+        student_id: String,
+    ) -> DomainResult<RecordId> {
+        ParentPortalService
+            .children(sdb, record_key(user_id))
+            .await?
+            .into_iter()
+            .find_map(|c| c.id.filter(|id| record_key(id) == student_id))
+            .ok_or_else(|| Error::Forbidden("not one of your children".into()))
+    }
+
+    /// An active member of staff at the event's school
+    async fn staff(
+        &self,
+        sdb: &Surreal<Client>,
+        user_id: &str,
+        event: &Event,
+    ) -> DomainResult<User> {
+        UserQ
+            .get_by_id(sdb, user_id.to_string())
+            .await?
+            .filter(|u| {
+                u.school_id == event.school_id
+                    && u.status == UserStatus::Active
+                    && !matches!(u.user_type, UserType::Parent | UserType::Student)
+            })
+            .ok_or_else(|| Error::Forbidden("only school staff can do this".into()))
+    }
+}
+
+/// Whether replies are still accepted: up to the deadline, and never after
+/// the event
+fn rsvp_open(event: &Event, today: NaiveDate) -> bool {
+    let before = |date: Option<NaiveDate>| date.is_none_or(|d| today <= d);
+    before(event.rsvp_deadline.as_ref().and_then(to_naive_date))
+        && before(to_naive_date(&event.event_date))
+}
+
+/// People a reply brings: the one who replied and their guests, if attending
+fn places(rsvp: &EventRsvp) -> i32 {
+    match rsvp.response {
+        RsvpResponse::Attending => 1 + rsvp.number_of_guests,
+        RsvpResponse::NotAttending | RsvpResponse::Maybe => 0,
+    }
+}
+
+/// Places still free, leaving out the reply being changed
+fn places_left(
+    capacity: Option<i32>,
+    rsvps: &[EventRsvp],
+    replacing: Option<&EventRsvp>,
+) -> Option<i32> {
+    let taken: i32 = rsvps
+        .iter()
+        .filter(|r| replacing.is_none_or(|old| old.id != r.id))
+        .map(places)
+        .sum();
+    capacity.map(|capacity| (capacity - taken).max(0))
+}
+
+fn summarize(event_id: String, event: &Event, rsvps: &[EventRsvp]) -> RsvpSummary {
+    let count = |response: RsvpResponse| rsvps.iter().filter(|r| r.response == response).count();
+    RsvpSummary {
+        event_id,
+        attending: count(RsvpResponse::Attending),
+        not_attending: count(RsvpResponse::NotAttending),
+        maybe: count(RsvpResponse::Maybe),
+        places_taken: rsvps.iter().map(places).sum(),
+        capacity: event.capacity,
+        places_left: places_left(event.capacity, rsvps, None),
+        checked_in: rsvps.iter().filter(|r| r.checked_in_at.is_some()).count(),
+    }
+}
+
+/// Invited users with no reply of any kind
+fn non_responders(invited: Vec<User>, rsvps: &[EventRsvp]) -> Vec<User> {
+    invited
+        .into_iter()
+        .filter(|u| !rsvps.iter().any(|r| Some(&r.user_id) == u.id.as_ref()))
+        .collect()
+}
+
+fn ticket_code() -> String {
+    let mut bytes = [0u8; TICKET_LEN];
+    OsRng.fill_bytes(&mut bytes);
+    bytes
+        .iter()
+        .map(|b| TICKET_ALPHABET[*b as usize % TICKET_ALPHABET.len()] as char)
+        .collect()
+}
+
+fn qr_svg(code: &str) -> DomainResult<String> {
+    let qr = QrCode::new(code.as_bytes())
+        .map_err(|e| Error::Validation(format!("ticket code can't be drawn: {e}")))?;
+    Ok(qr
+        .render::<svg::Color>()
+        .min_dimensions(240, 240)
+        .quiet_zone(true)
+        .build())
+}
+
+/// An in-app reminder, and an SMS to send if the user has a phone number
+fn reminder(
+    event: &Event,
+    user: &User,
+    school_name: &str,
+    i18n: &Localizer,
+) -> (Notification, Option<MessageDelivery>) {
+    let date = to_naive_date(&event.event_date)
+        .map(|d| i18n.date(d))
+        .unwrap_or_default();
+    let deadline = event
+        .rsvp_deadline
+        .as_ref()
+        .and_then(to_naive_date)
+        .map(|d| i18n.date(d))
+        .unwrap_or_else(|| date.clone());
+    let args = [
+        ("event", event.event_name.as_str()),
+        ("date", date.as_str()),
+        ("deadline", deadline.as_str()),
+        ("school", school_name),
+    ];
+    let user_id = user.id.clone().expect("invited users have ids");
+
+    let notification = Notification {
+        id: None,
+        user_id: user_id.clone(),
+        notification_type: NotificationType::Event,
+        title: i18n.format("event.rsvp_reminder.title", &args),
+        message: i18n.format("event.rsvp_reminder.body", &args),
+        link_url: None,
+        read: false,
+        read_at: None,
+        sent_at: Datetime::now(),
+        created_at: Datetime::now(),
+    };
+    let sms = user
+        .phone_number
+        .clone()
+        .filter(|p| !p.trim().is_empty())
+        .map(|phone| MessageDelivery {
+            id: None,
+            school_id: event.school_id.clone(),
+            announcement_id: None,
+            recipient_user_id: user_id,
+            channel: MessageChannel::Sms,
+            address: Some(phone),
+            subject: None,
+            body: i18n.format("event.rsvp_reminder.sms", &args),
+            status: DeliveryStatus::Queued,
+            attempts: 0,
+            last_error: None,
+            provider: None,
+            provider_message_id: None,
+            cost: None,
+            sent_at: None,
+            delivered_at: None,
+            template: None,
+            created_at: Datetime::now(),
+            updated_at: Datetime::now(),
+        });
+    (notification, sms)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shared::{
+        helpers::surreal_util::to_surreal_datetime,
+        i18n::{Currency, Locale},
+        models::TargetAudience,
+    };
+
+    fn day(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 11, d).unwrap()
+    }
+
+    fn event(capacity: Option<i32>) -> Event {
+        Event {
+            id: Some(RecordId::new("events", "e1")),
+            school_id: RecordId::new("schools", "s1"),
+            event_name: "PTA meeting".into(),
+            event_description: None,
+            event_date: to_surreal_datetime(day(20)),
+            event_time: Some("16:00".into()),
+            event_location: Some("Main hall".into()),
+            target_audience: TargetAudience::Parents,
+            class_id: None,
+            requires_rsvp: true,
+            rsvp_deadline: Some(to_surreal_datetime(day(15))),
+            capacity,
+            rsvp_reminder_sent_at: None,
+            created_by: None,
+            created_at: Datetime::now(),
+            updated_at: Datetime::now(),
+        }
+    }
+
+    fn rsvp(key: &str, user: &str, response: RsvpResponse, guests: i32) -> EventRsvp {
+        EventRsvp {
+            id: Some(RecordId::new("event_rsvps", key)),
+            event_id: RecordId::new("events", "e1"),
+            user_id: RecordId::new("users", user),
+            student_id: None,
+            response,
+            number_of_guests: guests,
+            ticket_code: None,
+            checked_in_at: None,
+            checked_in_by: None,
+            responded_at: Datetime::now(),
+            created_at: Datetime::now(),
+        }
+    }
+
+    fn parent(key: &str, phone: Option<&str>) -> User {
+        User {
+            id: Some(RecordId::new("users", key)),
+            school_id: RecordId::new("schools", "s1"),
+            user_type: UserType::Parent,
+            first_name: "Ngozi".into(),
+            last_name: "Okafor".into(),
+            email: None,
+            phone_number: phone.map(Into::into),
+            password_hash: String::new(),
+            status: UserStatus::Active,
+            last_login: None,
+            email_verified: false,
+            phone_verified: false,
+            locale: None,
+            created_at: Datetime::now(),
+            updated_at: Datetime::now(),
+        }
+    }
+
+    #[test]
+    fn replies_close_at_the_deadline() {
+        let event = event(None);
+        assert!(rsvp_open(&event, day(15)));
+        assert!(!rsvp_open(&event, day(16)));
+
+        let mut no_deadline = event.clone();
+        no_deadline.rsvp_deadline = None;
+        assert!(rsvp_open(&no_deadline, day(20)));
+        assert!(!rsvp_open(&no_deadline, day(21)));
+    }
+
+    #[test]
+    fn counts_places_against_capacity() {
+        let rsvps = vec![
+            rsvp("r1", "u1", RsvpResponse::Attending, 2),
+            rsvp("r2", "u2", RsvpResponse::Maybe, 4),
+            rsvp("r3", "u3", RsvpResponse::Attending, 0),
+        ];
+        assert_eq!(places_left(Some(10), &rsvps, None), Some(6));
+        // Changing a reply frees its own places first
+        assert_eq!(places_left(Some(10), &rsvps, Some(&rsvps[0])), Some(9));
+        assert_eq!(places_left(Some(3), &rsvps, None), Some(0));
+        assert_eq!(places_left(None, &rsvps, None), None);
+
+        let mut checked_in = rsvps.clone();
+        checked_in[2].checked_in_at = Some(Datetime::now());
+        let summary = summarize("e1".into(), &event(Some(10)), &checked_in);
+        assert_eq!(summary.attending, 2);
+        assert_eq!(summary.maybe, 1);
+        assert_eq!(summary.places_taken, 4);
+        assert_eq!(summary.places_left, Some(6));
+        assert_eq!(summary.checked_in, 1);
+    }
+
+    #[test]
+    fn reminds_only_those_who_have_not_replied() {
+        let invited = vec![parent("u1", Some("+2348012345678")), parent("u2", None)];
+        let rsvps = vec![rsvp("r1", "u1", RsvpResponse::NotAttending, 0)];
+        let pending = non_responders(invited, &rsvps);
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].id, Some(RecordId::new("users", "u2")));
+
+        let i18n = Localizer::new(Translations::global(), Locale::En, Currency::Ngn);
+        let (notification, sms) = reminder(
+            &event(None),
+            &parent("u1", Some("+2348012345678")),
+            "Greenfield Academy",
+            &i18n,
+        );
+        assert_eq!(notification.title, "Will you attend PTA meeting?");
+        assert!(notification.message.contains("PTA meeting is on"));
+        let sms = sms.unwrap();
+        assert!(sms.body.starts_with("Greenfield Academy: PTA meeting"));
+        assert_eq!(sms.status, DeliveryStatus::Queued);
+        assert!(
+            reminder(&event(None), &parent("u2", None), "", &i18n)
+                .1
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn issues_scannable_ticket_codes() {
+        let code = ticket_code();
+        assert_eq!(code.len(), TICKET_LEN);
+        assert!(code.bytes().all(|b| TICKET_ALPHABET.contains(&b)));
+        let svg = qr_svg(&code).unwrap();
+        assert!(svg.contains("<svg"));
+    }
+}
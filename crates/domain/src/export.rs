@@ -0,0 +1,707 @@
+use std::collections::HashMap;
+use std::future::Future;
+
+use db::{
+    error::DbResult,
+    pagination::Page,
+    queries::{
+        AcademicSessionQ, ActivityLogQ, AssessmentQ, AttendanceQ, ClassQ, InvoiceQ, PaymentQ,
+        StudentQ, StudentScoreQ, SubjectQ, TermQ,
+    },
+};
+use rust_xlsxwriter::{Format, Workbook};
+use serde::Serialize;
+use shared::{
+    helpers::{
+        create_update_dtos::{ExportEntity, ExportFormat, ExportRequest},
+        query_filters::StudentFilter,
+        surreal_util::{record_key, to_naive_date},
+    },
+    models::{
+        ActionType, Student,
+        assessments_nd_grading::Assessment,
+        attendance::Attendance,
+        fee_management::{Invoice, Payment},
+        system_and_audit::ActivityLog,
+    },
+};
+use surrealdb::{Surreal, engine::remote::ws::Client};
+use surrealdb_types::{Datetime, RecordId, Value};
+use tokio::sync::mpsc::Sender;
+
+use crate::error::{DomainResult, Error};
+
+const PAGE_SIZE: u32 = 500;
+
+const STUDENT_COLUMNS: &[&str] = &[
+    "admission_number",
+    "first_name",
+    "middle_name",
+    "last_name",
+    "gender",
+    "date_of_birth",
+    "class_name",
+    "status",
+    "admission_date",
+    "address",
+];
+const CLASS_REGISTER_COLUMNS: &[&str] = &[
+    "date",
+    "admission_number",
+    "student_name",
+    "class_name",
+    "status",
+    "arrival_time",
+    "reason",
+];
+const SCORE_SHEET_COLUMNS: &[&str] = &[
+    "admission_number",
+    "student_name",
+    "class_name",
+    "subject_name",
+    "assessment_name",
+    "assessment_type",
+    "max_score",
+    "score_obtained",
+    "verified",
+    "remarks",
+];
+const INVOICE_COLUMNS: &[&str] = &[
+    "invoice_number",
+    "admission_number",
+    "student_name",
+    "term_name",
+    "total_amount",
+    "discount_amount",
+    "amount_paid",
+    "balance",
+    "due_date",
+    "status",
+];
+const PAYMENT_COLUMNS: &[&str] = &[
+    "receipt_number",
+    "invoice_number",
+    "admission_number",
+    "student_name",
+    "amount_paid",
+    "payment_method",
+    "payment_reference",
+    "payment_date",
+    "paid_by",
+    "status",
+];
+const ACTIVITY_LOG_COLUMNS: &[&str] = &[
+    "created_at",
+    "user_id",
+    "action",
+    "entity_type",
+    "entity_id",
+    "description",
+    "ip_address",
+];
+
+/// Names needed to turn record links into readable cells, keyed by record key
+#[derive(Default)]
+struct Lookups {
+    students: HashMap<String, Student>,
+    classes: HashMap<String, String>,
+    subjects: HashMap<String, String>,
+    terms: HashMap<String, String>,
+    assessments: HashMap<String, Assessment>,
+    invoices: HashMap<String, String>,
+}
+
+impl Lookups {
+    fn admission_number(&self, student_id: &RecordId) -> String {
+        self.students
+            .get(&record_key(student_id))
+            .map(|s| s.admission_number.clone())
+            .unwrap_or_default()
+    }
+
+    fn student_name(&self, student_id: &RecordId) -> String {
+        self.students
+            .get(&record_key(student_id))
+            .map(|s| format!("{} {}", s.first_name, s.last_name))
+            .unwrap_or_default()
+    }
+
+    fn class_name(&self, class_id: Option<&RecordId>) -> String {
+        class_id
+            .and_then(|id| self.classes.get(&record_key(id)))
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+/// Serializes rows into the requested format, keeping only the chosen columns
+struct Encoder {
+    format: ExportFormat,
+    keys: Vec<&'static str>,
+    indices: Vec<usize>,
+    buffer: Vec<u8>,
+    rows: u64,
+    workbook: Option<Workbook>,
+}
+
+impl Encoder {
+    fn new(
+        format: ExportFormat,
+        available: &[&'static str],
+        columns: &[String],
+    ) -> DomainResult<Self> {
+        let indices = if columns.is_empty() {
+            (0..available.len()).collect()
+        } else {
+            columns
+                .iter()
+                .map(|column| {
+                    available
+                        .iter()
+                        .position(|c| c == column)
+                        .ok_or_else(|| Error::Validation(format!("Unknown column {column}")))
+                })
+                .collect::<DomainResult<Vec<_>>>()?
+        };
+        let keys: Vec<&'static str> = indices.iter().map(|i| available[*i]).collect();
+
+        let mut encoder = Self {
+            format,
+            keys,
+            indices,
+            buffer: Vec::new(),
+            rows: 0,
+            workbook: None,
+        };
+        match format {
+            ExportFormat::Csv => {
+                let header: Vec<String> = encoder.keys.iter().map(|k| k.to_string()).collect();
+                encoder.write_csv(&header)?;
+            }
+            ExportFormat::Json => encoder.buffer.push(b'['),
+            ExportFormat::Xlsx => {
+                let mut workbook = Workbook::new();
+                let sheet = workbook.add_worksheet_with_constant_memory();
+                let bold = Format::new().set_bold();
+                for (col, key) in encoder.keys.iter().enumerate() {
+                    sheet
+                        .write_string_with_format(0, col as u16, *key, &bold)
+                        .map_err(|e| Error::Export(e.to_string()))?;
+                }
+                encoder.workbook = Some(workbook);
+            }
+        }
+        Ok(encoder)
+    }
+
+    fn row(&mut self, full: Vec<String>) -> DomainResult<()> {
+        let cells: Vec<String> = self.indices.iter().map(|i| full[*i].clone()).collect();
+        self.rows += 1;
+        match self.format {
+            ExportFormat::Csv => self.write_csv(&cells)?,
+            ExportFormat::Json => {
+                if self.rows > 1 {
+                    self.buffer.push(b',');
+                }
+                let object: serde_json::Map<String, serde_json::Value> = self
+                    .keys
+                    .iter()
+                    .zip(cells)
+                    .map(|(k, v)| (k.to_string(), serde_json::Value::String(v)))
+                    .collect();
+                serde_json::to_writer(&mut self.buffer, &object)
+                    .map_err(|e| Error::Export(e.to_string()))?;
+            }
+            ExportFormat::Xlsx => {
+                let row = self.rows as u32;
+                if let Some(sheet) = self
+                    .workbook
+                    .as_mut()
+                    .and_then(|w| w.worksheet_from_index(0).ok())
+                {
+                    for (col, cell) in cells.iter().enumerate() {
+                        sheet
+                            .write_string(row, col as u16, cell)
+                            .map_err(|e| Error::Export(e.to_string()))?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Bytes ready to be sent; XLSX can only be produced once complete
+    fn take_chunk(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.buffer)
+    }
+
+    fn finish(mut self) -> DomainResult<Vec<u8>> {
+        match self.format {
+            ExportFormat::Csv => Ok(self.buffer),
+            ExportFormat::Json => {
+                self.buffer.push(b']');
+                Ok(self.buffer)
+            }
+            ExportFormat::Xlsx => match self.workbook.as_mut() {
+                Some(workbook) => workbook
+                    .save_to_buffer()
+                    .map_err(|e| Error::Export(e.to_string())),
+                None => Ok(Vec::new()),
+            },
+        }
+    }
+
+    fn write_csv(&mut self, cells: &[String]) -> DomainResult<()> {
+        let mut writer = csv::WriterBuilder::new().from_writer(&mut self.buffer);
+        writer
+            .write_record(cells)
+            .and_then(|_| writer.flush().map_err(csv::Error::from))
+            .map_err(|e| Error::Export(e.to_string()))
+    }
+}
+
+pub struct ExportService;
+
+impl ExportService {
+    /// Column keys that can be requested for an entity, in default order
+    pub fn columns(&self, entity: ExportEntity) -> &'static [&'static str] {
+        match entity {
+            ExportEntity::Students => STUDENT_COLUMNS,
+            ExportEntity::ClassRegister => CLASS_REGISTER_COLUMNS,
+            ExportEntity::ScoreSheet => SCORE_SHEET_COLUMNS,
+            ExportEntity::Invoices => INVOICE_COLUMNS,
+            ExportEntity::Payments => PAYMENT_COLUMNS,
+            ExportEntity::ActivityLog => ACTIVITY_LOG_COLUMNS,
+        }
+    }
+
+    /// Export an entity page by page, sending encoded chunks through `chunks` as
+    /// they are ready, then record the export in the activity log.
+    /// Returns the number of rows written.
+    pub async fn export(
+        &self,
+        sdb: &Surreal<Client>,
+        request: ExportRequest,
+        user_id: Option<String>,
+        chunks: Sender<Vec<u8>>,
+    ) -> DomainResult<u64> {
+        let mut encoder = Encoder::new(
+            request.format,
+            self.columns(request.entity),
+            &request.columns,
+        )?;
+        let school_id = request.school_id.clone();
+        let school = RecordId::new("schools", school_id.as_str());
+        let lookups = self.load_lookups(sdb, &request).await?;
+
+        match request.entity {
+            ExportEntity::Students => {
+                let mut filter = request.student_filter.clone();
+                filter.school_id = Some(school.clone());
+                write_pages(
+                    &mut encoder,
+                    &chunks,
+                    |page| {
+                        let filter = filter.clone();
+                        async move { StudentQ.get_filtered(sdb, filter, page).await }
+                    },
+                    |s: &Student| student_row(s, &lookups),
+                )
+                .await?;
+            }
+            ExportEntity::ClassRegister => {
+                let filter = request.attendance_filter.clone();
+                write_pages(
+                    &mut encoder,
+                    &chunks,
+                    |page| {
+                        let (school_id, filter) = (school_id.clone(), filter.clone());
+                        async move { AttendanceQ.get_filtered(sdb, school_id, filter, page).await }
+                    },
+                    |a: &Attendance| attendance_row(a, &lookups),
+                )
+                .await?;
+            }
+            ExportEntity::ScoreSheet => {
+                let filter = request.score_filter.clone();
+                write_pages(
+                    &mut encoder,
+                    &chunks,
+                    |page| {
+                        let (school_id, filter) = (school_id.clone(), filter.clone());
+                        async move {
+                            StudentScoreQ
+                                .get_filtered(sdb, school_id, filter, page)
+                                .await
+                        }
+                    },
+                    |score| {
+                        let assessment = lookups.assessments.get(&record_key(&score.assessment_id));
+                        vec![
+                            lookups.admission_number(&score.student_id),
+                            lookups.student_name(&score.student_id),
+                            lookups.class_name(assessment.map(|a| &a.class_id)),
+                            assessment
+                                .and_then(|a| lookups.subjects.get(&record_key(&a.subject_id)))
+                                .cloned()
+                                .unwrap_or_default(),
+                            assessment
+                                .and_then(|a| a.assessment_name.clone())
+                                .unwrap_or_default(),
+                            assessment
+                                .map(|a| enum_str(&a.assessment_type))
+                                .unwrap_or_default(),
+                            assessment
+                                .map(|a| a.max_score.to_string())
+                                .unwrap_or_default(),
+                            opt(score.score_obtained),
+                            score.verified.to_string(),
+                            score.remarks.clone().unwrap_or_default(),
+                        ]
+                    },
+                )
+                .await?;
+            }
+            ExportEntity::Invoices => {
+                let mut filter = request.invoice_filter.clone();
+                filter.school_id = Some(school.clone());
+                write_pages(
+                    &mut encoder,
+                    &chunks,
+                    |page| {
+                        let filter = filter.clone();
+                        async move { InvoiceQ.get_filtered(sdb, filter, page).await }
+                    },
+                    |i: &Invoice| invoice_row(i, &lookups),
+                )
+                .await?;
+            }
+            ExportEntity::Payments => {
+                let mut filter = request.invoice_filter.clone();
+                filter.school_id = Some(school.clone());
+                write_pages(
+                    &mut encoder,
+                    &chunks,
+                    |page| {
+                        let filter = filter.clone();
+                        async move { PaymentQ.get_filtered(sdb, filter, page).await }
+                    },
+                    |p: &Payment| payment_row(p, &lookups),
+                )
+                .await?;
+            }
+            ExportEntity::ActivityLog => {
+                write_pages(
+                    &mut encoder,
+                    &chunks,
+                    |page| {
+                        let school_id = school_id.clone();
+                        async move { ActivityLogQ.get_page(sdb, school_id, page).await }
+                    },
+                    |log: &ActivityLog| {
+                        vec![
+                            log.created_at.to_string(),
+                            log.user_id.as_ref().map(record_key).unwrap_or_default(),
+                            enum_str(&log.action),
+                            log.entity_type.clone().unwrap_or_default(),
+                            log.entity_id.clone().unwrap_or_default(),
+                            log.description.clone().unwrap_or_default(),
+                            log.ip_address.clone().unwrap_or_default(),
+                        ]
+                    },
+                )
+                .await?;
+            }
+        }
+
+        let rows = encoder.rows;
+        let columns = encoder.keys.clone();
+        send(&chunks, encoder.finish()?).await?;
+
+        ActivityLogQ
+            .log(
+                sdb,
+                ActivityLog {
+                    id: None,
+                    school_id: school,
+                    user_id: user_id.map(|id| RecordId::new("users", id.as_str())),
+                    action: ActionType::Export,
+                    entity_type: Some(enum_str(&request.entity)),
+                    entity_id: None,
+                    description: Some(format!(
+                        "Exported {rows} rows as {}",
+                        enum_str(&request.format)
+                    )),
+                    ip_address: None,
+                    metadata: Some(serde_json::json!({
+                        "columns": columns,
+                        "student_filter": request.student_filter,
+                        "invoice_filter": request.invoice_filter,
+                        "attendance_filter": request.attendance_filter,
+                        "score_filter": request.score_filter,
+                    })),
+                    created_at: Datetime::now(),
+                },
+            )
+            .await?;
+
+        Ok(rows)
+    }
+
+    async fn load_lookups(
+        &self,
+        sdb: &Surreal<Client>,
+        request: &ExportRequest,
+    ) -> DomainResult<Lookups> {
+        let school_id = request.school_id.clone();
+        let mut lookups = Lookups::default();
+        if request.entity == ExportEntity::ActivityLog {
+            return Ok(lookups);
+        }
+
+        lookups.classes = ClassQ
+            .get_by_school(sdb, school_id.clone())
+            .await?
+            .into_iter()
+            .filter_map(|c| Some((record_key(c.id.as_ref()?), c.class_name)))
+            .collect();
+
+        if request.entity != ExportEntity::Students {
+            let filter = StudentFilter {
+                school_id: Some(RecordId::new("schools", school_id.as_str())),
+                ..Default::default()
+            };
+            let mut offset = 0;
+            loop {
+                let page = Page {
+                    limit: PAGE_SIZE,
+                    offset,
+                };
+                let students = StudentQ.get_filtered(sdb, filter.clone(), page).await?;
+                let done = students.len() < PAGE_SIZE as usize;
+                for student in students {
+                    if let Some(id) = &student.id {
+                        lookups.students.insert(record_key(id), student);
+                    }
+                }
+                if done {
+                    break;
+                }
+                offset += PAGE_SIZE;
+            }
+        }
+
+        match request.entity {
+            ExportEntity::ScoreSheet => {
+                lookups.subjects = SubjectQ
+                    .get_by_school(sdb, school_id.clone())
+                    .await?
+                    .into_iter()
+                    .filter_map(|s| Some((record_key(s.id.as_ref()?), s.subject_name)))
+                    .collect();
+                lookups.assessments = AssessmentQ
+                    .get_by_school(sdb, school_id.clone())
+                    .await?
+                    .into_iter()
+                    .filter_map(|a| Some((record_key(a.id.as_ref()?), a)))
+                    .collect();
+            }
+            ExportEntity::Invoices | ExportEntity::Payments => {
+                for session in AcademicSessionQ
+                    .get_all_by_school(sdb, school_id.clone())
+                    .await?
+                {
+                    let Some(session_id) = &session.id else {
+                        continue;
+                    };
+                    for term in TermQ.get_by_session(sdb, record_key(session_id)).await? {
+                        if let Some(id) = &term.id {
+                            let name = term
+                                .term_name
+                                .clone()
+                                .unwrap_or_else(|| format!("Term {}", term.term_number));
+                            lookups.terms.insert(
+                                record_key(id),
+                                format!("{} {}", session.session_name, name),
+                            );
+                        }
+                    }
+                }
+                if request.entity == ExportEntity::Payments {
+                    let mut filter = request.invoice_filter.clone();
+                    filter.school_id = Some(RecordId::new("schools", school_id.as_str()));
+                    let mut offset = 0;
+                    loop {
+                        let page = Page {
+                            limit: PAGE_SIZE,
+                            offset,
+                        };
+                        let invoices = InvoiceQ.get_filtered(sdb, filter.clone(), page).await?;
+                        let done = invoices.len() < PAGE_SIZE as usize;
+                        for invoice in invoices {
+                            if let Some(id) = &invoice.id {
+                                lookups
+                                    .invoices
+                                    .insert(record_key(id), invoice.invoice_number);
+                            }
+                        }
+                        if done {
+                            break;
+                        }
+                        offset += PAGE_SIZE;
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        Ok(lookups)
+    }
+}
+
+/// Fetch pages until a short page comes back, encoding and sending each one
+async fn write_pages<T, F, Fut>(
+    encoder: &mut Encoder,
+    chunks: &Sender<Vec<u8>>,
+    mut fetch: F,
+    to_row: impl Fn(&T) -> Vec<String>,
+) -> DomainResult<()>
+where
+    F: FnMut(Page) -> Fut,
+    Fut: Future<Output = DbResult<Vec<T>>>,
+{
+    let mut offset = 0;
+    loop {
+        let records = fetch(Page {
+            limit: PAGE_SIZE,
+            offset,
+        })
+        .await?;
+        for record in &records {
+            encoder.row(to_row(record))?;
+        }
+        send(chunks, encoder.take_chunk()).await?;
+
+        if records.len() < PAGE_SIZE as usize {
+            return Ok(());
+        }
+        offset += PAGE_SIZE;
+    }
+}
+
+async fn send(chunks: &Sender<Vec<u8>>, chunk: Vec<u8>) -> DomainResult<()> {
+    if chunk.is_empty() {
+        return Ok(());
+    }
+    chunks
+        .send(chunk)
+        .await
+        .map_err(|_| Error::Export("export receiver was dropped".into()))
+}
+
+fn student_row(s: &Student, lookups: &Lookups) -> Vec<String> {
+    vec![
+        s.admission_number.clone(),
+        s.first_name.clone(),
+        s.middle_name.clone().unwrap_or_default(),
+        s.last_name.clone(),
+        enum_str(&s.gender),
+        date(&s.date_of_birth),
+        lookups.class_name(s.current_class_id.as_ref()),
+        enum_str(&s.status),
+        date(&s.admission_date),
+        s.address.clone().unwrap_or_default(),
+    ]
+}
+
+fn attendance_row(a: &Attendance, lookups: &Lookups) -> Vec<String> {
+    vec![
+        date(&a.date),
+        lookups.admission_number(&a.student_id),
+        lookups.student_name(&a.student_id),
+        lookups.class_name(Some(&a.class_id)),
+        enum_str(&a.status),
+        a.arrival_time.clone().unwrap_or_default(),
+        a.reason.clone().unwrap_or_default(),
+    ]
+}
+
+fn invoice_row(i: &Invoice, lookups: &Lookups) -> Vec<String> {
+    vec![
+        i.invoice_number.clone(),
+        lookups.admission_number(&i.student_id),
+        lookups.student_name(&i.student_id),
+        lookups
+            .terms
+            .get(&record_key(&i.term_id))
+            .cloned()
+            .unwrap_or_default(),
+        i.total_amount.to_string(),
+        i.discount_amount.to_string(),
+        i.amount_paid.to_string(),
+        i.balance.to_string(),
+        date(&i.due_date),
+        enum_str(&i.status),
+    ]
+}
+
+fn payment_row(p: &Payment, lookups: &Lookups) -> Vec<String> {
+    vec![
+        p.receipt_number.clone(),
+        lookups
+            .invoices
+            .get(&record_key(&p.invoice_id))
+            .cloned()
+            .unwrap_or_default(),
+        lookups.admission_number(&p.student_id),
+        lookups.student_name(&p.student_id),
+        p.amount_paid.to_string(),
+        enum_str(&p.payment_method),
+        p.payment_reference.clone().unwrap_or_default(),
+        p.payment_date.to_string(),
+        p.paid_by.clone().unwrap_or_default(),
+        enum_str(&p.status),
+    ]
+}
+
+/// snake_case name of a unit enum variant, as stored in the database
+pub(crate) fn enum_str<T: Serialize>(value: &T) -> String {
+    match serde_json::to_value(value) {
+        Ok(serde_json::Value::String(s)) => s,
+        _ => String::new(),
+    }
+}
+
+fn date(value: &Value) -> String {
+    to_naive_date(value)
+        .map(|d| d.format("%Y-%m-%d").to_string())
+        .unwrap_or_default()
+}
+
+fn opt<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_requested_columns_in_order() {
+        let columns = vec!["status".to_string(), "admission_number".to_string()];
+        let mut encoder = Encoder::new(ExportFormat::Csv, STUDENT_COLUMNS, &columns).unwrap();
+        let mut row: Vec<String> = STUDENT_COLUMNS.iter().map(|c| format!("<{c}>")).collect();
+        row[0] = "2024/001".into();
+        row[7] = "active".into();
+        encoder.row(row).unwrap();
+
+        let csv = String::from_utf8(encoder.finish().unwrap()).unwrap();
+        assert_eq!(csv, "status,admission_number\nactive,2024/001\n");
+    }
+
+    #[test]
+    fn rejects_unknown_columns() {
+        let columns = vec!["password_hash".to_string()];
+        assert!(Encoder::new(ExportFormat::Json, STUDENT_COLUMNS, &columns).is_err());
+    }
+}
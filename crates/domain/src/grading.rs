@@ -0,0 +1,252 @@
+use db::queries::{GradingSchemeQ, ReportCardQ};
+use shared::{
+    helpers::{
+        api_responses::GradingSchemeWithBands,
+        create_update_dtos::{GradeBandDto, SaveGradingSchemeDto},
+        surreal_util::record_key,
+    },
+    models::{
+        ClassLevel,
+        assessments_nd_grading::{GradingScheme, GradingSchemeVersion, ReportCard},
+    },
+};
+use surrealdb::{Surreal, engine::remote::ws::Client};
+use surrealdb_types::Decimal;
+
+use crate::{
+    error::{DomainResult, Error},
+    subscription::SubscriptionService,
+};
+
+pub struct GradingService;
+
+impl GradingService {
+    /// Save a named scheme as its next version. Levels already covered by a
+    /// different scheme are rejected so each class level has one scheme.
+    pub async fn save(
+        &self,
+        sdb: &Surreal<Client>,
+        data: SaveGradingSchemeDto,
+    ) -> DomainResult<GradingSchemeWithBands> {
+        validate_bands(&data.bands)?;
+        let name = data.scheme_name.trim().to_string();
+        if name.is_empty() {
+            return Err(Error::Validation("scheme name is required".into()));
+        }
+        SubscriptionService
+            .ensure_writable(sdb, &record_key(&data.school_id))
+            .await?;
+
+        let latest = latest_versions(
+            GradingSchemeQ
+                .get_versions(sdb, record_key(&data.school_id))
+                .await?,
+        );
+        if let Some(level) = data.class_levels.iter().find(|level| {
+            latest
+                .iter()
+                .any(|v| v.scheme_name != name && v.class_levels.contains(level))
+        }) {
+            return Err(Error::Validation(format!(
+                "{level:?} already uses another grading scheme"
+            )));
+        }
+        let scheme = GradingSchemeQ
+            .create_version_with_bands(
+                sdb,
+                SaveGradingSchemeDto {
+                    scheme_name: name.clone(),
+                    ..data
+                },
+            )
+            .await?
+            .ok_or_else(|| Error::NotFound(format!("grading scheme {name}")))?;
+        let bands = match &scheme.id {
+            Some(id) => GradingSchemeQ.get_bands(sdb, record_key(id)).await?,
+            None => Vec::new(),
+        };
+
+        Ok(GradingSchemeWithBands { scheme, bands })
+    }
+
+    /// Latest version of each named scheme in a school
+    pub async fn current_schemes(
+        &self,
+        sdb: &Surreal<Client>,
+        school_id: String,
+    ) -> DomainResult<Vec<GradingSchemeVersion>> {
+        let versions = GradingSchemeQ.get_versions(sdb, school_id).await?;
+        Ok(latest_versions(versions))
+    }
+
+    /// Bands currently used for a class level, falling back to the school's
+    /// legacy bands when no named scheme covers it
+    pub async fn bands_for_level(
+        &self,
+        sdb: &Surreal<Client>,
+        school_id: String,
+        class_level: &ClassLevel,
+    ) -> DomainResult<(Option<GradingSchemeVersion>, Vec<GradingScheme>)> {
+        let current = self
+            .current_schemes(sdb, school_id.clone())
+            .await?
+            .into_iter()
+            .find(|v| v.class_levels.contains(class_level));
+        match current.as_ref().and_then(|v| v.id.as_ref()) {
+            Some(id) => {
+                let bands = GradingSchemeQ.get_bands(sdb, record_key(id)).await?;
+                Ok((current, bands))
+            }
+            None => Ok((None, GradingSchemeQ.get_by_school(sdb, school_id).await?)),
+        }
+    }
+
+    /// Bands a report card is graded with: its pinned version once published,
+    /// otherwise whatever currently applies to the class level
+    pub async fn bands_for_card(
+        &self,
+        sdb: &Surreal<Client>,
+        card: &ReportCard,
+        school_id: String,
+        class_level: &ClassLevel,
+    ) -> DomainResult<(Option<GradingSchemeVersion>, Vec<GradingScheme>)> {
+        if let Some(version_id) = &card.grading_scheme_version_id {
+            let version_id = record_key(version_id);
+            let version = GradingSchemeQ.get_version(sdb, version_id.clone()).await?;
+            let bands = GradingSchemeQ.get_bands(sdb, version_id).await?;
+            return Ok((version, bands));
+        }
+        self.bands_for_level(sdb, school_id, class_level).await
+    }
+
+    /// Pin the current scheme version to a report card that doesn't have one yet
+    pub async fn pin_to_card(
+        &self,
+        sdb: &Surreal<Client>,
+        card: &ReportCard,
+        school_id: String,
+        class_level: &ClassLevel,
+    ) -> DomainResult<()> {
+        let Some(card_id) = &card.id else {
+            return Ok(());
+        };
+        if card.grading_scheme_version_id.is_some() {
+            return Ok(());
+        }
+        let (version, _) = self.bands_for_level(sdb, school_id, class_level).await?;
+        if let Some(version_id) = version.and_then(|v| v.id) {
+            ReportCardQ
+                .set_grading_scheme(sdb, record_key(card_id), record_key(&version_id))
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+/// Keep only the newest version of each scheme name
+fn latest_versions(mut versions: Vec<GradingSchemeVersion>) -> Vec<GradingSchemeVersion> {
+    versions.sort_by(|a, b| {
+        a.scheme_name
+            .cmp(&b.scheme_name)
+            .then(b.version.cmp(&a.version))
+    });
+    versions.dedup_by(|later, first| later.scheme_name == first.scheme_name);
+    versions
+}
+
+/// Bands must run from 0 to 100 without overlapping or leaving gaps. Consecutive
+/// bands may be one point apart (e.g. 0-39 then 40-49), since a score between
+/// them is graded by the lower band.
+pub fn validate_bands(bands: &[GradeBandDto]) -> DomainResult<()> {
+    if bands.is_empty() {
+        return Err(Error::Validation("at least one band is required".into()));
+    }
+    let mut sorted: Vec<&GradeBandDto> = bands.iter().collect();
+    sorted.sort_by_key(|band| band.min_score);
+
+    for band in &sorted {
+        if band.grade.trim().is_empty() {
+            return Err(Error::Validation("every band needs a grade".into()));
+        }
+        if band.min_score > band.max_score {
+            return Err(Error::Validation(format!(
+                "band {} has min {} above max {}",
+                band.grade, band.min_score, band.max_score
+            )));
+        }
+    }
+    if sorted[0].min_score != Decimal::ZERO {
+        return Err(Error::Validation(format!(
+            "lowest band {} must start at 0",
+            sorted[0].grade
+        )));
+    }
+    if sorted[sorted.len() - 1].max_score != Decimal::from(100) {
+        return Err(Error::Validation(format!(
+            "highest band {} must end at 100",
+            sorted[sorted.len() - 1].grade
+        )));
+    }
+    for pair in sorted.windows(2) {
+        let (lower, upper) = (pair[0], pair[1]);
+        if upper.min_score <= lower.max_score {
+            return Err(Error::Validation(format!(
+                "bands {} and {} overlap",
+                lower.grade, upper.grade
+            )));
+        }
+        if upper.min_score - lower.max_score > Decimal::ONE {
+            return Err(Error::Validation(format!(
+                "gap between bands {} and {}",
+                lower.grade, upper.grade
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Pick the band a score falls into: the one with the highest `min_score` not
+/// above it, so scores between integer bands (e.g. 69.5) take the lower grade
+pub fn grade_for_score(bands: &[GradingScheme], score: Decimal) -> Option<&GradingScheme> {
+    let top = bands.iter().map(|band| band.max_score).max()?;
+    if score > top {
+        return None;
+    }
+    bands
+        .iter()
+        .filter(|band| band.min_score <= score)
+        .max_by_key(|band| band.min_score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn band(min: i64, max: i64, grade: &str) -> GradeBandDto {
+        GradeBandDto {
+            min_score: Decimal::from(min),
+            max_score: Decimal::from(max),
+            grade: grade.into(),
+            grade_point: Decimal::ZERO,
+            remark: None,
+        }
+    }
+
+    #[test]
+    fn accepts_contiguous_bands() {
+        let bands = [band(70, 100, "A"), band(0, 49, "F"), band(50, 69, "C")];
+        assert!(validate_bands(&bands).is_ok());
+    }
+
+    #[test]
+    fn rejects_gaps_and_overlaps() {
+        let gap = [band(0, 49, "F"), band(55, 100, "A")];
+        assert!(validate_bands(&gap).is_err());
+
+        let overlap = [band(0, 50, "F"), band(50, 100, "A")];
+        assert!(validate_bands(&overlap).is_err());
+
+        let short = [band(0, 49, "F"), band(50, 90, "A")];
+        assert!(validate_bands(&short).is_err());
+    }
+}
@@ -0,0 +1,88 @@
+use std::future::Future;
+
+use db::queries::IdempotencyQ;
+use serde::{Serialize, de::DeserializeOwned};
+use surrealdb::{Surreal, engine::remote::ws::Client};
+
+use crate::error::{DomainResult, Error};
+
+const MIN_KEY_LEN: usize = 8;
+const MAX_KEY_LEN: usize = 100;
+
+/// Runs a write at most once per client-chosen key, so a request replayed by
+/// an offline device returns the first attempt's response instead of writing again
+pub struct IdempotencyService;
+
+impl IdempotencyService {
+    /// Run `write` unless the user has already sent `key`, in which case the
+    /// stored response is returned. A failed write releases the key for a retry.
+    pub async fn run<T, F, Fut>(
+        &self,
+        sdb: &Surreal<Client>,
+        user_id: String,
+        key: String,
+        write: F,
+    ) -> DomainResult<T>
+    where
+        T: Serialize + DeserializeOwned,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = DomainResult<T>>,
+    {
+        validate_key(&key)?;
+        let id = format!("{user_id}_{key}");
+
+        if !IdempotencyQ.claim(sdb, id.clone(), user_id).await? {
+            let stored = IdempotencyQ
+                .get(sdb, id)
+                .await?
+                .and_then(|key| key.response)
+                .ok_or_else(|| {
+                    Error::Conflict("a request with this key is still being processed".into())
+                })?;
+            return serde_json::from_str(&stored)
+                .map_err(|e| Error::Validation(format!("unreadable stored response: {e}")));
+        }
+
+        match write().await {
+            Ok(response) => {
+                let stored = serde_json::to_string(&response)
+                    .map_err(|e| Error::Validation(format!("unstorable response: {e}")))?;
+                IdempotencyQ.complete(sdb, id, stored).await?;
+                Ok(response)
+            }
+            Err(err) => {
+                IdempotencyQ.release(sdb, id).await?;
+                Err(err)
+            }
+        }
+    }
+}
+
+/// Keys are UUIDs in practice; anything record-id safe of a sensible length is accepted
+fn validate_key(key: &str) -> DomainResult<()> {
+    let valid = (MIN_KEY_LEN..=MAX_KEY_LEN).contains(&key.len())
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'));
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::Validation(format!(
+            "idempotency keys must be {MIN_KEY_LEN}-{MAX_KEY_LEN} letters, digits, '-' or '_'"
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validates_keys() {
+        assert!(validate_key("3f0c2a9e-6d1b-4c55-9a43-0e8f7b2d1c6a").is_ok());
+        assert!(validate_key("short").is_err());
+        assert!(validate_key("has spaces in it").is_err());
+        assert!(validate_key("users:injection").is_err());
+        assert!(validate_key(&"a".repeat(101)).is_err());
+    }
+}
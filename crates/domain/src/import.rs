@@ -0,0 +1,569 @@
+use std::collections::{HashMap, HashSet};
+use std::io::Cursor;
+
+use calamine::{Data, DataType, Reader, Xlsx};
+use chrono::{NaiveDate, Utc};
+use db::queries::{ClassQ, ImportedParent, ParentQ, StudentQ, UserQ, auth::AuthQ};
+use shared::{
+    helpers::{
+        api_responses::{ImportRowError, SessionUser, StudentImportReport},
+        create_update_dtos::{CreateStudentDto, ImportFormat, StudentImportMapping},
+        surreal_util::{record_key, to_surreal_datetime},
+    },
+    models::{Gender, RelationshipType, User, UserStatus, UserType},
+};
+use surrealdb::{Surreal, engine::remote::ws::Client};
+use surrealdb_types::{Datetime, RecordId};
+
+use crate::{
+    auth::{ADMINS, ensure_role},
+    error::{DomainResult, Error},
+    subscription::SubscriptionService,
+};
+
+type Row = HashMap<String, String>;
+
+struct ParentRow {
+    first_name: String,
+    last_name: String,
+    phone_number: Option<String>,
+    email: Option<String>,
+    relationship_type: RelationshipType,
+}
+
+struct ValidRow {
+    row: usize,
+    student: CreateStudentDto,
+    parent: Option<ParentRow>,
+}
+
+pub struct StudentImportService;
+
+impl StudentImportService {
+    /// Validate a CSV/XLSX student list and, unless `dry_run` is set, create the
+    /// students, their parents and the `student_parents` links into the
+    /// admin's school
+    pub async fn import(
+        &self,
+        sdb: &Surreal<Client>,
+        admin: &SessionUser,
+        format: ImportFormat,
+        bytes: &[u8],
+        mapping: &StudentImportMapping,
+        dry_run: bool,
+    ) -> DomainResult<StudentImportReport> {
+        let school_id = admin.school_id.clone();
+        ensure_role(admin, &school_id, ADMINS)?;
+        let rows = match format {
+            ImportFormat::Csv => read_csv(bytes)?,
+            ImportFormat::Xlsx => read_xlsx(bytes)?,
+        };
+        check_headers(rows.first(), mapping)?;
+
+        let classes: HashMap<String, RecordId> = ClassQ
+            .get_by_school(sdb, school_id.clone())
+            .await?
+            .into_iter()
+            .filter_map(|c| Some((c.class_name.trim().to_lowercase(), c.id?)))
+            .collect();
+        let school = RecordId::new("schools", school_id.as_str());
+
+        let mut errors = Vec::new();
+        let mut valid = Vec::new();
+        let mut seen = HashSet::new();
+        for (index, row) in rows.iter().enumerate() {
+            // Header is row 1
+            let row_number = index + 2;
+            if let Some(parsed) =
+                validate_row(row_number, row, mapping, &school, &classes, &mut errors)
+            {
+                let admission_number = parsed.student.admission_number.clone();
+                // Admission numbers are unique regardless of case, as in the database check
+                if !seen.insert(admission_number.to_lowercase()) {
+                    errors.push(row_error(
+                        row_number,
+                        &mapping.admission_number,
+                        format!("Duplicate admission number {admission_number} in file"),
+                    ));
+                } else if AuthQ::admission_number_exists(
+                    sdb,
+                    school_id.clone(),
+                    admission_number.clone(),
+                )
+                .await?
+                {
+                    errors.push(row_error(
+                        row_number,
+                        &mapping.admission_number,
+                        format!("Admission number {admission_number} already exists"),
+                    ));
+                } else {
+                    valid.push(parsed);
+                }
+            }
+        }
+
+        let mut report = StudentImportReport {
+            dry_run,
+            total_rows: rows.len(),
+            valid_rows: valid.len(),
+            students_created: 0,
+            parents_created: 0,
+            errors,
+        };
+        // A dry run already tells the school when the file won't fit its plan
+        SubscriptionService
+            .ensure_room_for_students(sdb, &school_id, valid.len())
+            .await?;
+        if dry_run {
+            return Ok(report);
+        }
+        SubscriptionService.ensure_writable(sdb, &school_id).await?;
+
+        for row in valid {
+            if let Err(e) = self
+                .create_row(sdb, &school, row.student, row.parent, &mut report)
+                .await
+            {
+                report.errors.push(ImportRowError {
+                    row: row.row,
+                    column: None,
+                    message: e.to_string(),
+                });
+            }
+        }
+        report.errors.sort_by_key(|e| e.row);
+
+        Ok(report)
+    }
+
+    /// Create one row's student and parent link in a single transaction.
+    /// Siblings share a parent, so a parent account in the school with the
+    /// same phone or email is reused.
+    async fn create_row(
+        &self,
+        sdb: &Surreal<Client>,
+        school: &RecordId,
+        student: CreateStudentDto,
+        parent: Option<ParentRow>,
+        report: &mut StudentImportReport,
+    ) -> DomainResult<()> {
+        let parent = match parent {
+            Some(parent) => Some(self.row_parent(sdb, school, parent).await?),
+            None => None,
+        };
+        let creates_parent = matches!(
+            parent,
+            Some(ImportedParent::ForUser { .. } | ImportedParent::New { .. })
+        );
+
+        StudentQ
+            .create_with_parent(sdb, student, parent)
+            .await?
+            .ok_or_else(|| Error::NotFound("created student".into()))?;
+        report.students_created += 1;
+        if creates_parent {
+            report.parents_created += 1;
+        }
+        Ok(())
+    }
+
+    /// The parent on file for a row, or the account and profile to create.
+    /// Only parent accounts in the same school are matched, so a teacher
+    /// who shares a number never gets a parent profile.
+    async fn row_parent(
+        &self,
+        sdb: &Surreal<Client>,
+        school: &RecordId,
+        parent: ParentRow,
+    ) -> DomainResult<ImportedParent> {
+        let is_parent_here =
+            |user: &User| &user.school_id == school && user.user_type == UserType::Parent;
+        let mut user = None;
+        if let Some(phone) = &parent.phone_number {
+            user = UserQ
+                .find_by_phone(sdb, phone.clone())
+                .await?
+                .filter(is_parent_here);
+        }
+        if user.is_none()
+            && let Some(email) = &parent.email
+        {
+            user = UserQ
+                .find_by_email(sdb, email.clone())
+                .await?
+                .filter(is_parent_here);
+        }
+
+        let Some(user_id) = user.and_then(|u| u.id) else {
+            let now = Datetime::now();
+            return Ok(ImportedParent::New {
+                user: Box::new(User {
+                    id: None,
+                    school_id: school.clone(),
+                    user_type: UserType::Parent,
+                    first_name: parent.first_name,
+                    last_name: parent.last_name,
+                    email: parent.email,
+                    phone_number: parent.phone_number,
+                    // No password yet; the account stays inactive until the parent sets one
+                    password_hash: String::new(),
+                    status: UserStatus::Inactive,
+                    last_login: None,
+                    email_verified: false,
+                    phone_verified: false,
+                    locale: None,
+                    created_at: now,
+                    updated_at: now,
+                }),
+                relationship_type: parent.relationship_type,
+            });
+        };
+        Ok(
+            match ParentQ
+                .get_by_user(sdb, record_key(&user_id))
+                .await?
+                .and_then(|p| p.id)
+            {
+                Some(parent_id) => ImportedParent::Existing(parent_id),
+                None => ImportedParent::ForUser {
+                    user_id,
+                    relationship_type: parent.relationship_type,
+                },
+            },
+        )
+    }
+}
+
+fn read_csv(bytes: &[u8]) -> DomainResult<Vec<Row>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .flexible(true)
+        .from_reader(bytes);
+    let headers: Vec<String> = reader
+        .headers()
+        .map_err(|e| Error::Validation(format!("Unreadable CSV header: {e}")))?
+        .iter()
+        .map(str::to_string)
+        .collect();
+
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(|e| Error::Validation(format!("Unreadable CSV row: {e}")))?;
+        rows.push(
+            headers
+                .iter()
+                .cloned()
+                .zip(record.iter().map(str::to_string))
+                .collect(),
+        );
+    }
+    Ok(rows)
+}
+
+fn read_xlsx(bytes: &[u8]) -> DomainResult<Vec<Row>> {
+    let mut workbook: Xlsx<_> = Xlsx::new(Cursor::new(bytes))
+        .map_err(|e| Error::Validation(format!("Unreadable XLSX file: {e}")))?;
+    let range = workbook
+        .worksheet_range_at(0)
+        .ok_or_else(|| Error::Validation("XLSX file has no worksheets".into()))?
+        .map_err(|e| Error::Validation(format!("Unreadable XLSX sheet: {e}")))?;
+
+    let mut sheet_rows = range.rows();
+    let headers: Vec<String> = match sheet_rows.next() {
+        Some(header) => header
+            .iter()
+            .map(|c| c.to_string().trim().to_string())
+            .collect(),
+        None => return Ok(Vec::new()),
+    };
+
+    Ok(sheet_rows
+        .filter(|cells| cells.iter().any(|c| !c.is_empty()))
+        .map(|cells| {
+            headers
+                .iter()
+                .cloned()
+                .zip(cells.iter().map(cell_text))
+                .collect()
+        })
+        .collect())
+}
+
+fn cell_text(cell: &Data) -> String {
+    match cell {
+        Data::DateTime(_) => cell
+            .as_date()
+            .map(|d| d.format("%Y-%m-%d").to_string())
+            .unwrap_or_default(),
+        // Whole numbers typed into a sheet (e.g. admission numbers) come back as floats
+        Data::Float(f) if f.fract() == 0.0 => format!("{}", *f as i64),
+        other => other.to_string().trim().to_string(),
+    }
+}
+
+fn check_headers(first: Option<&Row>, mapping: &StudentImportMapping) -> DomainResult<()> {
+    let Some(first) = first else {
+        return Ok(());
+    };
+    let required = [
+        &mapping.admission_number,
+        &mapping.first_name,
+        &mapping.last_name,
+        &mapping.date_of_birth,
+        &mapping.gender,
+    ];
+    let missing: Vec<&str> = required
+        .into_iter()
+        .filter(|column| !first.contains_key(column.as_str()))
+        .map(String::as_str)
+        .collect();
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::Validation(format!(
+            "Missing columns: {}",
+            missing.join(", ")
+        )))
+    }
+}
+
+fn validate_row(
+    row_number: usize,
+    row: &Row,
+    mapping: &StudentImportMapping,
+    school: &RecordId,
+    classes: &HashMap<String, RecordId>,
+    errors: &mut Vec<ImportRowError>,
+) -> Option<ValidRow> {
+    let before = errors.len();
+    let get = |column: &Option<String>| {
+        column
+            .as_ref()
+            .and_then(|c| row.get(c))
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+    };
+    let mut required = |column: &String| match row.get(column).map(|v| v.trim()) {
+        Some(value) if !value.is_empty() => value.to_string(),
+        _ => {
+            errors.push(row_error(
+                row_number,
+                column,
+                "Required value is missing".into(),
+            ));
+            String::new()
+        }
+    };
+
+    let admission_number = required(&mapping.admission_number);
+    let first_name = required(&mapping.first_name);
+    let last_name = required(&mapping.last_name);
+    let date_of_birth = required(&mapping.date_of_birth);
+    let gender = required(&mapping.gender);
+
+    let date_of_birth = if date_of_birth.is_empty() {
+        None
+    } else {
+        match parse_date(&date_of_birth) {
+            Some(date) if date < Utc::now().date_naive() => Some(date),
+            Some(_) => {
+                errors.push(row_error(
+                    row_number,
+                    &mapping.date_of_birth,
+                    "Date of birth is in the future".into(),
+                ));
+                None
+            }
+            None => {
+                errors.push(row_error(
+                    row_number,
+                    &mapping.date_of_birth,
+                    format!("Invalid date {date_of_birth}, expected YYYY-MM-DD or DD/MM/YYYY"),
+                ));
+                None
+            }
+        }
+    };
+
+    let gender = if gender.is_empty() {
+        None
+    } else {
+        let parsed = parse_gender(&gender);
+        if parsed.is_none() {
+            errors.push(row_error(
+                row_number,
+                &mapping.gender,
+                format!("Invalid gender {gender}, expected male or female"),
+            ));
+        }
+        parsed
+    };
+
+    let current_class_id = match get(&mapping.class_name) {
+        Some(name) => {
+            let class = classes.get(&name.to_lowercase()).cloned();
+            if class.is_none() {
+                errors.push(row_error(
+                    row_number,
+                    mapping.class_name.as_deref().unwrap_or_default(),
+                    format!("Unknown class {name}"),
+                ));
+            }
+            class
+        }
+        None => None,
+    };
+
+    let parent_first_name = get(&mapping.parent_first_name);
+    let parent_last_name = get(&mapping.parent_last_name);
+    let parent_phone = get(&mapping.parent_phone);
+    let parent_email = get(&mapping.parent_email);
+    let parent_relationship = get(&mapping.parent_relationship);
+    let has_parent = parent_first_name.is_some()
+        || parent_last_name.is_some()
+        || parent_phone.is_some()
+        || parent_email.is_some();
+
+    let parent = if has_parent {
+        let mut parent_ok = true;
+        if parent_first_name.is_none() || parent_last_name.is_none() {
+            errors.push(ImportRowError {
+                row: row_number,
+                column: None,
+                message: "Parent first and last name are required".into(),
+            });
+            parent_ok = false;
+        }
+        if parent_phone.is_none() && parent_email.is_none() {
+            errors.push(ImportRowError {
+                row: row_number,
+                column: None,
+                message: "Parent needs a phone number or an email".into(),
+            });
+            parent_ok = false;
+        }
+        if let Some(email) = &parent_email
+            && !email.contains('@')
+        {
+            errors.push(row_error(
+                row_number,
+                mapping.parent_email.as_deref().unwrap_or_default(),
+                format!("Invalid email {email}"),
+            ));
+            parent_ok = false;
+        }
+        let relationship_type = match parent_relationship.as_deref() {
+            None => Some(RelationshipType::Guardian),
+            Some(value) => {
+                let parsed = parse_relationship(value);
+                if parsed.is_none() {
+                    errors.push(row_error(
+                        row_number,
+                        mapping.parent_relationship.as_deref().unwrap_or_default(),
+                        format!("Invalid relationship {value}"),
+                    ));
+                }
+                parsed
+            }
+        };
+        match (parent_ok, relationship_type) {
+            (true, Some(relationship_type)) => Some(ParentRow {
+                first_name: parent_first_name.unwrap_or_default(),
+                last_name: parent_last_name.unwrap_or_default(),
+                phone_number: parent_phone,
+                email: parent_email,
+                relationship_type,
+            }),
+            _ => None,
+        }
+    } else {
+        None
+    };
+
+    if errors.len() > before {
+        return None;
+    }
+
+    Some(ValidRow {
+        row: row_number,
+        student: CreateStudentDto {
+            school_id: school.clone(),
+            admission_number,
+            first_name,
+            middle_name: get(&mapping.middle_name),
+            last_name,
+            date_of_birth: to_surreal_datetime(date_of_birth?),
+            gender: gender?,
+            address: get(&mapping.address),
+            current_class_id,
+        },
+        parent,
+    })
+}
+
+fn row_error(row: usize, column: &str, message: String) -> ImportRowError {
+    ImportRowError {
+        row,
+        column: Some(column.to_string()),
+        message,
+    }
+}
+
+fn parse_date(value: &str) -> Option<NaiveDate> {
+    ["%Y-%m-%d", "%d/%m/%Y", "%d-%m-%Y"]
+        .iter()
+        .find_map(|format| NaiveDate::parse_from_str(value, format).ok())
+}
+
+fn parse_gender(value: &str) -> Option<Gender> {
+    match value.to_lowercase().as_str() {
+        "m" | "male" => Some(Gender::Male),
+        "f" | "female" => Some(Gender::Female),
+        _ => None,
+    }
+}
+
+fn parse_relationship(value: &str) -> Option<RelationshipType> {
+    match value.to_lowercase().as_str() {
+        "father" => Some(RelationshipType::Father),
+        "mother" => Some(RelationshipType::Mother),
+        "guardian" => Some(RelationshipType::Guardian),
+        "other" => Some(RelationshipType::Other),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_supported_date_formats() {
+        let expected = NaiveDate::from_ymd_opt(2015, 3, 9);
+        assert_eq!(parse_date("2015-03-09"), expected);
+        assert_eq!(parse_date("09/03/2015"), expected);
+        assert_eq!(parse_date("09-03-2015"), expected);
+        assert_eq!(parse_date("March 9"), None);
+    }
+
+    #[test]
+    fn reports_each_invalid_field() {
+        let csv = "admission_number,first_name,last_name,date_of_birth,gender\n\
+                   2024/001,Ada,Obi,2015-03-09,F\n\
+                   2024/002,,Bello,31/02/2015,x\n";
+        let rows = read_csv(csv.as_bytes()).unwrap();
+        let mapping = StudentImportMapping::default();
+        let school = RecordId::new("schools", "demo");
+        let mut errors = Vec::new();
+
+        let first = validate_row(2, &rows[0], &mapping, &school, &HashMap::new(), &mut errors);
+        assert!(first.is_some());
+        assert!(errors.is_empty());
+
+        let second = validate_row(3, &rows[1], &mapping, &school, &HashMap::new(), &mut errors);
+        assert!(second.is_none());
+        let columns: Vec<_> = errors.iter().filter_map(|e| e.column.as_deref()).collect();
+        assert_eq!(columns, ["first_name", "date_of_birth", "gender"]);
+    }
+}
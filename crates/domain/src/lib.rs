@@ -8,6 +8,36 @@
 // but WITHOUT ANY WARRANTY; without even the implied warranty of
 // MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
 
+pub mod announcement;
+pub mod auth;
+pub mod billing;
+pub mod calendar;
+pub mod conversation;
+pub mod delivery;
+pub mod email;
+pub mod error;
+pub mod event;
+pub mod export;
+pub mod grading;
+pub mod idempotency;
+pub mod import;
+pub mod live;
+pub mod locale;
+pub mod parent_portal;
+pub mod pdf;
+pub mod push;
+pub mod ranking;
+pub mod report_card;
+pub mod scoring;
+pub mod storage;
+pub mod subscription;
+pub mod sync;
+pub mod templating;
+pub mod transcript;
+pub mod ussd;
+pub mod weighting;
+pub mod whatsapp;
+
 pub fn add(left: u64, right: u64) -> u64 {
     left + right
 }
@@ -0,0 +1,245 @@
+use std::sync::OnceLock;
+
+use db::queries::{LiveStream, MessageQ, NotificationQ};
+use serde::Serialize;
+use shared::{
+    helpers::{api_responses::SessionUser, surreal_util::record_key},
+    models::{communication::Message, system_and_audit::Notification},
+};
+use surrealdb::{Surreal, engine::remote::ws::Client};
+use surrealdb_types::{Action, Datetime};
+use tokio::sync::broadcast;
+
+use crate::error::DomainResult;
+
+/// Signals a slow connection can fall behind by before it misses some
+const SIGNAL_BUFFER: usize = 256;
+
+static SIGNALS: OnceLock<broadcast::Sender<Signal>> = OnceLock::new();
+
+/// Something a signed-in user should see as soon as it happens
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+pub enum LiveEvent {
+    Notification(Notification),
+    Message(Message),
+    /// The other side of a conversation is typing
+    Typing(ConversationSignal),
+    /// The other side of a conversation has read what they were sent
+    Read(ConversationSignal),
+}
+
+/// Something a conversation participant did, for the others to see
+#[derive(Debug, Clone, Serialize)]
+pub struct ConversationSignal {
+    pub conversation_id: String,
+    pub user_id: String,
+    pub at: Datetime,
+}
+
+/// A live event for one user that isn't stored anywhere. Signals only reach
+/// users connected to the server that raised them, and aren't replayed.
+#[derive(Debug, Clone)]
+pub struct Signal {
+    pub recipient_id: String,
+    pub event: LiveEvent,
+}
+
+impl LiveEvent {
+    /// The record's id, e.g. "notifications:abc"; a reconnecting client sends
+    /// back the last one it saw. Signals have none.
+    pub fn id(&self) -> String {
+        let id = match self {
+            LiveEvent::Notification(n) => n.id.as_ref(),
+            LiveEvent::Message(m) => m.id.as_ref(),
+            LiveEvent::Typing(_) | LiveEvent::Read(_) => None,
+        };
+        id.map(|id| format!("{}:{}", id.table, record_key(id)))
+            .unwrap_or_default()
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LiveEvent::Notification(_) => "notification",
+            LiveEvent::Message(_) => "message",
+            LiveEvent::Typing(_) => "typing",
+            LiveEvent::Read(_) => "read",
+        }
+    }
+
+    fn sent_at(&self) -> Datetime {
+        match self {
+            LiveEvent::Notification(n) => n.sent_at,
+            LiveEvent::Message(m) => m.sent_at,
+            LiveEvent::Typing(s) | LiveEvent::Read(s) => s.at,
+        }
+    }
+}
+
+/// Records created for a user, pushed to their open connections
+pub struct LiveService;
+
+impl LiveService {
+    /// Start watching for the user's new notifications and the messages they
+    /// receive in their school. Subscribe before asking for [`Self::missed`]
+    /// so nothing is lost in between.
+    pub async fn subscribe(
+        &self,
+        sdb: &Surreal<Client>,
+        user: &SessionUser,
+    ) -> DomainResult<(LiveStream<Notification>, LiveStream<Message>)> {
+        let notifications = NotificationQ.live(sdb, user.user_id.clone()).await?;
+        let messages = MessageQ
+            .live_received(sdb, user.school_id.clone(), user.user_id.clone())
+            .await?;
+        Ok((notifications, messages))
+    }
+
+    /// What the user was sent after the event a reconnecting client saw last,
+    /// oldest first. A fresh connection, or an id that isn't the user's,
+    /// has nothing to catch up on.
+    pub async fn missed(
+        &self,
+        sdb: &Surreal<Client>,
+        user: &SessionUser,
+        last_event_id: Option<String>,
+    ) -> DomainResult<Vec<LiveEvent>> {
+        let Some(last_event_id) = last_event_id else {
+            return Ok(Vec::new());
+        };
+        let since = match last_event_id.split_once(':') {
+            Some(("notifications", key)) => NotificationQ
+                .get_by_id(sdb, key.to_string())
+                .await?
+                .filter(|n| record_key(&n.user_id) == user.user_id)
+                .map(|n| n.sent_at),
+            Some(("messages", key)) => MessageQ
+                .get_by_id(sdb, key.to_string())
+                .await?
+                .filter(|m| {
+                    record_key(&m.recipient_id) == user.user_id
+                        && record_key(&m.school_id) == user.school_id
+                })
+                .map(|m| m.sent_at),
+            _ => None,
+        };
+        let Some(since) = since else {
+            return Ok(Vec::new());
+        };
+
+        let notifications = NotificationQ
+            .get_since(sdb, user.user_id.clone(), since)
+            .await?;
+        let messages = MessageQ
+            .get_received_since(sdb, user.school_id.clone(), user.user_id.clone(), since)
+            .await?;
+        Ok(merge_missed(notifications, messages, &last_event_id))
+    }
+
+    /// Start hearing signals raised on this server; callers keep the ones
+    /// addressed to their user
+    pub fn signals(&self) -> broadcast::Receiver<Signal> {
+        signal_sender().subscribe()
+    }
+}
+
+/// Pass a signal to whoever is connected. Nobody listening is fine.
+pub fn send_signal(recipient_id: String, event: LiveEvent) {
+    let _ = signal_sender().send(Signal {
+        recipient_id,
+        event,
+    });
+}
+
+fn signal_sender() -> &'static broadcast::Sender<Signal> {
+    SIGNALS.get_or_init(|| broadcast::channel(SIGNAL_BUFFER).0)
+}
+
+/// The record a live query reports, if the change created it
+pub fn created<T>(change: surrealdb::Result<surrealdb::Notification<T>>) -> Option<T> {
+    change
+        .ok()
+        .filter(|change| change.action == Action::Create)
+        .map(|change| change.data)
+}
+
+/// Notifications and messages in the order they were sent, without the one
+/// the client already has
+fn merge_missed(
+    notifications: Vec<Notification>,
+    messages: Vec<Message>,
+    last_event_id: &str,
+) -> Vec<LiveEvent> {
+    let mut events: Vec<LiveEvent> = notifications
+        .into_iter()
+        .map(LiveEvent::Notification)
+        .chain(messages.into_iter().map(LiveEvent::Message))
+        .filter(|event| event.id() != last_event_id)
+        .collect();
+    events.sort_by_key(LiveEvent::sent_at);
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+    use shared::models::NotificationType;
+    use surrealdb_types::RecordId;
+
+    fn at(minute: u32) -> Datetime {
+        Datetime::from(Utc.with_ymd_and_hms(2026, 3, 2, 9, minute, 0).unwrap())
+    }
+
+    fn notification(key: &str, minute: u32) -> Notification {
+        Notification {
+            id: Some(RecordId::new("notifications", key)),
+            user_id: RecordId::new("users", "u1"),
+            notification_type: NotificationType::Announcement,
+            title: "Mid-term break".into(),
+            message: "School closes on Friday.".into(),
+            link_url: None,
+            read: false,
+            read_at: None,
+            sent_at: at(minute),
+            created_at: at(minute),
+        }
+    }
+
+    fn message(key: &str, minute: u32) -> Message {
+        Message {
+            id: Some(RecordId::new("messages", key)),
+            school_id: RecordId::new("schools", "s1"),
+            sender_id: RecordId::new("users", "t1"),
+            recipient_id: RecordId::new("users", "u1"),
+            subject: None,
+            message_body: "Please check the homework diary.".into(),
+            read: false,
+            read_at: None,
+            replied: false,
+            parent_message_id: None,
+            conversation_id: None,
+            hidden_by: None,
+            hidden_at: None,
+            sent_at: at(minute),
+            created_at: at(minute),
+        }
+    }
+
+    #[test]
+    fn replays_missed_events_in_order() {
+        let events = merge_missed(
+            vec![notification("n1", 0), notification("n2", 7)],
+            vec![message("m1", 3)],
+            "notifications:n1",
+        );
+        let ids: Vec<String> = events.iter().map(LiveEvent::id).collect();
+        assert_eq!(ids, ["messages:m1", "notifications:n2"]);
+        assert_eq!(events[0].name(), "message");
+
+        let json = serde_json::to_value(&events[1]).unwrap();
+        assert_eq!(json["type"], "notification");
+        assert_eq!(json["data"]["title"], "Mid-term break");
+    }
+}
@@ -0,0 +1,179 @@
+use std::str::FromStr;
+
+use db::queries::{SchoolSettingQ, UserQ};
+use shared::{
+    helpers::surreal_util::record_key,
+    i18n::{Currency, Locale, Localizer, Translations},
+    models::{SettingType, User},
+};
+use surrealdb::{Surreal, engine::remote::ws::Client};
+
+use crate::error::{DomainResult, Error};
+
+/// School setting holding the default language code, e.g. "fr"
+pub const LOCALE_SETTING: &str = "locale";
+/// School setting holding the billing currency code, e.g. "GHS"
+pub const CURRENCY_SETTING: &str = "currency";
+
+/// Which language and currency people see: a user's own choice of language
+/// first, then the school's default
+pub struct LocaleService;
+
+impl LocaleService {
+    /// The school's default language and billing currency
+    pub async fn school_defaults(
+        &self,
+        sdb: &Surreal<Client>,
+        school_id: String,
+    ) -> DomainResult<(Locale, Currency)> {
+        let locale = SchoolSettingQ
+            .get(sdb, school_id.clone(), LOCALE_SETTING.into())
+            .await?;
+        let currency = SchoolSettingQ
+            .get(sdb, school_id, CURRENCY_SETTING.into())
+            .await?;
+        Ok((setting_or_default(locale), setting_or_default(currency)))
+    }
+
+    pub async fn set_school_defaults(
+        &self,
+        sdb: &Surreal<Client>,
+        school_id: String,
+        locale: Locale,
+        currency: Currency,
+    ) -> DomainResult<()> {
+        SchoolSettingQ
+            .upsert(
+                sdb,
+                school_id.clone(),
+                LOCALE_SETTING.into(),
+                locale.code().into(),
+                SettingType::Text,
+            )
+            .await?;
+        SchoolSettingQ
+            .upsert(
+                sdb,
+                school_id,
+                CURRENCY_SETTING.into(),
+                currency.code().into(),
+                SettingType::Text,
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Formatting for what a signed-in user sees
+    pub async fn for_user(&self, sdb: &Surreal<Client>, user: &User) -> DomainResult<Localizer> {
+        let (school_locale, currency) = self
+            .school_defaults(sdb, record_key(&user.school_id))
+            .await?;
+        Ok(Localizer::new(
+            Translations::global(),
+            user.locale.unwrap_or(school_locale),
+            currency,
+        ))
+    }
+
+    /// Formatting for documents and messages in the school's default language,
+    /// e.g. SMS to parents who have no account
+    pub async fn for_school(
+        &self,
+        sdb: &Surreal<Client>,
+        school_id: String,
+    ) -> DomainResult<Localizer> {
+        let (locale, currency) = self.school_defaults(sdb, school_id).await?;
+        Ok(Localizer::new(Translations::global(), locale, currency))
+    }
+
+    /// Set or clear (follow the school) a user's language
+    pub async fn set_user_locale(
+        &self,
+        sdb: &Surreal<Client>,
+        user_id: String,
+        locale: Option<Locale>,
+    ) -> DomainResult<()> {
+        UserQ
+            .update_locale(sdb, user_id.clone(), locale)
+            .await?
+            .ok_or_else(|| Error::NotFound(format!("user {user_id}")))?;
+        Ok(())
+    }
+}
+
+/// A stored setting, or the default when it is unset or unrecognised
+fn setting_or_default<T: FromStr + Default>(value: Option<String>) -> T {
+    value.and_then(|v| v.parse().ok()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use surrealdb_types::Decimal;
+
+    fn localizer(locale: Locale, currency: Currency) -> Localizer {
+        Localizer::new(Translations::bundled(), locale, currency)
+    }
+
+    #[test]
+    fn reads_school_settings() {
+        assert_eq!(
+            setting_or_default::<Locale>(Some("fr-CI".into())),
+            Locale::Fr
+        );
+        assert_eq!(setting_or_default::<Locale>(Some("de".into())), Locale::En);
+        assert_eq!(
+            setting_or_default::<Currency>(Some("kes".into())),
+            Currency::Kes
+        );
+        assert_eq!(setting_or_default::<Currency>(None), Currency::Ngn);
+    }
+
+    #[test]
+    fn formats_money_per_locale() {
+        let amount = Decimal::new(5_000_050, 2);
+        assert_eq!(
+            localizer(Locale::En, Currency::Ngn).money(amount),
+            "₦50,000.50"
+        );
+        assert_eq!(
+            localizer(Locale::Fr, Currency::Ghs).money(amount),
+            "50\u{202f}000,50\u{a0}GH₵"
+        );
+        assert_eq!(
+            localizer(Locale::Ha, Currency::Kes).money(Decimal::new(-1_234_567, 0)),
+            "-KSh1,234,567.00"
+        );
+        assert_eq!(
+            localizer(Locale::En, Currency::Ngn).money(Decimal::from(999)),
+            "₦999.00"
+        );
+    }
+
+    #[test]
+    fn formats_dates_and_falls_back_to_english() {
+        let day = NaiveDate::from_ymd_opt(2026, 3, 2).unwrap();
+        assert_eq!(
+            localizer(Locale::En, Currency::Ngn).date(day),
+            "2 March 2026"
+        );
+        assert_eq!(
+            localizer(Locale::Fr, Currency::Ngn).date(day),
+            "2 mars 2026"
+        );
+        assert_eq!(
+            localizer(Locale::Ha, Currency::Ngn).date(day),
+            "2 Maris 2026"
+        );
+
+        let yoruba = localizer(Locale::Yo, Currency::Ngn);
+        assert_eq!(yoruba.t("nav.sign_out"), "Jáde");
+        assert_eq!(yoruba.t("transcript.grade"), "Grade");
+        assert_eq!(yoruba.t("no.such.key"), "no.such.key");
+        assert_eq!(
+            yoruba.format("transcript.term", &[("number", "2")]),
+            "Term 2"
+        );
+    }
+}
@@ -0,0 +1,362 @@
+use db::queries::{
+    AnnouncementQ, AttendanceQ, ClassQ, InvoiceQ, MessageQ, ParentQ, ReportCardQ, SchoolSettingQ,
+    TermQ, UserQ,
+};
+use shared::{
+    helpers::{
+        api_responses::{
+            AttendanceTally, ChildOverview, Contact, OutstandingInvoice, PublishedReportCard,
+        },
+        surreal_util::record_key,
+    },
+    models::{
+        AttendanceStatus, InvoiceStatus, Student, TargetAudience,
+        attendance::Attendance,
+        communication::{Announcement, Message},
+        fee_management::Invoice,
+    },
+};
+use surrealdb::{Surreal, engine::remote::ws::Client};
+use surrealdb_types::{Decimal, RecordId, SurrealValue};
+
+use crate::{
+    conversation::{ConversationService, message_body},
+    error::{DomainResult, Error},
+    subscription::{Feature, SubscriptionService},
+};
+
+/// School setting holding the online payment page invoices are paid through
+pub const PAYMENT_LINK_SETTING: &str = "payment_link";
+const RECENT_ATTENDANCE_DAYS: u32 = 30;
+
+/// What a signed-in parent may see and do for the children linked to them
+pub struct ParentPortalService;
+
+impl ParentPortalService {
+    /// Children linked to a parent's user account
+    pub async fn children(
+        &self,
+        sdb: &Surreal<Client>,
+        user_id: String,
+    ) -> DomainResult<Vec<Student>> {
+        let Some(parent_id) = ParentQ
+            .get_by_user(sdb, user_id)
+            .await?
+            .and_then(|parent| parent.id)
+        else {
+            return Ok(Vec::new());
+        };
+        let Some(row) = ParentQ.get_children(sdb, record_key(&parent_id)).await? else {
+            return Ok(Vec::new());
+        };
+        Vec::<Student>::from_value(row.get("children").clone())
+            .map_err(|e| Error::Validation(format!("unreadable children: {e}")))
+    }
+
+    /// A child's attendance, published results and unpaid fees
+    pub async fn child_overview(
+        &self,
+        sdb: &Surreal<Client>,
+        user_id: String,
+        student_id: String,
+    ) -> DomainResult<ChildOverview> {
+        let student = self.child(sdb, user_id, &student_id).await?;
+        let school_id = record_key(&student.school_id);
+
+        let class = match &student.current_class_id {
+            Some(class_id) => ClassQ.get_by_id(sdb, record_key(class_id)).await?,
+            None => None,
+        };
+        let class_teacher = match class.as_ref().and_then(|c| c.class_teacher_id.as_ref()) {
+            Some(teacher_id) => UserQ
+                .get_by_id(sdb, record_key(teacher_id))
+                .await?
+                .and_then(|teacher| {
+                    Some(Contact {
+                        user_id: record_key(teacher.id.as_ref()?),
+                        name: format!("{} {}", teacher.first_name, teacher.last_name),
+                    })
+                }),
+            None => None,
+        };
+
+        let recent_attendance = AttendanceQ
+            .get_history(sdb, student_id.clone(), Some(RECENT_ATTENDANCE_DAYS))
+            .await?;
+
+        let mut report_cards = Vec::new();
+        for card in ReportCardQ.get_by_student(sdb, student_id.clone()).await? {
+            if !card.published {
+                continue;
+            }
+            let term_name = TermQ
+                .get_by_id(sdb, record_key(&card.term_id))
+                .await?
+                .map(|term| {
+                    term.term_name
+                        .unwrap_or_else(|| format!("Term {}", term.term_number))
+                })
+                .unwrap_or_default();
+            report_cards.push(PublishedReportCard { term_name, card });
+        }
+
+        // Pay links only on plans with online payments
+        let payment_link = if SubscriptionService
+            .has_feature(sdb, &school_id, Feature::OnlinePayments)
+            .await?
+        {
+            SchoolSettingQ
+                .get(sdb, school_id, PAYMENT_LINK_SETTING.into())
+                .await?
+                .filter(|link| !link.trim().is_empty())
+        } else {
+            None
+        };
+        let outstanding_invoices = InvoiceQ
+            .get_by_student(sdb, student_id)
+            .await?
+            .into_iter()
+            .filter(is_outstanding)
+            .map(|invoice| OutstandingInvoice {
+                pay_url: payment_link.as_deref().map(|link| pay_url(link, &invoice)),
+                invoice,
+            })
+            .collect();
+
+        Ok(ChildOverview {
+            attendance: tally_attendance(&recent_attendance),
+            class_name: class.map(|c| c.class_name),
+            class_teacher,
+            recent_attendance,
+            report_cards,
+            outstanding_invoices,
+            student,
+        })
+    }
+
+    /// Published announcements meant for parents, everyone, or one of the
+    /// parent's children's classes
+    pub async fn announcements(
+        &self,
+        sdb: &Surreal<Client>,
+        user_id: String,
+        school_id: String,
+    ) -> DomainResult<Vec<Announcement>> {
+        let class_ids: Vec<RecordId> = self
+            .children(sdb, user_id)
+            .await?
+            .into_iter()
+            .filter_map(|child| child.current_class_id)
+            .collect();
+        Ok(AnnouncementQ
+            .get_published(sdb, school_id, Some(20))
+            .await?
+            .into_iter()
+            .filter(|announcement| addressed_to_parent(announcement, &class_ids))
+            .collect())
+    }
+
+    /// Messages between the parent and a child's class teacher, oldest first.
+    /// The teacher's messages are marked read as they are returned.
+    pub async fn teacher_thread(
+        &self,
+        sdb: &Surreal<Client>,
+        user_id: String,
+        student_id: String,
+    ) -> DomainResult<Vec<Message>> {
+        let student = self.child(sdb, user_id.clone(), &student_id).await?;
+        let teacher = self.class_teacher(sdb, &student).await?;
+        let mut messages = MessageQ
+            .get_thread(sdb, user_id, record_key(&teacher))
+            .await?;
+        for message in messages
+            .iter_mut()
+            .filter(|m| m.sender_id == teacher && !m.read)
+        {
+            if let Some(id) = &message.id {
+                MessageQ.mark_read(sdb, record_key(id)).await?;
+                message.read = true;
+            }
+        }
+        Ok(messages)
+    }
+
+    /// Send a message to a child's class teacher, in the conversation about
+    /// that child
+    pub async fn message_teacher(
+        &self,
+        sdb: &Surreal<Client>,
+        user_id: String,
+        student_id: String,
+        body: String,
+    ) -> DomainResult<Message> {
+        let body = message_body(&body)?;
+        let student = self.child(sdb, user_id.clone(), &student_id).await?;
+        let teacher = self.class_teacher(sdb, &student).await?;
+        let parent = RecordId::new("users", user_id.as_str());
+
+        let conversation = ConversationService
+            .open(
+                sdb,
+                student.school_id.clone(),
+                [parent.clone(), teacher],
+                student.id.clone(),
+                Some(format!("About {}", student.first_name)),
+            )
+            .await?;
+        ConversationService
+            .post(sdb, &conversation, parent, body, Vec::new())
+            .await
+    }
+
+    /// The student, provided they are linked to the parent
+    async fn child(
+        &self,
+        sdb: &Surreal<Client>,
+        user_id: String,
+        student_id: &str,
+    ) -> DomainResult<Student> {
+        self.children(sdb, user_id)
+            .await?
+            .into_iter()
+            .find(|child| child.id.as_ref().map(record_key).as_deref() == Some(student_id))
+            .ok_or_else(|| Error::Forbidden("not one of your children".into()))
+    }
+
+    async fn class_teacher(
+        &self,
+        sdb: &Surreal<Client>,
+        student: &Student,
+    ) -> DomainResult<RecordId> {
+        let class_id = student
+            .current_class_id
+            .as_ref()
+            .ok_or_else(|| Error::NotFound("class for this child".into()))?;
+        ClassQ
+            .get_by_id(sdb, record_key(class_id))
+            .await?
+            .and_then(|class| class.class_teacher_id)
+            .ok_or_else(|| Error::NotFound("class teacher for this child".into()))
+    }
+}
+
+/// Count attendance marks by status
+pub fn tally_attendance(records: &[Attendance]) -> AttendanceTally {
+    let mut tally = AttendanceTally::default();
+    for record in records {
+        match record.status {
+            AttendanceStatus::Present => tally.present += 1,
+            AttendanceStatus::Absent => tally.absent += 1,
+            AttendanceStatus::Late => tally.late += 1,
+            AttendanceStatus::Excused => tally.excused += 1,
+        }
+    }
+    tally
+}
+
+pub(crate) fn is_outstanding(invoice: &Invoice) -> bool {
+    invoice.balance > Decimal::ZERO
+        && !matches!(
+            invoice.status,
+            InvoiceStatus::Paid | InvoiceStatus::Cancelled
+        )
+}
+
+fn addressed_to_parent(announcement: &Announcement, class_ids: &[RecordId]) -> bool {
+    match announcement.target_audience {
+        TargetAudience::All | TargetAudience::Parents => true,
+        TargetAudience::SpecificClass => announcement
+            .class_id
+            .as_ref()
+            .is_some_and(|class_id| class_ids.contains(class_id)),
+        TargetAudience::Teachers | TargetAudience::Students => false,
+    }
+}
+
+/// The school's payment page with the invoice number and balance filled in
+fn pay_url(link: &str, invoice: &Invoice) -> String {
+    let separator = if link.contains('?') { '&' } else { '?' };
+    let reference: String = invoice
+        .invoice_number
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        .collect();
+    format!(
+        "{link}{separator}reference={reference}&amount={}",
+        invoice.balance.normalize()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use surrealdb_types::{Datetime, Value};
+
+    fn announcement(target_audience: TargetAudience, class: Option<&str>) -> Announcement {
+        Announcement {
+            id: None,
+            school_id: RecordId::new("schools", "s1"),
+            title: "Sports day".into(),
+            message: "Bring kits".into(),
+            target_audience,
+            class_id: class.map(|c| RecordId::new("classes", c)),
+            priority: shared::models::Priority::Normal,
+            send_sms: false,
+            send_email: false,
+            send_push: false,
+            published: true,
+            published_at: None,
+            created_by: None,
+            created_at: Datetime::now(),
+            updated_at: Datetime::now(),
+        }
+    }
+
+    #[test]
+    fn filters_announcements_by_audience() {
+        let classes = [RecordId::new("classes", "jss1")];
+        assert!(addressed_to_parent(
+            &announcement(TargetAudience::Parents, None),
+            &classes
+        ));
+        assert!(addressed_to_parent(
+            &announcement(TargetAudience::SpecificClass, Some("jss1")),
+            &classes
+        ));
+        assert!(!addressed_to_parent(
+            &announcement(TargetAudience::SpecificClass, Some("jss2")),
+            &classes
+        ));
+        assert!(!addressed_to_parent(
+            &announcement(TargetAudience::Teachers, None),
+            &classes
+        ));
+    }
+
+    #[test]
+    fn builds_payment_url() {
+        let invoice = Invoice {
+            id: None,
+            student_id: RecordId::new("students", "st1"),
+            school_id: RecordId::new("schools", "s1"),
+            term_id: RecordId::new("terms", "t1"),
+            invoice_number: "INV-2026/001".into(),
+            total_amount: Decimal::new(50000, 0),
+            amount_paid: Decimal::new(20000, 0),
+            balance: Decimal::new(3000000, 2),
+            discount_amount: Decimal::ZERO,
+            discount_reason: None,
+            due_date: Value::None,
+            status: InvoiceStatus::Partial,
+            generated_by: None,
+            generated_at: Datetime::now(),
+            created_at: Datetime::now(),
+            updated_at: Datetime::now(),
+        };
+        assert!(is_outstanding(&invoice));
+        assert_eq!(
+            pay_url("https://pay.example/school", &invoice),
+            "https://pay.example/school?reference=INV-2026001&amount=30000"
+        );
+    }
+}
@@ -0,0 +1,84 @@
+use printpdf::{
+    BuiltinFont, IndirectFontRef, Mm, PdfDocument, PdfDocumentReference, PdfLayerReference,
+};
+
+use crate::error::{DomainResult, Error};
+
+const PAGE_WIDTH: f32 = 210.0;
+const PAGE_HEIGHT: f32 = 297.0;
+const MARGIN: f32 = 15.0;
+const LINE_HEIGHT: f32 = 6.0;
+
+/// Minimal A4 text writer used by the exporters (top-down, auto page breaks)
+pub struct PdfWriter {
+    doc: PdfDocumentReference,
+    layer: PdfLayerReference,
+    font: IndirectFontRef,
+    bold: IndirectFontRef,
+    y: f32,
+}
+
+impl PdfWriter {
+    pub fn new(title: &str) -> DomainResult<Self> {
+        let (doc, page, layer) = PdfDocument::new(title, Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Page 1");
+        let font = doc
+            .add_builtin_font(BuiltinFont::Helvetica)
+            .map_err(|e| Error::Export(e.to_string()))?;
+        let bold = doc
+            .add_builtin_font(BuiltinFont::HelveticaBold)
+            .map_err(|e| Error::Export(e.to_string()))?;
+        let layer = doc.get_page(page).get_layer(layer);
+
+        Ok(Self {
+            doc,
+            layer,
+            font,
+            bold,
+            y: PAGE_HEIGHT - MARGIN,
+        })
+    }
+
+    /// Bold line of text, slightly larger than body text
+    pub fn heading(&mut self, text: &str) {
+        self.ensure_space();
+        self.layer
+            .use_text(text, 13.0, Mm(MARGIN), Mm(self.y), &self.bold);
+        self.y -= LINE_HEIGHT + 1.0;
+    }
+
+    pub fn line(&mut self, text: &str) {
+        self.ensure_space();
+        self.layer
+            .use_text(text, 10.0, Mm(MARGIN), Mm(self.y), &self.font);
+        self.y -= LINE_HEIGHT;
+    }
+
+    /// One table row; each cell is (x offset from the left margin in mm, text)
+    pub fn row(&mut self, cells: &[(f32, String)], bold: bool) {
+        self.ensure_space();
+        let font = if bold { &self.bold } else { &self.font };
+        for (x, text) in cells {
+            self.layer
+                .use_text(text.as_str(), 9.0, Mm(MARGIN + x), Mm(self.y), font);
+        }
+        self.y -= LINE_HEIGHT;
+    }
+
+    pub fn gap(&mut self) {
+        self.y -= LINE_HEIGHT / 2.0;
+    }
+
+    pub fn finish(self) -> DomainResult<Vec<u8>> {
+        self.doc
+            .save_to_bytes()
+            .map_err(|e| Error::Export(e.to_string()))
+    }
+
+    fn ensure_space(&mut self) {
+        if self.y < MARGIN {
+            let (page, layer) = self.doc.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Page");
+            self.layer = self.doc.get_page(page).get_layer(layer);
+            self.y = PAGE_HEIGHT - MARGIN;
+        }
+    }
+}
@@ -0,0 +1,600 @@
+use std::{env, sync::OnceLock, time::Duration};
+
+use aes_gcm::{
+    Aes128Gcm, Nonce,
+    aead::{Aead, KeyInit},
+};
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::Utc;
+use db::queries::{PushSubscriptionQ, UserQ};
+use hkdf::Hkdf;
+use p256::{
+    PublicKey, SecretKey,
+    ecdh::diffie_hellman,
+    ecdsa::{Signature, SigningKey, signature::Signer},
+    elliptic_curve::sec1::ToEncodedPoint,
+};
+use reqwest::StatusCode;
+use serde_json::json;
+use sha2::Sha256;
+use shared::{
+    helpers::{create_update_dtos::PushSubscriptionDto, surreal_util::record_key},
+    models::{
+        MessageChannel,
+        system_and_audit::{MessageDelivery, PushSubscription},
+    },
+};
+use surrealdb::{Surreal, engine::remote::ws::Client};
+use surrealdb_types::Datetime;
+
+use crate::{
+    delivery::{DeliveryProvider, DeliveryService, SendError, SentMessage},
+    error::{DomainResult, Error},
+};
+
+/// The deployment's VAPID private key, base64url-encoded
+pub const VAPID_PRIVATE_KEY_VAR: &str = "VAPID_PRIVATE_KEY";
+/// File the VAPID key is kept in when the variable isn't set; generated on first use
+pub const VAPID_KEY_FILE_VAR: &str = "VAPID_KEY_FILE";
+/// Contact push services can reach the deployment at, e.g. "mailto:ops@school.example"
+pub const VAPID_SUBJECT_VAR: &str = "VAPID_SUBJECT";
+
+/// How long a push service keeps a message for an offline device
+const TTL_SECONDS: u32 = 24 * 60 * 60;
+/// The one aes128gcm record a payload is sent in
+const RECORD_SIZE: u32 = 4096;
+/// Push services accept at most 4096 bytes of encrypted payload
+const MAX_PAYLOAD: usize = 3800;
+/// Push services reject VAPID tokens valid for more than a day
+const TOKEN_SECONDS: i64 = 12 * 60 * 60;
+
+/// The key pair that identifies this deployment to push services. Browsers
+/// subscribe with its public key, so changing it invalidates every subscription.
+pub struct VapidKey {
+    key: SecretKey,
+    subject: String,
+}
+
+impl VapidKey {
+    pub fn generate(subject: String) -> Self {
+        Self {
+            key: SecretKey::random(&mut OsRng),
+            subject,
+        }
+    }
+
+    /// A key from its base64url-encoded private scalar
+    pub fn from_base64(private_key: &str, subject: String) -> DomainResult<Self> {
+        let bytes = decode(private_key)?;
+        let key = SecretKey::from_slice(&bytes)
+            .map_err(|_| Error::Validation("invalid VAPID private key".into()))?;
+        Ok(Self { key, subject })
+    }
+
+    /// The key in `VAPID_PRIVATE_KEY`, else the one in `VAPID_KEY_FILE`
+    /// (default `./vapid_private_key`), generating and saving it if the file
+    /// doesn't exist yet
+    pub fn from_env() -> DomainResult<Self> {
+        let subject =
+            env::var(VAPID_SUBJECT_VAR).unwrap_or_else(|_| "mailto:admin@localhost".into());
+        if let Ok(private_key) = env::var(VAPID_PRIVATE_KEY_VAR) {
+            return Self::from_base64(private_key.trim(), subject);
+        }
+        let path = env::var(VAPID_KEY_FILE_VAR).unwrap_or_else(|_| "vapid_private_key".into());
+        match std::fs::read_to_string(&path) {
+            Ok(private_key) => Self::from_base64(private_key.trim(), subject),
+            Err(_) => {
+                let vapid = Self::generate(subject);
+                std::fs::write(&path, vapid.private_key())
+                    .map_err(|e| Error::Validation(format!("cannot save VAPID key {path}: {e}")))?;
+                Ok(vapid)
+            }
+        }
+    }
+
+    pub fn private_key(&self) -> String {
+        URL_SAFE_NO_PAD.encode(self.key.to_bytes())
+    }
+
+    /// The uncompressed public key, base64url-encoded: the browser's
+    /// `applicationServerKey`
+    pub fn public_key(&self) -> String {
+        URL_SAFE_NO_PAD.encode(self.key.public_key().to_encoded_point(false).as_bytes())
+    }
+
+    /// The `Authorization` header for a push to `endpoint`: an ES256 token
+    /// for the endpoint's origin, and the public key it verifies with
+    fn authorization(&self, endpoint: &str, now: i64) -> DomainResult<String> {
+        let url = reqwest::Url::parse(endpoint)
+            .map_err(|_| Error::Validation(format!("invalid push endpoint {endpoint}")))?;
+        let audience = url.origin().ascii_serialization();
+        let header = URL_SAFE_NO_PAD.encode(r#"{"typ":"JWT","alg":"ES256"}"#);
+        let claims = URL_SAFE_NO_PAD.encode(
+            json!({
+                "aud": audience,
+                "exp": now + TOKEN_SECONDS,
+                "sub": self.subject,
+            })
+            .to_string(),
+        );
+        let unsigned = format!("{header}.{claims}");
+        let signature: Signature = SigningKey::from(&self.key).sign(unsigned.as_bytes());
+        let token = format!(
+            "{unsigned}.{}",
+            URL_SAFE_NO_PAD.encode(signature.to_bytes())
+        );
+        Ok(format!("vapid t={token}, k={}", self.public_key()))
+    }
+}
+
+/// base64url, with or without padding as browsers differ
+fn decode(value: &str) -> DomainResult<Vec<u8>> {
+    URL_SAFE_NO_PAD
+        .decode(value.trim().trim_end_matches('='))
+        .map_err(|_| Error::Validation("invalid base64url value".into()))
+}
+
+/// The browser's keys for a subscription, checked
+fn subscription_keys(p256dh: &str, auth: &str) -> DomainResult<(PublicKey, Vec<u8>)> {
+    let public = PublicKey::from_sec1_bytes(&decode(p256dh)?)
+        .map_err(|_| Error::Validation("invalid p256dh key".into()))?;
+    let auth = decode(auth)?;
+    if auth.len() != 16 {
+        return Err(Error::Validation("auth secret must be 16 bytes".into()));
+    }
+    Ok((public, auth))
+}
+
+/// Encrypt a payload for a subscription (RFC 8291, aes128gcm content coding)
+pub fn encrypt(p256dh: &str, auth: &str, payload: &[u8]) -> DomainResult<Vec<u8>> {
+    if payload.len() > MAX_PAYLOAD {
+        return Err(Error::Validation(format!(
+            "push payload is longer than {MAX_PAYLOAD} bytes"
+        )));
+    }
+    let (ua_public, auth_secret) = subscription_keys(p256dh, auth)?;
+    let mut salt = [0u8; 16];
+    OsRng.fill_bytes(&mut salt);
+    encrypt_with(
+        &ua_public,
+        &auth_secret,
+        &SecretKey::random(&mut OsRng),
+        salt,
+        payload,
+    )
+}
+
+fn encrypt_with(
+    ua_public: &PublicKey,
+    auth_secret: &[u8],
+    as_secret: &SecretKey,
+    salt: [u8; 16],
+    payload: &[u8],
+) -> DomainResult<Vec<u8>> {
+    let crypto = |_| Error::Validation("push encryption failed".into());
+    let ua_bytes = ua_public.to_encoded_point(false);
+    let as_public = as_secret.public_key().to_encoded_point(false);
+    let shared = diffie_hellman(as_secret.to_nonzero_scalar(), ua_public.as_affine());
+
+    let mut key_info = b"WebPush: info\0".to_vec();
+    key_info.extend_from_slice(ua_bytes.as_bytes());
+    key_info.extend_from_slice(as_public.as_bytes());
+    let mut ikm = [0u8; 32];
+    Hkdf::<Sha256>::new(Some(auth_secret), shared.raw_secret_bytes())
+        .expand(&key_info, &mut ikm)
+        .map_err(crypto)?;
+
+    let prk = Hkdf::<Sha256>::new(Some(&salt), &ikm);
+    let mut cek = [0u8; 16];
+    let mut nonce = [0u8; 12];
+    prk.expand(b"Content-Encoding: aes128gcm\0", &mut cek)
+        .map_err(crypto)?;
+    prk.expand(b"Content-Encoding: nonce\0", &mut nonce)
+        .map_err(crypto)?;
+
+    // A single record, so it is also the last: delimited by 0x02
+    let mut plaintext = payload.to_vec();
+    plaintext.push(2);
+    let ciphertext = Aes128Gcm::new_from_slice(&cek)
+        .map_err(|_| Error::Validation("push encryption failed".into()))?
+        .encrypt(Nonce::from_slice(&nonce), plaintext.as_slice())
+        .map_err(|_| Error::Validation("push encryption failed".into()))?;
+
+    let mut body = Vec::with_capacity(86 + ciphertext.len());
+    body.extend_from_slice(&salt);
+    body.extend_from_slice(&RECORD_SIZE.to_be_bytes());
+    body.push(as_public.as_bytes().len() as u8);
+    body.extend_from_slice(as_public.as_bytes());
+    body.extend_from_slice(&ciphertext);
+    Ok(body)
+}
+
+/// Why a push service didn't take a message
+#[derive(Debug, Clone, PartialEq)]
+pub enum PushError {
+    /// The subscription has expired or was withdrawn (404/410)
+    Gone,
+    /// Worth trying again later (429, 5xx, network errors)
+    Temporary(String),
+    /// Refused for good, e.g. a malformed request
+    Rejected(String),
+}
+
+/// Sends encrypted payloads to browsers' push services
+pub struct PushClient {
+    http: reqwest::Client,
+    vapid: VapidKey,
+}
+
+impl PushClient {
+    pub fn new(vapid: VapidKey) -> Self {
+        Self {
+            http: reqwest::Client::builder()
+                .timeout(Duration::from_secs(30))
+                .build()
+                .unwrap_or_default(),
+            vapid,
+        }
+    }
+
+    pub fn public_key(&self) -> String {
+        self.vapid.public_key()
+    }
+
+    pub async fn send(
+        &self,
+        subscription: &PushSubscription,
+        payload: &[u8],
+    ) -> Result<(), PushError> {
+        let rejected = |e: Error| PushError::Rejected(e.to_string());
+        let body = encrypt(&subscription.p256dh, &subscription.auth, payload).map_err(rejected)?;
+        let authorization = self
+            .vapid
+            .authorization(&subscription.endpoint, Utc::now().timestamp())
+            .map_err(rejected)?;
+        let response = self
+            .http
+            .post(&subscription.endpoint)
+            .header("Authorization", authorization)
+            .header("Content-Encoding", "aes128gcm")
+            .header("Content-Type", "application/octet-stream")
+            .header("TTL", TTL_SECONDS.to_string())
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| PushError::Temporary(e.to_string()))?;
+        match response.status() {
+            status if status.is_success() => Ok(()),
+            StatusCode::NOT_FOUND | StatusCode::GONE => Err(PushError::Gone),
+            status if status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error() => Err(
+                PushError::Temporary(format!("push service returned {status}")),
+            ),
+            status => Err(PushError::Rejected(format!(
+                "push service returned {status}"
+            ))),
+        }
+    }
+}
+
+static PUSH_CLIENT: OnceLock<PushClient> = OnceLock::new();
+
+/// The process-wide push client, with the deployment's VAPID key
+pub fn push_client() -> DomainResult<&'static PushClient> {
+    if let Some(client) = PUSH_CLIENT.get() {
+        return Ok(client);
+    }
+    let client = PushClient::new(VapidKey::from_env()?);
+    Ok(PUSH_CLIENT.get_or_init(|| client))
+}
+
+/// Delivers queued push messages to every device the recipient subscribed,
+/// removing subscriptions that have expired
+pub struct WebPush<'a> {
+    pub sdb: &'a Surreal<Client>,
+    pub client: &'a PushClient,
+}
+
+impl DeliveryProvider for WebPush<'_> {
+    fn channel(&self) -> MessageChannel {
+        MessageChannel::Push
+    }
+
+    async fn send(&self, delivery: &MessageDelivery) -> Result<SentMessage, SendError> {
+        let temporary = |e: db::error::Error| SendError::Temporary(e.to_string());
+        let subscriptions = PushSubscriptionQ
+            .get_by_user(self.sdb, record_key(&delivery.recipient_user_id))
+            .await
+            .map_err(temporary)?;
+        if subscriptions.is_empty() {
+            return Err(SendError::Rejected("no push subscriptions".into()));
+        }
+        let payload = json!({
+            "title": delivery.subject,
+            "body": delivery.body,
+        })
+        .to_string();
+
+        let mut delivered = 0;
+        let mut last_error = None;
+        for subscription in &subscriptions {
+            let Some(id) = subscription.id.as_ref().map(record_key) else {
+                continue;
+            };
+            match self.client.send(subscription, payload.as_bytes()).await {
+                Ok(()) => {
+                    delivered += 1;
+                    PushSubscriptionQ
+                        .touch(self.sdb, id)
+                        .await
+                        .map_err(temporary)?;
+                }
+                Err(PushError::Gone) => {
+                    PushSubscriptionQ
+                        .delete(self.sdb, id)
+                        .await
+                        .map_err(temporary)?;
+                }
+                Err(err) => last_error = Some(err),
+            }
+        }
+        match (delivered, last_error) {
+            (0, Some(PushError::Temporary(err))) => Err(SendError::Temporary(err)),
+            (0, Some(PushError::Rejected(err))) => Err(SendError::Rejected(err)),
+            (0, _) => Err(SendError::Rejected(
+                "every push subscription has expired".into(),
+            )),
+            _ => Ok(SentMessage {
+                provider: "web_push".into(),
+                provider_message_id: None,
+                cost: None,
+            }),
+        }
+    }
+}
+
+/// The registry of devices users receive push on
+pub struct PushService;
+
+impl PushService {
+    /// Register a browser's subscription for a user
+    pub async fn subscribe(
+        &self,
+        sdb: &Surreal<Client>,
+        user_id: String,
+        data: PushSubscriptionDto,
+    ) -> DomainResult<PushSubscription> {
+        let endpoint = reqwest::Url::parse(&data.endpoint)
+            .map_err(|_| Error::Validation("invalid push endpoint".into()))?;
+        let local = matches!(endpoint.host_str(), Some("localhost" | "127.0.0.1"));
+        if endpoint.scheme() != "https" && !local {
+            return Err(Error::Validation("push endpoint must use https".into()));
+        }
+        subscription_keys(&data.keys.p256dh, &data.keys.auth)?;
+        let user = UserQ
+            .get_by_id(sdb, user_id.clone())
+            .await?
+            .ok_or_else(|| Error::NotFound(format!("user {user_id}")))?;
+
+        PushSubscriptionQ
+            .upsert(
+                sdb,
+                PushSubscription {
+                    id: None,
+                    school_id: user.school_id,
+                    user_id: user.id.expect("loaded users have ids"),
+                    endpoint: data.endpoint,
+                    p256dh: data.keys.p256dh,
+                    auth: data.keys.auth,
+                    user_agent: data.user_agent,
+                    last_used_at: None,
+                    created_at: Datetime::now(),
+                },
+            )
+            .await?
+            .ok_or_else(|| Error::NotFound("push subscription".into()))
+    }
+
+    pub async fn unsubscribe(
+        &self,
+        sdb: &Surreal<Client>,
+        user_id: String,
+        endpoint: String,
+    ) -> DomainResult<()> {
+        let deleted = PushSubscriptionQ
+            .delete_by_endpoint(sdb, user_id, endpoint)
+            .await?;
+        if deleted.is_empty() {
+            return Err(Error::NotFound("push subscription".into()));
+        }
+        Ok(())
+    }
+
+    pub async fn list(
+        &self,
+        sdb: &Surreal<Client>,
+        user_id: String,
+    ) -> DomainResult<Vec<PushSubscription>> {
+        Ok(PushSubscriptionQ.get_by_user(sdb, user_id).await?)
+    }
+}
+
+/// Sends queued push messages
+pub async fn run_queue(
+    sdb: &Surreal<Client>,
+    client: &PushClient,
+    limit: u32,
+) -> DomainResult<usize> {
+    DeliveryService
+        .dispatch(sdb, &WebPush { sdb, client }, limit)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use p256::ecdsa::{VerifyingKey, signature::Verifier};
+    use surrealdb_types::RecordId;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    /// What a browser does with a push message (RFC 8291 section 3.4)
+    fn decrypt(ua_secret: &SecretKey, auth_secret: &[u8], body: &[u8]) -> Vec<u8> {
+        let salt = &body[..16];
+        assert_eq!(
+            u32::from_be_bytes(body[16..20].try_into().unwrap()),
+            RECORD_SIZE
+        );
+        let key_len = body[20] as usize;
+        let as_public = PublicKey::from_sec1_bytes(&body[21..21 + key_len]).unwrap();
+        let ciphertext = &body[21 + key_len..];
+
+        let shared = diffie_hellman(ua_secret.to_nonzero_scalar(), as_public.as_affine());
+        let mut key_info = b"WebPush: info\0".to_vec();
+        key_info.extend_from_slice(ua_secret.public_key().to_encoded_point(false).as_bytes());
+        key_info.extend_from_slice(as_public.to_encoded_point(false).as_bytes());
+        let mut ikm = [0u8; 32];
+        Hkdf::<Sha256>::new(Some(auth_secret), shared.raw_secret_bytes())
+            .expand(&key_info, &mut ikm)
+            .unwrap();
+        let prk = Hkdf::<Sha256>::new(Some(salt), &ikm);
+        let mut cek = [0u8; 16];
+        let mut nonce = [0u8; 12];
+        prk.expand(b"Content-Encoding: aes128gcm\0", &mut cek)
+            .unwrap();
+        prk.expand(b"Content-Encoding: nonce\0", &mut nonce)
+            .unwrap();
+
+        let mut plaintext = Aes128Gcm::new_from_slice(&cek)
+            .unwrap()
+            .decrypt(Nonce::from_slice(&nonce), ciphertext)
+            .unwrap();
+        assert_eq!(plaintext.pop(), Some(2));
+        plaintext
+    }
+
+    fn subscription(endpoint: String, ua_secret: &SecretKey, auth: &[u8]) -> PushSubscription {
+        PushSubscription {
+            id: Some(RecordId::new("push_subscriptions", "p1")),
+            school_id: RecordId::new("schools", "s1"),
+            user_id: RecordId::new("users", "u1"),
+            endpoint,
+            p256dh: URL_SAFE_NO_PAD
+                .encode(ua_secret.public_key().to_encoded_point(false).as_bytes()),
+            auth: URL_SAFE_NO_PAD.encode(auth),
+            user_agent: None,
+            last_used_at: None,
+            created_at: Datetime::now(),
+        }
+    }
+
+    #[test]
+    fn encrypts_payloads_the_browser_can_read() {
+        let ua_secret = SecretKey::random(&mut OsRng);
+        let auth = [7u8; 16];
+        let sub = subscription("https://push.example/abc".into(), &ua_secret, &auth);
+        let payload = br#"{"title":"Mid-term break","body":"School closes on Friday."}"#;
+
+        let body = encrypt(&sub.p256dh, &sub.auth, payload).unwrap();
+        assert_eq!(decrypt(&ua_secret, &auth, &body), payload);
+
+        assert!(encrypt(&sub.p256dh, "c2hvcnQ", payload).is_err());
+        assert!(encrypt("bm90IGEga2V5", &sub.auth, payload).is_err());
+    }
+
+    #[test]
+    fn signs_vapid_tokens_for_the_endpoint_origin() {
+        let vapid = VapidKey::generate("mailto:ops@school.example".into());
+        let restored = VapidKey::from_base64(&vapid.private_key(), String::new()).unwrap();
+        assert_eq!(restored.public_key(), vapid.public_key());
+
+        let header = vapid
+            .authorization("https://fcm.googleapis.com/fcm/send/abc", 1_700_000_000)
+            .unwrap();
+        let (token, key) = header
+            .strip_prefix("vapid t=")
+            .unwrap()
+            .split_once(", k=")
+            .unwrap();
+        assert_eq!(key, vapid.public_key());
+
+        let (unsigned, signature) = token.rsplit_once('.').unwrap();
+        let claims: serde_json::Value = serde_json::from_slice(
+            &URL_SAFE_NO_PAD
+                .decode(unsigned.split_once('.').unwrap().1)
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(claims["aud"], "https://fcm.googleapis.com");
+        assert_eq!(claims["exp"], 1_700_000_000 + TOKEN_SECONDS);
+        assert_eq!(claims["sub"], "mailto:ops@school.example");
+
+        let verifying = VerifyingKey::from_sec1_bytes(&decode(key).unwrap()).unwrap();
+        let signature = Signature::from_slice(&decode(signature).unwrap()).unwrap();
+        assert!(verifying.verify(unsigned.as_bytes(), &signature).is_ok());
+    }
+
+    /// A push service that answers every request with `status` and hands
+    /// back the request it received
+    async fn push_service_stub(status: &'static str) -> (String, tokio::task::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}/push/abc", listener.local_addr().unwrap());
+        let handle = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buffer = [0u8; 4096];
+            loop {
+                let read = socket.read(&mut buffer).await.unwrap();
+                request.extend_from_slice(&buffer[..read]);
+                let text = String::from_utf8_lossy(&request);
+                if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                    let length: usize = head
+                        .lines()
+                        .find_map(|l| {
+                            l.to_ascii_lowercase()
+                                .strip_prefix("content-length:")
+                                .map(|v| v.trim().parse().unwrap())
+                        })
+                        .unwrap_or(0);
+                    if body.len() >= length || read == 0 {
+                        break;
+                    }
+                }
+            }
+            socket
+                .write_all(format!("HTTP/1.1 {status}\r\ncontent-length: 0\r\n\r\n").as_bytes())
+                .await
+                .unwrap();
+            let text = String::from_utf8_lossy(&request);
+            text.split_once("\r\n\r\n").unwrap().0.to_ascii_lowercase()
+        });
+        (endpoint, handle)
+    }
+
+    #[tokio::test]
+    async fn reports_how_the_push_service_answered() {
+        let client = PushClient::new(VapidKey::generate("mailto:ops@school.example".into()));
+        let ua_secret = SecretKey::random(&mut OsRng);
+
+        let (endpoint, request) = push_service_stub("201 Created").await;
+        let sub = subscription(endpoint, &ua_secret, &[1u8; 16]);
+        assert_eq!(client.send(&sub, b"hello").await, Ok(()));
+        let head = request.await.unwrap();
+        assert!(head.contains("content-encoding: aes128gcm"));
+        assert!(head.contains("ttl: 86400"));
+        assert!(head.contains("authorization: vapid t="));
+
+        let (endpoint, _) = push_service_stub("410 Gone").await;
+        let sub = subscription(endpoint, &ua_secret, &[1u8; 16]);
+        assert_eq!(client.send(&sub, b"hello").await, Err(PushError::Gone));
+
+        let (endpoint, _) = push_service_stub("503 Service Unavailable").await;
+        let sub = subscription(endpoint, &ua_secret, &[1u8; 16]);
+        assert!(matches!(
+            client.send(&sub, b"hello").await,
+            Err(PushError::Temporary(_))
+        ));
+    }
+}
@@ -0,0 +1,158 @@
+use db::queries::{AssessmentQ, ClassQ, ReportCardQ};
+use shared::{
+    helpers::{
+        api_responses::{AssessmentMissingScores, ClassRankingEntry, SessionUser},
+        query_filters::RankingMethod,
+        surreal_util::record_key,
+    },
+    models::Student,
+};
+use surrealdb::{Surreal, engine::remote::ws::Client};
+use surrealdb_types::Decimal;
+
+use crate::{
+    auth::{STAFF, ensure_role},
+    error::{DomainResult, Error},
+    report_card::ReportCardService,
+};
+
+pub struct RankingService;
+
+impl RankingService {
+    /// Rank a class for a term by each student's weighted total across
+    /// subjects, the same total their report card shows
+    pub async fn class_ranking(
+        &self,
+        sdb: &Surreal<Client>,
+        viewer: &SessionUser,
+        class_id: String,
+        term_id: String,
+        method: RankingMethod,
+    ) -> DomainResult<Vec<ClassRankingEntry>> {
+        self.ensure_staff(sdb, viewer, &class_id).await?;
+        let totals = ReportCardService
+            .class_totals(sdb, class_id, term_id)
+            .await?;
+        Ok(rank(totals, method))
+    }
+
+    /// Students still missing a score, for every assessment of a class in a term.
+    /// Assessments with nobody missing are left out.
+    pub async fn missing_scores(
+        &self,
+        sdb: &Surreal<Client>,
+        viewer: &SessionUser,
+        class_id: String,
+        term_id: String,
+    ) -> DomainResult<Vec<AssessmentMissingScores>> {
+        self.ensure_staff(sdb, viewer, &class_id).await?;
+        let assessments = AssessmentQ
+            .get_by_class_term(sdb, class_id.clone(), term_id)
+            .await?;
+
+        let mut missing = Vec::new();
+        for assessment in assessments {
+            let Some(id) = &assessment.id else {
+                continue;
+            };
+            let students = ReportCardQ
+                .get_unscored_students(sdb, record_key(id), class_id.clone())
+                .await?;
+            if !students.is_empty() {
+                missing.push(AssessmentMissingScores {
+                    assessment,
+                    students,
+                });
+            }
+        }
+        Ok(missing)
+    }
+
+    /// Fails unless the viewer works at the class's school
+    async fn ensure_staff(
+        &self,
+        sdb: &Surreal<Client>,
+        viewer: &SessionUser,
+        class_id: &str,
+    ) -> DomainResult<()> {
+        let class = ClassQ
+            .get_by_id(sdb, class_id.to_string())
+            .await?
+            .ok_or_else(|| Error::NotFound(format!("class {class_id}")))?;
+        ensure_role(viewer, &record_key(&class.school_id), STAFF)
+    }
+}
+
+/// Assign positions to totals, highest first; ties share a position
+pub fn rank(mut totals: Vec<(Student, Decimal)>, method: RankingMethod) -> Vec<ClassRankingEntry> {
+    totals.sort_by_key(|(_, total)| std::cmp::Reverse(*total));
+
+    let mut entries: Vec<ClassRankingEntry> = Vec::with_capacity(totals.len());
+    for (index, (student, total)) in totals.into_iter().enumerate() {
+        let position = match entries.last() {
+            Some(prev) if prev.total_score == total => prev.position,
+            Some(prev) => match method {
+                RankingMethod::Standard => index as u32 + 1,
+                RankingMethod::Dense => prev.position + 1,
+            },
+            None => 1,
+        };
+        entries.push(ClassRankingEntry {
+            position,
+            student,
+            total_score: total,
+        });
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use shared::models::{Gender, StudentStatus};
+    use surrealdb_types::{Datetime, RecordId, Value};
+
+    use super::*;
+
+    fn student(admission_number: &str) -> Student {
+        Student {
+            id: Some(RecordId::new("students", admission_number)),
+            school_id: RecordId::new("schools", "s1"),
+            admission_number: admission_number.into(),
+            first_name: admission_number.into(),
+            middle_name: None,
+            last_name: "Test".into(),
+            date_of_birth: Value::None,
+            gender: Gender::Female,
+            address: None,
+            current_class_id: None,
+            admission_date: Value::None,
+            status: StudentStatus::Active,
+            profile_photo_url: None,
+            blood_group: None,
+            genotype: None,
+            medical_conditions: None,
+            created_at: Datetime::now(),
+            updated_at: Datetime::now(),
+        }
+    }
+
+    fn positions(method: RankingMethod) -> Vec<u32> {
+        let totals = vec![
+            (student("a"), Decimal::from(70)),
+            (student("b"), Decimal::from(90)),
+            (student("c"), Decimal::from(90)),
+            (student("d"), Decimal::from(60)),
+        ];
+        rank(totals, method).iter().map(|e| e.position).collect()
+    }
+
+    #[test]
+    fn standard_ranking_skips_after_ties() {
+        assert_eq!(positions(RankingMethod::Standard), vec![1, 1, 3, 4]);
+    }
+
+    #[test]
+    fn dense_ranking_does_not_skip() {
+        assert_eq!(positions(RankingMethod::Dense), vec![1, 1, 2, 3]);
+    }
+}
@@ -0,0 +1,402 @@
+use std::collections::{HashMap, HashSet};
+
+use db::{
+    pagination::Page,
+    queries::{
+        AssessmentQ, AssessmentWeightingQ, ClassQ, ReportCardQ, ReportCardTotals, StudentQ,
+        StudentScoreQ,
+    },
+};
+use shared::{
+    helpers::{api_responses::SessionUser, query_filters::ScoreFilter, surreal_util::record_key},
+    models::{
+        Student, UserType,
+        academic::Class,
+        assessments_nd_grading::{AssessmentWeight, GradingScheme, ReportCard, ReportCardScore},
+    },
+};
+use surrealdb::{Surreal, engine::remote::ws::Client};
+use surrealdb_types::{Datetime, Decimal, RecordId};
+
+use crate::{
+    auth::ensure_role,
+    error::{DomainResult, Error},
+    grading::{GradingService, grade_for_score},
+    subscription::SubscriptionService,
+    weighting::{WeightedScore, WeightingService, weighted_score},
+};
+
+const PAGE_SIZE: u32 = 500;
+
+pub struct ReportCardService;
+
+impl ReportCardService {
+    /// Publish a report card, pinning the grading scheme version it was graded
+    /// with so later scheme edits don't change its grades. Only the school's
+    /// admins may publish.
+    pub async fn publish(
+        &self,
+        sdb: &Surreal<Client>,
+        staff: &SessionUser,
+        report_card_id: String,
+    ) -> DomainResult<ReportCard> {
+        let card = ReportCardQ
+            .get_by_id(sdb, report_card_id.clone())
+            .await?
+            .ok_or_else(|| Error::NotFound(format!("report card {report_card_id}")))?;
+        let class_id = record_key(&card.class_id);
+        let class = ClassQ
+            .get_by_id(sdb, class_id.clone())
+            .await?
+            .ok_or_else(|| Error::NotFound(format!("class {class_id}")))?;
+        ensure_role(staff, &record_key(&class.school_id), &[UserType::Admin])?;
+        SubscriptionService
+            .ensure_writable(sdb, &record_key(&class.school_id))
+            .await?;
+
+        GradingService
+            .pin_to_card(sdb, &card, record_key(&class.school_id), &class.class_level)
+            .await?;
+        ReportCardQ
+            .publish(sdb, report_card_id.clone())
+            .await?
+            .ok_or_else(|| Error::NotFound(format!("report card {report_card_id}")))
+    }
+
+    /// Generate the report cards of every student with scores in a class for
+    /// a term: each card's subject rows are computed with the class level's
+    /// weighting and stored, replacing any from an earlier run, and its total,
+    /// average and class position set. Published terms can't be regenerated.
+    pub async fn generate(
+        &self,
+        sdb: &Surreal<Client>,
+        staff: &SessionUser,
+        class_id: String,
+        term_id: String,
+    ) -> DomainResult<Vec<ReportCard>> {
+        let class = ClassQ
+            .get_by_id(sdb, class_id.clone())
+            .await?
+            .ok_or_else(|| Error::NotFound(format!("class {class_id}")))?;
+        let school_id = record_key(&class.school_id);
+        ensure_role(staff, &school_id, &[UserType::Admin])?;
+        SubscriptionService.ensure_writable(sdb, &school_id).await?;
+        if ReportCardQ
+            .is_published(sdb, class_id.clone(), term_id.clone())
+            .await?
+        {
+            return Err(Error::Locked(
+                "report cards for this class and term are already published".into(),
+            ));
+        }
+
+        let weighting = WeightingService
+            .get(sdb, school_id.clone(), class.class_level.clone())
+            .await?;
+        let results = self
+            .class_results(sdb, &class, &weighting.weights, &term_id)
+            .await?;
+        let totals = student_totals(&results);
+        let class_average = average(&totals.iter().map(|(_, t)| *t).collect::<Vec<_>>());
+        let mut existing: HashMap<String, ReportCard> = ReportCardQ
+            .get_by_class_term(sdb, class_id.clone(), term_id.clone())
+            .await?
+            .into_iter()
+            .map(|card| (record_key(&card.student_id), card))
+            .collect();
+        let class_rid = RecordId::new("classes", class_id.as_str());
+        let term_rid = RecordId::new("terms", term_id.as_str());
+
+        let mut cards = Vec::with_capacity(totals.len());
+        for (student, total) in &totals {
+            let card = match existing.remove(student) {
+                Some(card) => card,
+                None => ReportCardQ
+                    .create(sdb, new_card(student, &class_rid, &term_rid))
+                    .await?
+                    .ok_or_else(|| Error::NotFound("created report card".into()))?,
+            };
+            let card_rid = card
+                .id
+                .clone()
+                .ok_or_else(|| Error::NotFound("report card id".into()))?;
+            let card_id = record_key(&card_rid);
+
+            let (_, bands) = GradingService
+                .bands_for_card(sdb, &card, school_id.clone(), &class.class_level)
+                .await?;
+            let rows = score_rows(
+                &card_rid,
+                results.get(student).map_or(&[], Vec::as_slice),
+                &bands,
+            );
+            ReportCardQ.clear_scores(sdb, card_id.clone()).await?;
+            for row in rows.iter().cloned() {
+                ReportCardQ.add_score(sdb, row).await?;
+            }
+
+            let card = ReportCardQ
+                .set_totals(
+                    sdb,
+                    card_id.clone(),
+                    ReportCardTotals {
+                        total_score: Some(*total),
+                        average_percentage: average(
+                            &rows
+                                .iter()
+                                .filter_map(|r| r.total_score)
+                                .collect::<Vec<_>>(),
+                        ),
+                        class_position: Some(
+                            totals.iter().filter(|(_, other)| other > total).count() as i32 + 1,
+                        ),
+                        total_students_in_class: Some(totals.len() as i32),
+                        class_average,
+                        generated_by: Some(RecordId::new("users", staff.user_id.as_str())),
+                    },
+                )
+                .await?
+                .ok_or_else(|| Error::NotFound(format!("report card {card_id}")))?;
+            cards.push(card);
+        }
+        Ok(cards)
+    }
+
+    /// Each student's weighted total across subjects for a class's term,
+    /// highest first. A class level without a weighting is totalled from the
+    /// raw scores instead.
+    pub async fn class_totals(
+        &self,
+        sdb: &Surreal<Client>,
+        class_id: String,
+        term_id: String,
+    ) -> DomainResult<Vec<(Student, Decimal)>> {
+        let class = ClassQ
+            .get_by_id(sdb, class_id.clone())
+            .await?
+            .ok_or_else(|| Error::NotFound(format!("class {class_id}")))?;
+        let Some(weighting) = AssessmentWeightingQ
+            .get(sdb, record_key(&class.school_id), class.class_level.clone())
+            .await?
+        else {
+            return Ok(ReportCardQ
+                .get_class_ranking(sdb, class_id, term_id)
+                .await?);
+        };
+
+        let totals = student_totals(
+            &self
+                .class_results(sdb, &class, &weighting.weights, &term_id)
+                .await?,
+        );
+        let ids = totals
+            .iter()
+            .map(|(student, _)| RecordId::new("students", student.as_str()))
+            .collect();
+        let mut students: HashMap<String, Student> = StudentQ
+            .get_by_ids(sdb, ids)
+            .await?
+            .into_iter()
+            .filter_map(|student| Some((record_key(student.id.as_ref()?), student)))
+            .collect();
+        Ok(totals
+            .into_iter()
+            .filter_map(|(student, total)| Some((students.remove(&student)?, total)))
+            .collect())
+    }
+
+    /// Weighted subject results of every student with a score in a class's
+    /// term, by student key
+    async fn class_results(
+        &self,
+        sdb: &Surreal<Client>,
+        class: &Class,
+        weights: &[AssessmentWeight],
+        term_id: &str,
+    ) -> DomainResult<HashMap<String, Vec<SubjectResult>>> {
+        let class_rid = class
+            .id
+            .clone()
+            .ok_or_else(|| Error::NotFound("class id".into()))?;
+        let school_id = record_key(&class.school_id);
+
+        let assessments = AssessmentQ
+            .get_by_class_term(sdb, record_key(&class_rid), term_id.to_string())
+            .await?;
+
+        // (assessment key, student key) -> score
+        let mut scores: HashMap<(String, String), Option<Decimal>> = HashMap::new();
+        let filter = ScoreFilter {
+            class_id: Some(class_rid),
+            term_id: Some(RecordId::new("terms", term_id)),
+            ..Default::default()
+        };
+        let mut offset = 0;
+        loop {
+            let page = Page {
+                limit: PAGE_SIZE,
+                offset,
+            };
+            let batch = StudentScoreQ
+                .get_filtered(sdb, school_id.clone(), filter.clone(), page)
+                .await?;
+            let done = batch.len() < PAGE_SIZE as usize;
+            for score in batch {
+                scores.insert(
+                    (
+                        record_key(&score.assessment_id),
+                        record_key(&score.student_id),
+                    ),
+                    score.score_obtained,
+                );
+            }
+            if done {
+                break;
+            }
+            offset += PAGE_SIZE;
+        }
+
+        let students: HashSet<String> = scores.keys().map(|(_, s)| s.clone()).collect();
+        let mut subjects: Vec<String> = Vec::new();
+        for assessment in &assessments {
+            let subject = record_key(&assessment.subject_id);
+            if !subjects.contains(&subject) {
+                subjects.push(subject);
+            }
+        }
+
+        let mut results: HashMap<String, Vec<SubjectResult>> = HashMap::new();
+        for subject in subjects {
+            let subject_assessments: Vec<_> = assessments
+                .iter()
+                .filter(|a| record_key(&a.subject_id) == subject)
+                .filter_map(|a| Some((a, record_key(a.id.as_ref()?))))
+                .collect();
+
+            // Only students with at least one score entered take part in the subject
+            let mut weighted = Vec::new();
+            for student in &students {
+                let entries: Vec<_> = subject_assessments
+                    .iter()
+                    .map(|(a, key)| (*a, scores.get(&(key.clone(), student.clone())).copied()))
+                    .collect();
+                if entries.iter().all(|(_, s)| s.flatten().is_none()) {
+                    continue;
+                }
+                let entries: Vec<_> = entries.into_iter().map(|(a, s)| (a, s.flatten())).collect();
+                weighted.push((student, weighted_score(weights, &entries)));
+            }
+
+            let totals: Vec<Decimal> = weighted.iter().filter_map(|(_, w)| w.total).collect();
+            let high = totals.iter().max().copied();
+            let low = totals.iter().min().copied();
+            let subject_average = average(&totals);
+            for (student, score) in weighted {
+                results
+                    .entry(student.clone())
+                    .or_default()
+                    .push(SubjectResult {
+                        subject_id: subject_assessments[0].0.subject_id.clone(),
+                        score,
+                        position: score
+                            .total
+                            .map(|t| totals.iter().filter(|other| **other > t).count() as i32 + 1),
+                        high,
+                        low,
+                        average: subject_average,
+                    });
+            }
+        }
+        Ok(results)
+    }
+}
+
+/// One student's weighted result in a subject, placed among classmates
+#[derive(Debug, Clone)]
+struct SubjectResult {
+    subject_id: RecordId,
+    score: WeightedScore,
+    position: Option<i32>,
+    high: Option<Decimal>,
+    low: Option<Decimal>,
+    average: Option<Decimal>,
+}
+
+/// Graded report card rows from a student's subject results
+fn score_rows(
+    report_card_id: &RecordId,
+    results: &[SubjectResult],
+    bands: &[GradingScheme],
+) -> Vec<ReportCardScore> {
+    results
+        .iter()
+        .map(|result| {
+            let band = result.score.total.and_then(|t| grade_for_score(bands, t));
+            ReportCardScore {
+                id: None,
+                report_card_id: report_card_id.clone(),
+                subject_id: result.subject_id.clone(),
+                ca_score: result.score.ca,
+                exam_score: result.score.exam,
+                total_score: result.score.total,
+                grade: band.map(|b| b.grade.clone()),
+                subject_position: result.position,
+                subject_high_score: result.high,
+                subject_low_score: result.low,
+                subject_average: result.average,
+                remarks: band.and_then(|b| b.remark.clone()),
+            }
+        })
+        .collect()
+}
+
+/// Sum of each student's subject totals, highest first
+fn student_totals(results: &HashMap<String, Vec<SubjectResult>>) -> Vec<(String, Decimal)> {
+    let mut totals: Vec<(String, Decimal)> = results
+        .iter()
+        .map(|(student, subjects)| {
+            (
+                student.clone(),
+                subjects.iter().filter_map(|s| s.score.total).sum(),
+            )
+        })
+        .collect();
+    totals.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    totals
+}
+
+fn average(values: &[Decimal]) -> Option<Decimal> {
+    if values.is_empty() {
+        return None;
+    }
+    let sum: Decimal = values.iter().sum();
+    Some((sum / Decimal::from(values.len())).round_dp(2))
+}
+
+fn new_card(student: &str, class_id: &RecordId, term_id: &RecordId) -> ReportCard {
+    ReportCard {
+        id: None,
+        student_id: RecordId::new("students", student),
+        class_id: class_id.clone(),
+        term_id: term_id.clone(),
+        total_score: None,
+        average_percentage: None,
+        class_position: None,
+        total_students_in_class: None,
+        class_average: None,
+        attendance_present: None,
+        attendance_absent: None,
+        attendance_late: None,
+        times_school_opened: None,
+        teacher_comment: None,
+        principal_comment: None,
+        next_term_begins: None,
+        generated_by: None,
+        generated_at: Datetime::now(),
+        published: false,
+        published_at: None,
+        grading_scheme_version_id: None,
+        pdf_url: None,
+        created_at: Datetime::now(),
+    }
+}
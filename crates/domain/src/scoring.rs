@@ -0,0 +1,433 @@
+use std::collections::HashMap;
+
+use db::queries::{ActivityLogQ, AssessmentQ, ClassSubjectQ, ReportCardQ, StudentQ, StudentScoreQ};
+use shared::{
+    helpers::{
+        api_responses::{ScoreCell, ScoreSheet, ScoreSheetColumn, ScoreSheetRow, SessionUser},
+        create_update_dtos::EnterScoreDto,
+        surreal_util::record_key,
+    },
+    models::{
+        ActionType, AssessmentType, StudentStatus,
+        assessments_nd_grading::{Assessment, StudentScore},
+        system_and_audit::ActivityLog,
+    },
+};
+use surrealdb::{Surreal, engine::remote::ws::Client};
+use surrealdb_types::{Datetime, Decimal, RecordId};
+
+use crate::{
+    auth::{ADMINS, ensure_role},
+    error::{DomainResult, Error},
+    subscription::SubscriptionService,
+};
+
+/// Score entry with the rules the raw queries don't enforce: range against
+/// `max_score`, enrolment, verification lock and report card publication
+pub struct ScoreService;
+
+impl ScoreService {
+    /// Enter a new score or change one that has not been verified yet.
+    /// `entered_by` is the user id recorded on a new score.
+    pub async fn enter(
+        &self,
+        sdb: &Surreal<Client>,
+        data: EnterScoreDto,
+        entered_by: String,
+    ) -> DomainResult<StudentScore> {
+        self.save(sdb, data, entered_by, None).await
+    }
+
+    /// Enter a score only if nobody changed it since the caller read it.
+    /// `seen` is the cell version the caller loaded, None if the cell was empty.
+    pub async fn enter_unchanged(
+        &self,
+        sdb: &Surreal<Client>,
+        data: EnterScoreDto,
+        seen: Option<String>,
+        entered_by: String,
+    ) -> DomainResult<StudentScore> {
+        let current = StudentScoreQ
+            .get(
+                sdb,
+                record_key(&data.assessment_id),
+                record_key(&data.student_id),
+            )
+            .await?;
+        check_version(current.as_ref(), seen.as_deref())?;
+        let expected = Some(current.map(|score| score.updated_at));
+        self.save(sdb, data, entered_by, expected).await
+    }
+
+    /// Write a score. With `expected` set, the write only goes through if the
+    /// stored score is still that version (None: no score yet), checked in
+    /// the same statement as the write so concurrent saves can't both win.
+    async fn save(
+        &self,
+        sdb: &Surreal<Client>,
+        data: EnterScoreDto,
+        entered_by: String,
+        expected: Option<Option<Datetime>>,
+    ) -> DomainResult<StudentScore> {
+        let assessment_id = record_key(&data.assessment_id);
+        let student_id = record_key(&data.student_id);
+        let assessment = self.open_assessment(sdb, &assessment_id).await?;
+        SubscriptionService
+            .ensure_writable(sdb, &record_key(&assessment.school_id))
+            .await?;
+        validate_score(data.score_obtained, assessment.max_score)?;
+        self.ensure_enrolled(sdb, &assessment, &student_id).await?;
+
+        match StudentScoreQ
+            .get(sdb, assessment_id.clone(), student_id.clone())
+            .await?
+        {
+            Some(existing) if existing.verified => Err(verified_locked()),
+            Some(_) => match expected {
+                Some(Some(seen)) => StudentScoreQ
+                    .update_if_unchanged(sdb, assessment_id, student_id, data.score_obtained, seen)
+                    .await?
+                    .ok_or_else(changed_meanwhile),
+                Some(None) => Err(changed_meanwhile()),
+                // Verified meanwhile if the unverified-only update misses
+                None => StudentScoreQ
+                    .update(sdb, assessment_id, student_id, data.score_obtained)
+                    .await?
+                    .ok_or_else(verified_locked),
+            },
+            None if matches!(expected, Some(Some(_))) => Err(changed_meanwhile()),
+            None => StudentScoreQ
+                .enter_if_absent(sdb, data, entered_by)
+                .await?
+                .ok_or_else(changed_meanwhile),
+        }
+    }
+
+    /// Every active student in a class subject against its assessments for a term
+    pub async fn sheet(
+        &self,
+        sdb: &Surreal<Client>,
+        class_subject_id: String,
+        term_id: String,
+    ) -> DomainResult<ScoreSheet> {
+        let class_subject = ClassSubjectQ
+            .get_by_id(sdb, class_subject_id.clone())
+            .await?
+            .ok_or_else(|| Error::NotFound(format!("class subject {class_subject_id}")))?;
+        let class_id = record_key(&class_subject.class_id);
+        let subject_id = record_key(&class_subject.subject_id);
+
+        let assessments = AssessmentQ
+            .get_by_class_subject_term(sdb, class_id.clone(), subject_id.clone(), term_id.clone())
+            .await?;
+        let students = StudentQ.get_by_class(sdb, class_id.clone()).await?;
+        let mut scores: HashMap<(String, String), StudentScore> = StudentScoreQ
+            .get_by_class_subject_term(sdb, class_id.clone(), subject_id, term_id.clone())
+            .await?
+            .into_iter()
+            .map(|score| {
+                let key = (
+                    record_key(&score.student_id),
+                    record_key(&score.assessment_id),
+                );
+                (key, score)
+            })
+            .collect();
+        let published = ReportCardQ
+            .is_published(sdb, class_id, term_id.clone())
+            .await?;
+
+        let columns: Vec<ScoreSheetColumn> = assessments
+            .iter()
+            .filter_map(|assessment| {
+                Some(ScoreSheetColumn {
+                    assessment_id: record_key(assessment.id.as_ref()?),
+                    label: column_label(assessment),
+                    max_score: assessment.max_score,
+                })
+            })
+            .collect();
+        let rows = students
+            .into_iter()
+            .filter_map(|student| {
+                let student_id = record_key(student.id.as_ref()?);
+                let cells = columns
+                    .iter()
+                    .map(|column| {
+                        scores
+                            .remove(&(student_id.clone(), column.assessment_id.clone()))
+                            .map_or_else(ScoreCell::default, |score| ScoreCell {
+                                score: score.score_obtained,
+                                verified: score.verified,
+                                version: Some(score_version(&score)),
+                            })
+                    })
+                    .collect();
+                Some(ScoreSheetRow {
+                    name: format!("{}, {}", student.last_name, student.first_name),
+                    admission_number: student.admission_number,
+                    student_id,
+                    cells,
+                })
+            })
+            .collect();
+
+        Ok(ScoreSheet {
+            class_subject_id,
+            term_id,
+            columns,
+            rows,
+            published,
+        })
+    }
+
+    /// Clear verification on an assessment's scores so teachers can edit them again
+    pub async fn unlock(
+        &self,
+        sdb: &Surreal<Client>,
+        admin: &SessionUser,
+        assessment_id: String,
+    ) -> DomainResult<usize> {
+        let assessment = self.open_assessment(sdb, &assessment_id).await?;
+        ensure_role(admin, &record_key(&assessment.school_id), ADMINS)?;
+        SubscriptionService
+            .ensure_writable(sdb, &record_key(&assessment.school_id))
+            .await?;
+
+        let unlocked = StudentScoreQ
+            .unverify_assessment_scores(sdb, assessment_id.clone())
+            .await?
+            .len();
+
+        ActivityLogQ
+            .log(
+                sdb,
+                ActivityLog {
+                    id: None,
+                    school_id: assessment.school_id,
+                    user_id: Some(RecordId::new("users", admin.user_id.as_str())),
+                    action: ActionType::Update,
+                    entity_type: Some("assessment".into()),
+                    entity_id: Some(assessment_id),
+                    description: Some(format!("Unlocked {unlocked} verified scores")),
+                    ip_address: None,
+                    metadata: Some(serde_json::json!({ "unlocked_scores": unlocked })),
+                    created_at: Datetime::now(),
+                },
+            )
+            .await?;
+
+        Ok(unlocked)
+    }
+
+    /// Change a score even when it is verified, recording the before and after
+    /// values in the activity log
+    pub async fn override_score(
+        &self,
+        sdb: &Surreal<Client>,
+        admin: &SessionUser,
+        data: EnterScoreDto,
+        reason: Option<String>,
+    ) -> DomainResult<StudentScore> {
+        let assessment_id = record_key(&data.assessment_id);
+        let student_id = record_key(&data.student_id);
+        let assessment = self.open_assessment(sdb, &assessment_id).await?;
+        ensure_role(admin, &record_key(&assessment.school_id), ADMINS)?;
+        SubscriptionService
+            .ensure_writable(sdb, &record_key(&assessment.school_id))
+            .await?;
+        validate_score(data.score_obtained, assessment.max_score)?;
+        self.ensure_enrolled(sdb, &assessment, &student_id).await?;
+
+        let before = StudentScoreQ
+            .get(sdb, assessment_id.clone(), student_id.clone())
+            .await?;
+        let after = data.score_obtained;
+        let score = match &before {
+            Some(_) => {
+                StudentScoreQ
+                    .override_score(sdb, assessment_id.clone(), student_id.clone(), after)
+                    .await?
+            }
+            None => {
+                StudentScoreQ
+                    .enter(sdb, data, admin.user_id.clone())
+                    .await?
+            }
+        }
+        .ok_or_else(|| Error::NotFound("student score".into()))?;
+
+        let before = before.and_then(|s| s.score_obtained);
+        ActivityLogQ
+            .log(
+                sdb,
+                ActivityLog {
+                    id: None,
+                    school_id: assessment.school_id,
+                    user_id: Some(RecordId::new("users", admin.user_id.as_str())),
+                    action: ActionType::Update,
+                    entity_type: Some("student_score".into()),
+                    entity_id: score.id.as_ref().map(record_key),
+                    description: Some(format!(
+                        "Score overridden from {} to {after}",
+                        before
+                            .map(|b| b.to_string())
+                            .unwrap_or_else(|| "none".into())
+                    )),
+                    ip_address: None,
+                    metadata: Some(serde_json::json!({
+                        "assessment_id": assessment_id,
+                        "student_id": student_id,
+                        "before": before.map(|b| b.to_string()),
+                        "after": after.to_string(),
+                        "reason": reason,
+                    })),
+                    created_at: Datetime::now(),
+                },
+            )
+            .await?;
+
+        Ok(score)
+    }
+
+    /// Load an assessment, failing once its class's report cards are published
+    async fn open_assessment(
+        &self,
+        sdb: &Surreal<Client>,
+        assessment_id: &str,
+    ) -> DomainResult<Assessment> {
+        let assessment = AssessmentQ
+            .get_by_id(sdb, assessment_id.to_string())
+            .await?
+            .ok_or_else(|| Error::NotFound(format!("assessment {assessment_id}")))?;
+
+        let published = ReportCardQ
+            .is_published(
+                sdb,
+                record_key(&assessment.class_id),
+                record_key(&assessment.term_id),
+            )
+            .await?;
+        if published {
+            return Err(Error::Locked(
+                "report cards for this class and term are published".into(),
+            ));
+        }
+        Ok(assessment)
+    }
+
+    async fn ensure_enrolled(
+        &self,
+        sdb: &Surreal<Client>,
+        assessment: &Assessment,
+        student_id: &str,
+    ) -> DomainResult<()> {
+        let student = StudentQ
+            .get_by_id(sdb, student_id.to_string())
+            .await?
+            .ok_or_else(|| Error::NotFound(format!("student {student_id}")))?;
+
+        let enrolled = student.status == StudentStatus::Active
+            && student.school_id == assessment.school_id
+            && student.current_class_id.as_ref() == Some(&assessment.class_id);
+        if !enrolled {
+            return Err(Error::Validation(format!(
+                "student {} is not enrolled in the assessment's class",
+                student.admission_number
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Scores must lie between zero and the assessment's `max_score`
+pub fn validate_score(score: Decimal, max_score: Decimal) -> DomainResult<()> {
+    if score < Decimal::ZERO {
+        return Err(Error::Validation(format!("score {score} is negative")));
+    }
+    if score > max_score {
+        return Err(Error::Validation(format!(
+            "score {score} is above the maximum of {max_score}"
+        )));
+    }
+    Ok(())
+}
+
+/// Version a score sheet cell carries for conflict detection
+pub fn score_version(score: &StudentScore) -> String {
+    score.updated_at.to_string()
+}
+
+/// The stored score must still be the one the caller saw
+fn check_version(current: Option<&StudentScore>, seen: Option<&str>) -> DomainResult<()> {
+    if current.map(score_version).as_deref() != seen {
+        return Err(changed_meanwhile());
+    }
+    Ok(())
+}
+
+fn verified_locked() -> Error {
+    Error::Locked("score has been verified; ask an admin to unlock it".into())
+}
+
+fn changed_meanwhile() -> Error {
+    Error::Conflict("score was changed by someone else; reload to see it".into())
+}
+
+fn column_label(assessment: &Assessment) -> String {
+    if let Some(name) = &assessment.assessment_name {
+        return name.clone();
+    }
+    match assessment.assessment_type {
+        AssessmentType::Ca1 => "CA 1",
+        AssessmentType::Ca2 => "CA 2",
+        AssessmentType::Ca3 => "CA 3",
+        AssessmentType::Exam => "Exam",
+        AssessmentType::Test => "Test",
+        AssessmentType::Assignment => "Assignment",
+    }
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_scores_within_range() {
+        let max = Decimal::new(40, 0);
+        assert!(validate_score(Decimal::ZERO, max).is_ok());
+        assert!(validate_score(Decimal::new(405, 1), max).is_err());
+        assert!(validate_score(Decimal::new(40, 0), max).is_ok());
+        assert!(validate_score(Decimal::new(-1, 1), max).is_err());
+    }
+
+    #[test]
+    fn detects_changed_scores() {
+        let score = StudentScore {
+            id: None,
+            assessment_id: RecordId::new("assessments", "a1"),
+            student_id: RecordId::new("students", "s1"),
+            score_obtained: Some(Decimal::new(12, 0)),
+            remarks: None,
+            entered_by: None,
+            entered_at: Datetime::now(),
+            updated_at: Datetime::now(),
+            verified: false,
+            verified_by: None,
+            verified_at: None,
+        };
+        let version = score_version(&score);
+
+        assert!(check_version(None, None).is_ok());
+        assert!(check_version(Some(&score), Some(&version)).is_ok());
+        assert!(matches!(
+            check_version(Some(&score), None),
+            Err(Error::Conflict(_))
+        ));
+        assert!(matches!(
+            check_version(None, Some(&version)),
+            Err(Error::Conflict(_))
+        ));
+    }
+}
@@ -0,0 +1,121 @@
+use std::{
+    env,
+    future::Future,
+    path::{Component, Path, PathBuf},
+    sync::OnceLock,
+};
+
+use crate::error::{DomainResult, Error};
+
+/// Directory uploaded files are kept in (default `./uploads`)
+const STORAGE_DIR_VAR: &str = "FILE_STORAGE_DIR";
+
+static FILE_STORE: OnceLock<LocalStore> = OnceLock::new();
+
+/// Somewhere to keep uploaded files, addressed by slash-separated keys such
+/// as `schools/s1/messages/abc-report.pdf`
+pub trait FileStore {
+    fn put(&self, key: &str, bytes: &[u8]) -> impl Future<Output = DomainResult<()>> + Send;
+
+    fn get(&self, key: &str) -> impl Future<Output = DomainResult<Vec<u8>>> + Send;
+
+    fn delete(&self, key: &str) -> impl Future<Output = DomainResult<()>> + Send;
+}
+
+/// Files on the server's disk
+pub struct LocalStore {
+    root: PathBuf,
+}
+
+impl LocalStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    pub fn from_env() -> Self {
+        Self::new(env::var(STORAGE_DIR_VAR).unwrap_or_else(|_| "uploads".into()))
+    }
+
+    /// The file for a key; keys can't climb out of the root
+    fn path(&self, key: &str) -> DomainResult<PathBuf> {
+        let relative = Path::new(key);
+        let plain = relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)));
+        if key.is_empty() || !plain {
+            return Err(Error::Validation(format!("invalid file key {key}")));
+        }
+        Ok(self.root.join(relative))
+    }
+}
+
+impl FileStore for LocalStore {
+    async fn put(&self, key: &str, bytes: &[u8]) -> DomainResult<()> {
+        let path = self.path(key)?;
+        if let Some(dir) = path.parent() {
+            tokio::fs::create_dir_all(dir)
+                .await
+                .map_err(storage_error)?;
+        }
+        tokio::fs::write(&path, bytes).await.map_err(storage_error)
+    }
+
+    async fn get(&self, key: &str) -> DomainResult<Vec<u8>> {
+        let path = self.path(key)?;
+        tokio::fs::read(&path).await.map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => Error::NotFound(format!("file {key}")),
+            _ => storage_error(e),
+        })
+    }
+
+    async fn delete(&self, key: &str) -> DomainResult<()> {
+        match tokio::fs::remove_file(self.path(key)?).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(storage_error(e)),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The process-wide file store, configured from the environment on first use
+pub fn file_store() -> &'static LocalStore {
+    FILE_STORE.get_or_init(LocalStore::from_env)
+}
+
+fn storage_error(e: std::io::Error) -> Error {
+    Error::Validation(format!("file storage: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn stores_files_under_their_keys() {
+        let root = env::temp_dir().join(format!("file-store-test-{}", std::process::id()));
+        let store = LocalStore::new(&root);
+
+        store
+            .put("schools/s1/messages/a-note.txt", b"see you at 3pm")
+            .await
+            .unwrap();
+        assert_eq!(
+            store.get("schools/s1/messages/a-note.txt").await.unwrap(),
+            b"see you at 3pm"
+        );
+        store
+            .delete("schools/s1/messages/a-note.txt")
+            .await
+            .unwrap();
+        assert!(matches!(
+            store.get("schools/s1/messages/a-note.txt").await,
+            Err(Error::NotFound(_))
+        ));
+        assert!(matches!(
+            store.put("../escape.txt", b"x").await,
+            Err(Error::Validation(_))
+        ));
+        assert!(store.path("/etc/passwd").is_err());
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
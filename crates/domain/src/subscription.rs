@@ -0,0 +1,286 @@
+use chrono::{DateTime, Duration, Utc};
+use db::queries::{SchoolQ, SmsLogQ, StudentQ};
+use shared::{
+    helpers::{api_responses::SubscriptionOverview, surreal_util::record_key},
+    models::{School, SubscriptionPlan, SubscriptionStatus},
+};
+use surrealdb::{Surreal, engine::remote::ws::Client};
+use surrealdb_types::Datetime;
+
+use crate::error::{DomainResult, Error};
+
+/// Days a school keeps full use after its subscription runs out, before it
+/// becomes read-only
+pub const GRACE_DAYS: u32 = 7;
+
+/// Parts of the product only some plans include
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feature {
+    OnlinePayments,
+    PdfReportCards,
+}
+
+impl Feature {
+    fn included_in(&self, plan: &SubscriptionPlan) -> bool {
+        let limits = plan.limits();
+        match self {
+            Feature::OnlinePayments => limits.online_payments,
+            Feature::PdfReportCards => limits.pdf_report_cards,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Feature::OnlinePayments => "Online payments",
+            Feature::PdfReportCards => "PDF report cards",
+        }
+    }
+}
+
+/// Where a school stands with its subscription at a given moment
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Standing {
+    Current,
+    /// Run out, but still fully usable until `ends`
+    Grace {
+        ends: DateTime<Utc>,
+    },
+    ReadOnly,
+}
+
+/// Plan limits and subscription expiry. Services call the `ensure_*` checks
+/// before doing what a plan restricts.
+pub struct SubscriptionService;
+
+impl SubscriptionService {
+    /// The school's plan, its limits and how much of them is used
+    pub async fn overview(
+        &self,
+        sdb: &Surreal<Client>,
+        school_id: String,
+    ) -> DomainResult<SubscriptionOverview> {
+        let school = self.school(sdb, &school_id).await?;
+        let standing = standing(&school, Utc::now());
+        Ok(SubscriptionOverview {
+            limits: school.subscription_plan.limits(),
+            students: StudentQ.count_active(sdb, school_id.clone()).await?,
+            sms_this_month: SmsLogQ.count_this_month(sdb, school_id).await?,
+            grace_ends_at: match standing {
+                Standing::Grace { ends } => Some(Datetime::from(ends)),
+                _ => None,
+            },
+            read_only: standing == Standing::ReadOnly,
+            plan: school.subscription_plan,
+            status: school.subscription_status,
+            expiry_date: school.subscription_expiry_date,
+        })
+    }
+
+    /// The school, provided its subscription still lets it change records
+    pub async fn ensure_writable(
+        &self,
+        sdb: &Surreal<Client>,
+        school_id: &str,
+    ) -> DomainResult<School> {
+        let school = self.school(sdb, school_id).await?;
+        match standing(&school, Utc::now()) {
+            Standing::ReadOnly => Err(Error::Subscription(format!(
+                "{}'s subscription has expired, so records are read-only until it is renewed",
+                school.school_name
+            ))),
+            _ => Ok(school),
+        }
+    }
+
+    /// Fails unless the school's plan includes the feature
+    pub async fn ensure_feature(
+        &self,
+        sdb: &Surreal<Client>,
+        school_id: &str,
+        feature: Feature,
+    ) -> DomainResult<()> {
+        let school = self.school(sdb, school_id).await?;
+        check_feature(&school.subscription_plan, feature)
+    }
+
+    /// Whether the school's plan includes the feature, for leaving out what
+    /// it doesn't rather than failing
+    pub async fn has_feature(
+        &self,
+        sdb: &Surreal<Client>,
+        school_id: &str,
+        feature: Feature,
+    ) -> DomainResult<bool> {
+        let school = self.school(sdb, school_id).await?;
+        Ok(feature.included_in(&school.subscription_plan))
+    }
+
+    /// Fails if adding `adding` students would take the school past its
+    /// plan's limit
+    pub async fn ensure_room_for_students(
+        &self,
+        sdb: &Surreal<Client>,
+        school_id: &str,
+        adding: usize,
+    ) -> DomainResult<()> {
+        let school = self.school(sdb, school_id).await?;
+        let enrolled = StudentQ.count_active(sdb, school_id.to_string()).await?;
+        check_students(&school.subscription_plan, enrolled, adding as u64)
+    }
+
+    /// SMS the school may still send this month; None is unlimited
+    pub async fn sms_left(
+        &self,
+        sdb: &Surreal<Client>,
+        school_id: &str,
+    ) -> DomainResult<Option<u64>> {
+        let school = self.school(sdb, school_id).await?;
+        let Some(quota) = school.subscription_plan.limits().monthly_sms else {
+            return Ok(None);
+        };
+        let sent = SmsLogQ.count_this_month(sdb, school_id.to_string()).await?;
+        Ok(Some(u64::from(quota).saturating_sub(sent)))
+    }
+
+    /// Mark schools whose grace period has passed as expired, returning how
+    /// many were
+    pub async fn expire_lapsed(&self, sdb: &Surreal<Client>) -> DomainResult<usize> {
+        let mut expired = 0;
+        for school in SchoolQ.get_lapsed(sdb, GRACE_DAYS).await? {
+            if let Some(id) = &school.id {
+                SchoolQ
+                    .update_subscription_status(sdb, record_key(id), SubscriptionStatus::Expired)
+                    .await?;
+                expired += 1;
+            }
+        }
+        Ok(expired)
+    }
+
+    async fn school(&self, sdb: &Surreal<Client>, school_id: &str) -> DomainResult<School> {
+        SchoolQ
+            .get_by_id(sdb, school_id.to_string())
+            .await?
+            .ok_or_else(|| Error::NotFound(format!("school {school_id}")))
+    }
+}
+
+/// Expired and cancelled schools are read-only, and so are active and trial
+/// schools once the grace period after their expiry date has passed, even
+/// before the expiry job has caught up with them
+pub fn standing(school: &School, now: DateTime<Utc>) -> Standing {
+    match school.subscription_status {
+        SubscriptionStatus::Expired | SubscriptionStatus::Cancelled => Standing::ReadOnly,
+        SubscriptionStatus::Active | SubscriptionStatus::Trial => {
+            match school.subscription_expiry_date.as_deref() {
+                Some(expiry) if *expiry <= now => {
+                    let ends = *expiry + Duration::days(GRACE_DAYS.into());
+                    if now < ends {
+                        Standing::Grace { ends }
+                    } else {
+                        Standing::ReadOnly
+                    }
+                }
+                _ => Standing::Current,
+            }
+        }
+    }
+}
+
+fn check_feature(plan: &SubscriptionPlan, feature: Feature) -> DomainResult<()> {
+    if feature.included_in(plan) {
+        Ok(())
+    } else {
+        Err(Error::Subscription(format!(
+            "{} come with the Premium and Enterprise plans; this school is on {}",
+            feature.name(),
+            plan_name(plan)
+        )))
+    }
+}
+
+fn check_students(plan: &SubscriptionPlan, enrolled: u64, adding: u64) -> DomainResult<()> {
+    match plan.limits().max_students {
+        Some(max) if enrolled + adding > u64::from(max) => Err(Error::Subscription(format!(
+            "the {} plan allows {max} students and this school has {enrolled}, so {adding} more can't be added; upgrade to enrol more",
+            plan_name(plan)
+        ))),
+        _ => Ok(()),
+    }
+}
+
+fn plan_name(plan: &SubscriptionPlan) -> &'static str {
+    match plan {
+        SubscriptionPlan::Starter => "Starter",
+        SubscriptionPlan::Basic => "Basic",
+        SubscriptionPlan::Standard => "Standard",
+        SubscriptionPlan::Premium => "Premium",
+        SubscriptionPlan::Enterprise => "Enterprise",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn school(status: SubscriptionStatus, expiry: Option<&str>) -> School {
+        School {
+            id: None,
+            school_name: "Greenfield College".into(),
+            school_address: None,
+            school_phone: None,
+            school_email: None,
+            school_logo_url: None,
+            school_motto: None,
+            total_students: 0,
+            subscription_plan: SubscriptionPlan::Basic,
+            subscription_expiry_date: expiry.map(|e| Datetime::from(at(e))),
+            subscription_status: status,
+            created_at: Datetime::now(),
+            updated_at: Datetime::now(),
+        }
+    }
+
+    fn at(rfc3339: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(rfc3339)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn becomes_read_only_after_the_grace_period() {
+        let now = at("2026-10-19T12:00:00Z");
+        let active = |expiry| standing(&school(SubscriptionStatus::Active, expiry), now);
+
+        assert_eq!(active(None), Standing::Current);
+        assert_eq!(active(Some("2026-11-01T00:00:00Z")), Standing::Current);
+        assert_eq!(
+            active(Some("2026-10-15T00:00:00Z")),
+            Standing::Grace {
+                ends: at("2026-10-22T00:00:00Z")
+            }
+        );
+        assert_eq!(active(Some("2026-10-01T00:00:00Z")), Standing::ReadOnly);
+        assert_eq!(
+            standing(&school(SubscriptionStatus::Expired, None), now),
+            Standing::ReadOnly
+        );
+    }
+
+    #[test]
+    fn gates_premium_features_and_student_numbers() {
+        assert!(check_feature(&SubscriptionPlan::Premium, Feature::OnlinePayments).is_ok());
+        assert!(check_feature(&SubscriptionPlan::Enterprise, Feature::PdfReportCards).is_ok());
+        assert!(matches!(
+            check_feature(&SubscriptionPlan::Standard, Feature::PdfReportCards),
+            Err(Error::Subscription(_))
+        ));
+
+        assert!(check_students(&SubscriptionPlan::Basic, 290, 10).is_ok());
+        assert!(matches!(
+            check_students(&SubscriptionPlan::Basic, 290, 11),
+            Err(Error::Subscription(_))
+        ));
+        assert!(check_students(&SubscriptionPlan::Enterprise, 50_000, 1_000).is_ok());
+    }
+}
@@ -0,0 +1,186 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::NaiveDate;
+use db::queries::{AssessmentQ, AttendanceQ, BulkQ, ClassQ, StudentQ, StudentScoreQ};
+use shared::{
+    helpers::{
+        api_responses::SyncOutcome,
+        create_update_dtos::{
+            EnterScoreDto, MarkAttendanceDto, OfflineAttendanceDto, OfflineScoreDto,
+        },
+        surreal_util::{record_key, to_surreal_datetime},
+    },
+    models::{AttendanceStatus, academic::ClassSubject, attendance::Attendance},
+};
+use surrealdb::{Surreal, engine::remote::ws::Client};
+use surrealdb_types::{Datetime, RecordId, SurrealValue};
+
+use crate::{
+    error::{DomainResult, Error},
+    scoring::ScoreService,
+    subscription::SubscriptionService,
+};
+
+/// Applies changes queued on a device while it was offline. Where the server
+/// copy was updated after the device recorded its change, the server copy wins.
+pub struct SyncService;
+
+impl SyncService {
+    /// Replay a class register saved offline; outcomes are in the order of `marks`
+    pub async fn attendance(
+        &self,
+        sdb: &Surreal<Client>,
+        class_id: String,
+        date: String,
+        marks: Vec<OfflineAttendanceDto>,
+        marked_by: String,
+    ) -> DomainResult<Vec<SyncOutcome>> {
+        let day = parse_register_date(&date)?;
+        let class = ClassQ
+            .get_by_id(sdb, class_id.clone())
+            .await?
+            .ok_or_else(|| Error::NotFound(format!("class {class_id}")))?;
+        SubscriptionService
+            .ensure_writable(sdb, &record_key(&class.school_id))
+            .await?;
+        let enrolled: HashSet<String> = StudentQ
+            .get_by_class(sdb, class_id.clone())
+            .await?
+            .iter()
+            .filter_map(|student| student.id.as_ref().map(record_key))
+            .collect();
+        let stored: HashMap<String, Datetime> = AttendanceQ
+            .get_by_class_and_date(sdb, class_id.clone(), date.clone())
+            .await?
+            .into_iter()
+            .filter_map(|row| Attendance::from_value(row).ok())
+            .map(|mark| (record_key(&mark.student_id), mark.updated_at))
+            .collect();
+
+        let mut outcomes = Vec::with_capacity(marks.len());
+        let mut records = Vec::new();
+        for mark in marks {
+            let outcome = if !enrolled.contains(&mark.student_id) {
+                SyncOutcome::Rejected("student is not in this class".into())
+            } else if !device_wins(stored.get(&mark.student_id), &mark.recorded_at) {
+                SyncOutcome::Superseded
+            } else {
+                let (arrival_time, reason) = match mark.status {
+                    AttendanceStatus::Present => (None, None),
+                    AttendanceStatus::Late => (mark.arrival_time, mark.reason),
+                    _ => (None, mark.reason),
+                };
+                records.push(MarkAttendanceDto {
+                    student_id: RecordId::new("students", mark.student_id.as_str()),
+                    class_id: RecordId::new("classes", class_id.as_str()),
+                    date: to_surreal_datetime(day),
+                    status: mark.status,
+                    arrival_time,
+                    reason,
+                });
+                SyncOutcome::Applied
+            };
+            outcomes.push(outcome);
+        }
+
+        if !records.is_empty() {
+            BulkQ
+                .mark_class_attendance(sdb, class_id, date, records, marked_by)
+                .await?;
+        }
+        Ok(outcomes)
+    }
+
+    /// Replay scores typed offline into one class subject's sheet; outcomes
+    /// are in the order of `edits`
+    pub async fn scores(
+        &self,
+        sdb: &Surreal<Client>,
+        class_subject: &ClassSubject,
+        edits: Vec<OfflineScoreDto>,
+        entered_by: String,
+    ) -> DomainResult<Vec<SyncOutcome>> {
+        let mut outcomes = Vec::with_capacity(edits.len());
+        for edit in edits {
+            let on_sheet = AssessmentQ
+                .get_by_id(sdb, edit.assessment_id.clone())
+                .await?
+                .is_some_and(|assessment| {
+                    assessment.class_id == class_subject.class_id
+                        && assessment.subject_id == class_subject.subject_id
+                });
+            if !on_sheet {
+                outcomes.push(SyncOutcome::Rejected(
+                    "assessment is not on this sheet".into(),
+                ));
+                continue;
+            }
+
+            let stored = StudentScoreQ
+                .get(sdb, edit.assessment_id.clone(), edit.student_id.clone())
+                .await?;
+            if !device_wins(stored.as_ref().map(|s| &s.updated_at), &edit.recorded_at) {
+                outcomes.push(SyncOutcome::Superseded);
+                continue;
+            }
+
+            let data = EnterScoreDto {
+                assessment_id: RecordId::new("assessments", edit.assessment_id.as_str()),
+                student_id: RecordId::new("students", edit.student_id.as_str()),
+                score_obtained: edit.score,
+                remarks: None,
+            };
+            outcomes.push(
+                match ScoreService.enter(sdb, data, entered_by.clone()).await {
+                    Ok(_) => SyncOutcome::Applied,
+                    Err(err @ Error::Db(_)) => return Err(err),
+                    Err(err) => SyncOutcome::Rejected(err.to_string()),
+                },
+            );
+        }
+        Ok(outcomes)
+    }
+}
+
+/// A change recorded on a device applies unless the server copy was updated
+/// after it; ties go to the device, which is replaying the user's latest intent
+pub fn device_wins(server_updated_at: Option<&Datetime>, recorded_at: &Datetime) -> bool {
+    server_updated_at.is_none_or(|updated_at| updated_at <= recorded_at)
+}
+
+/// Register dates are "YYYY-MM-DD" and can't be in the future
+pub fn parse_register_date(date: &str) -> DomainResult<NaiveDate> {
+    let day = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|_| Error::Validation(format!("invalid date {date}")))?;
+    if day > chrono::Local::now().date_naive() {
+        return Err(Error::Validation(
+            "cannot mark attendance for a future date".into(),
+        ));
+    }
+    Ok(day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    fn at(hour: u32) -> Datetime {
+        Utc.with_ymd_and_hms(2026, 3, 2, hour, 0, 0).unwrap().into()
+    }
+
+    #[test]
+    fn newer_server_copy_wins() {
+        assert!(device_wins(None, &at(9)));
+        assert!(device_wins(Some(&at(8)), &at(9)));
+        assert!(device_wins(Some(&at(9)), &at(9)));
+        assert!(!device_wins(Some(&at(10)), &at(9)));
+    }
+
+    #[test]
+    fn rejects_future_register_dates() {
+        assert!(parse_register_date("2026-03-02").is_ok());
+        assert!(parse_register_date("02/03/2026").is_err());
+        assert!(parse_register_date("2999-01-01").is_err());
+    }
+}
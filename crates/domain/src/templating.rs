@@ -0,0 +1,818 @@
+use std::collections::HashMap;
+
+use chrono::{NaiveDate, Utc};
+use db::queries::{InvoiceQ, MessageTemplateQ, SchoolQ, StudentQ};
+use serde::{Serialize, de::DeserializeOwned};
+use shared::{
+    helpers::{
+        api_responses::{SmsEncoding, SmsSegments, TemplatePreview},
+        create_update_dtos::{PreviewTemplateDto, SaveMessageTemplateDto},
+        surreal_util::{record_key, to_naive_date},
+    },
+    i18n::{Locale, Localizer, Translations},
+    models::{
+        AttendanceStatus, MessageChannel, NotificationType, School, SmsType, Student, User,
+        academic::Term,
+        attendance::Attendance,
+        communication::{Announcement, Event, Message, MessageTemplate},
+        fee_management::Invoice,
+    },
+};
+use surrealdb::{Surreal, engine::remote::ws::Client};
+use surrealdb_types::Decimal;
+
+use crate::{
+    error::{DomainResult, Error},
+    locale::LocaleService,
+    subscription::SubscriptionService,
+};
+
+const MAX_TEMPLATE_LEN: usize = 5000;
+
+/// Variables a template can use, by group; a message kind offers some of the groups
+const VARIABLES: [(&str, &[&str]); 9] = [
+    (
+        "school",
+        &[
+            "school_name",
+            "school_address",
+            "school_phone",
+            "school_email",
+            "school_motto",
+        ],
+    ),
+    ("recipient", &["first_name", "last_name"]),
+    (
+        "student",
+        &["first_name", "middle_name", "last_name", "admission_number"],
+    ),
+    (
+        "invoice",
+        &[
+            "invoice_number",
+            "total_amount",
+            "amount_paid",
+            "balance",
+            "due_date",
+        ],
+    ),
+    ("attendance", &["date", "status", "arrival_time", "reason"]),
+    ("term", &["term_name"]),
+    ("announcement", &["title", "message"]),
+    (
+        "event",
+        &["event_name", "event_date", "event_time", "event_location"],
+    ),
+    ("message", &["subject", "message_body"]),
+];
+
+/// GSM 03.38 characters sent as one septet
+const GSM7_BASIC: &str = "@£$¥èéùìòÇ\nØø\rÅåΔ_ΦΓΛΩΠΨΣΘΞÆæßÉ !\"#¤%&'()*+,-./0123456789:;<=>?\
+    ¡ABCDEFGHIJKLMNOPQRSTUVWXYZÄÖÑÜ§¿abcdefghijklmnopqrstuvwxyzäöñüà";
+/// GSM 03.38 characters sent as an escape plus one septet
+const GSM7_EXTENSION: &str = "\u{c}^{}\\[~]|€";
+
+/// Which message a template words: one per channel and message type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TemplateKey {
+    Sms(SmsType),
+    Email(NotificationType),
+    Notification(NotificationType),
+}
+
+impl TemplateKey {
+    /// The key for a channel and the snake_case name of its message type;
+    /// push notifications use the in-app wording and WhatsApp the SMS wording
+    pub fn parse(channel: &MessageChannel, name: &str) -> DomainResult<Self> {
+        let unknown = || Error::Validation(format!("unknown message type {name}"));
+        Ok(match channel {
+            MessageChannel::Sms | MessageChannel::Whatsapp => {
+                TemplateKey::Sms(from_name(name).ok_or_else(unknown)?)
+            }
+            MessageChannel::Email => TemplateKey::Email(from_name(name).ok_or_else(unknown)?),
+            MessageChannel::Notification | MessageChannel::Push => {
+                TemplateKey::Notification(from_name(name).ok_or_else(unknown)?)
+            }
+        })
+    }
+
+    pub fn channel(&self) -> MessageChannel {
+        match self {
+            TemplateKey::Sms(_) => MessageChannel::Sms,
+            TemplateKey::Email(_) => MessageChannel::Email,
+            TemplateKey::Notification(_) => MessageChannel::Notification,
+        }
+    }
+
+    /// The snake_case name stored as `MessageTemplate::template_key`
+    pub fn name(&self) -> String {
+        match self {
+            TemplateKey::Sms(sms_type) => to_name(sms_type),
+            TemplateKey::Email(kind) | TemplateKey::Notification(kind) => to_name(kind),
+        }
+    }
+
+    /// Variable groups this kind of message can use
+    fn groups(&self) -> &'static [&'static str] {
+        use NotificationType as N;
+
+        match self {
+            TemplateKey::Sms(SmsType::FeeReminder)
+            | TemplateKey::Email(N::FeeReminder)
+            | TemplateKey::Notification(N::FeeReminder) => {
+                &["school", "recipient", "student", "invoice"]
+            }
+            TemplateKey::Sms(SmsType::AttendanceAlert)
+            | TemplateKey::Email(N::AttendanceAlert)
+            | TemplateKey::Notification(N::AttendanceAlert) => {
+                &["school", "recipient", "student", "attendance"]
+            }
+            TemplateKey::Sms(SmsType::ResultNotification)
+            | TemplateKey::Email(N::ResultPublished)
+            | TemplateKey::Notification(N::ResultPublished) => {
+                &["school", "recipient", "student", "term"]
+            }
+            TemplateKey::Sms(SmsType::Announcement)
+            | TemplateKey::Email(N::Announcement)
+            | TemplateKey::Notification(N::Announcement) => {
+                &["school", "recipient", "announcement"]
+            }
+            TemplateKey::Sms(SmsType::Event)
+            | TemplateKey::Email(N::Event)
+            | TemplateKey::Notification(N::Event) => &["school", "recipient", "event"],
+            TemplateKey::Email(N::Message) | TemplateKey::Notification(N::Message) => {
+                &["school", "recipient", "message"]
+            }
+            TemplateKey::Sms(SmsType::Other)
+            | TemplateKey::Email(N::System)
+            | TemplateKey::Notification(N::System) => &["school", "recipient"],
+        }
+    }
+
+    /// Every `group.field` this kind of message can use
+    pub fn variables(&self) -> Vec<String> {
+        let groups = self.groups();
+        VARIABLES
+            .iter()
+            .filter(|(group, _)| groups.contains(group))
+            .flat_map(|(group, fields)| fields.iter().map(move |field| format!("{group}.{field}")))
+            .collect()
+    }
+
+    /// Catalog key of the built-in wording. Email shares the in-app wording.
+    fn catalog_key(&self) -> String {
+        match self {
+            TemplateKey::Sms(_) => format!("template.sms.{}", self.name()),
+            TemplateKey::Email(_) | TemplateKey::Notification(_) => {
+                format!("template.notification.{}", self.name())
+            }
+        }
+    }
+
+    /// Reject a template that doesn't parse or uses variables this kind of
+    /// message can't fill
+    pub fn check(&self, template: &str) -> DomainResult<()> {
+        let allowed = self.variables();
+        let unknown: Vec<String> = variables(template)?
+            .into_iter()
+            .filter(|name| !allowed.contains(name))
+            .collect();
+        if unknown.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Validation(format!(
+                "unknown variables: {}",
+                unknown.join(", ")
+            )))
+        }
+    }
+}
+
+fn to_name<T: Serialize>(value: &T) -> String {
+    serde_json::to_value(value)
+        .ok()
+        .and_then(|v| v.as_str().map(str::to_owned))
+        .unwrap_or_default()
+}
+
+fn from_name<T: DeserializeOwned>(name: &str) -> Option<T> {
+    serde_json::from_value(serde_json::Value::String(name.to_string())).ok()
+}
+
+/// A template split into literal text and `{{ variable }}` references
+enum Part<'a> {
+    Text(&'a str),
+    Var(&'a str),
+}
+
+fn parse(template: &str) -> DomainResult<Vec<Part<'_>>> {
+    let mut parts = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        if start > 0 {
+            parts.push(Part::Text(&rest[..start]));
+        }
+        let after = &rest[start + 2..];
+        let end = after
+            .find("}}")
+            .ok_or_else(|| Error::Validation("unclosed {{ in template".into()))?;
+        let name = after[..end].trim();
+        let valid = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '.');
+        if !valid {
+            return Err(Error::Validation(format!(
+                "invalid variable {{{{{}}}}}",
+                &after[..end]
+            )));
+        }
+        parts.push(Part::Var(name));
+        rest = &after[end + 2..];
+    }
+    if !rest.is_empty() {
+        parts.push(Part::Text(rest));
+    }
+    Ok(parts)
+}
+
+/// The distinct variables a template refers to, in order of first use
+pub fn variables(template: &str) -> DomainResult<Vec<String>> {
+    let mut names: Vec<String> = Vec::new();
+    for part in parse(template)? {
+        if let Part::Var(name) = part
+            && !names.iter().any(|n| n == name)
+        {
+            names.push(name.to_string());
+        }
+    }
+    Ok(names)
+}
+
+/// A template with its variables filled in
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rendered {
+    pub text: String,
+    /// Variables that had no value and were left blank
+    pub missing: Vec<String>,
+}
+
+/// Substitute each `{{ group.field }}` in `template` from `context`
+pub fn render(template: &str, context: &TemplateContext) -> DomainResult<Rendered> {
+    let mut text = String::with_capacity(template.len());
+    let mut missing: Vec<String> = Vec::new();
+    for part in parse(template)? {
+        match part {
+            Part::Text(literal) => text.push_str(literal),
+            Part::Var(name) => match context.values.get(name) {
+                Some(value) => text.push_str(value),
+                None if !missing.iter().any(|m| m == name) => missing.push(name.to_string()),
+                None => {}
+            },
+        }
+    }
+    Ok(Rendered { text, missing })
+}
+
+/// How a text is split into SMS segments: 160 GSM-7 or 70 UCS-2 characters in
+/// one message, 153 or 67 per part once it has to be concatenated
+pub fn sms_segments(text: &str) -> SmsSegments {
+    let gsm7 = text
+        .chars()
+        .all(|c| GSM7_BASIC.contains(c) || GSM7_EXTENSION.contains(c));
+    let (encoding, characters, single, multi) = if gsm7 {
+        let septets = text
+            .chars()
+            .map(|c| if GSM7_EXTENSION.contains(c) { 2 } else { 1 })
+            .sum();
+        (SmsEncoding::Gsm7, septets, 160, 153)
+    } else {
+        let units = text.chars().map(char::len_utf16).sum();
+        (SmsEncoding::Ucs2, units, 70, 67)
+    };
+    let segments = match characters {
+        0 => 0,
+        n if n <= single => 1,
+        n => n.div_ceil(multi),
+    };
+    let capacity = if segments > 1 {
+        segments * multi
+    } else {
+        single
+    };
+    SmsSegments {
+        encoding,
+        characters,
+        segments,
+        remaining: capacity - characters,
+    }
+}
+
+/// Values for a template's variables, filled from the records a message is about
+#[derive(Debug, Clone, Default)]
+pub struct TemplateContext {
+    values: HashMap<String, String>,
+}
+
+impl TemplateContext {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set a variable, or clear it when the record has no value
+    pub fn set(&mut self, name: &str, value: Option<String>) -> &mut Self {
+        match value.filter(|v| !v.is_empty()) {
+            Some(value) => self.values.insert(name.to_string(), value),
+            None => self.values.remove(name),
+        };
+        self
+    }
+
+    /// A variable's value, if the records had one
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.values.get(name).map(String::as_str)
+    }
+
+    pub fn school(&mut self, school: &School) -> &mut Self {
+        self.set("school.school_name", Some(school.school_name.clone()))
+            .set("school.school_address", school.school_address.clone())
+            .set("school.school_phone", school.school_phone.clone())
+            .set("school.school_email", school.school_email.clone())
+            .set("school.school_motto", school.school_motto.clone())
+    }
+
+    pub fn recipient(&mut self, user: &User) -> &mut Self {
+        self.set("recipient.first_name", Some(user.first_name.clone()))
+            .set("recipient.last_name", Some(user.last_name.clone()))
+    }
+
+    pub fn student(&mut self, student: &Student) -> &mut Self {
+        self.set("student.first_name", Some(student.first_name.clone()))
+            .set("student.middle_name", student.middle_name.clone())
+            .set("student.last_name", Some(student.last_name.clone()))
+            .set(
+                "student.admission_number",
+                Some(student.admission_number.clone()),
+            )
+    }
+
+    pub fn invoice(&mut self, invoice: &Invoice, i18n: &Localizer) -> &mut Self {
+        self.set(
+            "invoice.invoice_number",
+            Some(invoice.invoice_number.clone()),
+        )
+        .set(
+            "invoice.total_amount",
+            Some(i18n.money(invoice.total_amount)),
+        )
+        .set("invoice.amount_paid", Some(i18n.money(invoice.amount_paid)))
+        .set("invoice.balance", Some(i18n.money(invoice.balance)))
+        .set(
+            "invoice.due_date",
+            to_naive_date(&invoice.due_date).map(|d| i18n.date(d)),
+        )
+    }
+
+    pub fn attendance(&mut self, attendance: &Attendance, i18n: &Localizer) -> &mut Self {
+        let status = match attendance.status {
+            AttendanceStatus::Present => "attendance.present",
+            AttendanceStatus::Absent => "attendance.absent",
+            AttendanceStatus::Late => "attendance.late",
+            AttendanceStatus::Excused => "attendance.excused",
+        };
+        self.set(
+            "attendance.date",
+            to_naive_date(&attendance.date).map(|d| i18n.date(d)),
+        )
+        .set("attendance.status", Some(i18n.t(status).to_lowercase()))
+        .set("attendance.arrival_time", attendance.arrival_time.clone())
+        .set("attendance.reason", attendance.reason.clone())
+    }
+
+    pub fn term(&mut self, term: &Term, i18n: &Localizer) -> &mut Self {
+        let name = term.term_name.clone().unwrap_or_else(|| {
+            i18n.format(
+                "transcript.term",
+                &[("number", &term.term_number.to_string())],
+            )
+        });
+        self.set("term.term_name", Some(name))
+    }
+
+    pub fn announcement(&mut self, announcement: &Announcement) -> &mut Self {
+        self.set("announcement.title", Some(announcement.title.clone()))
+            .set("announcement.message", Some(announcement.message.clone()))
+    }
+
+    pub fn event(&mut self, event: &Event, i18n: &Localizer) -> &mut Self {
+        self.set("event.event_name", Some(event.event_name.clone()))
+            .set(
+                "event.event_date",
+                to_naive_date(&event.event_date).map(|d| i18n.date(d)),
+            )
+            .set("event.event_time", event.event_time.clone())
+            .set("event.event_location", event.event_location.clone())
+    }
+
+    pub fn message(&mut self, message: &Message) -> &mut Self {
+        self.set("message.subject", message.subject.clone())
+            .set("message.message_body", Some(message.message_body.clone()))
+    }
+
+    /// Made-up values for every variable a kind of message can use, for previews
+    pub fn sample(key: &TemplateKey, i18n: &Localizer) -> Self {
+        let today = Utc::now().date_naive();
+        let mut context = Self::new();
+        for name in key.variables() {
+            let value = sample_value(&name, i18n, today);
+            context.set(&name, value);
+        }
+        context
+    }
+}
+
+fn sample_value(name: &str, i18n: &Localizer, today: NaiveDate) -> Option<String> {
+    let value = match name {
+        "school.school_name" => "Greenfield Academy".into(),
+        "school.school_address" => "12 School Road".into(),
+        "school.school_phone" => "+2348012345678".into(),
+        "school.school_email" => "office@greenfield.example".into(),
+        "school.school_motto" => "Knowledge and service".into(),
+        "recipient.first_name" => "Ngozi".into(),
+        "recipient.last_name" | "student.last_name" => "Okafor".into(),
+        "student.first_name" => "Ada".into(),
+        "student.middle_name" => "Chioma".into(),
+        "student.admission_number" => "ADM/2026/001".into(),
+        "invoice.invoice_number" => "INV-2026-0001".into(),
+        "invoice.total_amount" => i18n.money(Decimal::from(150_000)),
+        "invoice.amount_paid" => i18n.money(Decimal::from(50_000)),
+        "invoice.balance" => i18n.money(Decimal::from(100_000)),
+        "invoice.due_date" => i18n.date(today + chrono::Duration::days(14)),
+        "attendance.date" | "event.event_date" => i18n.date(today),
+        "attendance.status" => i18n.t("attendance.absent").to_lowercase(),
+        "attendance.arrival_time" => "08:15".into(),
+        "attendance.reason" => "Unwell".into(),
+        "term.term_name" => i18n.format("transcript.term", &[("number", "1")]),
+        "announcement.title" => "Mid-term break".into(),
+        "announcement.message" => "School closes on Friday and resumes on Monday.".into(),
+        "event.event_name" => "Inter-house sports".into(),
+        "event.event_time" => "10:00".into(),
+        "event.event_location" => "School field".into(),
+        "message.subject" => "Homework".into(),
+        "message.message_body" => "Please check this week's homework diary.".into(),
+        _ => return None,
+    };
+    Some(value)
+}
+
+/// A message ready to send
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderedMessage {
+    /// Email subject or notification title; None for SMS
+    pub subject: Option<String>,
+    pub body: String,
+}
+
+/// Renders one kind of message for many recipients, looking each language's
+/// template up only once
+pub struct TemplateCache {
+    school_id: String,
+    templates: Vec<(TemplateKey, Locale, Option<String>, String)>,
+}
+
+impl TemplateCache {
+    pub fn new(school_id: String) -> Self {
+        Self {
+            school_id,
+            templates: Vec::new(),
+        }
+    }
+
+    pub async fn render(
+        &mut self,
+        sdb: &Surreal<Client>,
+        key: &TemplateKey,
+        i18n: &Localizer,
+        context: &TemplateContext,
+    ) -> DomainResult<RenderedMessage> {
+        let cached = self
+            .templates
+            .iter()
+            .position(|(k, locale, _, _)| k == key && *locale == i18n.locale);
+        let index = match cached {
+            Some(index) => index,
+            None => {
+                let (subject, body) = TemplateService
+                    .template(sdb, self.school_id.clone(), key, i18n.locale)
+                    .await?;
+                self.templates
+                    .push((key.clone(), i18n.locale, subject, body));
+                self.templates.len() - 1
+            }
+        };
+        let (_, _, subject, body) = &self.templates[index];
+        let subject = match subject {
+            Some(subject) => Some(render(subject, context)?.text),
+            None => None,
+        };
+        Ok(RenderedMessage {
+            subject,
+            body: render(body, context)?.text,
+        })
+    }
+}
+
+/// Per-school wording for SMS, email and in-app messages, falling back to the
+/// built-in wording in the recipient's language
+pub struct TemplateService;
+
+impl TemplateService {
+    /// The subject and body in use for a message kind and language: the
+    /// school's template for that language, then its template for any
+    /// language, then the built-in one
+    pub async fn template(
+        &self,
+        sdb: &Surreal<Client>,
+        school_id: String,
+        key: &TemplateKey,
+        locale: Locale,
+    ) -> DomainResult<(Option<String>, String)> {
+        let templates = MessageTemplateQ
+            .find(sdb, school_id, key.channel(), key.name())
+            .await?;
+        let custom = templates
+            .iter()
+            .find(|t| t.locale == Some(locale))
+            .or_else(|| templates.iter().find(|t| t.locale.is_none()));
+        if let Some(template) = custom {
+            return Ok((template.subject.clone(), template.body.clone()));
+        }
+
+        let translations = Translations::global();
+        let catalog_key = key.catalog_key();
+        let subject = match key {
+            TemplateKey::Sms(_) => None,
+            _ => Some(
+                translations
+                    .text(locale, &format!("{catalog_key}.subject"))
+                    .to_string(),
+            ),
+        };
+        Ok((subject, translations.text(locale, &catalog_key).to_string()))
+    }
+
+    /// Render a message for one recipient in the language of `i18n`; variables
+    /// without a value are left blank
+    pub async fn render(
+        &self,
+        sdb: &Surreal<Client>,
+        school_id: String,
+        key: &TemplateKey,
+        i18n: &Localizer,
+        context: &TemplateContext,
+    ) -> DomainResult<RenderedMessage> {
+        let (subject, body) = self.template(sdb, school_id, key, i18n.locale).await?;
+        let subject = match subject {
+            Some(subject) => Some(render(&subject, context)?.text),
+            None => None,
+        };
+        Ok(RenderedMessage {
+            subject,
+            body: render(&body, context)?.text,
+        })
+    }
+
+    /// Templates the school has customised
+    pub async fn list(
+        &self,
+        sdb: &Surreal<Client>,
+        school_id: String,
+    ) -> DomainResult<Vec<MessageTemplate>> {
+        Ok(MessageTemplateQ.get_by_school(sdb, school_id).await?)
+    }
+
+    pub async fn save(
+        &self,
+        sdb: &Surreal<Client>,
+        school_id: String,
+        mut data: SaveMessageTemplateDto,
+    ) -> DomainResult<()> {
+        let key = TemplateKey::parse(&data.channel, &data.template_key)?;
+        if data.body.trim().is_empty() {
+            return Err(Error::Validation("template body is empty".into()));
+        }
+        if data.body.len() > MAX_TEMPLATE_LEN {
+            return Err(Error::Validation(format!(
+                "template is longer than {MAX_TEMPLATE_LEN} characters"
+            )));
+        }
+        key.check(&data.body)?;
+        match (&key, &data.subject) {
+            (TemplateKey::Sms(_), Some(_)) => {
+                return Err(Error::Validation("SMS templates have no subject".into()));
+            }
+            (_, Some(subject)) => key.check(subject)?,
+            _ => {}
+        }
+        data.channel = key.channel();
+        data.template_key = key.name();
+        SubscriptionService.ensure_writable(sdb, &school_id).await?;
+        MessageTemplateQ.upsert(sdb, school_id, data).await?;
+        Ok(())
+    }
+
+    /// Go back to the built-in wording for a message kind and language
+    pub async fn reset(
+        &self,
+        sdb: &Surreal<Client>,
+        school_id: String,
+        channel: MessageChannel,
+        template_key: String,
+        locale: Option<Locale>,
+    ) -> DomainResult<()> {
+        let key = TemplateKey::parse(&channel, &template_key)?;
+        SubscriptionService.ensure_writable(sdb, &school_id).await?;
+        MessageTemplateQ
+            .delete(sdb, school_id, channel, key.name(), locale)
+            .await?;
+        Ok(())
+    }
+
+    /// Render a draft or saved template with sample data, or with a real
+    /// student and invoice of the school when given
+    pub async fn preview(
+        &self,
+        sdb: &Surreal<Client>,
+        school_id: String,
+        data: PreviewTemplateDto,
+    ) -> DomainResult<TemplatePreview> {
+        let key = TemplateKey::parse(&data.channel, &data.template_key)?;
+        let mut i18n = LocaleService.for_school(sdb, school_id.clone()).await?;
+        if let Some(locale) = data.locale {
+            i18n.locale = locale;
+        }
+        let (subject, body) = match data.body {
+            Some(body) => (data.subject, body),
+            None => {
+                self.template(sdb, school_id.clone(), &key, i18n.locale)
+                    .await?
+            }
+        };
+        key.check(&body)?;
+        if let Some(subject) = &subject {
+            key.check(subject)?;
+        }
+
+        let school = SchoolQ
+            .get_by_id(sdb, school_id.clone())
+            .await?
+            .ok_or_else(|| Error::NotFound(format!("school {school_id}")))?;
+        let mut context = TemplateContext::sample(&key, &i18n);
+        context.school(&school);
+        if let Some(student_id) = data.student_id {
+            let student = StudentQ
+                .get_by_id(sdb, student_id.clone())
+                .await?
+                .filter(|s| record_key(&s.school_id) == school_id)
+                .ok_or_else(|| Error::NotFound(format!("student {student_id}")))?;
+            context.student(&student);
+        }
+        if let Some(invoice_id) = data.invoice_id {
+            let invoice = InvoiceQ
+                .get_by_id(sdb, invoice_id.clone())
+                .await?
+                .filter(|i| record_key(&i.school_id) == school_id)
+                .ok_or_else(|| Error::NotFound(format!("invoice {invoice_id}")))?;
+            context.invoice(&invoice, &i18n);
+        }
+
+        let body = render(&body, &context)?;
+        let mut missing = body.missing;
+        let subject = match subject {
+            Some(subject) => {
+                let subject = render(&subject, &context)?;
+                for name in subject.missing {
+                    if !missing.contains(&name) {
+                        missing.push(name);
+                    }
+                }
+                Some(subject.text)
+            }
+            None => None,
+        };
+        let sms = matches!(key, TemplateKey::Sms(_)).then(|| sms_segments(&body.text));
+        Ok(TemplatePreview {
+            subject,
+            body: body.text,
+            missing_variables: missing,
+            sms,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shared::i18n::Currency;
+
+    #[test]
+    fn renders_variables_and_reports_missing_ones() {
+        let mut context = TemplateContext::new();
+        context
+            .set("student.first_name", Some("Ada".into()))
+            .set("invoice.balance", Some("₦5,000.00".into()))
+            .set("student.middle_name", None);
+        let rendered = render(
+            "{{student.first_name}} {{ student.middle_name }}owes {{invoice.balance}}.",
+            &context,
+        )
+        .unwrap();
+        assert_eq!(rendered.text, "Ada owes ₦5,000.00.");
+        assert_eq!(rendered.missing, vec!["student.middle_name".to_string()]);
+
+        assert!(render("Hello {{student.first_name", &context).is_err());
+        assert!(render("Hello {{Student Name}}", &context).is_err());
+        assert_eq!(
+            render("{ not a variable }", &context).unwrap().text,
+            "{ not a variable }"
+        );
+    }
+
+    #[test]
+    fn checks_variables_against_the_message_kind() {
+        let fees = TemplateKey::parse(&MessageChannel::Sms, "fee_reminder").unwrap();
+        assert_eq!(fees, TemplateKey::Sms(SmsType::FeeReminder));
+        assert_eq!(fees.name(), "fee_reminder");
+        assert!(
+            fees.check("{{student.first_name}} owes {{invoice.balance}} to {{school.school_name}}")
+                .is_ok()
+        );
+        assert!(fees.check("{{attendance.status}}").is_err());
+        assert!(TemplateKey::parse(&MessageChannel::Sms, "result_published").is_err());
+        assert!(TemplateKey::parse(&MessageChannel::Email, "result_published").is_ok());
+    }
+
+    #[test]
+    fn counts_sms_segments() {
+        let plain = sms_segments(&"a".repeat(160));
+        assert_eq!(plain.encoding, SmsEncoding::Gsm7);
+        assert_eq!((plain.segments, plain.remaining), (1, 0));
+
+        let long = sms_segments(&"a".repeat(161));
+        assert_eq!((long.segments, long.remaining), (2, 145));
+
+        let euro = sms_segments("Pay €5 [today]");
+        assert_eq!(euro.encoding, SmsEncoding::Gsm7);
+        assert_eq!(euro.characters, 17);
+
+        let naira = sms_segments(&format!("Balance ₦5,000 {}", "x".repeat(60)));
+        assert_eq!(naira.encoding, SmsEncoding::Ucs2);
+        assert_eq!((naira.characters, naira.segments), (75, 2));
+
+        assert_eq!(sms_segments("").segments, 0);
+    }
+
+    #[test]
+    fn built_in_wording_fills_from_sample_data() {
+        let i18n = Localizer::new(Translations::bundled(), Locale::En, Currency::Ngn);
+        for (channel, names) in [
+            (
+                MessageChannel::Sms,
+                &[
+                    "fee_reminder",
+                    "attendance_alert",
+                    "announcement",
+                    "event",
+                    "result_notification",
+                    "other",
+                ][..],
+            ),
+            (
+                MessageChannel::Notification,
+                &[
+                    "fee_reminder",
+                    "result_published",
+                    "attendance_alert",
+                    "announcement",
+                    "message",
+                    "event",
+                    "system",
+                ][..],
+            ),
+        ] {
+            for name in names {
+                let key = TemplateKey::parse(&channel, name).unwrap();
+                let catalog_key = key.catalog_key();
+                let body = Translations::bundled().text(Locale::En, &catalog_key);
+                assert_ne!(body, catalog_key, "no built-in wording for {name}");
+                key.check(body).unwrap();
+                let rendered = render(body, &TemplateContext::sample(&key, &i18n)).unwrap();
+                assert!(
+                    rendered.missing.is_empty(),
+                    "{name}: {:?}",
+                    rendered.missing
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,580 @@
+use std::collections::{HashMap, hash_map::Entry};
+
+use db::queries::{
+    AcademicSessionQ, ClassQ, GradingSchemeQ, ReportCardQ, SchoolQ, StudentQ, SubjectQ, TermQ,
+};
+use shared::{
+    helpers::{
+        api_responses::{
+            SessionUser, Transcript, TranscriptSession, TranscriptSubject, TranscriptTerm,
+            TranscriptTermScore,
+        },
+        surreal_util::{record_key, to_naive_date},
+    },
+    i18n::Localizer,
+    models::{
+        School, Student, UserType,
+        academic::{AcademicSession, Class, Term},
+        assessments_nd_grading::{GradingScheme, ReportCard, ReportCardScore},
+    },
+};
+use surrealdb::{Surreal, engine::remote::ws::Client};
+use surrealdb_types::{Datetime, Decimal, RecordId};
+
+use crate::{
+    auth::{ADMINS, STAFF, ensure_role},
+    error::{DomainResult, Error},
+    grading::{GradingService, grade_for_score},
+    locale::LocaleService,
+    parent_portal::ParentPortalService,
+    pdf::PdfWriter,
+    storage::FileStore,
+    subscription::{Feature, SubscriptionService},
+};
+
+/// A published report card together with its subject scores and the
+/// published cards of its classmates for the same term
+struct CardEntry {
+    card: ReportCard,
+    scores: Vec<ReportCardScore>,
+    peers: Vec<ReportCard>,
+}
+
+/// Lookup tables shared by every session while assembling a transcript
+struct Lookups<'a> {
+    subjects: &'a HashMap<String, String>,
+    classes: &'a HashMap<String, String>,
+    /// Bands currently used for each class's level, for cards published
+    /// before versions were pinned, keyed by class
+    class_bands: &'a HashMap<String, Vec<GradingScheme>>,
+    /// Bands of the scheme versions pinned to report cards, keyed by version
+    pinned_bands: &'a HashMap<String, Vec<GradingScheme>>,
+}
+
+pub struct TranscriptService;
+
+impl TranscriptService {
+    /// Build a transcript from every published report card of a student
+    pub async fn build(
+        &self,
+        sdb: &Surreal<Client>,
+        student_id: String,
+    ) -> DomainResult<Transcript> {
+        let student = StudentQ
+            .get_by_id(sdb, student_id.clone())
+            .await?
+            .ok_or_else(|| Error::NotFound(format!("student {student_id}")))?;
+        let school_id = record_key(&student.school_id);
+        let school = SchoolQ
+            .get_by_id(sdb, school_id.clone())
+            .await?
+            .ok_or_else(|| Error::NotFound(format!("school {school_id}")))?;
+
+        let sessions = AcademicSessionQ
+            .get_all_by_school(sdb, school_id.clone())
+            .await?;
+        let mut terms = Vec::new();
+        for session in &sessions {
+            if let Some(id) = &session.id {
+                terms.extend(TermQ.get_by_session(sdb, record_key(id)).await?);
+            }
+        }
+
+        let subjects: HashMap<String, String> = SubjectQ
+            .get_by_school(sdb, school_id.clone())
+            .await?
+            .into_iter()
+            .filter_map(|s| Some((record_key(s.id.as_ref()?), s.subject_name)))
+            .collect();
+        let school_classes: HashMap<String, Class> = ClassQ
+            .get_by_school(sdb, school_id.clone())
+            .await?
+            .into_iter()
+            .filter_map(|c| Some((record_key(c.id.as_ref()?), c)))
+            .collect();
+        let classes: HashMap<String, String> = school_classes
+            .iter()
+            .map(|(key, c)| (key.clone(), c.class_name.clone()))
+            .collect();
+
+        let mut entries = Vec::new();
+        let mut pinned_bands = HashMap::new();
+        let mut class_bands = HashMap::new();
+        for card in ReportCardQ.get_by_student(sdb, student_id).await? {
+            if !card.published {
+                continue;
+            }
+            if let Some(version_id) = &card.grading_scheme_version_id {
+                let key = record_key(version_id);
+                if let Entry::Vacant(slot) = pinned_bands.entry(key.clone()) {
+                    slot.insert(GradingSchemeQ.get_bands(sdb, key).await?);
+                }
+            } else if let Some(class) = school_classes.get(&record_key(&card.class_id))
+                && let Entry::Vacant(slot) = class_bands.entry(record_key(&card.class_id))
+            {
+                let (_, bands) = GradingService
+                    .bands_for_level(sdb, school_id.clone(), &class.class_level)
+                    .await?;
+                slot.insert(bands);
+            }
+            let scores = match &card.id {
+                Some(id) => ReportCardQ.get_scores(sdb, record_key(id)).await?,
+                None => Vec::new(),
+            };
+            let peers = ReportCardQ
+                .get_by_class_term(sdb, record_key(&card.class_id), record_key(&card.term_id))
+                .await?
+                .into_iter()
+                .filter(|c| c.published)
+                .collect();
+            entries.push(CardEntry {
+                card,
+                scores,
+                peers,
+            });
+        }
+
+        let lookups = Lookups {
+            subjects: &subjects,
+            classes: &classes,
+            class_bands: &class_bands,
+            pinned_bands: &pinned_bands,
+        };
+        Ok(assemble(
+            school, student, sessions, terms, entries, &lookups,
+        ))
+    }
+
+    /// A student's transcript as a PDF in the school's language, on plans
+    /// that include PDF report cards. Only the school's staff and the
+    /// student's own parents may download it.
+    pub async fn pdf(
+        &self,
+        sdb: &Surreal<Client>,
+        viewer: &SessionUser,
+        student_id: String,
+    ) -> DomainResult<Vec<u8>> {
+        let school_id = self.ensure_viewer(sdb, viewer, &student_id).await?;
+        SubscriptionService
+            .ensure_feature(sdb, &school_id, Feature::PdfReportCards)
+            .await?;
+        let i18n = LocaleService.for_school(sdb, school_id).await?;
+        let transcript = self.build(sdb, student_id).await?;
+        self.to_pdf(&transcript, &i18n)
+    }
+
+    /// A student's transcript as JSON for hand-over to another school, for
+    /// the same viewers as the PDF
+    pub async fn json(
+        &self,
+        sdb: &Surreal<Client>,
+        viewer: &SessionUser,
+        student_id: String,
+    ) -> DomainResult<Vec<u8>> {
+        self.ensure_viewer(sdb, viewer, &student_id).await?;
+        let transcript = self.build(sdb, student_id).await?;
+        self.to_json(&transcript)
+    }
+
+    /// Keep the transcript a student brought from their previous school with
+    /// their record, replacing any imported before. Only the school's admins
+    /// may import one.
+    pub async fn import_previous(
+        &self,
+        sdb: &Surreal<Client>,
+        store: &impl FileStore,
+        admin: &SessionUser,
+        student_id: String,
+        bytes: &[u8],
+    ) -> DomainResult<Transcript> {
+        let school_id = self.student_school(sdb, &student_id).await?;
+        ensure_role(admin, &school_id, ADMINS)?;
+        SubscriptionService.ensure_writable(sdb, &school_id).await?;
+        let transcript = self.from_json(bytes)?;
+        store
+            .put(
+                &previous_key(&school_id, &student_id),
+                &self.to_json(&transcript)?,
+            )
+            .await?;
+        Ok(transcript)
+    }
+
+    /// The transcript imported from a student's previous school
+    pub async fn previous(
+        &self,
+        sdb: &Surreal<Client>,
+        store: &impl FileStore,
+        viewer: &SessionUser,
+        student_id: String,
+    ) -> DomainResult<Transcript> {
+        let school_id = self.ensure_viewer(sdb, viewer, &student_id).await?;
+        let bytes = store.get(&previous_key(&school_id, &student_id)).await?;
+        self.from_json(&bytes)
+    }
+
+    /// Serialize a transcript for download or hand-over to another school
+    pub fn to_json(&self, transcript: &Transcript) -> DomainResult<Vec<u8>> {
+        serde_json::to_vec_pretty(transcript).map_err(|e| Error::Export(e.to_string()))
+    }
+
+    /// Read back a transcript exported by `to_json` (e.g. when a student transfers in)
+    pub fn from_json(&self, bytes: &[u8]) -> DomainResult<Transcript> {
+        serde_json::from_slice(bytes)
+            .map_err(|e| Error::Validation(format!("not a transcript export: {e}")))
+    }
+
+    /// The student's school, if the viewer is its staff or the student's parent
+    async fn ensure_viewer(
+        &self,
+        sdb: &Surreal<Client>,
+        viewer: &SessionUser,
+        student_id: &str,
+    ) -> DomainResult<String> {
+        let school_id = self.student_school(sdb, student_id).await?;
+        if viewer.user_type == UserType::Parent {
+            let is_child = ParentPortalService
+                .children(sdb, viewer.user_id.clone())
+                .await?
+                .iter()
+                .any(|child| child.id.as_ref().map(record_key).as_deref() == Some(student_id));
+            if !is_child {
+                return Err(Error::Forbidden("not one of your children".into()));
+            }
+        } else {
+            ensure_role(viewer, &school_id, STAFF)?;
+        }
+        Ok(school_id)
+    }
+
+    async fn student_school(
+        &self,
+        sdb: &Surreal<Client>,
+        student_id: &str,
+    ) -> DomainResult<String> {
+        StudentQ
+            .get_by_id(sdb, student_id.to_string())
+            .await?
+            .map(|student| record_key(&student.school_id))
+            .ok_or_else(|| Error::NotFound(format!("student {student_id}")))
+    }
+
+    /// Render a transcript as a printable A4 PDF in the given language
+    pub fn to_pdf(&self, transcript: &Transcript, i18n: &Localizer) -> DomainResult<Vec<u8>> {
+        let student = &transcript.student;
+        let title = i18n.t("transcript.title");
+        let mut pdf = PdfWriter::new(&title)?;
+
+        pdf.heading(&transcript.school_name);
+        if let Some(address) = &transcript.school_address {
+            pdf.line(address);
+        }
+        pdf.gap();
+        pdf.heading(&title);
+        let name = format!(
+            "{} {}{}",
+            student.first_name,
+            student
+                .middle_name
+                .as_ref()
+                .map(|m| format!("{m} "))
+                .unwrap_or_default(),
+            student.last_name
+        );
+        pdf.line(&i18n.format("transcript.name", &[("name", &name)]));
+        pdf.line(&i18n.format(
+            "transcript.admission_no",
+            &[("number", &student.admission_number)],
+        ));
+
+        for session in &transcript.sessions {
+            pdf.gap();
+            pdf.heading(&format!(
+                "{} - {}",
+                session.session.session_name,
+                session.class_name.as_deref().unwrap_or("-")
+            ));
+
+            let term_numbers: Vec<i32> = session.terms.iter().map(|t| t.term.term_number).collect();
+            let mut header = vec![(0.0, i18n.t("transcript.subject"))];
+            for (i, number) in term_numbers.iter().enumerate() {
+                header.push((
+                    70.0 + i as f32 * 20.0,
+                    i18n.format("transcript.term", &[("number", &number.to_string())]),
+                ));
+            }
+            let avg_x = 70.0 + term_numbers.len() as f32 * 20.0;
+            header.push((avg_x, i18n.t("transcript.average")));
+            header.push((avg_x + 25.0, i18n.t("transcript.grade")));
+            pdf.row(&header, true);
+
+            for subject in &session.subjects {
+                let mut row = vec![(0.0, subject.subject_name.clone())];
+                for (i, number) in term_numbers.iter().enumerate() {
+                    let score = subject
+                        .term_scores
+                        .iter()
+                        .find(|s| s.term_number == *number)
+                        .and_then(|s| s.total_score);
+                    row.push((70.0 + i as f32 * 20.0, fmt_decimal(score)));
+                }
+                row.push((avg_x, fmt_decimal(subject.session_average)));
+                row.push((avg_x + 25.0, subject.grade.clone().unwrap_or_default()));
+                pdf.row(&row, false);
+            }
+
+            let position = session
+                .session_position
+                .map(|p| p.to_string())
+                .unwrap_or_else(|| "-".into());
+            let total = session
+                .total_students_in_class
+                .map(|n| n.to_string())
+                .unwrap_or_else(|| "-".into());
+            pdf.line(&i18n.format(
+                "transcript.session_summary",
+                &[
+                    ("average", &fmt_decimal(session.session_average)),
+                    ("position", &position),
+                    ("total", &total),
+                    ("grade", session.grade.as_deref().unwrap_or("-")),
+                ],
+            ));
+        }
+
+        pdf.gap();
+        pdf.line(&i18n.format(
+            "transcript.cumulative_average",
+            &[("average", &fmt_decimal(transcript.cumulative_average))],
+        ));
+        pdf.line(&i18n.format(
+            "transcript.generated",
+            &[("date", &i18n.date(transcript.generated_at.date_naive()))],
+        ));
+
+        pdf.finish()
+    }
+}
+
+fn assemble(
+    school: School,
+    student: Student,
+    mut sessions: Vec<AcademicSession>,
+    terms: Vec<Term>,
+    entries: Vec<CardEntry>,
+    lookups: &Lookups,
+) -> Transcript {
+    sessions.sort_by_key(|s| to_naive_date(&s.start_date));
+
+    let mut transcript_sessions = Vec::new();
+    for session in sessions {
+        let mut session_terms: Vec<&Term> = terms
+            .iter()
+            .filter(|t| session.id.as_ref() == Some(&t.session_id))
+            .collect();
+        session_terms.sort_by_key(|t| t.term_number);
+
+        let session_entries: Vec<(&Term, &CardEntry)> = session_terms
+            .iter()
+            .filter_map(|term| {
+                entries
+                    .iter()
+                    .find(|e| term.id.as_ref() == Some(&e.card.term_id))
+                    .map(|e| (*term, e))
+            })
+            .collect();
+        if session_entries.is_empty() {
+            continue;
+        }
+
+        transcript_sessions.push(build_session(session, &session_entries, &student, lookups));
+    }
+
+    let session_averages: Vec<Decimal> = transcript_sessions
+        .iter()
+        .filter_map(|s| s.session_average)
+        .collect();
+
+    Transcript {
+        school_name: school.school_name,
+        school_address: school.school_address,
+        student,
+        sessions: transcript_sessions,
+        cumulative_average: mean(&session_averages),
+        generated_at: Datetime::now(),
+    }
+}
+
+fn build_session(
+    session: AcademicSession,
+    entries: &[(&Term, &CardEntry)],
+    student: &Student,
+    lookups: &Lookups,
+) -> TranscriptSession {
+    let terms = entries
+        .iter()
+        .map(|(term, entry)| TranscriptTerm {
+            term: (*term).clone(),
+            report_card_id: entry.card.id.clone(),
+            average_percentage: entry.card.average_percentage,
+            class_position: entry.card.class_position,
+            total_students_in_class: entry.card.total_students_in_class,
+        })
+        .collect();
+
+    // Session grades use the scheme pinned to the session's latest published
+    // card, or the one its class level uses now if it wasn't pinned
+    let bands = entries
+        .last()
+        .and_then(|(_, e)| match &e.card.grading_scheme_version_id {
+            Some(id) => lookups.pinned_bands.get(&record_key(id)),
+            None => lookups.class_bands.get(&record_key(&e.card.class_id)),
+        })
+        .map_or(&[][..], Vec::as_slice);
+
+    // Per-subject scores, keeping the order subjects first appear in
+    let mut subject_order: Vec<RecordId> = Vec::new();
+    let mut subject_scores: HashMap<String, Vec<TranscriptTermScore>> = HashMap::new();
+    for (term, entry) in entries {
+        for score in &entry.scores {
+            let key = record_key(&score.subject_id);
+            if !subject_scores.contains_key(&key) {
+                subject_order.push(score.subject_id.clone());
+            }
+            subject_scores
+                .entry(key)
+                .or_default()
+                .push(TranscriptTermScore {
+                    term_number: term.term_number,
+                    total_score: score.total_score,
+                    grade: score.grade.clone(),
+                    subject_position: score.subject_position,
+                });
+        }
+    }
+    let subjects = subject_order
+        .into_iter()
+        .map(|subject_id| {
+            let key = record_key(&subject_id);
+            let term_scores = subject_scores.remove(&key).unwrap_or_default();
+            let totals: Vec<Decimal> = term_scores.iter().filter_map(|s| s.total_score).collect();
+            let session_average = mean(&totals);
+            TranscriptSubject {
+                subject_name: lookups.subjects.get(&key).cloned().unwrap_or_default(),
+                subject_id,
+                term_scores,
+                session_average,
+                grade: grade_of(bands, session_average),
+            }
+        })
+        .collect();
+
+    let averages: Vec<Decimal> = entries
+        .iter()
+        .filter_map(|(_, e)| e.card.average_percentage)
+        .collect();
+    let session_average = mean(&averages);
+
+    // Session position: rank each classmate by the mean of their term averages
+    let mut peer_averages: HashMap<String, Vec<Decimal>> = HashMap::new();
+    for (_, entry) in entries {
+        for peer in &entry.peers {
+            if let Some(avg) = peer.average_percentage {
+                peer_averages
+                    .entry(record_key(&peer.student_id))
+                    .or_default()
+                    .push(avg);
+            }
+        }
+    }
+    let peer_means: Vec<Decimal> = peer_averages.values().filter_map(|v| mean(v)).collect();
+    let session_position = student
+        .id
+        .as_ref()
+        .and_then(|id| peer_averages.get(&record_key(id)))
+        .and_then(|v| mean(v))
+        .map(|own| position_of(&peer_means, own));
+
+    let class_name = entries
+        .last()
+        .and_then(|(_, e)| lookups.classes.get(&record_key(&e.card.class_id)))
+        .cloned();
+
+    TranscriptSession {
+        session,
+        class_name,
+        terms,
+        subjects,
+        session_average,
+        session_position,
+        total_students_in_class: (!peer_means.is_empty()).then_some(peer_means.len() as i32),
+        grade: grade_of(bands, session_average),
+    }
+}
+
+/// Where a student's transcript from their previous school is kept
+fn previous_key(school_id: &str, student_id: &str) -> String {
+    format!("schools/{school_id}/students/{student_id}/previous-transcript.json")
+}
+
+fn grade_of(bands: &[GradingScheme], score: Option<Decimal>) -> Option<String> {
+    score
+        .and_then(|s| grade_for_score(bands, s))
+        .map(|band| band.grade.clone())
+}
+
+fn mean(values: &[Decimal]) -> Option<Decimal> {
+    if values.is_empty() {
+        return None;
+    }
+    let total: Decimal = values.iter().sum();
+    Some((total / Decimal::from(values.len())).round_dp(2))
+}
+
+/// Standard competition position (1, 2, 2, 4) of `value` among `values`
+fn position_of(values: &[Decimal], value: Decimal) -> i32 {
+    values.iter().filter(|v| **v > value).count() as i32 + 1
+}
+
+fn fmt_decimal(value: Option<Decimal>) -> String {
+    value
+        .map(|v| v.round_dp(2).to_string())
+        .unwrap_or_else(|| "-".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mean_rounds_to_two_places() {
+        let values = [Decimal::from(70), Decimal::from(65), Decimal::from(81)];
+        assert_eq!(mean(&values), Some(Decimal::new(7200, 2)));
+        assert_eq!(mean(&[]), None);
+    }
+
+    #[test]
+    fn position_shares_rank_on_ties() {
+        let values = [
+            Decimal::from(90),
+            Decimal::from(75),
+            Decimal::from(75),
+            Decimal::from(60),
+        ];
+        assert_eq!(position_of(&values, Decimal::from(90)), 1);
+        assert_eq!(position_of(&values, Decimal::from(75)), 2);
+        assert_eq!(position_of(&values, Decimal::from(60)), 4);
+    }
+
+    #[test]
+    fn imports_reject_anything_but_a_transcript_export() {
+        assert!(matches!(
+            TranscriptService.from_json(br#"{"school_name": "Elsewhere"}"#),
+            Err(Error::Validation(_))
+        ));
+        assert_eq!(
+            previous_key("s1", "st9"),
+            "schools/s1/students/st9/previous-transcript.json"
+        );
+    }
+}
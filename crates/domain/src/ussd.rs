@@ -0,0 +1,892 @@
+use std::{env, future::Future, str::FromStr};
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use chrono::{Duration, Utc};
+use db::queries::{InvoiceQ, PaymentQ, ReportCardQ, StudentQ, TermQ, UserQ, UssdSessionQ};
+use serde::Deserialize;
+use shared::{
+    helpers::surreal_util::record_key,
+    i18n::{Localizer, Translations},
+    models::{
+        PaymentMethod, PaymentStatus, Student, User, UssdStep,
+        academic::Term,
+        assessments_nd_grading::ReportCard,
+        fee_management::{Invoice, Payment},
+        system_and_audit::UssdSession,
+    },
+};
+use surrealdb::{Surreal, engine::remote::ws::Client};
+use surrealdb_types::{Datetime, Decimal, RecordId};
+
+use crate::{
+    error::{DomainResult, Error},
+    locale::LocaleService,
+    parent_portal::{ParentPortalService, is_outstanding},
+    subscription::{Feature, SubscriptionService},
+    whatsapp::phone_variants,
+};
+
+/// Idle time after which a session is abandoned; gateways hang up sooner
+const SESSION_TIMEOUT_SECONDS: i64 = 180;
+/// Characters in a payment reference after the "USSD-" prefix
+const REFERENCE_LEN: usize = 10;
+
+/// Secret the gateway's callback URL carries as `?key=`; without it every
+/// callback is refused
+pub const USSD_GATEWAY_SECRET_VAR: &str = "USSD_GATEWAY_SECRET";
+
+/// A gateway callback (Africa's Talking style). `text` is everything the
+/// caller has typed this session, joined by `*`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UssdRequest {
+    pub session_id: String,
+    pub phone_number: String,
+    #[serde(default)]
+    pub service_code: String,
+    #[serde(default)]
+    pub text: String,
+}
+
+/// The next screen, and whether the session stays open for an answer
+#[derive(Debug, Clone, PartialEq)]
+pub struct UssdReply {
+    pub text: String,
+    pub end: bool,
+}
+
+impl UssdReply {
+    fn more(text: String) -> Self {
+        Self { text, end: false }
+    }
+
+    fn end(text: String) -> Self {
+        Self { text, end: true }
+    }
+
+    /// The reply in the gateway's `CON`/`END` format
+    pub fn to_gateway(&self) -> String {
+        let verb = if self.end { "END" } else { "CON" };
+        format!("{verb} {}", self.text)
+    }
+}
+
+/// Sessions and the school records the menu reads; the database in
+/// production, a stand-in when simulating a gateway
+pub trait UssdBackend {
+    fn session(
+        &self,
+        session_id: &str,
+    ) -> impl Future<Output = DomainResult<Option<UssdSession>>> + Send;
+
+    fn save_session(
+        &self,
+        session_id: &str,
+        session: UssdSession,
+    ) -> impl Future<Output = DomainResult<()>> + Send;
+
+    fn end_session(&self, session_id: &str) -> impl Future<Output = DomainResult<()>> + Send;
+
+    /// Drop sessions callers walked away from
+    fn expire_sessions(&self) -> impl Future<Output = DomainResult<()>> + Send;
+
+    /// Active parent accounts with this phone number, one per school
+    fn parents(&self, phone_number: &str) -> impl Future<Output = DomainResult<Vec<User>>> + Send;
+
+    fn localizer(&self, user: &User) -> impl Future<Output = DomainResult<Localizer>> + Send;
+
+    fn children(&self, user: &User) -> impl Future<Output = DomainResult<Vec<Student>>> + Send;
+
+    /// Unpaid invoices, oldest first
+    fn outstanding(
+        &self,
+        student_id: &RecordId,
+    ) -> impl Future<Output = DomainResult<Vec<Invoice>>> + Send;
+
+    /// The latest published report card and its term
+    fn last_result(
+        &self,
+        student_id: &RecordId,
+    ) -> impl Future<Output = DomainResult<Option<(ReportCard, Term)>>> + Send;
+
+    fn start_payment(&self, payment: Payment)
+    -> impl Future<Output = DomainResult<Payment>> + Send;
+}
+
+/// The menu backed by the school's database
+pub struct SchoolRecords<'a> {
+    pub sdb: &'a Surreal<Client>,
+}
+
+impl UssdBackend for SchoolRecords<'_> {
+    async fn session(&self, session_id: &str) -> DomainResult<Option<UssdSession>> {
+        Ok(UssdSessionQ.get(self.sdb, session_id.into()).await?)
+    }
+
+    async fn save_session(&self, session_id: &str, session: UssdSession) -> DomainResult<()> {
+        UssdSessionQ
+            .save(self.sdb, session_id.into(), session)
+            .await?;
+        Ok(())
+    }
+
+    async fn end_session(&self, session_id: &str) -> DomainResult<()> {
+        Ok(UssdSessionQ.delete(self.sdb, session_id.into()).await?)
+    }
+
+    async fn expire_sessions(&self) -> DomainResult<()> {
+        Ok(UssdSessionQ.delete_expired(self.sdb).await?)
+    }
+
+    async fn parents(&self, phone_number: &str) -> DomainResult<Vec<User>> {
+        Ok(UserQ
+            .find_parents_by_phone(self.sdb, phone_variants(phone_number))
+            .await?)
+    }
+
+    async fn localizer(&self, user: &User) -> DomainResult<Localizer> {
+        LocaleService.for_user(self.sdb, user).await
+    }
+
+    async fn children(&self, user: &User) -> DomainResult<Vec<Student>> {
+        let user_id = user.id.as_ref().map(record_key).unwrap_or_default();
+        ParentPortalService.children(self.sdb, user_id).await
+    }
+
+    async fn outstanding(&self, student_id: &RecordId) -> DomainResult<Vec<Invoice>> {
+        let mut invoices: Vec<Invoice> = InvoiceQ
+            .get_by_student(self.sdb, record_key(student_id))
+            .await?
+            .into_iter()
+            .filter(is_outstanding)
+            .collect();
+        invoices.reverse();
+        Ok(invoices)
+    }
+
+    async fn last_result(&self, student_id: &RecordId) -> DomainResult<Option<(ReportCard, Term)>> {
+        let cards = ReportCardQ
+            .get_by_student(self.sdb, record_key(student_id))
+            .await?;
+        let Some(card) = cards.into_iter().rev().find(|card| card.published) else {
+            return Ok(None);
+        };
+        let term = TermQ.get_by_id(self.sdb, record_key(&card.term_id)).await?;
+        Ok(term.map(|term| (card, term)))
+    }
+
+    async fn start_payment(&self, payment: Payment) -> DomainResult<Payment> {
+        let student_id = record_key(&payment.student_id);
+        let school_id = StudentQ
+            .get_by_id(self.sdb, student_id.clone())
+            .await?
+            .map(|student| record_key(&student.school_id))
+            .ok_or_else(|| Error::NotFound(format!("student {student_id}")))?;
+        SubscriptionService
+            .ensure_feature(self.sdb, &school_id, Feature::OnlinePayments)
+            .await?;
+        PaymentQ
+            .record(self.sdb, payment)
+            .await?
+            .ok_or_else(|| Error::NotFound("payment".into()))
+    }
+}
+
+/// Fee balances, results and payment for parents on feature phones
+pub struct UssdService;
+
+impl UssdService {
+    /// Refuse callbacks that don't carry the gateway's shared secret
+    pub fn authorize(&self, key: Option<&str>) -> DomainResult<()> {
+        let secret = env::var(USSD_GATEWAY_SECRET_VAR).ok();
+        if !carries_secret(secret.as_deref(), key) {
+            return Err(Error::Unauthorized("invalid gateway key".into()));
+        }
+        Ok(())
+    }
+
+    /// Answer one gateway request, carrying on from the session's screen
+    pub async fn handle<B: UssdBackend>(
+        &self,
+        backend: &B,
+        request: UssdRequest,
+    ) -> DomainResult<UssdReply> {
+        let parents = backend.parents(&request.phone_number).await?;
+        let Some(first) = parents.first() else {
+            let i18n = Localizer::new(
+                Translations::global(),
+                Default::default(),
+                Default::default(),
+            );
+            return Ok(UssdReply::end(i18n.t("ussd.not_registered")));
+        };
+        let input = latest_input(&request.text);
+
+        let Some(mut session) = backend.session(&request.session_id).await? else {
+            let i18n = backend.localizer(first).await?;
+            if !input.is_empty() {
+                return Ok(UssdReply::end(i18n.t("ussd.timed_out")));
+            }
+            backend.expire_sessions().await?;
+            let session = UssdSession {
+                id: None,
+                phone_number: request.phone_number,
+                user_id: account_id(first)?,
+                student_id: None,
+                invoice_id: None,
+                step: UssdStep::AdmissionNumber,
+                amount: None,
+                expires_at: expiry(),
+                created_at: Datetime::now(),
+                updated_at: Datetime::now(),
+            };
+            backend.save_session(&request.session_id, session).await?;
+            return Ok(UssdReply::more(
+                i18n.format("ussd.welcome", &[("name", &first.first_name)]),
+            ));
+        };
+
+        let parent = parents
+            .iter()
+            .find(|p| p.id.as_ref() == Some(&session.user_id))
+            .unwrap_or(first);
+        let i18n = backend.localizer(parent).await?;
+        let reply = match session.step {
+            UssdStep::AdmissionNumber => {
+                self.choose_ward(backend, &parents, &mut session, input)
+                    .await?
+            }
+            step => {
+                let children = backend.children(parent).await?;
+                let student = children
+                    .into_iter()
+                    .find(|c| c.id.is_some() && c.id == session.student_id)
+                    .ok_or_else(|| Error::NotFound("student".into()))?;
+                self.answer(backend, &i18n, &mut session, &student, step, input)
+                    .await?
+            }
+        };
+
+        if reply.end {
+            backend.end_session(&request.session_id).await?;
+        } else {
+            session.expires_at = expiry();
+            session.updated_at = Datetime::now();
+            backend.save_session(&request.session_id, session).await?;
+        }
+        Ok(reply)
+    }
+
+    /// Find the caller's child by admission number, in any school they are a
+    /// parent at
+    async fn choose_ward<B: UssdBackend>(
+        &self,
+        backend: &B,
+        parents: &[User],
+        session: &mut UssdSession,
+        input: &str,
+    ) -> DomainResult<UssdReply> {
+        for parent in parents {
+            let ward = backend
+                .children(parent)
+                .await?
+                .into_iter()
+                .find(|c| c.id.is_some() && c.admission_number.eq_ignore_ascii_case(input));
+            if let Some(ward) = ward {
+                let i18n = backend.localizer(parent).await?;
+                session.user_id = account_id(parent)?;
+                session.student_id = ward.id.clone();
+                session.step = UssdStep::Menu;
+                return Ok(UssdReply::more(menu(&i18n, &ward)));
+            }
+        }
+        let i18n = backend.localizer(&parents[0]).await?;
+        Ok(UssdReply::more(
+            i18n.format("ussd.unknown_ward", &[("number", input)]),
+        ))
+    }
+
+    /// Move on from the screen the session is on
+    async fn answer<B: UssdBackend>(
+        &self,
+        backend: &B,
+        i18n: &Localizer,
+        session: &mut UssdSession,
+        student: &Student,
+        step: UssdStep,
+        input: &str,
+    ) -> DomainResult<UssdReply> {
+        let student_id = student
+            .id
+            .as_ref()
+            .ok_or_else(|| Error::NotFound("student".into()))?;
+        let name = student.first_name.as_str();
+        let invalid =
+            |screen: String| UssdReply::more(format!("{}\n{screen}", i18n.t("ussd.invalid")));
+        let info = |session: &mut UssdSession, text: String| {
+            session.step = UssdStep::Info;
+            UssdReply::more(format!("{text}\n{}", i18n.t("ussd.back")))
+        };
+
+        let reply = match (step, input) {
+            (UssdStep::Info, _) | (UssdStep::ConfirmPayment, "2") => {
+                session.step = UssdStep::Menu;
+                session.invoice_id = None;
+                session.amount = None;
+                UssdReply::more(menu(i18n, student))
+            }
+            (UssdStep::Menu, "1") => {
+                let invoices = backend.outstanding(student_id).await?;
+                let total: Decimal = invoices.iter().map(|i| i.balance).sum();
+                let text = if invoices.is_empty() {
+                    i18n.format("ussd.no_balance", &[("student", name)])
+                } else {
+                    i18n.format(
+                        "ussd.balance",
+                        &[
+                            ("student", name),
+                            ("amount", &i18n.money(total)),
+                            ("count", &invoices.len().to_string()),
+                        ],
+                    )
+                };
+                info(session, text)
+            }
+            (UssdStep::Menu, "2") => {
+                let text = match backend.last_result(student_id).await? {
+                    Some((card, term)) => result(i18n, &card, &term),
+                    None => i18n.format("ussd.no_result", &[("student", name)]),
+                };
+                info(session, text)
+            }
+            (UssdStep::Menu, "3") => match backend.outstanding(student_id).await?.first() {
+                Some(invoice) => {
+                    session.step = UssdStep::PaymentAmount;
+                    session.invoice_id = invoice.id.clone();
+                    UssdReply::more(pay(i18n, invoice))
+                }
+                None => info(
+                    session,
+                    i18n.format("ussd.no_balance", &[("student", name)]),
+                ),
+            },
+            (UssdStep::Menu, "0") => UssdReply::end(i18n.t("ussd.goodbye")),
+            (UssdStep::Menu, _) => invalid(menu(i18n, student)),
+            (UssdStep::PaymentAmount | UssdStep::OtherAmount | UssdStep::ConfirmPayment, _) => {
+                let Some(invoice) = self.invoice(backend, session, student_id).await? else {
+                    return Ok(info(
+                        session,
+                        i18n.format("ussd.no_balance", &[("student", name)]),
+                    ));
+                };
+                let enter_amount = || {
+                    i18n.format(
+                        "ussd.enter_amount",
+                        &[("amount", &i18n.money(invoice.balance))],
+                    )
+                };
+                match (step, input) {
+                    (UssdStep::PaymentAmount, "1") => {
+                        session.amount = Some(invoice.balance);
+                        session.step = UssdStep::ConfirmPayment;
+                        UssdReply::more(confirm(i18n, invoice.balance, name))
+                    }
+                    (UssdStep::PaymentAmount, "2") => {
+                        session.step = UssdStep::OtherAmount;
+                        UssdReply::more(enter_amount())
+                    }
+                    (UssdStep::PaymentAmount, _) => invalid(pay(i18n, &invoice)),
+                    (UssdStep::OtherAmount, _) => match parse_amount(input, invoice.balance) {
+                        Some(amount) => {
+                            session.amount = Some(amount);
+                            session.step = UssdStep::ConfirmPayment;
+                            UssdReply::more(confirm(i18n, amount, name))
+                        }
+                        None => invalid(enter_amount()),
+                    },
+                    (_, "1") => {
+                        let amount = session.amount.unwrap_or(invoice.balance);
+                        let payment = match backend
+                            .start_payment(payment(&invoice, amount, &session.phone_number)?)
+                            .await
+                        {
+                            Err(Error::Subscription(_)) => {
+                                return Ok(UssdReply::end(i18n.t("ussd.payments_unavailable")));
+                            }
+                            payment => payment?,
+                        };
+                        UssdReply::end(i18n.format(
+                            "ussd.payment_started",
+                            &[
+                                ("amount", &i18n.money(amount)),
+                                (
+                                    "reference",
+                                    payment.payment_reference.as_deref().unwrap_or_default(),
+                                ),
+                            ],
+                        ))
+                    }
+                    _ => invalid(confirm(
+                        i18n,
+                        session.amount.unwrap_or(invoice.balance),
+                        name,
+                    )),
+                }
+            }
+            (UssdStep::AdmissionNumber, _) => {
+                invalid(i18n.format("ussd.unknown_ward", &[("number", input)]))
+            }
+        };
+        Ok(reply)
+    }
+
+    /// The invoice being paid, if it is still unpaid
+    async fn invoice<B: UssdBackend>(
+        &self,
+        backend: &B,
+        session: &UssdSession,
+        student_id: &RecordId,
+    ) -> DomainResult<Option<Invoice>> {
+        Ok(backend
+            .outstanding(student_id)
+            .await?
+            .into_iter()
+            .find(|i| i.id.is_some() && i.id == session.invoice_id))
+    }
+}
+
+/// What the caller typed last; gateways resend the whole session's input
+fn latest_input(text: &str) -> &str {
+    text.rsplit('*').next().unwrap_or_default().trim()
+}
+
+fn expiry() -> Datetime {
+    Datetime::from(Utc::now() + Duration::seconds(SESSION_TIMEOUT_SECONDS))
+}
+
+fn menu(i18n: &Localizer, student: &Student) -> String {
+    let name = format!("{} {}", student.first_name, student.last_name);
+    i18n.format("ussd.menu", &[("student", &name)])
+}
+
+fn result(i18n: &Localizer, card: &ReportCard, term: &Term) -> String {
+    let term_name = term.term_name.clone().unwrap_or_else(|| {
+        i18n.format(
+            "transcript.term",
+            &[("number", &term.term_number.to_string())],
+        )
+    });
+    let average = card
+        .average_percentage
+        .map(|a| i18n.number(a, 1))
+        .unwrap_or_else(|| "-".into());
+    let mut text = i18n.format(
+        "ussd.result",
+        &[("term", &term_name), ("average", &average)],
+    );
+    if let (Some(position), Some(size)) = (card.class_position, card.total_students_in_class) {
+        text.push('\n');
+        text.push_str(&i18n.format(
+            "ussd.position",
+            &[
+                ("position", &position.to_string()),
+                ("size", &size.to_string()),
+            ],
+        ));
+    }
+    text
+}
+
+fn pay(i18n: &Localizer, invoice: &Invoice) -> String {
+    i18n.format(
+        "ussd.pay",
+        &[
+            ("invoice", &invoice.invoice_number),
+            ("amount", &i18n.money(invoice.balance)),
+        ],
+    )
+}
+
+fn confirm(i18n: &Localizer, amount: Decimal, student: &str) -> String {
+    i18n.format(
+        "ussd.confirm",
+        &[("amount", &i18n.money(amount)), ("student", student)],
+    )
+}
+
+/// The parent account's id, which the session is tied to
+fn account_id(user: &User) -> DomainResult<RecordId> {
+    user.id
+        .clone()
+        .ok_or_else(|| Error::NotFound("parent account".into()))
+}
+
+/// Whether `key` is the configured secret, compared in constant time
+fn carries_secret(secret: Option<&str>, key: Option<&str>) -> bool {
+    let (Some(secret), Some(key)) = (secret.filter(|s| !s.is_empty()), key) else {
+        return false;
+    };
+    secret.len() == key.len()
+        && secret
+            .bytes()
+            .zip(key.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// A positive amount no larger than the balance; keypads have no decimal
+/// separator on some phones, so whole amounts are expected
+fn parse_amount(input: &str, balance: Decimal) -> Option<Decimal> {
+    let cleaned: String = input.chars().filter(|c| *c != ',').collect();
+    Decimal::from_str(&cleaned)
+        .ok()
+        .map(|amount| amount.round_dp(2))
+        .filter(|amount| *amount > Decimal::ZERO && *amount <= balance)
+}
+
+/// A pending USSD payment the bank or aggregator confirms later
+fn payment(invoice: &Invoice, amount: Decimal, phone_number: &str) -> DomainResult<Payment> {
+    const ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+    let invoice_id = invoice
+        .id
+        .clone()
+        .ok_or_else(|| Error::NotFound("invoice".into()))?;
+    let mut bytes = [0u8; REFERENCE_LEN];
+    OsRng.fill_bytes(&mut bytes);
+    let reference: String = bytes
+        .iter()
+        .map(|b| ALPHABET[*b as usize % ALPHABET.len()] as char)
+        .collect();
+    let reference = format!("USSD-{reference}");
+    Ok(Payment {
+        id: None,
+        invoice_id,
+        student_id: invoice.student_id.clone(),
+        // Receipts are numbered once the payment is confirmed
+        receipt_number: reference.clone(),
+        amount_paid: amount,
+        payment_method: PaymentMethod::Ussd,
+        payment_reference: Some(reference),
+        payment_date: Datetime::now(),
+        paid_by: Some(phone_number.to_string()),
+        received_by: None,
+        transaction_fee: Decimal::ZERO,
+        net_amount: None,
+        status: PaymentStatus::Pending,
+        receipt_url: None,
+        notes: None,
+        created_at: Datetime::now(),
+        updated_at: Datetime::now(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Mutex};
+
+    use super::*;
+    use shared::{
+        i18n::{Currency, Locale},
+        models::{Gender, InvoiceStatus, StudentStatus, UserStatus, UserType},
+    };
+    use surrealdb_types::Value;
+
+    /// A school with one parent, one child, one unpaid invoice and one
+    /// published report card
+    struct Simulated {
+        sessions: Mutex<HashMap<String, UssdSession>>,
+        payments: Mutex<Vec<Payment>>,
+        invoices: Vec<Invoice>,
+    }
+
+    impl Simulated {
+        fn new() -> Self {
+            Self {
+                sessions: Mutex::new(HashMap::new()),
+                payments: Mutex::new(Vec::new()),
+                invoices: vec![Invoice {
+                    id: Some(RecordId::new("invoices", "i1")),
+                    student_id: RecordId::new("students", "st1"),
+                    school_id: RecordId::new("schools", "s1"),
+                    term_id: RecordId::new("terms", "t1"),
+                    invoice_number: "INV-2026-000042".into(),
+                    total_amount: Decimal::from(60000),
+                    amount_paid: Decimal::from(15000),
+                    balance: Decimal::from(45000),
+                    discount_amount: Decimal::ZERO,
+                    discount_reason: None,
+                    due_date: Value::None,
+                    status: InvoiceStatus::Partial,
+                    generated_by: None,
+                    generated_at: Datetime::now(),
+                    created_at: Datetime::now(),
+                    updated_at: Datetime::now(),
+                }],
+            }
+        }
+    }
+
+    impl UssdBackend for Simulated {
+        async fn session(&self, session_id: &str) -> DomainResult<Option<UssdSession>> {
+            let sessions = self.sessions.lock().unwrap();
+            Ok(sessions
+                .get(session_id)
+                .filter(|s| s.expires_at.into_inner() > Utc::now())
+                .cloned())
+        }
+
+        async fn save_session(&self, session_id: &str, session: UssdSession) -> DomainResult<()> {
+            self.sessions
+                .lock()
+                .unwrap()
+                .insert(session_id.into(), session);
+            Ok(())
+        }
+
+        async fn end_session(&self, session_id: &str) -> DomainResult<()> {
+            self.sessions.lock().unwrap().remove(session_id);
+            Ok(())
+        }
+
+        async fn expire_sessions(&self) -> DomainResult<()> {
+            self.sessions
+                .lock()
+                .unwrap()
+                .retain(|_, s| s.expires_at.into_inner() > Utc::now());
+            Ok(())
+        }
+
+        async fn parents(&self, phone_number: &str) -> DomainResult<Vec<User>> {
+            if !phone_variants(phone_number).contains(&"+2348012345678".to_string()) {
+                return Ok(Vec::new());
+            }
+            Ok(vec![User {
+                id: Some(RecordId::new("users", "u1")),
+                school_id: RecordId::new("schools", "s1"),
+                user_type: UserType::Parent,
+                first_name: "Ngozi".into(),
+                last_name: "Okafor".into(),
+                email: None,
+                phone_number: Some("+2348012345678".into()),
+                password_hash: String::new(),
+                status: UserStatus::Active,
+                last_login: None,
+                email_verified: false,
+                phone_verified: false,
+                locale: None,
+                created_at: Datetime::now(),
+                updated_at: Datetime::now(),
+            }])
+        }
+
+        async fn localizer(&self, _user: &User) -> DomainResult<Localizer> {
+            Ok(Localizer::new(
+                Translations::global(),
+                Locale::En,
+                Currency::Ngn,
+            ))
+        }
+
+        async fn children(&self, _user: &User) -> DomainResult<Vec<Student>> {
+            Ok(vec![Student {
+                id: Some(RecordId::new("students", "st1")),
+                school_id: RecordId::new("schools", "s1"),
+                admission_number: "GFA/2024/017".into(),
+                first_name: "Chidi".into(),
+                middle_name: None,
+                last_name: "Okafor".into(),
+                date_of_birth: Value::None,
+                gender: Gender::Male,
+                address: None,
+                current_class_id: None,
+                admission_date: Value::None,
+                status: StudentStatus::Active,
+                profile_photo_url: None,
+                blood_group: None,
+                genotype: None,
+                medical_conditions: None,
+                created_at: Datetime::now(),
+                updated_at: Datetime::now(),
+            }])
+        }
+
+        async fn outstanding(&self, _student_id: &RecordId) -> DomainResult<Vec<Invoice>> {
+            let paid: Decimal = self
+                .payments
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|p| p.amount_paid)
+                .sum();
+            Ok(self
+                .invoices
+                .iter()
+                .filter(|i| i.balance > paid)
+                .cloned()
+                .collect())
+        }
+
+        async fn last_result(
+            &self,
+            student_id: &RecordId,
+        ) -> DomainResult<Option<(ReportCard, Term)>> {
+            let card = ReportCard {
+                id: None,
+                student_id: student_id.clone(),
+                class_id: RecordId::new("classes", "c1"),
+                term_id: RecordId::new("terms", "t1"),
+                total_score: None,
+                average_percentage: Some(Decimal::new(6754, 2)),
+                class_position: Some(4),
+                total_students_in_class: Some(32),
+                class_average: None,
+                attendance_present: None,
+                attendance_absent: None,
+                attendance_late: None,
+                times_school_opened: None,
+                teacher_comment: None,
+                principal_comment: None,
+                next_term_begins: None,
+                generated_by: None,
+                generated_at: Datetime::now(),
+                published: true,
+                published_at: None,
+                grading_scheme_version_id: None,
+                pdf_url: None,
+                created_at: Datetime::now(),
+            };
+            let term = Term {
+                id: Some(RecordId::new("terms", "t1")),
+                session_id: RecordId::new("academic_sessions", "a1"),
+                term_number: 1,
+                term_name: Some("First Term".into()),
+                start_date: Value::None,
+                end_date: Value::None,
+                is_current: true,
+                created_at: Datetime::now(),
+            };
+            Ok(Some((card, term)))
+        }
+
+        async fn start_payment(&self, payment: Payment) -> DomainResult<Payment> {
+            self.payments.lock().unwrap().push(payment.clone());
+            Ok(payment)
+        }
+    }
+
+    async fn dial(school: &Simulated, text: &str) -> UssdReply {
+        let request = UssdRequest {
+            session_id: "ATUid_1".into(),
+            phone_number: "+2348012345678".into(),
+            service_code: "*384*1234#".into(),
+            text: text.into(),
+        };
+        UssdService.handle(school, request).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn walks_a_parent_through_balance_results_and_payment() {
+        let school = Simulated::new();
+
+        let welcome = dial(&school, "").await;
+        assert_eq!(
+            welcome.to_gateway(),
+            "CON Welcome, Ngozi. Enter your ward's admission number:"
+        );
+        let unknown = dial(&school, "GFA/2024/999").await;
+        assert!(unknown.text.starts_with("No child of yours"));
+        assert!(!unknown.end);
+
+        let menu = dial(&school, "GFA/2024/999*gfa/2024/017").await;
+        assert!(menu.text.starts_with("Chidi Okafor\n1. Fee balance"));
+
+        let balance = dial(&school, "GFA/2024/999*gfa/2024/017*1").await;
+        assert!(
+            balance
+                .text
+                .contains("Chidi owes ₦45,000.00 on 1 invoice(s).")
+        );
+        dial(&school, "GFA/2024/999*gfa/2024/017*1*0").await;
+
+        let result = dial(&school, "GFA/2024/999*gfa/2024/017*1*0*2").await;
+        assert!(
+            result
+                .text
+                .starts_with("First Term: average 67.5%\nPosition 4 of 32")
+        );
+        dial(&school, "GFA/2024/999*gfa/2024/017*1*0*2*0").await;
+
+        let pay = dial(&school, "GFA/2024/999*gfa/2024/017*1*0*2*0*3").await;
+        assert!(pay.text.starts_with("Invoice INV-2026-000042"));
+        let other = dial(&school, "GFA/2024/999*gfa/2024/017*1*0*2*0*3*2").await;
+        assert!(other.text.contains("up to ₦45,000.00"));
+        let too_much = dial(&school, "GFA/2024/999*gfa/2024/017*1*0*2*0*3*2*50000").await;
+        assert!(too_much.text.starts_with("Invalid choice."));
+        let confirm = dial(
+            &school,
+            "GFA/2024/999*gfa/2024/017*1*0*2*0*3*2*50000*20,000",
+        )
+        .await;
+        assert!(confirm.text.starts_with("Pay ₦20,000.00 for Chidi?"));
+
+        let started = dial(
+            &school,
+            "GFA/2024/999*gfa/2024/017*1*0*2*0*3*2*50000*20,000*1",
+        )
+        .await;
+        assert!(started.end);
+        assert!(
+            started
+                .to_gateway()
+                .starts_with("END Payment of ₦20,000.00 started")
+        );
+        let payments = school.payments.lock().unwrap();
+        assert_eq!(payments.len(), 1);
+        assert_eq!(payments[0].amount_paid, Decimal::from(20000));
+        assert_eq!(payments[0].payment_method, PaymentMethod::Ussd);
+        assert_eq!(payments[0].status, PaymentStatus::Pending);
+        assert!(
+            started
+                .text
+                .contains(payments[0].payment_reference.as_deref().unwrap())
+        );
+        assert!(school.sessions.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn ends_unknown_callers_and_idle_sessions() {
+        let school = Simulated::new();
+        let stranger = UssdRequest {
+            session_id: "ATUid_2".into(),
+            phone_number: "+2349099999999".into(),
+            service_code: "*384*1234#".into(),
+            text: String::new(),
+        };
+        let reply = UssdService.handle(&school, stranger).await.unwrap();
+        assert!(reply.end);
+        assert!(reply.text.starts_with("This number is not registered"));
+
+        dial(&school, "").await;
+        dial(&school, "GFA/2024/017").await;
+        school
+            .sessions
+            .lock()
+            .unwrap()
+            .values_mut()
+            .for_each(|s| s.expires_at = Datetime::from(Utc::now() - Duration::seconds(1)));
+        let reply = dial(&school, "GFA/2024/017*1").await;
+        assert_eq!(
+            reply.to_gateway(),
+            "END Your session timed out. Please dial again."
+        );
+    }
+
+    #[test]
+    fn checks_the_gateway_key() {
+        assert!(carries_secret(Some("s3cret"), Some("s3cret")));
+        assert!(!carries_secret(Some("s3cret"), Some("s3cre")));
+        assert!(!carries_secret(Some("s3cret"), None));
+        assert!(!carries_secret(None, Some("anything")));
+        assert!(!carries_secret(Some(""), Some("")));
+    }
+}
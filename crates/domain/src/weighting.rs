@@ -0,0 +1,236 @@
+use db::queries::AssessmentWeightingQ;
+use shared::{
+    helpers::{create_update_dtos::SetAssessmentWeightingDto, surreal_util::record_key},
+    models::{
+        AssessmentType, ClassLevel,
+        assessments_nd_grading::{Assessment, AssessmentWeight, AssessmentWeighting},
+    },
+};
+use surrealdb::{Surreal, engine::remote::ws::Client};
+use surrealdb_types::Decimal;
+
+use crate::{
+    error::{DomainResult, Error},
+    subscription::SubscriptionService,
+};
+
+/// CA, exam and total for one subject after weighting, each out of its share of 100
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct WeightedScore {
+    pub ca: Option<Decimal>,
+    pub exam: Option<Decimal>,
+    pub total: Option<Decimal>,
+}
+
+pub struct WeightingService;
+
+impl WeightingService {
+    /// Validate and save the weighting for a class level
+    pub async fn save(
+        &self,
+        sdb: &Surreal<Client>,
+        data: SetAssessmentWeightingDto,
+    ) -> DomainResult<()> {
+        validate_weights(&data.weights)?;
+        SubscriptionService
+            .ensure_writable(sdb, &record_key(&data.school_id))
+            .await?;
+        AssessmentWeightingQ.upsert(sdb, data).await?;
+        Ok(())
+    }
+
+    /// The weighting for a class level, or an error if the school hasn't set one
+    pub async fn get(
+        &self,
+        sdb: &Surreal<Client>,
+        school_id: String,
+        class_level: ClassLevel,
+    ) -> DomainResult<AssessmentWeighting> {
+        AssessmentWeightingQ
+            .get(sdb, school_id, class_level.clone())
+            .await?
+            .ok_or_else(|| Error::NotFound(format!("assessment weighting for {class_level:?}")))
+    }
+}
+
+/// Weights must be positive, name each assessment type once and sum to 100
+pub fn validate_weights(weights: &[AssessmentWeight]) -> DomainResult<()> {
+    if weights.is_empty() {
+        return Err(Error::Validation("at least one weight is required".into()));
+    }
+    for (i, w) in weights.iter().enumerate() {
+        if w.weight <= Decimal::ZERO {
+            return Err(Error::Validation(format!(
+                "weight for {:?} must be positive",
+                w.assessment_type
+            )));
+        }
+        if weights[..i]
+            .iter()
+            .any(|other| other.assessment_type == w.assessment_type)
+        {
+            return Err(Error::Validation(format!(
+                "{:?} is weighted more than once",
+                w.assessment_type
+            )));
+        }
+    }
+    let sum: Decimal = weights.iter().map(|w| w.weight).sum();
+    if sum != Decimal::from(100) {
+        return Err(Error::Validation(format!(
+            "weights sum to {sum}, expected 100"
+        )));
+    }
+    Ok(())
+}
+
+/// Combine one student's scores in a subject. Each score is scaled from the
+/// assessment's `max_score` to its type's weight; several assessments of the
+/// same type are averaged and a missing score counts as zero. Weighted types
+/// with no assessments this term are left out and the remaining weights scaled
+/// up, so the total is still out of 100. Unweighted types are ignored.
+pub fn weighted_score(
+    weights: &[AssessmentWeight],
+    entries: &[(&Assessment, Option<Decimal>)],
+) -> WeightedScore {
+    let assessed = |w: &&AssessmentWeight| {
+        entries
+            .iter()
+            .any(|(a, _)| a.assessment_type == w.assessment_type && a.max_score > Decimal::ZERO)
+    };
+    let covered: Decimal = weights.iter().filter(assessed).map(|w| w.weight).sum();
+    if covered <= Decimal::ZERO {
+        return WeightedScore::default();
+    }
+    let scale = Decimal::from(100) / covered;
+
+    let mut ca: Option<Decimal> = None;
+    let mut exam: Option<Decimal> = None;
+
+    for w in weights {
+        let fractions: Vec<Decimal> = entries
+            .iter()
+            .filter(|(a, _)| a.assessment_type == w.assessment_type && a.max_score > Decimal::ZERO)
+            .map(|(a, score)| score.unwrap_or(Decimal::ZERO) / a.max_score)
+            .collect();
+        if fractions.is_empty() {
+            continue;
+        }
+        let part =
+            fractions.iter().sum::<Decimal>() / Decimal::from(fractions.len()) * w.weight * scale;
+        let slot = if w.assessment_type == AssessmentType::Exam {
+            &mut exam
+        } else {
+            &mut ca
+        };
+        *slot = Some(slot.unwrap_or(Decimal::ZERO) + part);
+    }
+
+    let ca = ca.map(|v| v.round_dp(2));
+    let exam = exam.map(|v| v.round_dp(2));
+    let total = match (ca, exam) {
+        (None, None) => None,
+        _ => Some(ca.unwrap_or(Decimal::ZERO) + exam.unwrap_or(Decimal::ZERO)),
+    };
+    WeightedScore { ca, exam, total }
+}
+
+#[cfg(test)]
+mod tests {
+    use surrealdb_types::{Datetime, RecordId};
+
+    use super::*;
+
+    fn weight(assessment_type: AssessmentType, weight: i64) -> AssessmentWeight {
+        AssessmentWeight {
+            assessment_type,
+            weight: Decimal::from(weight),
+        }
+    }
+
+    fn assessment(assessment_type: AssessmentType, max_score: i64) -> Assessment {
+        Assessment {
+            id: None,
+            school_id: RecordId::new("schools", "s1"),
+            class_id: RecordId::new("classes", "c1"),
+            subject_id: RecordId::new("subjects", "math"),
+            term_id: RecordId::new("terms", "t1"),
+            assessment_type,
+            assessment_name: None,
+            max_score: Decimal::from(max_score),
+            assessment_date: None,
+            created_by: None,
+            created_at: Datetime::now(),
+            updated_at: Datetime::now(),
+        }
+    }
+
+    #[test]
+    fn rejects_weights_not_summing_to_100() {
+        let weights = [
+            weight(AssessmentType::Ca1, 20),
+            weight(AssessmentType::Exam, 70),
+        ];
+        assert!(validate_weights(&weights).is_err());
+
+        let weights = [
+            weight(AssessmentType::Ca1, 20),
+            weight(AssessmentType::Ca1, 20),
+            weight(AssessmentType::Exam, 60),
+        ];
+        assert!(validate_weights(&weights).is_err());
+
+        let weights = [
+            weight(AssessmentType::Ca1, 20),
+            weight(AssessmentType::Ca2, 20),
+            weight(AssessmentType::Exam, 60),
+        ];
+        assert!(validate_weights(&weights).is_ok());
+    }
+
+    #[test]
+    fn normalizes_and_averages_scores() {
+        let weights = [
+            weight(AssessmentType::Test, 30),
+            weight(AssessmentType::Exam, 70),
+        ];
+        let test1 = assessment(AssessmentType::Test, 20);
+        let test2 = assessment(AssessmentType::Test, 10);
+        let exam = assessment(AssessmentType::Exam, 100);
+        let entries = [
+            (&test1, Some(Decimal::from(10))),
+            (&test2, Some(Decimal::from(10))),
+            (&exam, Some(Decimal::from(50))),
+        ];
+
+        let score = weighted_score(&weights, &entries);
+        assert_eq!(score.ca, Some(Decimal::new(2250, 2)));
+        assert_eq!(score.exam, Some(Decimal::new(3500, 2)));
+        assert_eq!(score.total, Some(Decimal::new(5750, 2)));
+    }
+
+    #[test]
+    fn scales_up_when_a_weighted_type_has_no_assessments() {
+        let weights = [
+            weight(AssessmentType::Ca1, 20),
+            weight(AssessmentType::Ca2, 20),
+            weight(AssessmentType::Exam, 60),
+        ];
+        let ca1 = assessment(AssessmentType::Ca1, 20);
+        let exam = assessment(AssessmentType::Exam, 100);
+
+        // Full marks with no CA2 set yet is still 100
+        let score = weighted_score(
+            &weights,
+            &[
+                (&ca1, Some(Decimal::from(20))),
+                (&exam, Some(Decimal::from(100))),
+            ],
+        );
+        assert_eq!(score.ca, Some(Decimal::from(25)));
+        assert_eq!(score.exam, Some(Decimal::from(75)));
+        assert_eq!(score.total, Some(Decimal::from(100)));
+
+        assert_eq!(weighted_score(&weights, &[]), WeightedScore::default());
+    }
+}
@@ -0,0 +1,1078 @@
+use std::{env, sync::OnceLock, time::Duration};
+
+use chrono::DateTime;
+use db::queries::{
+    AnnouncementQ, ConversationQ, IdempotencyQ, InvoiceQ, MessageDeliveryQ, MessageQ, ParentQ,
+    PaymentReminderQ, SchoolSettingQ, TermQ, UserQ, WhatsappTemplateQ,
+};
+use hmac::{Hmac, Mac};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use sha2::Sha256;
+use shared::{
+    helpers::{
+        api_responses::SessionUser, create_update_dtos::SaveWhatsappTemplateDto,
+        surreal_util::record_key,
+    },
+    i18n::{Locale, Localizer},
+    models::{
+        DeliveryStatus, MessageChannel, ReminderType, School, SmsType, User, UserStatus, UserType,
+        communication::{Message, WhatsappTemplate},
+        fee_management::PaymentReminder,
+        system_and_audit::{MessageDelivery, TemplateMessage},
+    },
+};
+use surrealdb::{Surreal, engine::remote::ws::Client};
+use surrealdb_types::{Datetime, RecordId};
+
+use crate::{
+    auth::ensure_role,
+    conversation::ConversationService,
+    delivery::{DeliveryProvider, DeliveryService, SendError, SentMessage},
+    email::EmailService,
+    error::{DomainResult, Error},
+    locale::LocaleService,
+    subscription::SubscriptionService,
+    templating::{TemplateContext, TemplateKey, TemplateService},
+};
+
+/// Access token of the WhatsApp Business account's system user
+pub const WHATSAPP_ACCESS_TOKEN_VAR: &str = "WHATSAPP_ACCESS_TOKEN";
+/// Meta app secret webhook payloads are signed with
+pub const WHATSAPP_APP_SECRET_VAR: &str = "WHATSAPP_APP_SECRET";
+/// Token Meta echoes back when the webhook is registered
+pub const WHATSAPP_VERIFY_TOKEN_VAR: &str = "WHATSAPP_VERIFY_TOKEN";
+/// Graph API base, e.g. a local stub in development
+pub const WHATSAPP_API_URL_VAR: &str = "WHATSAPP_API_URL";
+/// School setting with the id of the WhatsApp number the school sends from
+pub const PHONE_NUMBER_ID_SETTING: &str = "whatsapp_phone_number_id";
+
+const DEFAULT_API_URL: &str = "https://graph.facebook.com/v21.0";
+
+/// Talks to the WhatsApp Cloud API for every school; each school sends from
+/// its own number, set as [`PHONE_NUMBER_ID_SETTING`]
+pub struct WhatsappClient {
+    http: reqwest::Client,
+    api_url: String,
+    access_token: String,
+    app_secret: Option<String>,
+    verify_token: Option<String>,
+}
+
+impl WhatsappClient {
+    pub fn new(
+        api_url: String,
+        access_token: String,
+        app_secret: Option<String>,
+        verify_token: Option<String>,
+    ) -> Self {
+        Self {
+            http: reqwest::Client::builder()
+                .timeout(Duration::from_secs(30))
+                .build()
+                .unwrap_or_default(),
+            api_url: api_url.trim_end_matches('/').to_string(),
+            access_token,
+            app_secret,
+            verify_token,
+        }
+    }
+
+    pub fn from_env() -> DomainResult<Self> {
+        let access_token = env::var(WHATSAPP_ACCESS_TOKEN_VAR)
+            .map_err(|_| Error::Validation(format!("{WHATSAPP_ACCESS_TOKEN_VAR} is not set")))?;
+        Ok(Self::new(
+            env::var(WHATSAPP_API_URL_VAR).unwrap_or_else(|_| DEFAULT_API_URL.into()),
+            access_token,
+            env::var(WHATSAPP_APP_SECRET_VAR).ok(),
+            env::var(WHATSAPP_VERIFY_TOKEN_VAR).ok(),
+        ))
+    }
+
+    /// Send a delivery from a school's number; returns the message's wamid
+    pub async fn send(
+        &self,
+        phone_number_id: &str,
+        delivery: &MessageDelivery,
+    ) -> Result<String, SendError> {
+        let to = delivery
+            .address
+            .as_deref()
+            .map(international)
+            .filter(|to| !to.is_empty())
+            .ok_or_else(|| SendError::Rejected("no phone number".into()))?;
+        let response = self
+            .http
+            .post(format!("{}/{phone_number_id}/messages", self.api_url))
+            .bearer_auth(&self.access_token)
+            .json(&request_body(&to, delivery))
+            .send()
+            .await
+            .map_err(|e| SendError::Temporary(e.to_string()))?;
+        let status = response.status();
+        let body: Value = response.json().await.unwrap_or(Value::Null);
+        if !status.is_success() {
+            return Err(send_error(status, &body));
+        }
+        body["messages"][0]["id"]
+            .as_str()
+            .map(str::to_owned)
+            .ok_or_else(|| SendError::Temporary("no message id in the response".into()))
+    }
+
+    /// Answer Meta's webhook registration: the challenge, if the token matches
+    pub fn verify_subscription(
+        &self,
+        mode: &str,
+        token: &str,
+        challenge: String,
+    ) -> Option<String> {
+        let expected = self.verify_token.as_deref()?;
+        (mode == "subscribe" && token == expected).then_some(challenge)
+    }
+
+    /// Whether a webhook body carries the app's `X-Hub-Signature-256`
+    pub fn verify_signature(&self, body: &[u8], signature: &str) -> bool {
+        self.app_secret
+            .as_deref()
+            .is_some_and(|secret| signed(secret, body, signature))
+    }
+}
+
+static WHATSAPP_CLIENT: OnceLock<WhatsappClient> = OnceLock::new();
+
+/// The process-wide WhatsApp client, configured from the environment
+pub fn whatsapp_client() -> DomainResult<&'static WhatsappClient> {
+    if let Some(client) = WHATSAPP_CLIENT.get() {
+        return Ok(client);
+    }
+    let client = WhatsappClient::from_env()?;
+    Ok(WHATSAPP_CLIENT.get_or_init(|| client))
+}
+
+/// Digits only, as the Cloud API wants numbers: "+234 801 234 5678" → "2348012345678"
+fn international(phone: &str) -> String {
+    phone.chars().filter(char::is_ascii_digit).collect()
+}
+
+/// Ways a number WhatsApp reports may have been entered for a user
+pub(crate) fn phone_variants(from: &str) -> Vec<String> {
+    let digits = international(from);
+    vec![format!("+{digits}"), digits]
+}
+
+/// The Cloud API message for a delivery: its approved template, or free
+/// text (only accepted while the recipient's 24-hour window is open)
+fn request_body(to: &str, delivery: &MessageDelivery) -> Value {
+    match &delivery.template {
+        Some(template) => {
+            let parameters: Vec<Value> = template
+                .parameters
+                .iter()
+                .map(|text| json!({ "type": "text", "text": text }))
+                .collect();
+            let mut body = json!({
+                "messaging_product": "whatsapp",
+                "recipient_type": "individual",
+                "to": to,
+                "type": "template",
+                "template": {
+                    "name": template.name,
+                    "language": { "code": template.language },
+                },
+            });
+            if !parameters.is_empty() {
+                body["template"]["components"] =
+                    json!([{ "type": "body", "parameters": parameters }]);
+            }
+            body
+        }
+        None => json!({
+            "messaging_product": "whatsapp",
+            "recipient_type": "individual",
+            "to": to,
+            "type": "text",
+            "text": { "preview_url": false, "body": delivery.body },
+        }),
+    }
+}
+
+/// Throttling and server errors are retried; anything else, such as an
+/// unapproved template or a number not on WhatsApp, is refused for good
+fn send_error(status: StatusCode, body: &Value) -> SendError {
+    let message = body["error"]["message"]
+        .as_str()
+        .map(str::to_owned)
+        .unwrap_or_else(|| format!("WhatsApp returned {status}"));
+    if status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error() {
+        SendError::Temporary(message)
+    } else {
+        SendError::Rejected(message)
+    }
+}
+
+/// Whether `signature` is `sha256=` and the hex HMAC-SHA256 of the body
+pub(crate) fn signed(secret: &str, body: &[u8], signature: &str) -> bool {
+    let Some(expected) = signature.strip_prefix("sha256=").and_then(from_hex) else {
+        return false;
+    };
+    let Ok(mut mac) = Hmac::<Sha256>::new_from_slice(secret.as_bytes()) else {
+        return false;
+    };
+    mac.update(body);
+    mac.verify_slice(&expected).is_ok()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Sends queued WhatsApp deliveries from each school's number
+pub struct WhatsappCloud<'a> {
+    pub sdb: &'a Surreal<Client>,
+    pub client: &'a WhatsappClient,
+}
+
+impl DeliveryProvider for WhatsappCloud<'_> {
+    fn channel(&self) -> MessageChannel {
+        MessageChannel::Whatsapp
+    }
+
+    async fn send(&self, delivery: &MessageDelivery) -> Result<SentMessage, SendError> {
+        let phone_number_id = SchoolSettingQ
+            .get(
+                self.sdb,
+                record_key(&delivery.school_id),
+                PHONE_NUMBER_ID_SETTING.into(),
+            )
+            .await
+            .map_err(|e| SendError::Temporary(e.to_string()))?
+            .ok_or_else(|| SendError::Rejected("the school has no WhatsApp number".into()))?;
+        let message_id = self.client.send(&phone_number_id, delivery).await?;
+        Ok(SentMessage {
+            provider: "whatsapp".into(),
+            provider_message_id: Some(message_id),
+            cost: None,
+        })
+    }
+}
+
+/// What Meta posts to the webhook: replies from parents and receipts for
+/// messages we sent
+#[derive(Debug, Default, Deserialize)]
+pub struct Webhook {
+    #[serde(default)]
+    entry: Vec<WebhookEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct WebhookEntry {
+    #[serde(default)]
+    changes: Vec<WebhookChange>,
+}
+
+#[derive(Debug, Deserialize)]
+struct WebhookChange {
+    value: WebhookValue,
+}
+
+#[derive(Debug, Deserialize)]
+struct WebhookValue {
+    metadata: Option<WebhookMetadata>,
+    #[serde(default)]
+    messages: Vec<InboundMessage>,
+    #[serde(default)]
+    statuses: Vec<StatusUpdate>,
+}
+
+#[derive(Debug, Deserialize)]
+struct WebhookMetadata {
+    phone_number_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct InboundMessage {
+    from: String,
+    id: String,
+    timestamp: Option<String>,
+    #[serde(rename = "type")]
+    kind: String,
+    text: Option<InboundText>,
+    /// A tapped quick-reply button of a template
+    button: Option<InboundButton>,
+    /// The message this one replies to
+    context: Option<InboundContext>,
+}
+
+#[derive(Debug, Deserialize)]
+struct InboundText {
+    body: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct InboundButton {
+    text: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct InboundContext {
+    id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct StatusUpdate {
+    id: String,
+    status: String,
+    #[serde(default)]
+    errors: Vec<StatusError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StatusError {
+    title: Option<String>,
+    message: Option<String>,
+}
+
+impl InboundMessage {
+    /// The reply as text; media is noted by kind since it isn't downloaded
+    fn text(&self) -> String {
+        match (&self.text, &self.button) {
+            (Some(text), _) => text.body.clone(),
+            (None, Some(button)) => button.text.clone(),
+            (None, None) => format!("[WhatsApp {}]", self.kind),
+        }
+    }
+
+    fn sent_at(&self) -> Datetime {
+        self.timestamp
+            .as_deref()
+            .and_then(|t| t.parse().ok())
+            .and_then(|t| DateTime::from_timestamp(t, 0))
+            .map(Datetime::from)
+            .unwrap_or_else(Datetime::now)
+    }
+}
+
+/// The delivery status a WhatsApp status update means; "sent" is already
+/// recorded when the API accepts the message
+fn receipt_status(status: &str) -> Option<DeliveryStatus> {
+    match status {
+        "delivered" | "read" => Some(DeliveryStatus::Delivered),
+        "failed" => Some(DeliveryStatus::Failed),
+        _ => None,
+    }
+}
+
+/// What a webhook call changed
+#[derive(Debug, Default)]
+pub struct WebhookOutcome {
+    pub messages: Vec<Message>,
+    pub receipts: usize,
+}
+
+/// Approved templates, outgoing reminders and incoming replies on WhatsApp
+pub struct WhatsappService;
+
+impl WhatsappService {
+    /// Register a template Meta approved for one kind of message
+    pub async fn save_template(
+        &self,
+        sdb: &Surreal<Client>,
+        school_id: String,
+        mut data: SaveWhatsappTemplateDto,
+    ) -> DomainResult<()> {
+        let key = TemplateKey::parse(&MessageChannel::Whatsapp, &data.template_key)?;
+        data.name = data.name.trim().to_string();
+        data.language = data.language.trim().to_string();
+        if data.name.is_empty() || data.language.is_empty() {
+            return Err(Error::Validation(
+                "a WhatsApp template needs its approved name and language".into(),
+            ));
+        }
+        let allowed = key.variables();
+        let unknown: Vec<&str> = data
+            .parameters
+            .iter()
+            .map(String::as_str)
+            .filter(|name| !allowed.iter().any(|a| a == name))
+            .collect();
+        if !unknown.is_empty() {
+            return Err(Error::Validation(format!(
+                "unknown variables: {}",
+                unknown.join(", ")
+            )));
+        }
+        data.template_key = key.name();
+        SubscriptionService.ensure_writable(sdb, &school_id).await?;
+        WhatsappTemplateQ.upsert(sdb, school_id, data).await?;
+        Ok(())
+    }
+
+    pub async fn templates(
+        &self,
+        sdb: &Surreal<Client>,
+        school_id: String,
+    ) -> DomainResult<Vec<WhatsappTemplate>> {
+        Ok(WhatsappTemplateQ.get_by_school(sdb, school_id).await?)
+    }
+
+    pub async fn delete_template(
+        &self,
+        sdb: &Surreal<Client>,
+        school_id: String,
+        template_key: String,
+        locale: Option<Locale>,
+    ) -> DomainResult<()> {
+        let key = TemplateKey::parse(&MessageChannel::Whatsapp, &template_key)?;
+        SubscriptionService.ensure_writable(sdb, &school_id).await?;
+        WhatsappTemplateQ
+            .delete(sdb, school_id, key.name(), locale)
+            .await?;
+        Ok(())
+    }
+
+    /// Queue a reminder of an invoice's balance to the student's parents, and
+    /// log it against the invoice
+    pub async fn send_fee_reminder(
+        &self,
+        sdb: &Surreal<Client>,
+        staff: &SessionUser,
+        invoice_id: String,
+    ) -> DomainResult<Vec<MessageDelivery>> {
+        let invoice = InvoiceQ
+            .get_by_id(sdb, invoice_id.clone())
+            .await?
+            .ok_or_else(|| Error::NotFound(format!("invoice {invoice_id}")))?;
+        let (school, student) = EmailService
+            .school_and_student(sdb, &invoice.student_id)
+            .await?;
+        ensure_role(
+            staff,
+            &record_key(&student.school_id),
+            &[UserType::Admin, UserType::Accountant],
+        )?;
+        let parents = UserQ
+            .get_student_parents(sdb, record_key(&invoice.student_id))
+            .await?;
+
+        let key = TemplateKey::Sms(SmsType::FeeReminder);
+        let mut deliveries = Vec::new();
+        for parent in active(parents) {
+            let i18n = LocaleService.for_user(sdb, &parent).await?;
+            let mut context = TemplateContext::new();
+            context
+                .school(&school)
+                .recipient(&parent)
+                .student(&student)
+                .invoice(&invoice, &i18n);
+            deliveries.push(
+                self.delivery(sdb, &school, &parent, &key, &i18n, &context)
+                    .await?,
+            );
+        }
+        let deliveries = MessageDeliveryQ.create_many(sdb, deliveries).await?;
+
+        for delivery in &deliveries {
+            let parent = ParentQ
+                .get_by_user(sdb, record_key(&delivery.recipient_user_id))
+                .await?;
+            let Some(parent_id) = parent.and_then(|p| p.id) else {
+                continue;
+            };
+            PaymentReminderQ
+                .create(
+                    sdb,
+                    PaymentReminder {
+                        id: None,
+                        invoice_id: invoice.id.clone().expect("loaded invoices have ids"),
+                        parent_id,
+                        reminder_type: ReminderType::Whatsapp,
+                        message: delivery.body.clone(),
+                        sent_at: Datetime::now(),
+                        delivery_status: delivery.status.clone(),
+                        delivered_at: None,
+                        created_at: Datetime::now(),
+                    },
+                )
+                .await?;
+        }
+        Ok(deliveries)
+    }
+
+    /// Queue a message telling the student's parents a term's results are out
+    pub async fn send_result_notification(
+        &self,
+        sdb: &Surreal<Client>,
+        staff: &SessionUser,
+        student_id: String,
+        term_id: String,
+    ) -> DomainResult<Vec<MessageDelivery>> {
+        let (school, student) = EmailService
+            .school_and_student(sdb, &RecordId::new("students", student_id.as_str()))
+            .await?;
+        ensure_role(staff, &record_key(&student.school_id), &[UserType::Admin])?;
+        let term = TermQ
+            .get_by_id(sdb, term_id.clone())
+            .await?
+            .ok_or_else(|| Error::NotFound(format!("term {term_id}")))?;
+        let parents = UserQ.get_student_parents(sdb, student_id).await?;
+
+        let key = TemplateKey::Sms(SmsType::ResultNotification);
+        let mut deliveries = Vec::new();
+        for parent in active(parents) {
+            let i18n = LocaleService.for_user(sdb, &parent).await?;
+            let mut context = TemplateContext::new();
+            context
+                .school(&school)
+                .recipient(&parent)
+                .student(&student)
+                .term(&term, &i18n);
+            deliveries.push(
+                self.delivery(sdb, &school, &parent, &key, &i18n, &context)
+                    .await?,
+            );
+        }
+        Ok(MessageDeliveryQ.create_many(sdb, deliveries).await?)
+    }
+
+    /// One parent's delivery, with the school's approved template for their
+    /// language and the SMS wording as the readable copy
+    async fn delivery(
+        &self,
+        sdb: &Surreal<Client>,
+        school: &School,
+        user: &User,
+        key: &TemplateKey,
+        i18n: &Localizer,
+        context: &TemplateContext,
+    ) -> DomainResult<MessageDelivery> {
+        let school_id = school.id.as_ref().map(record_key).unwrap_or_default();
+        let templates = WhatsappTemplateQ
+            .find(sdb, school_id.clone(), key.name())
+            .await?;
+        let text = TemplateService
+            .render(sdb, school_id, key, i18n, context)
+            .await?;
+        Ok(delivery(
+            school,
+            user,
+            key,
+            approved_template(&templates, i18n.locale),
+            context,
+            text.body,
+        ))
+    }
+
+    /// Apply a signed webhook call: receipts update deliveries and replies
+    /// become messages to the staff member the parent is talking to.
+    /// Meta retries calls, so a reply seen before is skipped.
+    pub async fn receive(
+        &self,
+        sdb: &Surreal<Client>,
+        client: &WhatsappClient,
+        signature: Option<&str>,
+        body: &[u8],
+    ) -> DomainResult<WebhookOutcome> {
+        if !signature.is_some_and(|s| client.verify_signature(body, s)) {
+            return Err(Error::Unauthorized("invalid webhook signature".into()));
+        }
+        let webhook: Webhook = serde_json::from_slice(body)
+            .map_err(|e| Error::Validation(format!("unreadable webhook: {e}")))?;
+
+        let mut outcome = WebhookOutcome::default();
+        for value in webhook
+            .entry
+            .into_iter()
+            .flat_map(|e| e.changes)
+            .map(|c| c.value)
+        {
+            for update in &value.statuses {
+                let Some(status) = receipt_status(&update.status) else {
+                    continue;
+                };
+                let error = update
+                    .errors
+                    .first()
+                    .and_then(|e| e.message.clone().or_else(|| e.title.clone()));
+                outcome.receipts += DeliveryService
+                    .receipt(sdb, update.id.clone(), status, error)
+                    .await?;
+            }
+            let Some(metadata) = &value.metadata else {
+                continue;
+            };
+            for inbound in &value.messages {
+                if let Some(message) = self
+                    .thread_reply(sdb, &metadata.phone_number_id, inbound)
+                    .await?
+                {
+                    outcome.messages.push(message);
+                }
+            }
+        }
+        Ok(outcome)
+    }
+
+    /// File a parent's reply in the school the number belongs to, in the
+    /// thread it answers. Replies from numbers no user has are dropped.
+    async fn thread_reply(
+        &self,
+        sdb: &Surreal<Client>,
+        phone_number_id: &str,
+        inbound: &InboundMessage,
+    ) -> DomainResult<Option<Message>> {
+        let Some(setting) = SchoolSettingQ
+            .find_school(
+                sdb,
+                PHONE_NUMBER_ID_SETTING.into(),
+                phone_number_id.to_string(),
+            )
+            .await?
+        else {
+            return Ok(None);
+        };
+        let school_id = record_key(&setting.school_id);
+        let Some(sender) = UserQ
+            .find_in_school_by_phone(sdb, school_id.clone(), phone_variants(&inbound.from))
+            .await?
+        else {
+            return Ok(None);
+        };
+        let sender_id = sender.id.clone().expect("loaded users have ids");
+        let seen = format!("whatsapp_{}", inbound.id);
+        if !IdempotencyQ
+            .claim(sdb, seen.clone(), record_key(&sender_id))
+            .await?
+        {
+            return Ok(None);
+        }
+        let Some(recipient_id) = self.staff_for(sdb, school_id, &sender_id, inbound).await? else {
+            return Ok(None);
+        };
+
+        let conversation = ConversationService
+            .open(
+                sdb,
+                setting.school_id.clone(),
+                [sender_id.clone(), recipient_id.clone()],
+                None,
+                None,
+            )
+            .await?;
+        let previous = MessageQ
+            .get_latest_between(sdb, record_key(&sender_id), record_key(&recipient_id))
+            .await?;
+        if let Some(previous) = previous.as_ref().filter(|m| m.recipient_id == sender_id) {
+            MessageQ
+                .mark_replied(
+                    sdb,
+                    previous.id.as_ref().map(record_key).unwrap_or_default(),
+                )
+                .await?;
+        }
+        let message = MessageQ
+            .send(
+                sdb,
+                Message {
+                    id: None,
+                    school_id: setting.school_id,
+                    sender_id,
+                    recipient_id,
+                    subject: None,
+                    message_body: inbound.text(),
+                    read: false,
+                    read_at: None,
+                    replied: false,
+                    parent_message_id: previous.and_then(|m| m.id),
+                    conversation_id: conversation.id.clone(),
+                    hidden_by: None,
+                    hidden_at: None,
+                    sent_at: inbound.sent_at(),
+                    created_at: Datetime::now(),
+                },
+            )
+            .await?;
+        if let Some(message) = &message {
+            if let Some(id) = &conversation.id {
+                ConversationQ
+                    .touch(sdb, record_key(id), message.sent_at)
+                    .await?;
+            }
+            if let Some(id) = &message.id {
+                IdempotencyQ.complete(sdb, seen, record_key(id)).await?;
+            }
+        }
+        Ok(message)
+    }
+
+    /// Who a parent's reply is for: whoever published the announcement it
+    /// answers, else whoever they last exchanged messages with, else the
+    /// school's first admin
+    async fn staff_for(
+        &self,
+        sdb: &Surreal<Client>,
+        school_id: String,
+        sender_id: &RecordId,
+        inbound: &InboundMessage,
+    ) -> DomainResult<Option<RecordId>> {
+        if let Some(context) = &inbound.context {
+            let delivery = MessageDeliveryQ
+                .get_by_provider_id(sdb, context.id.clone())
+                .await?;
+            if let Some(announcement_id) = delivery.and_then(|d| d.announcement_id) {
+                let author = AnnouncementQ
+                    .get_by_id(sdb, record_key(&announcement_id))
+                    .await?
+                    .and_then(|a| a.created_by);
+                if author.is_some() {
+                    return Ok(author);
+                }
+            }
+        }
+        if let Some(latest) = MessageQ.get_latest_for(sdb, record_key(sender_id)).await? {
+            let other = if &latest.sender_id == sender_id {
+                latest.recipient_id
+            } else {
+                latest.sender_id
+            };
+            return Ok(Some(other));
+        }
+        let admins = UserQ.get_by_type(sdb, school_id, UserType::Admin).await?;
+        Ok(active(admins).into_iter().find_map(|u| u.id))
+    }
+}
+
+fn active(users: Vec<User>) -> Vec<User> {
+    users
+        .into_iter()
+        .filter(|u| u.id.is_some() && u.status == UserStatus::Active)
+        .collect()
+}
+
+/// The template for the recipient's language, else the one for every language
+fn approved_template(templates: &[WhatsappTemplate], locale: Locale) -> Option<&WhatsappTemplate> {
+    templates
+        .iter()
+        .find(|t| t.locale == Some(locale))
+        .or_else(|| templates.iter().find(|t| t.locale.is_none()))
+}
+
+/// A delivery queued with its template filled in, or failed when the parent
+/// has no number, the school has no approved template, or a placeholder's
+/// value is missing (WhatsApp refuses empty parameters)
+fn delivery(
+    school: &School,
+    user: &User,
+    key: &TemplateKey,
+    template: Option<&WhatsappTemplate>,
+    context: &TemplateContext,
+    text: String,
+) -> MessageDelivery {
+    let address = user
+        .phone_number
+        .clone()
+        .filter(|p| !international(p).is_empty());
+    let filled = template.map(|template| {
+        let mut parameters = Vec::new();
+        for name in &template.parameters {
+            match context.get(name) {
+                Some(value) => parameters.push(value.to_string()),
+                None => return Err(format!("no value for {name}")),
+            }
+        }
+        Ok(TemplateMessage {
+            name: template.name.clone(),
+            language: template.language.clone(),
+            parameters,
+        })
+    });
+    let (status, last_error, template) = match (&address, filled) {
+        (None, _) => (DeliveryStatus::Failed, Some("no phone number".into()), None),
+        (_, None) => (
+            DeliveryStatus::Failed,
+            Some(format!("no approved WhatsApp template for {}", key.name())),
+            None,
+        ),
+        (_, Some(Err(err))) => (DeliveryStatus::Failed, Some(err), None),
+        (_, Some(Ok(template))) => (DeliveryStatus::Queued, None, Some(template)),
+    };
+    MessageDelivery {
+        id: None,
+        school_id: school.id.clone().expect("loaded schools have ids"),
+        announcement_id: None,
+        recipient_user_id: user.id.clone().expect("recipients have ids"),
+        channel: MessageChannel::Whatsapp,
+        address,
+        subject: None,
+        body: text,
+        status,
+        attempts: 0,
+        last_error,
+        provider: None,
+        provider_message_id: None,
+        cost: None,
+        sent_at: None,
+        delivered_at: None,
+        template,
+        created_at: Datetime::now(),
+        updated_at: Datetime::now(),
+    }
+}
+
+/// Sends queued WhatsApp messages
+pub async fn run_queue(
+    sdb: &Surreal<Client>,
+    client: &WhatsappClient,
+    limit: u32,
+) -> DomainResult<usize> {
+    DeliveryService
+        .dispatch(sdb, &WhatsappCloud { sdb, client }, limit)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shared::models::{SubscriptionPlan, SubscriptionStatus};
+
+    fn school() -> School {
+        School {
+            id: Some(RecordId::new("schools", "s1")),
+            school_name: "Greenfield Academy".into(),
+            school_address: None,
+            school_phone: None,
+            school_email: None,
+            school_logo_url: None,
+            school_motto: None,
+            total_students: 0,
+            subscription_plan: SubscriptionPlan::Basic,
+            subscription_expiry_date: None,
+            subscription_status: SubscriptionStatus::Active,
+            created_at: Datetime::now(),
+            updated_at: Datetime::now(),
+        }
+    }
+
+    fn parent(phone: Option<&str>) -> User {
+        User {
+            id: Some(RecordId::new("users", "u1")),
+            school_id: RecordId::new("schools", "s1"),
+            user_type: UserType::Parent,
+            first_name: "Ngozi".into(),
+            last_name: "Okafor".into(),
+            email: None,
+            phone_number: phone.map(Into::into),
+            password_hash: String::new(),
+            status: UserStatus::Active,
+            last_login: None,
+            email_verified: false,
+            phone_verified: false,
+            locale: None,
+            created_at: Datetime::now(),
+            updated_at: Datetime::now(),
+        }
+    }
+
+    fn template(locale: Option<Locale>, parameters: &[&str]) -> WhatsappTemplate {
+        WhatsappTemplate {
+            id: None,
+            school_id: RecordId::new("schools", "s1"),
+            template_key: "fee_reminder".into(),
+            locale,
+            name: "fee_balance_reminder".into(),
+            language: "en".into(),
+            parameters: parameters.iter().map(|p| p.to_string()).collect(),
+            created_at: Datetime::now(),
+            updated_at: Datetime::now(),
+        }
+    }
+
+    #[test]
+    fn fills_approved_templates_or_fails_the_delivery() {
+        let key = TemplateKey::Sms(SmsType::FeeReminder);
+        let mut context = TemplateContext::new();
+        context
+            .set("recipient.first_name", Some("Ngozi".into()))
+            .set("invoice.balance", Some("₦45,000.00".into()));
+        let fee = template(None, &["recipient.first_name", "invoice.balance"]);
+        let french = template(Some(Locale::Fr), &[]);
+        let templates = [fee.clone(), french];
+
+        assert_eq!(
+            approved_template(&templates, Locale::Fr).unwrap().locale,
+            Some(Locale::Fr)
+        );
+        let chosen = approved_template(&templates, Locale::En);
+        let queued = delivery(
+            &school(),
+            &parent(Some("+234 801 234 5678")),
+            &key,
+            chosen,
+            &context,
+            "text".into(),
+        );
+        assert_eq!(queued.status, DeliveryStatus::Queued);
+        assert_eq!(
+            queued.template.unwrap().parameters,
+            vec!["Ngozi".to_string(), "₦45,000.00".to_string()]
+        );
+
+        let no_phone = delivery(
+            &school(),
+            &parent(None),
+            &key,
+            chosen,
+            &context,
+            "text".into(),
+        );
+        assert_eq!(no_phone.last_error.as_deref(), Some("no phone number"));
+
+        let unapproved = delivery(
+            &school(),
+            &parent(Some("+2348012345678")),
+            &key,
+            None,
+            &context,
+            "text".into(),
+        );
+        assert_eq!(
+            unapproved.last_error.as_deref(),
+            Some("no approved WhatsApp template for fee_reminder")
+        );
+
+        let due = template(None, &["invoice.due_date"]);
+        let missing = delivery(
+            &school(),
+            &parent(Some("+2348012345678")),
+            &key,
+            Some(&due),
+            &context,
+            "text".into(),
+        );
+        assert_eq!(missing.status, DeliveryStatus::Failed);
+        assert_eq!(
+            missing.last_error.as_deref(),
+            Some("no value for invoice.due_date")
+        );
+    }
+
+    #[test]
+    fn builds_cloud_api_requests() {
+        let key = TemplateKey::Sms(SmsType::FeeReminder);
+        let mut context = TemplateContext::new();
+        context.set("recipient.first_name", Some("Ngozi".into()));
+        let fee = template(None, &["recipient.first_name"]);
+        let mut queued = delivery(
+            &school(),
+            &parent(Some("+234 801 234 5678")),
+            &key,
+            Some(&fee),
+            &context,
+            "Dear Ngozi".into(),
+        );
+        let to = international(queued.address.as_deref().unwrap());
+        assert_eq!(to, "2348012345678");
+
+        let body = request_body(&to, &queued);
+        assert_eq!(body["type"], "template");
+        assert_eq!(body["template"]["name"], "fee_balance_reminder");
+        assert_eq!(body["template"]["language"]["code"], "en");
+        assert_eq!(
+            body["template"]["components"][0]["parameters"][0]["text"],
+            "Ngozi"
+        );
+
+        queued.template = None;
+        let body = request_body(&to, &queued);
+        assert_eq!(body["type"], "text");
+        assert_eq!(body["text"]["body"], "Dear Ngozi");
+
+        let throttled = json!({ "error": { "message": "Rate limit hit", "code": 130429 } });
+        assert_eq!(
+            send_error(StatusCode::TOO_MANY_REQUESTS, &throttled),
+            SendError::Temporary("Rate limit hit".into())
+        );
+        assert!(matches!(
+            send_error(StatusCode::BAD_REQUEST, &Value::Null),
+            SendError::Rejected(_)
+        ));
+    }
+
+    #[test]
+    fn reads_signed_webhooks() {
+        let client = WhatsappClient::new(
+            DEFAULT_API_URL.into(),
+            "token".into(),
+            Some("app-secret".into()),
+            Some("verify-me".into()),
+        );
+        let body = br#"{
+            "object": "whatsapp_business_account",
+            "entry": [{
+                "id": "WABA_ID",
+                "changes": [{
+                    "field": "messages",
+                    "value": {
+                        "messaging_product": "whatsapp",
+                        "metadata": { "display_phone_number": "2348000000000", "phone_number_id": "1098" },
+                        "contacts": [{ "profile": { "name": "Ngozi" }, "wa_id": "2348012345678" }],
+                        "messages": [{
+                            "from": "2348012345678",
+                            "id": "wamid.in1",
+                            "timestamp": "1760000000",
+                            "type": "text",
+                            "text": { "body": "I will pay on Friday" },
+                            "context": { "from": "2348000000000", "id": "wamid.out1" }
+                        }],
+                        "statuses": [{ "id": "wamid.out2", "status": "read", "timestamp": "1760000001" }]
+                    }
+                }]
+            }]
+        }"#;
+
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"app-secret").unwrap();
+        mac.update(body);
+        let signature: String = mac
+            .finalize()
+            .into_bytes()
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect();
+        assert!(client.verify_signature(body, &format!("sha256={signature}")));
+        assert!(!client.verify_signature(b"{}", &format!("sha256={signature}")));
+        assert!(!client.verify_signature(body, "sha256=zz"));
+
+        assert_eq!(
+            client.verify_subscription("subscribe", "verify-me", "42".into()),
+            Some("42".into())
+        );
+        assert_eq!(
+            client.verify_subscription("subscribe", "wrong", "42".into()),
+            None
+        );
+
+        let webhook: Webhook = serde_json::from_slice(body).unwrap();
+        let value = &webhook.entry[0].changes[0].value;
+        assert_eq!(value.metadata.as_ref().unwrap().phone_number_id, "1098");
+        let inbound = &value.messages[0];
+        assert_eq!(inbound.text(), "I will pay on Friday");
+        assert_eq!(inbound.context.as_ref().unwrap().id, "wamid.out1");
+        assert_eq!(inbound.sent_at().into_inner().timestamp(), 1_760_000_000);
+        assert_eq!(
+            phone_variants(&inbound.from),
+            vec!["+2348012345678".to_string(), "2348012345678".to_string()]
+        );
+        assert_eq!(
+            receipt_status(&value.statuses[0].status),
+            Some(DeliveryStatus::Delivered)
+        );
+        assert_eq!(receipt_status("sent"), None);
+    }
+}
@@ -1,11 +1,19 @@
 use serde::{Deserialize, Serialize};
 use surrealdb::types::Decimal;
+use surrealdb_types::{Datetime, RecordId};
 
+use crate::i18n::{Currency, Locale};
 use crate::models::{
-    Parent, Student, User,
-    academic::Class,
-    assessments_nd_grading::{ReportCard, ReportCardScore},
-    fee_management::{Invoice, InvoiceItem},
+    MessageChannel, Parent, PlanLimits, Student, SubscriptionPlan, SubscriptionStatus, User,
+    UserType,
+    academic::{AcademicSession, Class, Term},
+    assessments_nd_grading::{
+        Assessment, GradingScheme, GradingSchemeVersion, ReportCard, ReportCardScore,
+    },
+    attendance::Attendance,
+    communication::{Conversation, Event, EventRsvp, Message, MessageAttachment},
+    fee_management::{Invoice, InvoiceItem, PlatformInvoice, PlatformPayment},
+    system_and_audit::CalendarFeed,
 };
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -46,4 +54,335 @@ pub struct DashboardMetrics {
     pub total_collected: Decimal,
     pub total_expected: Decimal,
     pub collection_rate: Decimal,
+    /// Share of attendance marks in the term that were present or late
+    pub attendance_rate: Decimal,
+}
+
+/// Cumulative academic record for a student across every published report card
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Transcript {
+    pub school_name: String,
+    pub school_address: Option<String>,
+    pub student: Student,
+    pub sessions: Vec<TranscriptSession>,
+    pub cumulative_average: Option<Decimal>,
+    pub generated_at: Datetime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranscriptSession {
+    pub session: AcademicSession,
+    pub class_name: Option<String>,
+    pub terms: Vec<TranscriptTerm>,
+    pub subjects: Vec<TranscriptSubject>,
+    pub session_average: Option<Decimal>,
+    pub session_position: Option<i32>,
+    pub total_students_in_class: Option<i32>,
+    pub grade: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranscriptTerm {
+    pub term: Term,
+    pub report_card_id: Option<RecordId>,
+    pub average_percentage: Option<Decimal>,
+    pub class_position: Option<i32>,
+    pub total_students_in_class: Option<i32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranscriptSubject {
+    pub subject_id: RecordId,
+    pub subject_name: String,
+    pub term_scores: Vec<TranscriptTermScore>,
+    pub session_average: Option<Decimal>,
+    pub grade: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranscriptTermScore {
+    pub term_number: i32,
+    pub total_score: Option<Decimal>,
+    pub grade: Option<String>,
+    pub subject_position: Option<i32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportRowError {
+    /// Spreadsheet row number, counting the header as row 1
+    pub row: usize,
+    pub column: Option<String>,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StudentImportReport {
+    pub dry_run: bool,
+    pub total_rows: usize,
+    pub valid_rows: usize,
+    pub students_created: usize,
+    pub parents_created: usize,
+    pub errors: Vec<ImportRowError>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GradingSchemeWithBands {
+    #[serde(flatten)]
+    pub scheme: GradingSchemeVersion,
+    pub bands: Vec<GradingScheme>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClassRankingEntry {
+    pub position: u32,
+    pub student: Student,
+    pub total_score: Decimal,
+}
+
+/// Score entry grid for one class subject in a term: students down, assessments across
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ScoreSheet {
+    pub class_subject_id: String,
+    pub term_id: String,
+    pub columns: Vec<ScoreSheetColumn>,
+    pub rows: Vec<ScoreSheetRow>,
+    /// Report cards for the class and term are out, so nothing can be edited
+    pub published: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ScoreSheetColumn {
+    pub assessment_id: String,
+    pub label: String,
+    pub max_score: Decimal,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ScoreSheetRow {
+    pub student_id: String,
+    pub admission_number: String,
+    pub name: String,
+    /// One per column, in column order
+    pub cells: Vec<ScoreCell>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ScoreCell {
+    pub score: Option<Decimal>,
+    pub verified: bool,
+    /// `updated_at` of the stored score, sent back on save to detect conflicting edits
+    pub version: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AssessmentMissingScores {
+    pub assessment: Assessment,
+    pub students: Vec<Student>,
+}
+
+/// The signed-in user as the web app sees it
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SessionUser {
+    pub user_id: String,
+    pub school_id: String,
+    pub first_name: String,
+    pub last_name: String,
+    pub user_type: UserType,
+    pub locale: Locale,
+    pub currency: Currency,
+}
+
+/// Counts of attendance marks by status
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AttendanceTally {
+    pub present: u32,
+    pub absent: u32,
+    pub late: u32,
+    pub excused: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PublishedReportCard {
+    pub term_name: String,
+    pub card: ReportCard,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutstandingInvoice {
+    pub invoice: Invoice,
+    /// Online payment page for the balance, when the school has one set up
+    pub pay_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Contact {
+    pub user_id: String,
+    pub name: String,
+}
+
+/// Everything a parent sees about one of their children
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChildOverview {
+    pub student: Student,
+    pub class_name: Option<String>,
+    pub class_teacher: Option<Contact>,
+    /// Tally over `recent_attendance`
+    pub attendance: AttendanceTally,
+    pub recent_attendance: Vec<Attendance>,
+    pub report_cards: Vec<PublishedReportCard>,
+    pub outstanding_invoices: Vec<OutstandingInvoice>,
+}
+
+/// What happened to one change replayed from an offline device
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SyncOutcome {
+    Applied,
+    /// The server copy was changed after the device recorded its change, so it was kept
+    Superseded,
+    Rejected(String),
+}
+
+/// Character set an SMS is sent in: GSM-7 unless the text has a character
+/// outside it, in which case the whole message goes as UCS-2
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SmsEncoding {
+    Gsm7,
+    Ucs2,
+}
+
+/// How many billable parts an SMS body is split into
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SmsSegments {
+    pub encoding: SmsEncoding,
+    /// Length in encoding units; GSM-7 extension characters such as `€` count twice
+    pub characters: usize,
+    pub segments: usize,
+    /// Characters left before another segment is needed
+    pub remaining: usize,
+}
+
+/// A template rendered with sample or real data
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplatePreview {
+    pub subject: Option<String>,
+    pub body: String,
+    /// Variables the template uses that had no value, rendered as blanks
+    pub missing_variables: Vec<String>,
+    /// Set for SMS templates
+    pub sms: Option<SmsSegments>,
+}
+
+/// Delivery counts and spend for one channel of an announcement
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChannelDeliverySummary {
+    pub channel: MessageChannel,
+    pub queued: usize,
+    pub sent: usize,
+    pub delivered: usize,
+    pub failed: usize,
+    pub bounced: usize,
+    pub cost: Decimal,
+}
+
+/// A recipient an announcement could not reach on one channel
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeliveryFailure {
+    pub recipient_user_id: String,
+    pub channel: MessageChannel,
+    pub error: String,
+}
+
+/// Where an announcement's messages have got to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnnouncementDeliveryReport {
+    pub announcement_id: String,
+    pub recipients: usize,
+    pub channels: Vec<ChannelDeliverySummary>,
+    pub total_cost: Decimal,
+    pub failures: Vec<DeliveryFailure>,
+}
+
+/// A conversation in a user's inbox
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationSummary {
+    pub conversation: Conversation,
+    pub participants: Vec<Contact>,
+    pub last_message: Option<Message>,
+    pub unread_count: u64,
+}
+
+/// A conversation's messages, oldest first, with their attachments
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationThread {
+    pub conversation: Conversation,
+    pub participants: Vec<Contact>,
+    pub messages: Vec<Message>,
+    pub attachments: Vec<MessageAttachment>,
+}
+
+/// Replies to an event and how full it is
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RsvpSummary {
+    pub event_id: String,
+    pub attending: usize,
+    pub not_attending: usize,
+    pub maybe: usize,
+    /// People expected, guests included
+    pub places_taken: i32,
+    pub capacity: Option<i32>,
+    pub places_left: Option<i32>,
+    /// Attending replies scanned in at the door
+    pub checked_in: usize,
+}
+
+/// An attending reply's ticket, with its code as an SVG QR image
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventTicket {
+    pub event: Event,
+    pub rsvp: EventRsvp,
+    pub qr_svg: String,
+}
+
+/// Who a scanned ticket let in
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventCheckIn {
+    pub event: Event,
+    pub rsvp: EventRsvp,
+    pub attendee: Contact,
+    /// People the ticket admits, guests included
+    pub places: i32,
+}
+
+/// A calendar subscription; `path` is the feed's address on the API, which
+/// calendar apps fetch without signing in
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CalendarFeedLink {
+    pub feed: CalendarFeed,
+    pub path: String,
+}
+
+/// A school's plan, what it allows and how much of it is used
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubscriptionOverview {
+    pub plan: SubscriptionPlan,
+    pub status: SubscriptionStatus,
+    pub expiry_date: Option<Datetime>,
+    /// Set once the subscription has run out: full use continues until then
+    pub grace_ends_at: Option<Datetime>,
+    /// Expired schools can look at their records but not change them
+    pub read_only: bool,
+    pub limits: PlanLimits,
+    pub students: u64,
+    pub sms_this_month: u64,
+}
+
+/// A payment against a platform invoice and where it left the school's
+/// subscription
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlatformPaymentReceipt {
+    pub payment: PlatformPayment,
+    pub invoice: PlatformInvoice,
+    pub subscription_status: SubscriptionStatus,
+    pub subscription_expiry_date: Option<Datetime>,
 }
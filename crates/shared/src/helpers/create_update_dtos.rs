@@ -1,7 +1,14 @@
 use serde::{Deserialize, Serialize};
-use surrealdb_types::{Decimal, RecordId, SurrealValue, Value};
+use surrealdb_types::{Datetime, Decimal, RecordId, SurrealValue, Value};
 
-use crate::models::{AttendanceStatus, Gender, PaymentMethod, SubscriptionPlan, UserType};
+use crate::{
+    helpers::query_filters::{AttendanceFilter, InvoiceFilter, ScoreFilter, StudentFilter},
+    i18n::Locale,
+    models::{
+        AttendanceStatus, ClassLevel, Gender, MessageChannel, PaymentMethod, RsvpResponse,
+        SubscriptionPlan, UserType, assessments_nd_grading::AssessmentWeight,
+    },
+};
 
 #[derive(Debug, Clone, SurrealValue, Serialize, Deserialize)]
 pub struct CreateSchoolDto {
@@ -54,6 +61,99 @@ pub struct EnterScoreDto {
     pub remarks: Option<String>,
 }
 
+/// An attendance mark made while offline; `recorded_at` is when the teacher
+/// made it on their device, compared against the stored `updated_at` on sync
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OfflineAttendanceDto {
+    pub student_id: String,
+    pub status: AttendanceStatus,
+    pub arrival_time: Option<String>,
+    pub reason: Option<String>,
+    pub recorded_at: Datetime,
+}
+
+/// A score typed while offline; `recorded_at` as for [`OfflineAttendanceDto`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OfflineScoreDto {
+    pub assessment_id: String,
+    pub student_id: String,
+    pub score: Decimal,
+    pub recorded_at: Datetime,
+}
+
+/// A school's wording for one kind of message; fields as on `MessageTemplate`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SaveMessageTemplateDto {
+    pub channel: MessageChannel,
+    pub template_key: String,
+    pub locale: Option<Locale>,
+    pub subject: Option<String>,
+    pub body: String,
+}
+
+/// An approved WhatsApp template; fields as on `WhatsappTemplate`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SaveWhatsappTemplateDto {
+    pub template_key: String,
+    pub locale: Option<Locale>,
+    pub name: String,
+    pub language: String,
+    pub parameters: Vec<String>,
+}
+
+/// A browser's `PushSubscription.toJSON()`, plus its user agent for telling
+/// devices apart
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PushSubscriptionDto {
+    pub endpoint: String,
+    pub keys: PushSubscriptionKeys,
+    pub user_agent: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PushSubscriptionKeys {
+    pub p256dh: String,
+    pub auth: String,
+}
+
+/// Render a template before saving it. Without `body` the template in use is
+/// previewed; without `student_id`/`invoice_id` sample values are filled in.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PreviewTemplateDto {
+    pub channel: MessageChannel,
+    pub template_key: String,
+    pub locale: Option<Locale>,
+    pub subject: Option<String>,
+    pub body: Option<String>,
+    pub student_id: Option<String>,
+    pub invoice_id: Option<String>,
+}
+
+#[derive(Debug, Clone, SurrealValue, Serialize, Deserialize)]
+pub struct SetAssessmentWeightingDto {
+    pub school_id: RecordId,
+    pub class_level: ClassLevel,
+    pub weights: Vec<AssessmentWeight>,
+}
+
+#[derive(Debug, Clone, SurrealValue, Serialize, Deserialize)]
+pub struct SaveGradingSchemeDto {
+    pub school_id: RecordId,
+    pub scheme_name: String,
+    pub class_levels: Vec<ClassLevel>,
+    pub bands: Vec<GradeBandDto>,
+    pub created_by: Option<RecordId>,
+}
+
+#[derive(Debug, Clone, SurrealValue, Serialize, Deserialize)]
+pub struct GradeBandDto {
+    pub min_score: Decimal,
+    pub max_score: Decimal,
+    pub grade: String,
+    pub grade_point: Decimal,
+    pub remark: Option<String>,
+}
+
 #[derive(Debug, Clone, SurrealValue, Serialize, Deserialize)]
 pub struct RecordPaymentDto {
     pub invoice_id: RecordId,
@@ -63,3 +163,130 @@ pub struct RecordPaymentDto {
     pub payment_reference: Option<String>,
     pub paid_by: Option<String>,
 }
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportFormat {
+    Csv,
+    Xlsx,
+}
+
+/// Spreadsheet column header that feeds each student/parent field; fields
+/// left out keep their default header
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StudentImportMapping {
+    pub admission_number: String,
+    pub first_name: String,
+    pub middle_name: Option<String>,
+    pub last_name: String,
+    pub date_of_birth: String,
+    pub gender: String,
+    pub address: Option<String>,
+    pub class_name: Option<String>,
+    pub parent_first_name: Option<String>,
+    pub parent_last_name: Option<String>,
+    pub parent_phone: Option<String>,
+    pub parent_email: Option<String>,
+    pub parent_relationship: Option<String>,
+}
+
+impl Default for StudentImportMapping {
+    fn default() -> Self {
+        Self {
+            admission_number: "admission_number".into(),
+            first_name: "first_name".into(),
+            middle_name: Some("middle_name".into()),
+            last_name: "last_name".into(),
+            date_of_birth: "date_of_birth".into(),
+            gender: "gender".into(),
+            address: Some("address".into()),
+            class_name: Some("class_name".into()),
+            parent_first_name: Some("parent_first_name".into()),
+            parent_last_name: Some("parent_last_name".into()),
+            parent_phone: Some("parent_phone".into()),
+            parent_email: Some("parent_email".into()),
+            parent_relationship: Some("parent_relationship".into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    Csv,
+    Xlsx,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportEntity {
+    Students,
+    ClassRegister,
+    ScoreSheet,
+    Invoices,
+    Payments,
+    ActivityLog,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportRequest {
+    pub school_id: String,
+    pub entity: ExportEntity,
+    pub format: ExportFormat,
+    /// Column keys in output order; every column when empty
+    #[serde(default)]
+    pub columns: Vec<String>,
+    #[serde(default)]
+    pub student_filter: StudentFilter,
+    #[serde(default)]
+    pub invoice_filter: InvoiceFilter,
+    #[serde(default)]
+    pub attendance_filter: AttendanceFilter,
+    #[serde(default)]
+    pub score_filter: ScoreFilter,
+}
+
+/// Open a conversation with another user in the school
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StartConversationDto {
+    pub recipient_id: String,
+    /// The child the conversation is about
+    pub student_id: Option<String>,
+    pub subject: Option<String>,
+    pub body: String,
+    #[serde(default)]
+    pub attachment_ids: Vec<String>,
+}
+
+/// A reply in a conversation, with any attachments uploaded for it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SendMessageDto {
+    pub body: String,
+    #[serde(default)]
+    pub attachment_ids: Vec<String>,
+}
+
+/// A reply to an event invitation; sending it again changes the reply
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RsvpDto {
+    /// The child the reply is for, when a parent replies per child
+    pub student_id: Option<String>,
+    pub response: RsvpResponse,
+    #[serde(default)]
+    pub number_of_guests: i32,
+}
+
+/// Bill a school for a term; the current term when `term_id` is None
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GeneratePlatformInvoiceDto {
+    pub term_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordPlatformPaymentDto {
+    pub amount: Decimal,
+    pub payment_method: PaymentMethod,
+    pub reference: Option<String>,
+}
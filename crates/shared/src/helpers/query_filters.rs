@@ -27,3 +27,20 @@ pub struct AttendanceFilter {
     pub date_to: Option<Value>,   //Option<NaiveDate>,
     pub status: Option<AttendanceStatus>,
 }
+
+#[derive(Debug, Clone, SurrealValue, Serialize, Deserialize, Default)]
+pub struct ScoreFilter {
+    pub class_id: Option<RecordId>,
+    pub subject_id: Option<RecordId>,
+    pub term_id: Option<RecordId>,
+    pub assessment_id: Option<RecordId>,
+}
+
+/// How tied totals share positions: standard gives 1, 1, 3 and dense gives 1, 1, 2
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RankingMethod {
+    #[default]
+    Standard,
+    Dense,
+}
@@ -1,8 +1,26 @@
 use chrono::{DateTime, NaiveDate, Utc};
-use surrealdb_types::Datetime;
+use surrealdb_types::{Datetime, RecordId, RecordIdKey, ToSql};
 
 pub fn to_surreal_datetime(date: NaiveDate) -> surrealdb_types::Value {
     let datetime: DateTime<Utc> = date.and_hms_opt(0, 0, 0).unwrap().and_utc();
 
     surrealdb_types::Value::Datetime(Datetime::from(datetime))
 }
+
+/// Read a date stored as a `Value` (datetime or "YYYY-MM-DD" string) back into a `NaiveDate`
+pub fn to_naive_date(value: &surrealdb_types::Value) -> Option<NaiveDate> {
+    match value {
+        surrealdb_types::Value::Datetime(dt) => Some(dt.date_naive()),
+        surrealdb_types::Value::String(s) => NaiveDate::parse_from_str(s, "%Y-%m-%d").ok(),
+        _ => None,
+    }
+}
+
+/// Key part of a record id, in the form the query helpers pass to `type::thing`
+pub fn record_key(id: &RecordId) -> String {
+    match &id.key {
+        RecordIdKey::String(s) => s.clone(),
+        RecordIdKey::Number(n) => n.to_string(),
+        other => other.to_sql(),
+    }
+}
@@ -0,0 +1,281 @@
+use std::{collections::HashMap, fmt, path::Path, str::FromStr, sync::OnceLock};
+
+use chrono::{Datelike, NaiveDate};
+use serde::{Deserialize, Serialize};
+use surrealdb_types::{Decimal, SurrealValue};
+
+/// Languages the UI, messages and documents can be shown in
+#[derive(
+    Debug, Clone, Copy, Default, SurrealValue, Serialize, Deserialize, PartialEq, Eq, Hash,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Locale {
+    #[default]
+    En,
+    Fr,
+    Yo,
+    Ha,
+    Ig,
+}
+
+impl Locale {
+    pub const ALL: [Locale; 5] = [Locale::En, Locale::Fr, Locale::Yo, Locale::Ha, Locale::Ig];
+
+    /// ISO 639-1 code, also the catalog file name
+    pub fn code(self) -> &'static str {
+        match self {
+            Locale::En => "en",
+            Locale::Fr => "fr",
+            Locale::Yo => "yo",
+            Locale::Ha => "ha",
+            Locale::Ig => "ig",
+        }
+    }
+
+    /// The language's name in itself, for language pickers
+    pub fn native_name(self) -> &'static str {
+        match self {
+            Locale::En => "English",
+            Locale::Fr => "Français",
+            Locale::Yo => "Yorùbá",
+            Locale::Ha => "Hausa",
+            Locale::Ig => "Igbo",
+        }
+    }
+}
+
+impl FromStr for Locale {
+    type Err = String;
+
+    /// Accepts a bare code or a tag such as "fr-CI"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let code = s.trim().split(['-', '_']).next().unwrap_or_default();
+        Locale::ALL
+            .into_iter()
+            .find(|locale| locale.code().eq_ignore_ascii_case(code))
+            .ok_or_else(|| format!("unsupported language {s}"))
+    }
+}
+
+impl fmt::Display for Locale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+/// Currencies schools bill in
+#[derive(
+    Debug, Clone, Copy, Default, SurrealValue, Serialize, Deserialize, PartialEq, Eq, Hash,
+)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Currency {
+    #[default]
+    Ngn,
+    Ghs,
+    Kes,
+}
+
+impl Currency {
+    pub const ALL: [Currency; 3] = [Currency::Ngn, Currency::Ghs, Currency::Kes];
+
+    /// ISO 4217 code
+    pub fn code(self) -> &'static str {
+        match self {
+            Currency::Ngn => "NGN",
+            Currency::Ghs => "GHS",
+            Currency::Kes => "KES",
+        }
+    }
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Currency::Ngn => "₦",
+            Currency::Ghs => "GH₵",
+            Currency::Kes => "KSh",
+        }
+    }
+}
+
+impl FromStr for Currency {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Currency::ALL
+            .into_iter()
+            .find(|currency| currency.code().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| format!("unsupported currency {s}"))
+    }
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+/// Catalogs shipped in `crates/shared/locales`, one flat JSON object per locale
+const BUNDLED: [(Locale, &str); 5] = [
+    (Locale::En, include_str!("../locales/en.json")),
+    (Locale::Fr, include_str!("../locales/fr.json")),
+    (Locale::Yo, include_str!("../locales/yo.json")),
+    (Locale::Ha, include_str!("../locales/ha.json")),
+    (Locale::Ig, include_str!("../locales/ig.json")),
+];
+
+static BUNDLED_TRANSLATIONS: OnceLock<Translations> = OnceLock::new();
+static INSTALLED_TRANSLATIONS: OnceLock<Translations> = OnceLock::new();
+
+/// Translation catalogs keyed by locale. Lookups fall back to English, then
+/// to the key itself, so a partly translated catalog still renders.
+#[derive(Debug, Clone, Default)]
+pub struct Translations {
+    catalogs: HashMap<Locale, HashMap<String, String>>,
+}
+
+impl Translations {
+    /// The catalogs compiled into the binary
+    pub fn bundled() -> &'static Translations {
+        BUNDLED_TRANSLATIONS.get_or_init(|| {
+            let mut translations = Translations::default();
+            for (locale, json) in BUNDLED {
+                translations
+                    .merge_json(locale, json)
+                    .expect("bundled catalogs are valid JSON");
+            }
+            translations
+        })
+    }
+
+    /// The bundled catalogs with any `<code>.json` found in `dir` layered over
+    /// them, so a deployment can correct or extend wording without a rebuild
+    pub fn load_dir(dir: &Path) -> Result<Translations, String> {
+        let mut translations = Translations::bundled().clone();
+        for locale in Locale::ALL {
+            let path = dir.join(format!("{}.json", locale.code()));
+            if !path.exists() {
+                continue;
+            }
+            let json =
+                std::fs::read_to_string(&path).map_err(|e| format!("{}: {e}", path.display()))?;
+            translations
+                .merge_json(locale, &json)
+                .map_err(|e| format!("{}: {e}", path.display()))?;
+        }
+        Ok(translations)
+    }
+
+    /// Make these the catalogs returned by [`Translations::global`]; only the
+    /// first call has any effect
+    pub fn install(self) {
+        let _ = INSTALLED_TRANSLATIONS.set(self);
+    }
+
+    /// Installed catalogs if the server loaded some, otherwise the bundled ones
+    pub fn global() -> &'static Translations {
+        INSTALLED_TRANSLATIONS
+            .get()
+            .unwrap_or_else(|| Translations::bundled())
+    }
+
+    fn merge_json(&mut self, locale: Locale, json: &str) -> Result<(), String> {
+        let messages: HashMap<String, String> =
+            serde_json::from_str(json).map_err(|e| e.to_string())?;
+        self.catalogs.entry(locale).or_default().extend(messages);
+        Ok(())
+    }
+
+    /// The message for `key`
+    pub fn text<'a>(&'a self, locale: Locale, key: &'a str) -> &'a str {
+        [locale, Locale::En]
+            .iter()
+            .find_map(|l| self.catalogs.get(l)?.get(key))
+            .map_or(key, String::as_str)
+    }
+}
+
+/// Formats text, dates and money for one locale and currency
+#[derive(Debug, Clone, Copy)]
+pub struct Localizer {
+    translations: &'static Translations,
+    pub locale: Locale,
+    pub currency: Currency,
+}
+
+impl Localizer {
+    pub fn new(translations: &'static Translations, locale: Locale, currency: Currency) -> Self {
+        Self {
+            translations,
+            locale,
+            currency,
+        }
+    }
+
+    /// The message for `key`
+    pub fn t(&self, key: &str) -> String {
+        self.translations.text(self.locale, key).to_string()
+    }
+
+    /// The message for `key` with each `{name}` replaced by its value
+    pub fn format(&self, key: &str, args: &[(&str, &str)]) -> String {
+        fill(self.translations.text(self.locale, key), args)
+    }
+
+    /// e.g. "2 March 2026", "2 mars 2026"
+    pub fn date(&self, date: NaiveDate) -> String {
+        let day = date.day().to_string();
+        let month = self.t(&format!("month.{}", date.month()));
+        let year = date.year().to_string();
+        self.format(
+            "date.pattern",
+            &[("day", &day), ("month", &month), ("year", &year)],
+        )
+    }
+
+    /// A number with the locale's grouping and decimal separators
+    pub fn number(&self, value: Decimal, places: u32) -> String {
+        let text = format!("{:.*}", places as usize, value.round_dp(places));
+        let (sign, digits) = match text.strip_prefix('-') {
+            Some(rest) => ("-", rest),
+            None => ("", text.as_str()),
+        };
+        let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+
+        let group = self.t("number.group");
+        let mut grouped = String::new();
+        for (i, digit) in whole.chars().enumerate() {
+            if i > 0 && (whole.len() - i) % 3 == 0 {
+                grouped.push_str(&group);
+            }
+            grouped.push(digit);
+        }
+        if fraction.is_empty() {
+            format!("{sign}{grouped}")
+        } else {
+            format!("{sign}{grouped}{}{fraction}", self.t("number.decimal"))
+        }
+    }
+
+    /// An amount in the school's currency, e.g. "₦50,000.00", "50 000,00 GH₵"
+    pub fn money(&self, amount: Decimal) -> String {
+        let formatted = self.format(
+            "currency.pattern",
+            &[
+                ("symbol", self.currency.symbol()),
+                ("amount", &self.number(amount.abs(), 2)),
+            ],
+        );
+        if amount.round_dp(2) < Decimal::ZERO {
+            format!("-{formatted}")
+        } else {
+            formatted
+        }
+    }
+}
+
+/// Replace each `{name}` in `template` with its value; unknown names are left as they are
+pub fn fill(template: &str, args: &[(&str, &str)]) -> String {
+    args.iter()
+        .fold(template.to_string(), |text, (name, value)| {
+            text.replace(&format!("{{{name}}}"), value)
+        })
+}
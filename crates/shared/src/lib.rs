@@ -10,6 +10,7 @@
 
 use serde::{Deserialize, Serialize};
 pub mod helpers;
+pub mod i18n;
 pub mod models;
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
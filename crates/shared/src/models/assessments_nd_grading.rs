@@ -3,7 +3,7 @@ use serde::{Deserialize, Serialize};
 // use surrealdb::types::{Decimal, RecordId};
 use surrealdb_types::{Datetime, Decimal, RecordId, SurrealValue, Value};
 
-use crate::models::AssessmentType;
+use crate::models::{AssessmentType, ClassLevel};
 
 #[derive(Debug, Clone, SurrealValue, Serialize, Deserialize)]
 pub struct Assessment {
@@ -46,6 +46,22 @@ pub struct GradingScheme {
     pub grade: String,
     pub grade_point: Decimal,
     pub remark: Option<String>,
+    /// Version of the named scheme this band belongs to; None for legacy school-wide bands
+    pub scheme_version_id: Option<RecordId>,
+    pub created_at: Datetime,
+}
+
+/// One version of a named grading scheme (e.g. WAEC A1-F9) and the class levels
+/// it applies to. Its bands are `GradingScheme` rows pointing back here; saving
+/// the scheme again creates the next version and leaves this one untouched.
+#[derive(Debug, Clone, SurrealValue, Serialize, Deserialize)]
+pub struct GradingSchemeVersion {
+    pub id: Option<RecordId>,
+    pub school_id: RecordId,
+    pub scheme_name: String,
+    pub version: i32,
+    pub class_levels: Vec<ClassLevel>,
+    pub created_by: Option<RecordId>,
     pub created_at: Datetime,
 }
 
@@ -71,6 +87,8 @@ pub struct ReportCard {
     pub generated_at: Datetime,
     pub published: bool,
     pub published_at: Option<Datetime>,
+    /// Grading scheme version pinned when the card was published
+    pub grading_scheme_version_id: Option<RecordId>,
     pub pdf_url: Option<String>,
     pub created_at: Datetime,
 }
@@ -90,3 +108,21 @@ pub struct ReportCardScore {
     pub subject_average: Option<Decimal>,
     pub remarks: Option<String>,
 }
+
+/// How much each assessment type counts towards a subject total for one class level.
+/// Weights are percentages and must sum to 100.
+#[derive(Debug, Clone, SurrealValue, Serialize, Deserialize)]
+pub struct AssessmentWeighting {
+    pub id: Option<RecordId>,
+    pub school_id: RecordId,
+    pub class_level: ClassLevel,
+    pub weights: Vec<AssessmentWeight>,
+    pub created_at: Datetime,
+    pub updated_at: Datetime,
+}
+
+#[derive(Debug, Clone, SurrealValue, Serialize, Deserialize, PartialEq)]
+pub struct AssessmentWeight {
+    pub assessment_type: AssessmentType,
+    pub weight: Decimal,
+}
@@ -3,7 +3,10 @@ use serde::{Deserialize, Serialize};
 // use surrealdb::types::RecordId;
 use surrealdb_types::{Datetime, Decimal, RecordId, SurrealValue, Value};
 
-use crate::models::{Priority, RsvpResponse, TargetAudience};
+use crate::{
+    i18n::Locale,
+    models::{MessageChannel, Priority, RsvpResponse, TargetAudience},
+};
 
 #[derive(Debug, Clone, SurrealValue, Serialize, Deserialize)]
 pub struct Announcement {
@@ -36,10 +39,48 @@ pub struct Message {
     pub read_at: Option<Datetime>,
     pub replied: bool,
     pub parent_message_id: Option<RecordId>,
+    pub conversation_id: Option<RecordId>,
+    /// Set when an admin withdraws the message for safeguarding
+    pub hidden_by: Option<RecordId>,
+    pub hidden_at: Option<Datetime>,
     pub sent_at: Datetime,
     pub created_at: Datetime,
 }
 
+/// A two-way conversation between a parent and a member of staff, or two
+/// members of staff
+#[derive(Debug, Clone, SurrealValue, Serialize, Deserialize)]
+pub struct Conversation {
+    pub id: Option<RecordId>,
+    pub school_id: RecordId,
+    pub participants: Vec<RecordId>,
+    /// The child the conversation is about, if any
+    pub student_id: Option<RecordId>,
+    pub subject: Option<String>,
+    /// Closed by an admin; no new messages are accepted
+    pub closed: bool,
+    pub last_message_at: Datetime,
+    pub created_at: Datetime,
+    pub updated_at: Datetime,
+}
+
+/// A file shared in a conversation. Uploaded first, then linked to the
+/// message it is sent with.
+#[derive(Debug, Clone, SurrealValue, Serialize, Deserialize)]
+pub struct MessageAttachment {
+    pub id: Option<RecordId>,
+    pub school_id: RecordId,
+    pub conversation_id: RecordId,
+    pub message_id: Option<RecordId>,
+    pub uploaded_by: RecordId,
+    pub file_name: String,
+    pub content_type: String,
+    pub size_bytes: i64,
+    /// Where the bytes are kept in the file store
+    pub storage_key: String,
+    pub created_at: Datetime,
+}
+
 #[derive(Debug, Clone, SurrealValue, Serialize, Deserialize)]
 pub struct Event {
     pub id: Option<RecordId>,
@@ -53,11 +94,52 @@ pub struct Event {
     pub class_id: Option<RecordId>,
     pub requires_rsvp: bool,
     pub rsvp_deadline: Option<Value>, //Option<NaiveDate>,
+    /// Most people who can attend, guests included; None is unlimited
+    pub capacity: Option<i32>,
+    /// When non-responders were last reminded to RSVP
+    pub rsvp_reminder_sent_at: Option<Datetime>,
     pub created_by: Option<RecordId>,
     pub created_at: Datetime,
     pub updated_at: Datetime,
 }
 
+/// A school's own wording for one kind of message. `template_key` is the
+/// snake_case name of the `SmsType` (SMS) or `NotificationType` (email and
+/// in-app) it replaces; `locale` None applies to every language.
+#[derive(Debug, Clone, SurrealValue, Serialize, Deserialize)]
+pub struct MessageTemplate {
+    pub id: Option<RecordId>,
+    pub school_id: RecordId,
+    pub channel: MessageChannel,
+    pub template_key: String,
+    pub locale: Option<Locale>,
+    /// Email subject or notification title; unused for SMS
+    pub subject: Option<String>,
+    pub body: String,
+    pub created_at: Datetime,
+    pub updated_at: Datetime,
+}
+
+/// A WhatsApp template the school had approved by Meta, and which of our
+/// variables fill its `{{1}}`, `{{2}}`, ... placeholders in order.
+/// `template_key` is the snake_case name of the `SmsType` it is sent for;
+/// `locale` None applies to every language.
+#[derive(Debug, Clone, SurrealValue, Serialize, Deserialize)]
+pub struct WhatsappTemplate {
+    pub id: Option<RecordId>,
+    pub school_id: RecordId,
+    pub template_key: String,
+    pub locale: Option<Locale>,
+    /// Template name as approved in WhatsApp Manager
+    pub name: String,
+    /// WhatsApp language code the template was approved in, e.g. "en_US"
+    pub language: String,
+    /// Variables such as "student.first_name", one per placeholder
+    pub parameters: Vec<String>,
+    pub created_at: Datetime,
+    pub updated_at: Datetime,
+}
+
 #[derive(Debug, Clone, SurrealValue, Serialize, Deserialize)]
 pub struct EventRsvp {
     pub id: Option<RecordId>,
@@ -65,7 +147,12 @@ pub struct EventRsvp {
     pub user_id: RecordId,
     pub student_id: Option<RecordId>,
     pub response: RsvpResponse,
+    /// People coming besides the one who responded
     pub number_of_guests: i32,
+    /// Shown as a QR code and scanned at the door
+    pub ticket_code: Option<String>,
+    pub checked_in_at: Option<Datetime>,
+    pub checked_in_by: Option<RecordId>,
     pub responded_at: Datetime,
     pub created_at: Datetime,
 }
@@ -5,7 +5,7 @@ use surrealdb_types::{Datetime, Decimal, RecordId, SurrealValue, Value};
 
 use crate::models::{
     DeliveryStatus, FeeFrequency, FeeType, InvoiceStatus, PaymentMethod, PaymentStatus,
-    ReminderType,
+    ReminderType, SubscriptionPlan,
 };
 
 #[derive(Debug, Clone, SurrealValue, Serialize, Deserialize)]
@@ -86,3 +86,46 @@ pub struct PaymentReminder {
     pub delivered_at: Option<Datetime>,
     pub created_at: Datetime,
 }
+
+/// What the platform charges a school for a term: its plan's rate for each
+/// student on roll, plus the SMS it has sent since its last platform
+/// invoice at what the gateway charged
+#[derive(Debug, Clone, SurrealValue, Serialize, Deserialize)]
+pub struct PlatformInvoice {
+    pub id: Option<RecordId>,
+    pub school_id: RecordId,
+    pub term_id: RecordId,
+    pub invoice_number: String,
+    pub plan: SubscriptionPlan,
+    pub student_count: i32,
+    pub rate_per_student: Decimal,
+    pub subscription_amount: Decimal,
+    pub sms_count: i64,
+    pub sms_amount: Decimal,
+    /// SMS sent from here up to `created_at` are on this invoice
+    pub sms_from: Datetime,
+    pub total_amount: Decimal,
+    pub amount_paid: Decimal,
+    pub balance: Decimal,
+    pub status: InvoiceStatus,
+    /// Paying the invoice in full keeps the school active until here
+    pub period_end: Datetime,
+    pub due_date: Datetime,
+    pub paid_at: Option<Datetime>,
+    pub created_at: Datetime,
+    pub updated_at: Datetime,
+}
+
+/// Money a school paid towards a platform invoice
+#[derive(Debug, Clone, SurrealValue, Serialize, Deserialize)]
+pub struct PlatformPayment {
+    pub id: Option<RecordId>,
+    pub school_id: RecordId,
+    pub invoice_id: RecordId,
+    pub amount: Decimal,
+    pub payment_method: PaymentMethod,
+    pub reference: Option<String>,
+    pub recorded_by: Option<RecordId>,
+    pub paid_at: Datetime,
+    pub created_at: Datetime,
+}
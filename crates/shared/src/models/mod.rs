@@ -3,9 +3,12 @@ use serde::{Deserialize, Serialize};
 // use surrealdb::types::{RecordId, SurrealValue};
 use surrealdb_types::{Datetime, RecordId, SurrealValue, Value};
 
-use crate::models::{
-    academic::{Class, Subject},
-    fee_management::Invoice,
+use crate::{
+    i18n::Locale,
+    models::{
+        academic::{Class, Subject},
+        fee_management::Invoice,
+    },
 };
 
 pub mod academic;
@@ -45,6 +48,34 @@ pub enum SubscriptionStatus {
     Cancelled,
 }
 
+/// What a subscription plan allows; None is unlimited
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PlanLimits {
+    pub max_students: Option<u32>,
+    /// SMS sent per calendar month
+    pub monthly_sms: Option<u32>,
+    pub online_payments: bool,
+    pub pdf_report_cards: bool,
+}
+
+impl SubscriptionPlan {
+    pub const fn limits(&self) -> PlanLimits {
+        let (max_students, monthly_sms, premium) = match self {
+            SubscriptionPlan::Starter => (Some(100), Some(200), false),
+            SubscriptionPlan::Basic => (Some(300), Some(1_000), false),
+            SubscriptionPlan::Standard => (Some(800), Some(3_000), false),
+            SubscriptionPlan::Premium => (Some(2_000), Some(10_000), true),
+            SubscriptionPlan::Enterprise => (None, None, true),
+        };
+        PlanLimits {
+            max_students,
+            monthly_sms,
+            online_payments: premium,
+            pdf_report_cards: premium,
+        }
+    }
+}
+
 #[derive(Debug, Clone, SurrealValue, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum UserType {
@@ -188,6 +219,8 @@ pub enum ReminderType {
 #[derive(Debug, Clone, SurrealValue, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum DeliveryStatus {
+    /// Waiting to be handed to the provider
+    Queued,
     Sent,
     Delivered,
     Failed,
@@ -258,6 +291,36 @@ pub enum SmsType {
     Other,
 }
 
+/// Which screen of the USSD menu a session is on
+#[derive(Debug, Clone, Copy, SurrealValue, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum UssdStep {
+    /// Waiting for a ward's admission number
+    AdmissionNumber,
+    Menu,
+    /// Showing a balance or result; any key goes back to the menu
+    Info,
+    /// Choosing between the full balance and another amount
+    PaymentAmount,
+    OtherAmount,
+    ConfirmPayment,
+}
+
+/// How a message reaches its recipient
+#[derive(Debug, Clone, SurrealValue, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageChannel {
+    Sms,
+    Email,
+    /// In-app notification
+    Notification,
+    /// Browser push, worded like in-app notifications
+    Push,
+    /// WhatsApp Business: approved templates worded like SMS, or free text
+    /// while a parent's conversation window is open
+    Whatsapp,
+}
+
 #[derive(Debug, Clone, SurrealValue, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum SettingType {
@@ -298,6 +361,8 @@ pub struct User {
     pub last_login: Option<Datetime>,
     pub email_verified: bool,
     pub phone_verified: bool,
+    /// Language the user chose; None follows the school's default
+    pub locale: Option<Locale>,
     pub created_at: Datetime,
     pub updated_at: Datetime,
 }
@@ -3,7 +3,9 @@ use serde::{Deserialize, Serialize};
 // use surrealdb::types::{Decimal, RecordId};
 use surrealdb_types::{Datetime, Decimal, RecordId, SurrealValue, Value};
 
-use crate::models::{ActionType, DeliveryStatus, NotificationType, SmsType};
+use crate::models::{
+    ActionType, DeliveryStatus, MessageChannel, NotificationType, SmsType, UssdStep,
+};
 
 #[derive(Debug, Clone, SurrealValue, Serialize, Deserialize)]
 pub struct ActivityLog {
@@ -49,3 +51,117 @@ pub struct SmsLog {
     pub delivered_at: Option<Datetime>,
     pub created_at: Datetime,
 }
+
+/// One message to one recipient over one channel, queued when an announcement
+/// is published and handed to the channel's provider by a dispatcher
+#[derive(Debug, Clone, SurrealValue, Serialize, Deserialize)]
+pub struct MessageDelivery {
+    pub id: Option<RecordId>,
+    pub school_id: RecordId,
+    pub announcement_id: Option<RecordId>,
+    pub recipient_user_id: RecordId,
+    pub channel: MessageChannel,
+    /// Phone number or email address; None for in-app and push
+    pub address: Option<String>,
+    pub subject: Option<String>,
+    pub body: String,
+    pub status: DeliveryStatus,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    pub provider: Option<String>,
+    pub provider_message_id: Option<String>,
+    pub cost: Option<Decimal>,
+    pub sent_at: Option<Datetime>,
+    pub delivered_at: Option<Datetime>,
+    /// Approved template to send instead of `body`, for WhatsApp
+    pub template: Option<TemplateMessage>,
+    pub created_at: Datetime,
+    pub updated_at: Datetime,
+}
+
+/// A pre-approved template with its placeholders filled in
+#[derive(Debug, Clone, SurrealValue, Serialize, Deserialize, PartialEq)]
+pub struct TemplateMessage {
+    pub name: String,
+    pub language: String,
+    pub parameters: Vec<String>,
+}
+
+/// A signed-in browser session; `token` is the opaque value kept in the session cookie
+#[derive(Debug, Clone, SurrealValue, Serialize, Deserialize)]
+pub struct UserSession {
+    pub id: Option<RecordId>,
+    pub user_id: RecordId,
+    pub token: String,
+    pub expires_at: Datetime,
+    pub created_at: Datetime,
+}
+
+/// A browser or installed app that receives Web Push for a user. `p256dh` and
+/// `auth` are the browser's base64url keys for encrypting payloads.
+#[derive(Debug, Clone, SurrealValue, Serialize, Deserialize)]
+pub struct PushSubscription {
+    pub id: Option<RecordId>,
+    pub school_id: RecordId,
+    pub user_id: RecordId,
+    pub endpoint: String,
+    pub p256dh: String,
+    pub auth: String,
+    pub user_agent: Option<String>,
+    pub last_used_at: Option<Datetime>,
+    pub created_at: Datetime,
+}
+
+/// A USSD dial-in, stored under the gateway's session id so each request
+/// carries on from the screen the last one left
+#[derive(Debug, Clone, SurrealValue, Serialize, Deserialize)]
+pub struct UssdSession {
+    pub id: Option<RecordId>,
+    pub phone_number: String,
+    /// The parent account the caller's number belongs to
+    pub user_id: RecordId,
+    pub student_id: Option<RecordId>,
+    /// Invoice being paid
+    pub invoice_id: Option<RecordId>,
+    pub step: UssdStep,
+    pub amount: Option<Decimal>,
+    /// When an idle session is abandoned
+    pub expires_at: Datetime,
+    pub created_at: Datetime,
+    pub updated_at: Datetime,
+}
+
+/// A code emailed to a user to prove they own the address; only its hash is kept
+#[derive(Debug, Clone, SurrealValue, Serialize, Deserialize)]
+pub struct EmailVerification {
+    pub id: Option<RecordId>,
+    pub user_id: RecordId,
+    pub email: String,
+    pub code_hash: String,
+    pub attempts: i32,
+    pub expires_at: Datetime,
+    pub created_at: Datetime,
+}
+
+/// A client-chosen key for a write that may be replayed; `response` holds the
+/// JSON result once the first attempt has finished
+#[derive(Debug, Clone, SurrealValue, Serialize, Deserialize)]
+pub struct IdempotencyKey {
+    pub id: Option<RecordId>,
+    pub user_id: RecordId,
+    pub response: Option<String>,
+    pub created_at: Datetime,
+}
+
+/// A secret calendar subscription URL. With no `user_id` it is the school's
+/// whole calendar; otherwise only the dates that concern that user.
+#[derive(Debug, Clone, SurrealValue, Serialize, Deserialize)]
+pub struct CalendarFeed {
+    pub id: Option<RecordId>,
+    pub school_id: RecordId,
+    pub user_id: Option<RecordId>,
+    pub token: String,
+    /// When a calendar app last fetched the feed
+    pub last_fetched_at: Option<Datetime>,
+    pub created_at: Datetime,
+}
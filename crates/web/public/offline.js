@@ -0,0 +1,88 @@
+// Copyright (C) 2026 Ahmad Olukotun
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+
+// IndexedDB storage for the offline mode, bound from src/offline.rs.
+// "outbox" holds writes waiting to be synced, in the order they were made;
+// "cache" holds the last rosters and score sheets loaded, keyed by name.
+
+const DB_NAME = "sms-offline";
+const DB_VERSION = 1;
+
+let opening = null;
+
+function open() {
+  if (!opening) {
+    opening = new Promise((resolve, reject) => {
+      const request = indexedDB.open(DB_NAME, DB_VERSION);
+      request.onupgradeneeded = () => {
+        const db = request.result;
+        db.createObjectStore("outbox", { keyPath: "seq", autoIncrement: true });
+        db.createObjectStore("cache");
+      };
+      request.onsuccess = () => resolve(request.result);
+      request.onerror = () => {
+        opening = null;
+        reject(request.error);
+      };
+    });
+  }
+  return opening;
+}
+
+async function run(store, mode, action) {
+  const db = await open();
+  return new Promise((resolve, reject) => {
+    const tx = db.transaction(store, mode);
+    const request = action(tx.objectStore(store));
+    tx.oncomplete = () => resolve(request ? request.result : undefined);
+    tx.onerror = () => reject(tx.error);
+  });
+}
+
+// Queue a write; the idempotency key is fixed now so every replay reuses it
+export function outboxPush(payload) {
+  const entry = { key: crypto.randomUUID(), payload, queued_at: Date.now() };
+  return run("outbox", "readwrite", (store) => store.add(entry));
+}
+
+// Every queued write as a JSON array, oldest first
+export async function outboxEntries() {
+  const entries = await run("outbox", "readonly", (store) => store.getAll());
+  return JSON.stringify(entries);
+}
+
+export function outboxRemove(seq) {
+  return run("outbox", "readwrite", (store) => store.delete(seq));
+}
+
+export function outboxCount() {
+  return run("outbox", "readonly", (store) => store.count());
+}
+
+export function cachePut(name, json) {
+  return run("cache", "readwrite", (store) => store.put(json, name));
+}
+
+export async function cacheGet(name) {
+  const json = await run("cache", "readonly", (store) => store.get(name));
+  return json ?? null;
+}
+
+export function isOnline() {
+  return navigator.onLine;
+}
+
+export function registerServiceWorker() {
+  if ("serviceWorker" in navigator) {
+    navigator.serviceWorker.register("/sw.js").catch((err) => {
+      console.warn("service worker registration failed", err);
+    });
+  }
+}
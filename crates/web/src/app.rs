@@ -11,12 +11,19 @@
 use leptos::prelude::*;
 use leptos_meta::{provide_meta_context, MetaTags, Stylesheet, Title};
 use leptos_router::{
-    components::{Route, Router, Routes},
-    StaticSegment,
+    components::{Outlet, ParentRoute, Redirect, Route, Router, Routes},
+    ParamSegment, StaticSegment,
 };
 
-use reqwasm::http::Request;
-use shared::UserDto;
+use crate::{
+    auth::AuthContext,
+    components::nav::Nav,
+    offline::{SyncContext, SyncStatus},
+    pages::{
+        attendance::AttendancePage, dashboard::HomePage, login::LoginPage, parent::ChildPage,
+        scores::ScoresPage,
+    },
+};
 
 pub fn shell(options: LeptosOptions) -> impl IntoView {
     view! {
@@ -25,6 +32,8 @@ pub fn shell(options: LeptosOptions) -> impl IntoView {
             <head>
                 <meta charset="utf-8"/>
                 <meta name="viewport" content="width=device-width, initial-scale=1"/>
+                <meta name="theme-color" content="#1f3a5f"/>
+                <link rel="manifest" href="/manifest.webmanifest"/>
                 <AutoReload options=options.clone() />
                 <HydrationScripts options/>
                 <MetaTags/>
@@ -40,54 +49,55 @@ pub fn shell(options: LeptosOptions) -> impl IntoView {
 pub fn App() -> impl IntoView {
     // Provides context that manages stylesheets, titles, meta tags, etc.
     provide_meta_context();
+    AuthContext::provide();
+    SyncContext::provide();
 
     view! {
         // injects a stylesheet into the document <head>
         // id=leptos means cargo-leptos will hot-reload this stylesheet
         <Stylesheet id="leptos" href="/pkg/sms-front.css"/>
 
-        // sets the document title
-        <Title text="Welcome to Leptos"/>
+        <Title formatter=|title| format!("{title} | School Manager")/>
 
-        // content for this welcome page
         <Router>
-            <main>
-                <Routes fallback=|| "Page not found.".into_view()>
+            <Routes fallback=|| "Page not found.".into_view()>
+                <Route path=StaticSegment("login") view=LoginPage/>
+                <ParentRoute path=StaticSegment("") view=AppLayout>
                     <Route path=StaticSegment("") view=HomePage/>
-                </Routes>
-            </main>
+                    <Route path=StaticSegment("attendance") view=AttendancePage/>
+                    <Route path=StaticSegment("scores") view=ScoresPage/>
+                    <Route path=(StaticSegment("children"), ParamSegment("id")) view=ChildPage/>
+                </ParentRoute>
+            </Routes>
         </Router>
     }
 }
 
-/// Renders the home page of your application.
+/// Frame for every signed-in page; sends visitors without a session to the login page
 #[component]
-fn HomePage() -> impl IntoView {
-    // let user = create_resource(
-    //     || (),
-    //     |_| async {
-    //         Request::get("http://localhost:3000/user")
-    //             .send()
-    //             .await
-    //             .unwrap()
-    //             .json::<UserDto>()
-    //             .await
-    //             .unwrap()
-    //     },
-    // );
-    // Creates a reactive value to update the button
-    let count = RwSignal::new(0);
-    let on_click = move |_| *count.write() += 1;
+fn AppLayout() -> impl IntoView {
+    let auth = expect_context::<AuthContext>();
 
     view! {
-        <h1>"Welcome to Leptos! b"</h1>
-        <button on:click=on_click>"Click Me: " {count}</button>
-        // <Suspense fallback=move || view! { <p>"Loading..."</p> }>
-        //           {move || {
-        //               user.get().map(|u| view! {
-        //                   <p>{u.name}</p>
-        //               })
-        //           }}
-        // </Suspense>
+        <Transition fallback=|| view! { <p>"Loading…"</p> }>
+            {move || Suspend::new(async move {
+                match auth.user.await {
+                    Ok(Some(user)) => {
+                        provide_context(user.clone());
+                        view! {
+                            <div class="layout">
+                                <Nav user/>
+                                <main>
+                                    <SyncStatus/>
+                                    <Outlet/>
+                                </main>
+                            </div>
+                        }
+                        .into_any()
+                    }
+                    _ => view! { <Redirect path="/login"/> }.into_any(),
+                }
+            })}
+        </Transition>
     }
 }
@@ -0,0 +1,125 @@
+// Copyright (C) 2026 Ahmad Olukotun
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+
+use leptos::prelude::*;
+use shared::helpers::api_responses::SessionUser;
+
+/// Shared by every page: the signed-in user, refetched after login/logout
+#[derive(Clone, Copy)]
+pub struct AuthContext {
+    pub user: Resource<Result<Option<SessionUser>, ServerFnError>>,
+    pub login: ServerAction<Login>,
+    pub logout: ServerAction<Logout>,
+}
+
+impl AuthContext {
+    /// Create the context and make it available to every child component
+    pub fn provide() {
+        let login = ServerAction::<Login>::new();
+        let logout = ServerAction::<Logout>::new();
+        let user = Resource::new(
+            move || (login.version().get(), logout.version().get()),
+            |_| current_user(),
+        );
+        provide_context(Self {
+            user,
+            login,
+            logout,
+        });
+    }
+}
+
+#[server]
+pub async fn current_user() -> Result<Option<SessionUser>, ServerFnError> {
+    let Some(token) = ssr::session_token().await? else {
+        return Ok(None);
+    };
+    domain::auth::AuthService
+        .session_user(db::get_db(), token)
+        .await
+        .map_err(ServerFnError::new)
+}
+
+#[server]
+pub async fn login(identifier: String, password: String) -> Result<(), ServerFnError> {
+    let (session, _) = domain::auth::AuthService
+        .login(db::get_db(), identifier, password, None)
+        .await
+        .map_err(ServerFnError::new)?;
+    ssr::set_session_cookie(&session.token, ssr::SESSION_MAX_AGE);
+    leptos_axum::redirect("/");
+    Ok(())
+}
+
+#[server]
+pub async fn logout() -> Result<(), ServerFnError> {
+    if let Some(token) = ssr::session_token().await? {
+        domain::auth::AuthService
+            .logout(db::get_db(), token)
+            .await
+            .map_err(ServerFnError::new)?;
+    }
+    ssr::set_session_cookie("", 0);
+    leptos_axum::redirect("/login");
+    Ok(())
+}
+
+#[cfg(feature = "ssr")]
+pub mod ssr {
+    use axum::http::{header, HeaderMap, HeaderValue};
+    use leptos::prelude::*;
+    use leptos_axum::ResponseOptions;
+    use shared::{helpers::api_responses::SessionUser, models::UserType};
+
+    const SESSION_COOKIE: &str = "sms_session";
+    pub const SESSION_MAX_AGE: i64 = 7 * 24 * 60 * 60;
+
+    /// Session token from the request's cookie header
+    pub async fn session_token() -> Result<Option<String>, ServerFnError> {
+        let headers: HeaderMap = leptos_axum::extract().await?;
+        let prefix = format!("{SESSION_COOKIE}=");
+        Ok(headers
+            .get_all(header::COOKIE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(';'))
+            .filter_map(|cookie| cookie.trim().strip_prefix(prefix.as_str()))
+            .find(|token| !token.is_empty())
+            .map(str::to_string))
+    }
+
+    pub fn set_session_cookie(token: &str, max_age: i64) {
+        let cookie =
+            format!("{SESSION_COOKIE}={token}; Path=/; HttpOnly; SameSite=Lax; Max-Age={max_age}");
+        if let (Some(response), Ok(value)) = (
+            use_context::<ResponseOptions>(),
+            HeaderValue::from_str(&cookie),
+        ) {
+            response.append_header(header::SET_COOKIE, value);
+        }
+    }
+
+    /// The signed-in user, for server functions that need one
+    pub async fn require_user() -> Result<SessionUser, ServerFnError> {
+        super::current_user()
+            .await?
+            .ok_or_else(|| ServerFnError::new("not signed in"))
+    }
+
+    /// The signed-in user if they have one of the given roles
+    pub async fn require_role(roles: &[UserType]) -> Result<SessionUser, ServerFnError> {
+        let user = require_user().await?;
+        if roles.contains(&user.user_type) {
+            Ok(user)
+        } else {
+            Err(ServerFnError::new("not allowed"))
+        }
+    }
+}
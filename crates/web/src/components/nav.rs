@@ -0,0 +1,84 @@
+// Copyright (C) 2026 Ahmad Olukotun
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+
+use leptos::{prelude::*, task::spawn_local};
+use leptos_router::components::A;
+use shared::{helpers::api_responses::SessionUser, i18n::Locale, models::UserType};
+
+use crate::{
+    auth::AuthContext,
+    i18n::{set_language, use_i18n},
+};
+
+/// Links shown to each role, as (href, catalog key of the label)
+pub fn nav_links(user_type: &UserType) -> &'static [(&'static str, &'static str)] {
+    match user_type {
+        UserType::SuperAdmin | UserType::Admin => &[
+            ("/", "nav.dashboard"),
+            ("/attendance", "nav.attendance"),
+            ("/scores", "nav.scores"),
+        ],
+        UserType::Accountant => &[("/", "nav.dashboard")],
+        UserType::Teacher => &[
+            ("/", "nav.home"),
+            ("/attendance", "nav.attendance"),
+            ("/scores", "nav.scores"),
+        ],
+        UserType::Parent | UserType::Student => &[("/", "nav.home")],
+    }
+}
+
+#[component]
+pub fn Nav(user: SessionUser) -> impl IntoView {
+    let auth = expect_context::<AuthContext>();
+    let i18n = use_i18n();
+    let links = nav_links(&user.user_type)
+        .iter()
+        .map(|(href, label)| {
+            view! {
+                <li>
+                    <A href=*href>{i18n.t(label)}</A>
+                </li>
+            }
+        })
+        .collect_view();
+    let languages = Locale::ALL
+        .into_iter()
+        .map(|locale| {
+            view! {
+                <option value=locale.code() selected=locale == user.locale>
+                    {locale.native_name()}
+                </option>
+            }
+        })
+        .collect_view();
+    let choose_language = move |ev| {
+        let code = event_target_value(&ev);
+        spawn_local(async move {
+            if set_language(code).await.is_ok() {
+                auth.user.refetch();
+            }
+        });
+    };
+
+    view! {
+        <nav class="sidebar">
+            <p class="sidebar-user">{format!("{} {}", user.first_name, user.last_name)}</p>
+            <ul>{links}</ul>
+            <label class="language">
+                {i18n.t("nav.language")}
+                <select on:change=choose_language>{languages}</select>
+            </label>
+            <ActionForm action=auth.logout>
+                <button type="submit">{i18n.t("nav.sign_out")}</button>
+            </ActionForm>
+        </nav>
+    }
+}
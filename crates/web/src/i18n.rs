@@ -0,0 +1,57 @@
+// Copyright (C) 2026 Ahmad Olukotun
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+
+use leptos::prelude::*;
+use shared::{
+    helpers::api_responses::SessionUser,
+    i18n::{Localizer, Translations},
+};
+
+/// Formatting in the signed-in user's language and school currency. Pages use
+/// the catalogs compiled into the app so the server and browser render the same text.
+pub fn use_i18n() -> Localizer {
+    let (locale, currency) = use_context::<SessionUser>()
+        .map(|user| (user.locale, user.currency))
+        .unwrap_or_default();
+    Localizer::new(Translations::bundled(), locale, currency)
+}
+
+/// Choose the signed-in user's language; an empty code follows the school's default
+#[server]
+pub async fn set_language(code: String) -> Result<(), ServerFnError> {
+    use shared::i18n::Locale;
+
+    let user = crate::auth::ssr::require_user().await?;
+    let locale = match code.trim() {
+        "" => None,
+        code => Some(code.parse::<Locale>().map_err(ServerFnError::new)?),
+    };
+    domain::locale::LocaleService
+        .set_user_locale(db::get_db(), user.user_id, locale)
+        .await
+        .map_err(ServerFnError::new)
+}
+
+/// Set the school's default language and billing currency
+#[server]
+pub async fn set_school_region(locale: String, currency: String) -> Result<(), ServerFnError> {
+    use shared::{
+        i18n::{Currency, Locale},
+        models::UserType,
+    };
+
+    let user = crate::auth::ssr::require_role(&[UserType::SuperAdmin, UserType::Admin]).await?;
+    let locale = locale.parse::<Locale>().map_err(ServerFnError::new)?;
+    let currency = currency.parse::<Currency>().map_err(ServerFnError::new)?;
+    domain::locale::LocaleService
+        .set_school_defaults(db::get_db(), user.school_id, locale, currency)
+        .await
+        .map_err(ServerFnError::new)
+}
@@ -9,6 +9,11 @@
 // MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
 
 pub mod app;
+pub mod auth;
+pub mod components;
+pub mod i18n;
+pub mod offline;
+pub mod pages;
 
 #[cfg(feature = "hydrate")]
 #[wasm_bindgen::prelude::wasm_bindgen]
@@ -17,6 +17,17 @@ async fn main() {
     use leptos_axum::{generate_route_list, LeptosRoutes};
     use web::app::*;
 
+    db::init_db()
+        .await
+        .expect("failed to connect to the database");
+
+    // Deployments can override or extend the bundled wording without a rebuild
+    if let Ok(dir) = std::env::var("LOCALES_DIR") {
+        shared::i18n::Translations::load_dir(std::path::Path::new(&dir))
+            .expect("failed to load translation catalogs")
+            .install();
+    }
+
     let conf = get_configuration(None).unwrap();
     let addr = conf.leptos_options.site_addr;
     let leptos_options = conf.leptos_options;
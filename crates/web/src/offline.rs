@@ -0,0 +1,378 @@
+// Copyright (C) 2026 Ahmad Olukotun
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+
+use std::future::Future;
+
+use leptos::{prelude::*, server_fn::codec::Json, task::spawn_local};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use shared::helpers::{
+    api_responses::SyncOutcome,
+    create_update_dtos::{OfflineAttendanceDto, OfflineScoreDto},
+};
+
+/// A write made offline, waiting in the IndexedDB outbox until the browser is
+/// back online. Its idempotency key is fixed when queued, so a replay the
+/// server already applied is not applied twice.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum QueuedWrite {
+    Attendance {
+        class_id: String,
+        date: String,
+        marks: Vec<OfflineAttendanceDto>,
+    },
+    Scores {
+        class_subject_id: String,
+        edits: Vec<OfflineScoreDto>,
+    },
+}
+
+/// Tally of the last outbox sync
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SyncReport {
+    pub applied: usize,
+    pub superseded: usize,
+    pub rejected: Vec<String>,
+}
+
+impl SyncReport {
+    fn add(&mut self, outcomes: Vec<SyncOutcome>) {
+        for outcome in outcomes {
+            match outcome {
+                SyncOutcome::Applied => self.applied += 1,
+                SyncOutcome::Superseded => self.superseded += 1,
+                SyncOutcome::Rejected(reason) => self.rejected.push(reason),
+            }
+        }
+    }
+}
+
+/// Replay a register saved offline
+#[server(input = Json)]
+pub async fn sync_attendance(
+    key: String,
+    class_id: String,
+    date: String,
+    marks: Vec<OfflineAttendanceDto>,
+) -> Result<Vec<SyncOutcome>, ServerFnError> {
+    use crate::pages::attendance::ssr::{register_class, require_register_user};
+    use domain::{idempotency::IdempotencyService, sync::SyncService};
+
+    let user = require_register_user().await?;
+    register_class(&user, &class_id).await?;
+    let sdb = db::get_db();
+    let marked_by = user.user_id.clone();
+    IdempotencyService
+        .run(sdb, user.user_id, key, move || {
+            SyncService.attendance(sdb, class_id, date, marks, marked_by)
+        })
+        .await
+        .map_err(ServerFnError::new)
+}
+
+/// Replay scores typed offline into one class subject's sheet
+#[server(input = Json)]
+pub async fn sync_scores(
+    key: String,
+    class_subject_id: String,
+    edits: Vec<OfflineScoreDto>,
+) -> Result<Vec<SyncOutcome>, ServerFnError> {
+    use crate::pages::scores::ssr::{require_score_user, sheet_access};
+    use domain::{idempotency::IdempotencyService, sync::SyncService};
+
+    let user = require_score_user().await?;
+    let class_subject = sheet_access(&user, &class_subject_id).await?;
+    let sdb = db::get_db();
+    let entered_by = user.user_id.clone();
+    IdempotencyService
+        .run(sdb, user.user_id, key, move || async move {
+            SyncService
+                .scores(sdb, &class_subject, edits, entered_by)
+                .await
+        })
+        .await
+        .map_err(ServerFnError::new)
+}
+
+/// True when a server function failed because the server could not be reached
+pub fn is_network_error(err: &ServerFnError) -> bool {
+    matches!(err, ServerFnError::Request(_))
+}
+
+/// Run a load and keep its result under `name`; when the server can't be
+/// reached, the last kept result is returned instead
+pub async fn cached<T, F>(name: String, load: F) -> Result<T, ServerFnError>
+where
+    T: Serialize + DeserializeOwned,
+    F: Future<Output = Result<T, ServerFnError>>,
+{
+    match load.await {
+        Ok(value) => {
+            store::cache_put(&name, &value).await;
+            Ok(value)
+        }
+        Err(err) if is_network_error(&err) => store::cache_get(&name).await.ok_or(err),
+        Err(err) => Err(err),
+    }
+}
+
+/// Replace what is kept under `name`, e.g. with marks just queued offline
+pub async fn keep<T: Serialize>(name: &str, value: &T) {
+    store::cache_put(name, value).await;
+}
+
+/// Outbox state shared by every page, plus the sync that drains it
+#[derive(Clone, Copy)]
+pub struct SyncContext {
+    pub pending: RwSignal<usize>,
+    pub syncing: RwSignal<bool>,
+    pub last_report: RwSignal<Option<SyncReport>>,
+}
+
+impl SyncContext {
+    /// Create the context, register the service worker and sync whenever the
+    /// browser comes back online
+    pub fn provide() {
+        let sync = Self {
+            pending: RwSignal::new(0),
+            syncing: RwSignal::new(false),
+            last_report: RwSignal::new(None),
+        };
+        provide_context(sync);
+
+        Effect::new(move |_| {
+            store::register_service_worker();
+            spawn_local(sync.sync());
+            let handle =
+                window_event_listener(leptos::ev::online, move |_| spawn_local(sync.sync()));
+            on_cleanup(move || handle.remove());
+        });
+    }
+
+    /// Put a write in the outbox
+    pub async fn queue(self, write: QueuedWrite) -> Result<(), String> {
+        store::outbox_push(&write).await?;
+        self.pending.set(store::outbox_count().await);
+        Ok(())
+    }
+
+    /// Send queued writes oldest first, stopping if the server can't be reached.
+    /// Writes the server refuses are dropped and reported.
+    pub async fn sync(self) {
+        if self.syncing.get_untracked() {
+            return;
+        }
+        self.syncing.set(true);
+        let mut report = SyncReport::default();
+        for entry in store::outbox_entries().await {
+            let result = match entry.write {
+                QueuedWrite::Attendance {
+                    class_id,
+                    date,
+                    marks,
+                } => sync_attendance(entry.key, class_id, date, marks).await,
+                QueuedWrite::Scores {
+                    class_subject_id,
+                    edits,
+                } => sync_scores(entry.key, class_subject_id, edits).await,
+            };
+            match result {
+                Err(err) if is_network_error(&err) => break,
+                Ok(outcomes) => report.add(outcomes),
+                Err(err) => report.rejected.push(err.to_string()),
+            }
+            store::outbox_remove(entry.seq).await;
+        }
+        self.pending.set(store::outbox_count().await);
+        if report != SyncReport::default() {
+            self.last_report.set(Some(report));
+        }
+        self.syncing.set(false);
+    }
+}
+
+/// Banner showing queued writes and what the last sync did with them
+#[component]
+pub fn SyncStatus() -> impl IntoView {
+    let sync = expect_context::<SyncContext>();
+    let pending = move || {
+        let count = sync.pending.get();
+        (count > 0).then(|| {
+            let label = if count == 1 { "change" } else { "changes" };
+            view! {
+                <p class="sync-status pending">
+                    {format!("{count} {label} saved on this device, waiting to sync. ")}
+                    <button type="button" disabled=move || sync.syncing.get() on:click=move |_| spawn_local(sync.sync())>
+                        "Sync now"
+                    </button>
+                </p>
+            }
+        })
+    };
+    let report = move || {
+        sync.last_report.get().map(|report| {
+            let mut summary = format!("Synced {} offline changes.", report.applied);
+            if report.superseded > 0 {
+                summary.push_str(&format!(
+                    " {} were skipped because someone updated them after you.",
+                    report.superseded
+                ));
+            }
+            let rejected = report
+                .rejected
+                .into_iter()
+                .map(|reason| view! { <li>{reason}</li> })
+                .collect_view();
+            view! {
+                <div class="sync-status report">
+                    <p>{summary}</p>
+                    <ul>{rejected}</ul>
+                    <button type="button" on:click=move |_| sync.last_report.set(None)>"Dismiss"</button>
+                </div>
+            }
+        })
+    };
+
+    view! {
+        {pending}
+        {report}
+    }
+}
+
+struct OutboxEntry {
+    seq: f64,
+    key: String,
+    write: QueuedWrite,
+}
+
+/// IndexedDB access through public/offline.js; without a browser every call
+/// is a no-op so pages render the same on the server
+#[cfg(feature = "hydrate")]
+mod store {
+    use send_wrapper::SendWrapper;
+    use serde::{de::DeserializeOwned, Deserialize, Serialize};
+    use wasm_bindgen::prelude::*;
+
+    use super::{OutboxEntry, QueuedWrite};
+
+    // JS promises aren't Send, but resources need Send futures; the browser
+    // runs everything on one thread, so wrapping them is sound
+    #[wasm_bindgen(module = "/public/offline.js")]
+    extern "C" {
+        #[wasm_bindgen(js_name = outboxPush, catch)]
+        async fn js_outbox_push(payload: String) -> Result<JsValue, JsValue>;
+        #[wasm_bindgen(js_name = outboxEntries, catch)]
+        async fn js_outbox_entries() -> Result<JsValue, JsValue>;
+        #[wasm_bindgen(js_name = outboxRemove, catch)]
+        async fn js_outbox_remove(seq: f64) -> Result<JsValue, JsValue>;
+        #[wasm_bindgen(js_name = outboxCount, catch)]
+        async fn js_outbox_count() -> Result<JsValue, JsValue>;
+        #[wasm_bindgen(js_name = cachePut, catch)]
+        async fn js_cache_put(name: &str, json: String) -> Result<JsValue, JsValue>;
+        #[wasm_bindgen(js_name = cacheGet, catch)]
+        async fn js_cache_get(name: &str) -> Result<JsValue, JsValue>;
+        #[wasm_bindgen(js_name = registerServiceWorker)]
+        fn js_register_service_worker();
+    }
+
+    #[derive(Deserialize)]
+    struct StoredEntry {
+        seq: f64,
+        key: String,
+        payload: String,
+    }
+
+    pub async fn outbox_push(write: &QueuedWrite) -> Result<(), String> {
+        let payload = serde_json::to_string(write).map_err(|e| e.to_string())?;
+        SendWrapper::new(js_outbox_push(payload))
+            .await
+            .map(|_| ())
+            .map_err(|_| "could not save on this device".to_string())
+    }
+
+    pub async fn outbox_entries() -> Vec<OutboxEntry> {
+        let Some(json) = SendWrapper::new(js_outbox_entries())
+            .await
+            .ok()
+            .and_then(|v| v.as_string())
+        else {
+            return Vec::new();
+        };
+        serde_json::from_str::<Vec<StoredEntry>>(&json)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|entry| {
+                Some(OutboxEntry {
+                    write: serde_json::from_str(&entry.payload).ok()?,
+                    seq: entry.seq,
+                    key: entry.key,
+                })
+            })
+            .collect()
+    }
+
+    pub async fn outbox_remove(seq: f64) {
+        let _ = SendWrapper::new(js_outbox_remove(seq)).await;
+    }
+
+    pub async fn outbox_count() -> usize {
+        SendWrapper::new(js_outbox_count())
+            .await
+            .ok()
+            .and_then(|count| count.as_f64())
+            .unwrap_or_default() as usize
+    }
+
+    pub async fn cache_put<T: Serialize>(name: &str, value: &T) {
+        if let Ok(json) = serde_json::to_string(value) {
+            let _ = SendWrapper::new(js_cache_put(name, json)).await;
+        }
+    }
+
+    pub async fn cache_get<T: DeserializeOwned>(name: &str) -> Option<T> {
+        let json = SendWrapper::new(js_cache_get(name))
+            .await
+            .ok()?
+            .as_string()?;
+        serde_json::from_str(&json).ok()
+    }
+
+    pub fn register_service_worker() {
+        js_register_service_worker();
+    }
+}
+
+#[cfg(not(feature = "hydrate"))]
+mod store {
+    use serde::{de::DeserializeOwned, Serialize};
+
+    use super::{OutboxEntry, QueuedWrite};
+
+    pub async fn outbox_push(_write: &QueuedWrite) -> Result<(), String> {
+        Err("offline saving needs a browser".into())
+    }
+
+    pub async fn outbox_entries() -> Vec<OutboxEntry> {
+        Vec::new()
+    }
+
+    pub async fn outbox_remove(_seq: f64) {}
+
+    pub async fn outbox_count() -> usize {
+        0
+    }
+
+    pub async fn cache_put<T: Serialize>(_name: &str, _value: &T) {}
+
+    pub async fn cache_get<T: DeserializeOwned>(_name: &str) -> Option<T> {
+        None
+    }
+
+    pub fn register_service_worker() {}
+}
@@ -0,0 +1,528 @@
+// Copyright (C) 2026 Ahmad Olukotun
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+
+use leptos::{prelude::*, server_fn::codec::Json, task::spawn_local};
+use leptos_meta::Title;
+use serde::{Deserialize, Serialize};
+use shared::{helpers::create_update_dtos::OfflineAttendanceDto, models::AttendanceStatus};
+use surrealdb_types::Datetime;
+
+use crate::{
+    i18n::use_i18n,
+    offline::{self, QueuedWrite, SyncContext},
+};
+
+/// Statuses with the catalog key of their label
+const STATUSES: [(AttendanceStatus, &str); 4] = [
+    (AttendanceStatus::Present, "attendance.present"),
+    (AttendanceStatus::Absent, "attendance.absent"),
+    (AttendanceStatus::Late, "attendance.late"),
+    (AttendanceStatus::Excused, "attendance.excused"),
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RegisterClass {
+    pub id: String,
+    pub name: String,
+}
+
+/// One student's line in the register; `status` is None until marked
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RegisterEntry {
+    pub student_id: String,
+    pub admission_number: String,
+    pub name: String,
+    pub status: Option<AttendanceStatus>,
+    pub arrival_time: Option<String>,
+    pub reason: Option<String>,
+}
+
+#[server]
+pub async fn register_classes() -> Result<Vec<RegisterClass>, ServerFnError> {
+    use db::queries::ClassQ;
+    use shared::{helpers::surreal_util::record_key, models::UserType};
+
+    let user =
+        crate::auth::ssr::require_role(&[UserType::SuperAdmin, UserType::Admin, UserType::Teacher])
+            .await?;
+    let sdb = db::get_db();
+    let classes = match user.user_type {
+        UserType::Teacher => ClassQ.get_by_class_teacher(sdb, user.user_id).await,
+        _ => ClassQ.get_by_school(sdb, user.school_id).await,
+    }
+    .map_err(ServerFnError::new)?;
+
+    Ok(classes
+        .into_iter()
+        .filter_map(|class| {
+            Some(RegisterClass {
+                id: record_key(class.id.as_ref()?),
+                name: class.class_name,
+            })
+        })
+        .collect())
+}
+
+/// Every active student in the class, prefilled with marks already saved for the date
+#[server]
+pub async fn load_register(
+    class_id: String,
+    date: String,
+) -> Result<Vec<RegisterEntry>, ServerFnError> {
+    use std::collections::HashMap;
+
+    use db::queries::{AttendanceQ, StudentQ};
+    use domain::sync::parse_register_date;
+    use shared::{helpers::surreal_util::record_key, models::attendance::Attendance};
+    use surrealdb_types::SurrealValue;
+
+    let user = ssr::require_register_user().await?;
+    ssr::register_class(&user, &class_id).await?;
+    parse_register_date(&date).map_err(ServerFnError::new)?;
+    let sdb = db::get_db();
+
+    let students = StudentQ
+        .get_by_class(sdb, class_id.clone())
+        .await
+        .map_err(ServerFnError::new)?;
+    let mut marks: HashMap<String, Attendance> = AttendanceQ
+        .get_by_class_and_date(sdb, class_id, date)
+        .await
+        .map_err(ServerFnError::new)?
+        .into_iter()
+        .filter_map(|row| Attendance::from_value(row).ok())
+        .map(|mark| (record_key(&mark.student_id), mark))
+        .collect();
+
+    Ok(students
+        .into_iter()
+        .filter_map(|student| {
+            let student_id = record_key(student.id.as_ref()?);
+            let mark = marks.remove(&student_id);
+            Some(RegisterEntry {
+                name: format!("{}, {}", student.last_name, student.first_name),
+                admission_number: student.admission_number,
+                status: mark.as_ref().map(|m| m.status.clone()),
+                arrival_time: mark.as_ref().and_then(|m| m.arrival_time.clone()),
+                reason: mark.and_then(|m| m.reason),
+                student_id,
+            })
+        })
+        .collect())
+}
+
+/// Save the register in one bulk write; unmarked students are left out.
+/// Returns how many marks were saved.
+#[server(input = Json)]
+pub async fn save_register(
+    class_id: String,
+    date: String,
+    entries: Vec<RegisterEntry>,
+) -> Result<usize, ServerFnError> {
+    use std::collections::HashSet;
+
+    use db::queries::{BulkQ, StudentQ};
+    use domain::{subscription::SubscriptionService, sync::parse_register_date};
+    use shared::helpers::{
+        create_update_dtos::MarkAttendanceDto,
+        surreal_util::{record_key, to_surreal_datetime},
+    };
+    use surrealdb_types::RecordId;
+
+    let user = ssr::require_register_user().await?;
+    let class = ssr::register_class(&user, &class_id).await?;
+    let day = parse_register_date(&date).map_err(ServerFnError::new)?;
+    let sdb = db::get_db();
+    SubscriptionService
+        .ensure_writable(sdb, &record_key(&class.school_id))
+        .await
+        .map_err(ServerFnError::new)?;
+
+    let enrolled: HashSet<String> = StudentQ
+        .get_by_class(sdb, class_id.clone())
+        .await
+        .map_err(ServerFnError::new)?
+        .iter()
+        .filter_map(|student| student.id.as_ref().map(record_key))
+        .collect();
+
+    let mut records = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let Some(status) = entry.status else {
+            continue;
+        };
+        if !enrolled.contains(&entry.student_id) {
+            return Err(ServerFnError::new(format!(
+                "{} is not in this class",
+                entry.name
+            )));
+        }
+        let arrival_time = match status {
+            AttendanceStatus::Late => non_empty(entry.arrival_time),
+            _ => None,
+        };
+        let reason = match status {
+            AttendanceStatus::Present => None,
+            _ => non_empty(entry.reason),
+        };
+        records.push(MarkAttendanceDto {
+            student_id: RecordId::new("students", entry.student_id.as_str()),
+            class_id: RecordId::new("classes", class_id.as_str()),
+            date: to_surreal_datetime(day),
+            status,
+            arrival_time,
+            reason,
+        });
+    }
+    if records.is_empty() {
+        return Ok(0);
+    }
+
+    let saved = BulkQ
+        .mark_class_attendance(sdb, class_id, date, records, user.user_id)
+        .await
+        .map_err(ServerFnError::new)?;
+    Ok(saved.len())
+}
+
+#[cfg(feature = "ssr")]
+fn non_empty(value: Option<String>) -> Option<String> {
+    value
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+#[cfg(feature = "ssr")]
+pub(crate) mod ssr {
+    use db::queries::ClassQ;
+    use leptos::prelude::*;
+    use shared::{
+        helpers::{api_responses::SessionUser, surreal_util::record_key},
+        models::{academic::Class, UserType},
+    };
+
+    pub async fn require_register_user() -> Result<SessionUser, ServerFnError> {
+        crate::auth::ssr::require_role(&[UserType::SuperAdmin, UserType::Admin, UserType::Teacher])
+            .await
+    }
+
+    /// The class, if the user may take its register: admins for any class in
+    /// their school, teachers only for classes they are class teacher of
+    pub async fn register_class(
+        user: &SessionUser,
+        class_id: &str,
+    ) -> Result<Class, ServerFnError> {
+        let class = ClassQ
+            .get_by_id(db::get_db(), class_id.to_string())
+            .await
+            .map_err(ServerFnError::new)?
+            .ok_or_else(|| ServerFnError::new("class not found"))?;
+        let in_school = record_key(&class.school_id) == user.school_id;
+        let is_class_teacher = class
+            .class_teacher_id
+            .as_ref()
+            .is_some_and(|id| record_key(id) == user.user_id);
+        if !in_school || (user.user_type == UserType::Teacher && !is_class_teacher) {
+            return Err(ServerFnError::new("not allowed"));
+        }
+        Ok(class)
+    }
+}
+
+/// Editable state for one register line
+#[derive(Clone, Copy)]
+struct RegisterRow {
+    entry: StoredValue<RegisterEntry>,
+    status: RwSignal<Option<AttendanceStatus>>,
+    arrival_time: RwSignal<String>,
+    reason: RwSignal<String>,
+}
+
+impl RegisterRow {
+    fn new(entry: RegisterEntry) -> Self {
+        Self {
+            status: RwSignal::new(entry.status.clone()),
+            arrival_time: RwSignal::new(entry.arrival_time.clone().unwrap_or_default()),
+            reason: RwSignal::new(entry.reason.clone().unwrap_or_default()),
+            entry: StoredValue::new(entry),
+        }
+    }
+
+    fn to_entry(self) -> RegisterEntry {
+        let optional = |value: String| (!value.trim().is_empty()).then_some(value);
+        RegisterEntry {
+            status: self.status.get_untracked(),
+            arrival_time: optional(self.arrival_time.get_untracked()),
+            reason: optional(self.reason.get_untracked()),
+            ..self.entry.get_value()
+        }
+    }
+}
+
+/// Daily register: pick a class and date, mark each student, save in one go
+#[component]
+pub fn AttendancePage() -> impl IntoView {
+    let classes = Resource::new(
+        || (),
+        |_| offline::cached("register-classes".into(), register_classes()),
+    );
+    let class_id = RwSignal::new(String::new());
+    let date = RwSignal::new(
+        chrono::Local::now()
+            .date_naive()
+            .format("%Y-%m-%d")
+            .to_string(),
+    );
+    let register = Resource::new(
+        move || (class_id.get(), date.get()),
+        |(class_id, date)| async move {
+            if class_id.is_empty() {
+                Ok(Vec::new())
+            } else {
+                let name = register_cache_name(&class_id, &date);
+                offline::cached(name, load_register(class_id, date)).await
+            }
+        },
+    );
+
+    view! {
+        <Title text="Attendance"/>
+        <h1>"Attendance register"</h1>
+        <div class="register-filters">
+            <label>
+                "Class"
+                <Suspense fallback=|| view! { <select disabled=true></select> }>
+                    {move || Suspend::new(async move {
+                        let options = classes
+                            .await
+                            .unwrap_or_default()
+                            .into_iter()
+                            .map(|class| view! { <option value=class.id>{class.name}</option> })
+                            .collect_view();
+                        view! {
+                            <select on:change=move |ev| class_id.set(event_target_value(&ev))>
+                                <option value="">"Choose a class"</option>
+                                {options}
+                            </select>
+                        }
+                    })}
+                </Suspense>
+            </label>
+            <label>
+                "Date"
+                <input type="date" prop:value=move || date.get() on:change=move |ev| date.set(event_target_value(&ev))/>
+            </label>
+        </div>
+        <Transition fallback=|| view! { <p>"Loading…"</p> }>
+            {move || Suspend::new(async move {
+                match register.await {
+                    Ok(entries) if entries.is_empty() => {
+                        view! { <p>"Choose a class to take the register."</p> }.into_any()
+                    }
+                    Ok(entries) => {
+                        let rows = entries.into_iter().map(RegisterRow::new).collect::<Vec<_>>();
+                        view! {
+                            <RegisterForm
+                                class_id=class_id.get_untracked()
+                                date=date.get_untracked()
+                                rows
+                            />
+                        }
+                        .into_any()
+                    }
+                    Err(err) => view! { <p class="error">{err.to_string()}</p> }.into_any(),
+                }
+            })}
+        </Transition>
+    }
+}
+
+fn register_cache_name(class_id: &str, date: &str) -> String {
+    format!("register-{class_id}-{date}")
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum RegisterSave {
+    Saved(usize),
+    Queued,
+    Failed(String),
+}
+
+/// Keep the register on the device for the outbox to send once back online
+async fn save_offline(
+    sync: SyncContext,
+    class_id: String,
+    date: String,
+    entries: Vec<RegisterEntry>,
+) -> RegisterSave {
+    let recorded_at = Datetime::now();
+    let marks: Vec<OfflineAttendanceDto> = entries
+        .iter()
+        .filter_map(|entry| {
+            Some(OfflineAttendanceDto {
+                student_id: entry.student_id.clone(),
+                status: entry.status.clone()?,
+                arrival_time: entry.arrival_time.clone(),
+                reason: entry.reason.clone(),
+                recorded_at,
+            })
+        })
+        .collect();
+    if marks.is_empty() {
+        return RegisterSave::Saved(0);
+    }
+    offline::keep(&register_cache_name(&class_id, &date), &entries).await;
+    match sync
+        .queue(QueuedWrite::Attendance {
+            class_id,
+            date,
+            marks,
+        })
+        .await
+    {
+        Ok(()) => RegisterSave::Queued,
+        Err(err) => RegisterSave::Failed(err),
+    }
+}
+
+#[component]
+fn RegisterForm(class_id: String, date: String, rows: Vec<RegisterRow>) -> impl IntoView {
+    let sync = expect_context::<SyncContext>();
+    let saving = RwSignal::new(false);
+    let last_save = RwSignal::new(None::<RegisterSave>);
+    let ids = StoredValue::new((class_id, date));
+    let rows = StoredValue::new(rows);
+
+    let mark_all_present = move |_| {
+        rows.with_value(|rows| {
+            for row in rows
+                .iter()
+                .filter(|row| row.status.get_untracked().is_none())
+            {
+                row.status.set(Some(AttendanceStatus::Present));
+            }
+        })
+    };
+    let on_submit = move |ev: leptos::ev::SubmitEvent| {
+        ev.prevent_default();
+        let (class_id, date) = ids.get_value();
+        let entries: Vec<RegisterEntry> =
+            rows.with_value(|rows| rows.iter().map(|row| row.to_entry()).collect());
+        saving.set(true);
+        spawn_local(async move {
+            let outcome = match save_register(class_id.clone(), date.clone(), entries.clone()).await
+            {
+                Ok(saved) => RegisterSave::Saved(saved),
+                Err(err) if offline::is_network_error(&err) => {
+                    save_offline(sync, class_id, date, entries).await
+                }
+                Err(err) => RegisterSave::Failed(err.to_string()),
+            };
+            last_save.set(Some(outcome));
+            saving.set(false);
+        });
+    };
+    let i18n = use_i18n();
+    let tally = move || {
+        rows.with_value(|rows| {
+            let count = |status: Option<AttendanceStatus>| {
+                rows.iter().filter(|row| row.status.get() == status).count()
+            };
+            let mut parts: Vec<String> = STATUSES
+                .iter()
+                .map(|(status, label)| format!("{} {}", i18n.t(label), count(Some(status.clone()))))
+                .collect();
+            parts.push(format!("{} {}", i18n.t("attendance.unmarked"), count(None)));
+            parts.join(" · ")
+        })
+    };
+    let result = move || {
+        last_save.get().map(|outcome| match outcome {
+            RegisterSave::Saved(saved) => {
+                view! { <p class="saved">{format!("Saved {saved} marks")}</p> }.into_any()
+            }
+            RegisterSave::Queued => {
+                view! {
+                    <p class="queued">"You're offline. The register is saved on this device and will sync when you reconnect."</p>
+                }
+                .into_any()
+            }
+            RegisterSave::Failed(err) => view! { <p class="error">{err}</p> }.into_any(),
+        })
+    };
+
+    view! {
+        <form class="register" on:submit=on_submit>
+            <div class="register-actions">
+                <button type="button" on:click=mark_all_present>"Mark rest present"</button>
+                <span class="tally">{tally}</span>
+            </div>
+            <ul class="register-rows">
+                {rows.get_value().into_iter().map(|row| view! { <RegisterLine row/> }).collect_view()}
+            </ul>
+            <button type="submit" class="primary" disabled=move || saving.get()>
+                "Save register"
+            </button>
+            {result}
+        </form>
+    }
+}
+
+#[component]
+fn RegisterLine(row: RegisterRow) -> impl IntoView {
+    let entry = row.entry.get_value();
+    let i18n = use_i18n();
+    let buttons = STATUSES
+        .iter()
+        .map(|(status, label)| {
+            let status = status.clone();
+            let selected = {
+                let status = status.clone();
+                move || row.status.get().as_ref() == Some(&status)
+            };
+            view! {
+                <button
+                    type="button"
+                    class="status"
+                    class:selected=selected
+                    on:click=move |_| row.status.set(Some(status.clone()))
+                >
+                    {i18n.t(label)}
+                </button>
+            }
+        })
+        .collect_view();
+
+    view! {
+        <li class="register-row">
+            <div class="student">
+                <strong>{entry.name}</strong>
+                <small>{entry.admission_number}</small>
+            </div>
+            <div class="statuses">{buttons}</div>
+            <Show when=move || row.status.get() == Some(AttendanceStatus::Late)>
+                <input
+                    type="time"
+                    aria-label="Arrival time"
+                    prop:value=move || row.arrival_time.get()
+                    on:input=move |ev| row.arrival_time.set(event_target_value(&ev))
+                />
+            </Show>
+            <Show when=move || matches!(row.status.get(), Some(AttendanceStatus::Absent | AttendanceStatus::Late | AttendanceStatus::Excused))>
+                <input
+                    type="text"
+                    placeholder="Reason (optional)"
+                    prop:value=move || row.reason.get()
+                    on:input=move |ev| row.reason.set(event_target_value(&ev))
+                />
+            </Show>
+        </li>
+    }
+}
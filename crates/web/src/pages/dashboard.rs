@@ -0,0 +1,269 @@
+// Copyright (C) 2026 Ahmad Olukotun
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+
+use leptos::prelude::*;
+use leptos_meta::Title;
+use serde::{Deserialize, Serialize};
+use shared::{
+    helpers::api_responses::{DashboardMetrics, SessionUser},
+    i18n::{Currency, Locale},
+    models::{system_and_audit::ActivityLog, ActionType, UserType},
+};
+
+use crate::{
+    auth::AuthContext,
+    i18n::{use_i18n, SetSchoolRegion},
+    pages::parent::ParentHome,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DashboardSummary {
+    pub term_name: Option<String>,
+    /// None when the school has no current term
+    pub metrics: Option<DashboardMetrics>,
+    pub recent_activity: Vec<ActivityLog>,
+}
+
+#[server]
+pub async fn dashboard_data() -> Result<DashboardSummary, ServerFnError> {
+    use db::queries::{AcademicSessionQ, ActivityLogQ, AnalyticsQ, TermQ};
+    use shared::helpers::surreal_util::record_key;
+
+    let user = crate::auth::ssr::require_role(&[
+        UserType::SuperAdmin,
+        UserType::Admin,
+        UserType::Accountant,
+    ])
+    .await?;
+    let sdb = db::get_db();
+    let error = |e: db::error::Error| ServerFnError::new(e);
+
+    let session = AcademicSessionQ
+        .get_current(sdb, user.school_id.clone())
+        .await
+        .map_err(error)?;
+    let term = match session.as_ref().and_then(|s| s.id.as_ref()) {
+        Some(id) => TermQ
+            .get_current(sdb, record_key(id))
+            .await
+            .map_err(error)?,
+        None => None,
+    };
+
+    let mut metrics = None;
+    let mut term_name = None;
+    if let (Some(session), Some(term)) = (&session, &term) {
+        if let Some(term_id) = &term.id {
+            metrics = Some(
+                AnalyticsQ
+                    .get_dashboard_metrics(sdb, user.school_id.clone(), record_key(term_id))
+                    .await
+                    .map_err(error)?,
+            );
+        }
+        let name = term
+            .term_name
+            .clone()
+            .unwrap_or_else(|| format!("Term {}", term.term_number));
+        term_name = Some(format!("{} {}", session.session_name, name));
+    }
+
+    let recent_activity = ActivityLogQ
+        .get_by_school(sdb, user.school_id, Some(10))
+        .await
+        .map_err(error)?;
+
+    Ok(DashboardSummary {
+        term_name,
+        metrics,
+        recent_activity,
+    })
+}
+
+/// Landing page: the metrics dashboard for staff who manage the school,
+/// the portal for parents and a short welcome for everyone else
+#[component]
+pub fn HomePage() -> impl IntoView {
+    let user = expect_context::<SessionUser>();
+    match user.user_type {
+        UserType::SuperAdmin | UserType::Admin | UserType::Accountant => {
+            view! { <DashboardPage/> }.into_any()
+        }
+        UserType::Parent => view! { <ParentHome/> }.into_any(),
+        _ => view! {
+            <Title text="Home"/>
+            <h1>{format!("Welcome, {}", user.first_name)}</h1>
+        }
+        .into_any(),
+    }
+}
+
+#[component]
+pub fn DashboardPage() -> impl IntoView {
+    let user = expect_context::<SessionUser>();
+    let data = Resource::new(|| (), |_| dashboard_data());
+    let manages_school = matches!(user.user_type, UserType::SuperAdmin | UserType::Admin);
+
+    view! {
+        <Title text="Dashboard"/>
+        <h1>"Dashboard"</h1>
+        <Suspense fallback=|| view! { <p>"Loading…"</p> }>
+            {move || Suspend::new(async move {
+                match data.await {
+                    Ok(data) => view! {
+                        <p class="term">{data.term_name.unwrap_or_else(|| "No current term".into())}</p>
+                        {data.metrics.map(|m| view! { <MetricCards metrics=m/> })}
+                        <RecentActivity logs=data.recent_activity/>
+                    }
+                    .into_any(),
+                    Err(err) => view! { <p class="error">{err.to_string()}</p> }.into_any(),
+                }
+            })}
+        </Suspense>
+        {manages_school.then(|| view! { <RegionalSettings user=user.clone()/> })}
+    }
+}
+
+#[component]
+fn MetricCards(metrics: DashboardMetrics) -> impl IntoView {
+    let i18n = use_i18n();
+    let fees_label = i18n.format(
+        "dashboard.fees_collected",
+        &[
+            ("collected", &i18n.money(metrics.total_collected)),
+            ("expected", &i18n.money(metrics.total_expected)),
+        ],
+    );
+    view! {
+        <div class="metrics">
+            <div class="metric">
+                <span class="metric-value">{metrics.total_students}</span>
+                <span class="metric-label">{i18n.t("dashboard.students")}</span>
+            </div>
+            <div class="metric">
+                <span class="metric-value">{metrics.total_teachers}</span>
+                <span class="metric-label">{i18n.t("dashboard.teachers")}</span>
+            </div>
+            <div class="metric">
+                <span class="metric-value">{format!("{}%", metrics.collection_rate.round_dp(1))}</span>
+                <span class="metric-label">{fees_label}</span>
+            </div>
+            <div class="metric">
+                <span class="metric-value">{format!("{}%", metrics.attendance_rate.round_dp(1))}</span>
+                <span class="metric-label">{i18n.t("dashboard.attendance")}</span>
+            </div>
+        </div>
+    }
+}
+
+/// The school's default language and billing currency. The user's own
+/// language is not changed, but amounts on the page switch currency.
+#[component]
+fn RegionalSettings(user: SessionUser) -> impl IntoView {
+    let auth = expect_context::<AuthContext>();
+    let i18n = use_i18n();
+    let save = ServerAction::<SetSchoolRegion>::new();
+    Effect::new(move |_| {
+        if let Some(Ok(())) = save.value().get() {
+            auth.user.refetch();
+        }
+    });
+    let languages = Locale::ALL
+        .into_iter()
+        .map(|locale| {
+            view! {
+                <option value=locale.code() selected=locale == user.locale>
+                    {locale.native_name()}
+                </option>
+            }
+        })
+        .collect_view();
+    let currencies = Currency::ALL
+        .into_iter()
+        .map(|currency| {
+            view! {
+                <option value=currency.code() selected=currency == user.currency>
+                    {format!("{} ({})", currency.code(), currency.symbol())}
+                </option>
+            }
+        })
+        .collect_view();
+
+    view! {
+        <h2>{i18n.t("dashboard.regional")}</h2>
+        <ActionForm action=save attr:class="regional">
+            <label>
+                {i18n.t("dashboard.default_language")}
+                <select name="locale">{languages}</select>
+            </label>
+            <label>
+                {i18n.t("dashboard.currency")}
+                <select name="currency">{currencies}</select>
+            </label>
+            <button type="submit">{i18n.t("dashboard.save")}</button>
+        </ActionForm>
+        {move || {
+            save.value()
+                .get()
+                .and_then(Result::err)
+                .map(|err| view! { <p class="error">{err.to_string()}</p> })
+        }}
+    }
+}
+
+#[component]
+fn RecentActivity(logs: Vec<ActivityLog>) -> impl IntoView {
+    if logs.is_empty() {
+        return view! { <p>"No recent activity."</p> }.into_any();
+    }
+    let rows = logs
+        .into_iter()
+        .map(|log| {
+            view! {
+                <tr>
+                    <td>{log.created_at.format("%d %b %Y %H:%M").to_string()}</td>
+                    <td>{action_label(&log.action)}</td>
+                    <td>{log.entity_type.unwrap_or_default()}</td>
+                    <td>{log.description.unwrap_or_default()}</td>
+                </tr>
+            }
+        })
+        .collect_view();
+
+    view! {
+        <h2>"Recent activity"</h2>
+        <table class="activity">
+            <thead>
+                <tr>
+                    <th>"When"</th>
+                    <th>"Action"</th>
+                    <th>"Record"</th>
+                    <th>"Details"</th>
+                </tr>
+            </thead>
+            <tbody>{rows}</tbody>
+        </table>
+    }
+    .into_any()
+}
+
+fn action_label(action: &ActionType) -> &'static str {
+    match action {
+        ActionType::Login => "Login",
+        ActionType::Logout => "Logout",
+        ActionType::Create => "Create",
+        ActionType::Update => "Update",
+        ActionType::Delete => "Delete",
+        ActionType::View => "View",
+        ActionType::Export => "Export",
+        ActionType::Publish => "Publish",
+        ActionType::Payment => "Payment",
+    }
+}
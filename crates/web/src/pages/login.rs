@@ -0,0 +1,47 @@
+// Copyright (C) 2026 Ahmad Olukotun
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+
+use leptos::prelude::*;
+use leptos_meta::Title;
+
+use crate::auth::AuthContext;
+
+#[component]
+pub fn LoginPage() -> impl IntoView {
+    let auth = expect_context::<AuthContext>();
+    let error = move || {
+        auth.login
+            .value()
+            .get()
+            .and_then(|result| result.err())
+            .map(|_| "Invalid email/phone or password")
+    };
+
+    view! {
+        <Title text="Sign in"/>
+        <section class="login">
+            <h1>"Sign in"</h1>
+            <ActionForm action=auth.login>
+                <label>
+                    "Email or phone number"
+                    <input type="text" name="identifier" autocomplete="username" required/>
+                </label>
+                <label>
+                    "Password"
+                    <input type="password" name="password" autocomplete="current-password" required/>
+                </label>
+                <button type="submit" disabled=move || auth.login.pending().get()>
+                    "Sign in"
+                </button>
+            </ActionForm>
+            <p class="error">{error}</p>
+        </section>
+    }
+}
@@ -0,0 +1,441 @@
+// Copyright (C) 2026 Ahmad Olukotun
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+
+use leptos::prelude::*;
+use leptos_meta::Title;
+use leptos_router::{components::A, hooks::use_params_map};
+use serde::{Deserialize, Serialize};
+use shared::{
+    helpers::{
+        api_responses::{ChildOverview, OutstandingInvoice, PublishedReportCard},
+        surreal_util::to_naive_date,
+    },
+    i18n::Localizer,
+    models::{attendance::Attendance, communication::Announcement, AttendanceStatus},
+};
+
+use crate::i18n::use_i18n;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ChildCard {
+    pub id: String,
+    pub name: String,
+    pub admission_number: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ThreadMessage {
+    /// Sent by the signed-in parent rather than the teacher
+    pub mine: bool,
+    pub body: String,
+    pub sent_at: String,
+}
+
+#[server]
+pub async fn my_children() -> Result<Vec<ChildCard>, ServerFnError> {
+    use shared::{helpers::surreal_util::record_key, models::UserType};
+
+    let user = crate::auth::ssr::require_role(&[UserType::Parent]).await?;
+    let children = domain::parent_portal::ParentPortalService
+        .children(db::get_db(), user.user_id)
+        .await
+        .map_err(ServerFnError::new)?;
+    Ok(children
+        .into_iter()
+        .filter_map(|child| {
+            Some(ChildCard {
+                id: record_key(child.id.as_ref()?),
+                name: format!("{} {}", child.first_name, child.last_name),
+                admission_number: child.admission_number,
+            })
+        })
+        .collect())
+}
+
+#[server]
+pub async fn parent_announcements() -> Result<Vec<Announcement>, ServerFnError> {
+    use shared::models::UserType;
+
+    let user = crate::auth::ssr::require_role(&[UserType::Parent]).await?;
+    domain::parent_portal::ParentPortalService
+        .announcements(db::get_db(), user.user_id, user.school_id)
+        .await
+        .map_err(ServerFnError::new)
+}
+
+#[server]
+pub async fn load_child_overview(student_id: String) -> Result<ChildOverview, ServerFnError> {
+    use shared::models::UserType;
+
+    let user = crate::auth::ssr::require_role(&[UserType::Parent]).await?;
+    domain::parent_portal::ParentPortalService
+        .child_overview(db::get_db(), user.user_id, student_id)
+        .await
+        .map_err(ServerFnError::new)
+}
+
+#[server]
+pub async fn load_teacher_thread(student_id: String) -> Result<Vec<ThreadMessage>, ServerFnError> {
+    use shared::{helpers::surreal_util::record_key, models::UserType};
+
+    let user = crate::auth::ssr::require_role(&[UserType::Parent]).await?;
+    let messages = domain::parent_portal::ParentPortalService
+        .teacher_thread(db::get_db(), user.user_id.clone(), student_id)
+        .await
+        .map_err(ServerFnError::new)?;
+    Ok(messages
+        .into_iter()
+        .map(|message| ThreadMessage {
+            mine: record_key(&message.sender_id) == user.user_id,
+            body: message.message_body,
+            sent_at: message.sent_at.format("%d %b %Y, %H:%M").to_string(),
+        })
+        .collect())
+}
+
+#[server]
+pub async fn message_teacher(student_id: String, body: String) -> Result<(), ServerFnError> {
+    use shared::models::UserType;
+
+    let user = crate::auth::ssr::require_role(&[UserType::Parent]).await?;
+    domain::parent_portal::ParentPortalService
+        .message_teacher(db::get_db(), user.user_id, student_id, body)
+        .await
+        .map_err(ServerFnError::new)?;
+    Ok(())
+}
+
+fn format_date(i18n: &Localizer, value: &surrealdb_types::Value) -> String {
+    to_naive_date(value)
+        .map(|date| i18n.date(date))
+        .unwrap_or_default()
+}
+
+/// A parent's landing page: their children and the school's announcements
+#[component]
+pub fn ParentHome() -> impl IntoView {
+    let children = Resource::new(|| (), |_| my_children());
+    let announcements = Resource::new(|| (), |_| parent_announcements());
+
+    view! {
+        <Title text="Home"/>
+        <h1>"Your children"</h1>
+        <Suspense fallback=|| view! { <p>"Loading…"</p> }>
+            {move || Suspend::new(async move {
+                match children.await {
+                    Ok(children) if children.is_empty() => {
+                        view! { <p>"No children are linked to your account yet. Please contact the school."</p> }
+                            .into_any()
+                    }
+                    Ok(children) => {
+                        let cards = children
+                            .into_iter()
+                            .map(|child| {
+                                view! {
+                                    <li>
+                                        <A href=format!("/children/{}", child.id)>
+                                            <strong>{child.name}</strong>
+                                            <small>{child.admission_number}</small>
+                                        </A>
+                                    </li>
+                                }
+                            })
+                            .collect_view();
+                        view! { <ul class="child-cards">{cards}</ul> }.into_any()
+                    }
+                    Err(err) => view! { <p class="error">{err.to_string()}</p> }.into_any(),
+                }
+            })}
+        </Suspense>
+        <h2>"Announcements"</h2>
+        <Suspense fallback=|| view! { <p>"Loading…"</p> }>
+            {move || Suspend::new(async move {
+                match announcements.await {
+                    Ok(list) if list.is_empty() => view! { <p>"No announcements."</p> }.into_any(),
+                    Ok(list) => {
+                        let items = list
+                            .into_iter()
+                            .map(|a| {
+                                view! {
+                                    <article class="announcement">
+                                        <h3>{a.title}</h3>
+                                        <p>{a.message}</p>
+                                    </article>
+                                }
+                            })
+                            .collect_view();
+                        view! { <div>{items}</div> }.into_any()
+                    }
+                    Err(err) => view! { <p class="error">{err.to_string()}</p> }.into_any(),
+                }
+            })}
+        </Suspense>
+    }
+}
+
+/// One child's attendance, results, fees and a thread with their class teacher
+#[component]
+pub fn ChildPage() -> impl IntoView {
+    let params = use_params_map();
+    let student_id = move || params.read().get("id").unwrap_or_default();
+    let overview = Resource::new(student_id, load_child_overview);
+
+    view! {
+        <Suspense fallback=|| view! { <p>"Loading…"</p> }>
+            {move || Suspend::new(async move {
+                match overview.await {
+                    Ok(overview) => {
+                        let name = format!(
+                            "{} {}",
+                            overview.student.first_name,
+                            overview.student.last_name,
+                        );
+                        let has_teacher = overview.class_teacher.is_some();
+                        let teacher = overview
+                            .class_teacher
+                            .map(|t| t.name)
+                            .unwrap_or_else(|| "No class teacher assigned".into());
+                        view! {
+                            <Title text=name.clone()/>
+                            <h1>{name}</h1>
+                            <p class="term">
+                                {overview.class_name.unwrap_or_default()} " · " {teacher.clone()}
+                            </p>
+                            <AttendanceSection
+                                tally=overview.attendance
+                                records=overview.recent_attendance
+                            />
+                            <ResultsSection cards=overview.report_cards/>
+                            <FeesSection invoices=overview.outstanding_invoices/>
+                            {has_teacher
+                                .then(|| view! { <TeacherThread student_id=student_id() teacher/> })}
+                        }
+                        .into_any()
+                    }
+                    Err(err) => view! { <p class="error">{err.to_string()}</p> }.into_any(),
+                }
+            })}
+        </Suspense>
+    }
+}
+
+#[component]
+fn AttendanceSection(
+    tally: shared::helpers::api_responses::AttendanceTally,
+    records: Vec<Attendance>,
+) -> impl IntoView {
+    let i18n = use_i18n();
+    let days = records
+        .into_iter()
+        .filter(|record| record.status != AttendanceStatus::Present)
+        .map(|record| {
+            let status = match record.status {
+                AttendanceStatus::Present => "attendance.present",
+                AttendanceStatus::Absent => "attendance.absent",
+                AttendanceStatus::Late => "attendance.late",
+                AttendanceStatus::Excused => "attendance.excused",
+            };
+            view! {
+                <tr>
+                    <td>{format_date(&i18n, &record.date)}</td>
+                    <td>{i18n.t(status)}</td>
+                    <td>{record.arrival_time.unwrap_or_default()}</td>
+                    <td>{record.reason.unwrap_or_default()}</td>
+                </tr>
+            }
+        })
+        .collect_view();
+
+    view! {
+        <section>
+            <h2>"Attendance"</h2>
+            <div class="metrics">
+                <div class="metric">
+                    <span class="metric-value">{tally.present}</span>
+                    <span>{i18n.t("attendance.present")}</span>
+                </div>
+                <div class="metric">
+                    <span class="metric-value">{tally.absent}</span>
+                    <span>{i18n.t("attendance.absent")}</span>
+                </div>
+                <div class="metric">
+                    <span class="metric-value">{tally.late}</span>
+                    <span>{i18n.t("attendance.late")}</span>
+                </div>
+                <div class="metric">
+                    <span class="metric-value">{tally.excused}</span>
+                    <span>{i18n.t("attendance.excused")}</span>
+                </div>
+            </div>
+            <table>
+                <thead>
+                    <tr>
+                        <th>"Date"</th>
+                        <th>"Status"</th>
+                        <th>"Arrived"</th>
+                        <th>"Reason"</th>
+                    </tr>
+                </thead>
+                <tbody>{days}</tbody>
+            </table>
+        </section>
+    }
+}
+
+#[component]
+fn ResultsSection(cards: Vec<PublishedReportCard>) -> impl IntoView {
+    let empty = cards.is_empty();
+    let rows = cards
+        .into_iter()
+        .map(|PublishedReportCard { term_name, card }| {
+            let position = match (card.class_position, card.total_students_in_class) {
+                (Some(position), Some(total)) => format!("{position} of {total}"),
+                (Some(position), None) => position.to_string(),
+                _ => String::new(),
+            };
+            view! {
+                <tr>
+                    <td>{term_name}</td>
+                    <td>{card.average_percentage.map(|a| format!("{a:.1}%")).unwrap_or_default()}</td>
+                    <td>{position}</td>
+                    <td>
+                        {card
+                            .pdf_url
+                            .map(|url| view! { <a href=url target="_blank">"Download"</a> })}
+                    </td>
+                </tr>
+            }
+        })
+        .collect_view();
+
+    view! {
+        <section>
+            <h2>"Report cards"</h2>
+            {if empty {
+                view! { <p>"No report cards published yet."</p> }.into_any()
+            } else {
+                view! {
+                    <table>
+                        <thead>
+                            <tr>
+                                <th>"Term"</th>
+                                <th>"Average"</th>
+                                <th>"Position"</th>
+                                <th></th>
+                            </tr>
+                        </thead>
+                        <tbody>{rows}</tbody>
+                    </table>
+                }
+                .into_any()
+            }}
+        </section>
+    }
+}
+
+#[component]
+fn FeesSection(invoices: Vec<OutstandingInvoice>) -> impl IntoView {
+    let i18n = use_i18n();
+    let empty = invoices.is_empty();
+    let rows = invoices
+        .into_iter()
+        .map(|OutstandingInvoice { invoice, pay_url }| {
+            let pay = match pay_url {
+                Some(url) => {
+                    view! { <a class="button" href=url target="_blank">{i18n.t("fees.pay_now")}</a> }.into_any()
+                }
+                None => view! { <small>{i18n.t("fees.pay_at_bursary")}</small> }
+                    .into_any(),
+            };
+            view! {
+                <tr>
+                    <td>{invoice.invoice_number}</td>
+                    <td>{format_date(&i18n, &invoice.due_date)}</td>
+                    <td>{i18n.money(invoice.total_amount)}</td>
+                    <td>{i18n.money(invoice.balance)}</td>
+                    <td>{pay}</td>
+                </tr>
+            }
+        })
+        .collect_view();
+
+    view! {
+        <section>
+            <h2>{i18n.t("fees.heading")}</h2>
+            {if empty {
+                view! { <p>{i18n.t("fees.nothing_outstanding")}</p> }.into_any()
+            } else {
+                view! {
+                    <table>
+                        <thead>
+                            <tr>
+                                <th>{i18n.t("fees.invoice")}</th>
+                                <th>{i18n.t("fees.due")}</th>
+                                <th>{i18n.t("fees.total")}</th>
+                                <th>{i18n.t("fees.balance")}</th>
+                                <th></th>
+                            </tr>
+                        </thead>
+                        <tbody>{rows}</tbody>
+                    </table>
+                }
+                .into_any()
+            }}
+        </section>
+    }
+}
+
+#[component]
+fn TeacherThread(student_id: String, teacher: String) -> impl IntoView {
+    let send = ServerAction::<MessageTeacher>::new();
+    let thread = Resource::new(
+        {
+            let student_id = student_id.clone();
+            move || (student_id.clone(), send.version().get())
+        },
+        |(student_id, _)| load_teacher_thread(student_id),
+    );
+
+    view! {
+        <section class="thread">
+            <h2>{format!("Messages with {teacher}")}</h2>
+            <Transition fallback=|| view! { <p>"Loading…"</p> }>
+                {move || Suspend::new(async move {
+                    match thread.await {
+                        Ok(messages) => messages
+                            .into_iter()
+                            .map(|message| {
+                                view! {
+                                    <div class="message" class:mine=message.mine>
+                                        <p>{message.body}</p>
+                                        <small>{message.sent_at}</small>
+                                    </div>
+                                }
+                            })
+                            .collect_view()
+                            .into_any(),
+                        Err(err) => view! { <p class="error">{err.to_string()}</p> }.into_any(),
+                    }
+                })}
+            </Transition>
+            <ActionForm action=send>
+                <input type="hidden" name="student_id" value=student_id/>
+                <textarea name="body" rows="3" maxlength="2000" required></textarea>
+                <button type="submit" disabled=move || send.pending().get()>"Send"</button>
+            </ActionForm>
+            {move || {
+                send.value()
+                    .get()
+                    .and_then(|result| result.err())
+                    .map(|err| view! { <p class="error">{err.to_string()}</p> })
+            }}
+        </section>
+    }
+}
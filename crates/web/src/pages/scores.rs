@@ -0,0 +1,846 @@
+// Copyright (C) 2026 Ahmad Olukotun
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, version 3.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+
+use std::{collections::HashMap, str::FromStr};
+
+use leptos::{
+    prelude::*, server_fn::codec::Json, task::spawn_local, wasm_bindgen::JsCast, web_sys,
+};
+use leptos_meta::Title;
+use serde::{Deserialize, Serialize};
+use shared::{
+    helpers::{
+        api_responses::{ScoreCell, ScoreSheet, ScoreSheetColumn, SessionUser},
+        create_update_dtos::OfflineScoreDto,
+    },
+    models::UserType,
+};
+use surrealdb_types::{Datetime, Decimal};
+
+use crate::offline::{self, QueuedWrite, SyncContext};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SheetOption {
+    pub id: String,
+    pub label: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ScoreSheetOptions {
+    pub class_subjects: Vec<SheetOption>,
+    pub terms: Vec<SheetOption>,
+    pub current_term: Option<String>,
+}
+
+/// A changed cell; `version` is what the client last loaded for it
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ScoreEdit {
+    pub student_id: String,
+    pub assessment_id: String,
+    pub score: Decimal,
+    pub version: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum SaveOutcome {
+    Saved(ScoreCell),
+    /// Someone else changed the score first; carries what is stored now
+    Conflict(ScoreCell),
+    Rejected(String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ScoreSaveResult {
+    pub student_id: String,
+    pub assessment_id: String,
+    pub outcome: SaveOutcome,
+}
+
+/// Class subjects the user may enter scores for, and the current session's terms
+#[server]
+pub async fn load_sheet_options() -> Result<ScoreSheetOptions, ServerFnError> {
+    use db::queries::{AcademicSessionQ, ClassQ, ClassSubjectQ, TermQ};
+    use shared::{
+        helpers::surreal_util::record_key,
+        models::{
+            academic::{Class, Subject},
+            UserType,
+        },
+    };
+    use surrealdb_types::{RecordId, SurrealValue};
+
+    #[derive(SurrealValue)]
+    struct Taught {
+        id: RecordId,
+        class: Class,
+        subject: Subject,
+    }
+
+    #[derive(SurrealValue)]
+    struct Offered {
+        id: RecordId,
+        subject: Subject,
+    }
+
+    let user = ssr::require_score_user().await?;
+    let sdb = db::get_db();
+    let error = |e: db::error::Error| ServerFnError::new(e);
+
+    let mut class_subjects = Vec::new();
+    if user.user_type == UserType::Teacher {
+        for row in ClassSubjectQ
+            .get_by_teacher(sdb, user.user_id.clone())
+            .await
+            .map_err(error)?
+        {
+            if let Ok(taught) = Taught::from_value(row) {
+                class_subjects.push(SheetOption {
+                    id: record_key(&taught.id),
+                    label: format!(
+                        "{} {}",
+                        taught.class.class_name, taught.subject.subject_name
+                    ),
+                });
+            }
+        }
+    } else {
+        for class in ClassQ
+            .get_by_school(sdb, user.school_id.clone())
+            .await
+            .map_err(error)?
+        {
+            let Some(class_id) = class.id.as_ref() else {
+                continue;
+            };
+            for row in ClassSubjectQ
+                .get_by_class(sdb, record_key(class_id))
+                .await
+                .map_err(error)?
+            {
+                if let Ok(offered) = Offered::from_value(row) {
+                    class_subjects.push(SheetOption {
+                        id: record_key(&offered.id),
+                        label: format!("{} {}", class.class_name, offered.subject.subject_name),
+                    });
+                }
+            }
+        }
+    }
+
+    let session = AcademicSessionQ
+        .get_current(sdb, user.school_id)
+        .await
+        .map_err(error)?;
+    let terms = match session.and_then(|s| s.id) {
+        Some(id) => TermQ
+            .get_by_session(sdb, record_key(&id))
+            .await
+            .map_err(error)?,
+        None => Vec::new(),
+    };
+    let current_term = terms
+        .iter()
+        .find(|term| term.is_current)
+        .and_then(|term| term.id.as_ref().map(record_key));
+    let terms = terms
+        .into_iter()
+        .filter_map(|term| {
+            Some(SheetOption {
+                label: term
+                    .term_name
+                    .unwrap_or_else(|| format!("Term {}", term.term_number)),
+                id: record_key(term.id.as_ref()?),
+            })
+        })
+        .collect();
+
+    Ok(ScoreSheetOptions {
+        class_subjects,
+        terms,
+        current_term,
+    })
+}
+
+#[server]
+pub async fn load_score_sheet(
+    class_subject_id: String,
+    term_id: String,
+) -> Result<ScoreSheet, ServerFnError> {
+    let user = ssr::require_score_user().await?;
+    ssr::sheet_access(&user, &class_subject_id).await?;
+    domain::scoring::ScoreService
+        .sheet(db::get_db(), class_subject_id, term_id)
+        .await
+        .map_err(ServerFnError::new)
+}
+
+/// Save edited cells one by one so a conflict or bad value only affects its own cell
+#[server(input = Json)]
+pub async fn save_scores(
+    class_subject_id: String,
+    term_id: String,
+    edits: Vec<ScoreEdit>,
+) -> Result<Vec<ScoreSaveResult>, ServerFnError> {
+    use std::collections::HashSet;
+
+    use db::queries::{AssessmentQ, StudentScoreQ};
+    use domain::{error::Error, scoring::ScoreService};
+    use shared::helpers::{create_update_dtos::EnterScoreDto, surreal_util::record_key};
+    use surrealdb_types::RecordId;
+
+    let user = ssr::require_score_user().await?;
+    let class_subject = ssr::sheet_access(&user, &class_subject_id).await?;
+    let sdb = db::get_db();
+
+    let on_sheet: HashSet<String> = AssessmentQ
+        .get_by_class_subject_term(
+            sdb,
+            record_key(&class_subject.class_id),
+            record_key(&class_subject.subject_id),
+            term_id,
+        )
+        .await
+        .map_err(ServerFnError::new)?
+        .iter()
+        .filter_map(|assessment| assessment.id.as_ref().map(record_key))
+        .collect();
+
+    let mut results = Vec::with_capacity(edits.len());
+    for edit in edits {
+        let outcome = if !on_sheet.contains(&edit.assessment_id) {
+            SaveOutcome::Rejected("assessment is not on this sheet".into())
+        } else {
+            let data = EnterScoreDto {
+                assessment_id: RecordId::new("assessments", edit.assessment_id.as_str()),
+                student_id: RecordId::new("students", edit.student_id.as_str()),
+                score_obtained: edit.score,
+                remarks: None,
+            };
+            match ScoreService
+                .enter_unchanged(sdb, data, edit.version, user.user_id.clone())
+                .await
+            {
+                Ok(score) => SaveOutcome::Saved(ssr::cell(score)),
+                Err(Error::Conflict(_)) => {
+                    let current = StudentScoreQ
+                        .get(sdb, edit.assessment_id.clone(), edit.student_id.clone())
+                        .await
+                        .map_err(ServerFnError::new)?;
+                    SaveOutcome::Conflict(current.map(ssr::cell).unwrap_or_default())
+                }
+                Err(err) => SaveOutcome::Rejected(err.to_string()),
+            }
+        };
+        results.push(ScoreSaveResult {
+            student_id: edit.student_id,
+            assessment_id: edit.assessment_id,
+            outcome,
+        });
+    }
+    Ok(results)
+}
+
+/// Clear verification on a column so teachers can edit it again; admins only
+#[server]
+pub async fn unlock_scores(assessment_id: String) -> Result<usize, ServerFnError> {
+    use domain::scoring::ScoreService;
+
+    let user = ssr::require_score_user().await?;
+    ScoreService
+        .unlock(db::get_db(), &user, assessment_id)
+        .await
+        .map_err(ServerFnError::new)
+}
+
+/// Change a verified score; admins only, logged with the reason
+#[server(input = Json)]
+pub async fn override_score(
+    student_id: String,
+    assessment_id: String,
+    score: Decimal,
+    reason: Option<String>,
+) -> Result<ScoreCell, ServerFnError> {
+    use domain::scoring::ScoreService;
+    use shared::helpers::create_update_dtos::EnterScoreDto;
+    use surrealdb_types::RecordId;
+
+    let user = ssr::require_score_user().await?;
+    let data = EnterScoreDto {
+        assessment_id: RecordId::new("assessments", assessment_id.as_str()),
+        student_id: RecordId::new("students", student_id.as_str()),
+        score_obtained: score,
+        remarks: None,
+    };
+    ScoreService
+        .override_score(db::get_db(), &user, data, reason)
+        .await
+        .map(ssr::cell)
+        .map_err(ServerFnError::new)
+}
+
+#[cfg(feature = "ssr")]
+pub(crate) mod ssr {
+    use db::queries::{ClassQ, ClassSubjectQ};
+    use domain::scoring::score_version;
+    use leptos::prelude::*;
+    use shared::{
+        helpers::{
+            api_responses::{ScoreCell, SessionUser},
+            surreal_util::record_key,
+        },
+        models::{academic::ClassSubject, assessments_nd_grading::StudentScore, UserType},
+    };
+
+    pub async fn require_score_user() -> Result<SessionUser, ServerFnError> {
+        crate::auth::ssr::require_role(&[UserType::SuperAdmin, UserType::Admin, UserType::Teacher])
+            .await
+    }
+
+    /// The class subject, if the user may enter its scores: admins for any in
+    /// their school, teachers only for subjects assigned to them
+    pub async fn sheet_access(
+        user: &SessionUser,
+        class_subject_id: &str,
+    ) -> Result<ClassSubject, ServerFnError> {
+        let sdb = db::get_db();
+        let class_subject = ClassSubjectQ
+            .get_by_id(sdb, class_subject_id.to_string())
+            .await
+            .map_err(ServerFnError::new)?
+            .ok_or_else(|| ServerFnError::new("class subject not found"))?;
+        let class = ClassQ
+            .get_by_id(sdb, record_key(&class_subject.class_id))
+            .await
+            .map_err(ServerFnError::new)?
+            .ok_or_else(|| ServerFnError::new("class not found"))?;
+
+        let in_school = record_key(&class.school_id) == user.school_id;
+        let teaches = class_subject
+            .teacher_id
+            .as_ref()
+            .is_some_and(|id| record_key(id) == user.user_id);
+        if !in_school || (user.user_type == UserType::Teacher && !teaches) {
+            return Err(ServerFnError::new("not allowed"));
+        }
+        Ok(class_subject)
+    }
+
+    pub fn cell(score: StudentScore) -> ScoreCell {
+        ScoreCell {
+            version: Some(score_version(&score)),
+            score: score.score_obtained,
+            verified: score.verified,
+        }
+    }
+}
+
+/// A typed score: blank is "no score", anything else must lie in 0..=max
+fn parse_score(text: &str, max_score: Decimal) -> Result<Option<Decimal>, String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Ok(None);
+    }
+    let score = Decimal::from_str(text).map_err(|_| format!("{text} is not a number"))?;
+    if score < Decimal::ZERO {
+        return Err("Scores can't be negative".into());
+    }
+    if score > max_score {
+        return Err(format!("Maximum is {max_score}"));
+    }
+    Ok(Some(score))
+}
+
+fn score_text(score: Option<Decimal>) -> String {
+    score.map(|s| s.normalize().to_string()).unwrap_or_default()
+}
+
+fn cell_id(row: usize, col: usize) -> String {
+    format!("score-{row}-{col}")
+}
+
+fn focus_cell(row: usize, col: usize) {
+    let input = document()
+        .get_element_by_id(&cell_id(row, col))
+        .and_then(|el| el.dyn_into::<web_sys::HtmlInputElement>().ok());
+    if let Some(input) = input {
+        let _ = input.focus();
+        input.select();
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum CellStatus {
+    Idle,
+    Saving,
+    Saved,
+    /// Saved on the device while offline, waiting in the outbox
+    Queued,
+    Invalid(String),
+    Conflict(String),
+    Failed(String),
+}
+
+/// Editable state for one grid cell
+#[derive(Clone, Copy)]
+struct CellState {
+    text: RwSignal<String>,
+    stored: RwSignal<ScoreCell>,
+    status: RwSignal<CellStatus>,
+}
+
+impl CellState {
+    fn new(cell: ScoreCell) -> Self {
+        Self {
+            text: RwSignal::new(score_text(cell.score)),
+            stored: RwSignal::new(cell),
+            status: RwSignal::new(CellStatus::Idle),
+        }
+    }
+
+    /// Validate the typed text and return an edit if it differs from what is stored
+    fn edit(self, student_id: &str, column: &ScoreSheetColumn) -> Option<ScoreEdit> {
+        let stored = self.stored.get_untracked();
+        match parse_score(&self.text.get_untracked(), column.max_score) {
+            Err(message) => {
+                self.status.set(CellStatus::Invalid(message));
+                None
+            }
+            Ok(None) if stored.score.is_some() => {
+                self.status
+                    .set(CellStatus::Invalid("Scores can't be removed".into()));
+                None
+            }
+            Ok(None) => {
+                self.status.set(CellStatus::Idle);
+                None
+            }
+            Ok(Some(score)) if Some(score) == stored.score => {
+                self.status.set(CellStatus::Idle);
+                None
+            }
+            Ok(Some(score)) => {
+                self.status.set(CellStatus::Saving);
+                Some(ScoreEdit {
+                    student_id: student_id.to_string(),
+                    assessment_id: column.assessment_id.clone(),
+                    score,
+                    version: stored.version,
+                })
+            }
+        }
+    }
+
+    fn apply(self, outcome: SaveOutcome) {
+        match outcome {
+            SaveOutcome::Saved(cell) => {
+                self.stored.set(cell);
+                self.status.set(CellStatus::Saved);
+            }
+            SaveOutcome::Conflict(cell) => {
+                let mine = self.text.get_untracked();
+                self.text.set(score_text(cell.score));
+                self.stored.set(cell);
+                self.status.set(CellStatus::Conflict(format!(
+                    "Changed by someone else while you typed {mine}; their value is shown"
+                )));
+            }
+            SaveOutcome::Rejected(message) => self.status.set(CellStatus::Failed(message)),
+        }
+    }
+}
+
+/// Spreadsheet-style score entry: students down, assessments across
+#[component]
+pub fn ScoresPage() -> impl IntoView {
+    let options = Resource::new(
+        || (),
+        |_| offline::cached("score-sheet-options".into(), load_sheet_options()),
+    );
+    let class_subject = RwSignal::new(String::new());
+    let term = RwSignal::new(String::new());
+    let sheet = Resource::new(
+        move || (class_subject.get(), term.get()),
+        |(class_subject, term)| async move {
+            if class_subject.is_empty() || term.is_empty() {
+                Ok(None)
+            } else {
+                let name = format!("score-sheet-{class_subject}-{term}");
+                offline::cached(name, load_score_sheet(class_subject, term))
+                    .await
+                    .map(Some)
+            }
+        },
+    );
+
+    view! {
+        <Title text="Scores"/>
+        <h1>"Score entry"</h1>
+        <Suspense fallback=|| view! { <p>"Loading…"</p> }>
+            {move || Suspend::new(async move {
+                match options.await {
+                    Ok(options) => {
+                        if term.get_untracked().is_empty() {
+                            term.set(options.current_term.clone().unwrap_or_default());
+                        }
+                        let subjects = options
+                            .class_subjects
+                            .into_iter()
+                            .map(|o| view! { <option value=o.id>{o.label}</option> })
+                            .collect_view();
+                        let terms = options
+                            .terms
+                            .into_iter()
+                            .map(|o| {
+                                let selected = o.id == term.get_untracked();
+                                view! { <option value=o.id selected=selected>{o.label}</option> }
+                            })
+                            .collect_view();
+                        view! {
+                            <div class="register-filters">
+                                <label>
+                                    "Class subject"
+                                    <select on:change=move |ev| class_subject.set(event_target_value(&ev))>
+                                        <option value="">"Choose a subject"</option>
+                                        {subjects}
+                                    </select>
+                                </label>
+                                <label>
+                                    "Term"
+                                    <select on:change=move |ev| term.set(event_target_value(&ev))>
+                                        {terms}
+                                    </select>
+                                </label>
+                            </div>
+                        }
+                        .into_any()
+                    }
+                    Err(err) => view! { <p class="error">{err.to_string()}</p> }.into_any(),
+                }
+            })}
+        </Suspense>
+        <Transition fallback=|| view! { <p>"Loading…"</p> }>
+            {move || Suspend::new(async move {
+                match sheet.await {
+                    Ok(Some(sheet)) if sheet.columns.is_empty() => {
+                        view! { <p>"No assessments have been set up for this subject and term."</p> }
+                            .into_any()
+                    }
+                    Ok(Some(sheet)) => view! { <ScoreGrid sheet/> }.into_any(),
+                    Ok(None) => view! { <p>"Choose a subject to enter scores."</p> }.into_any(),
+                    Err(err) => view! { <p class="error">{err.to_string()}</p> }.into_any(),
+                }
+            })}
+        </Transition>
+    }
+}
+
+#[component]
+fn ScoreGrid(sheet: ScoreSheet) -> impl IntoView {
+    let ScoreSheet {
+        class_subject_id,
+        term_id,
+        columns,
+        rows,
+        published,
+    } = sheet;
+    let cells: Vec<Vec<CellState>> = rows
+        .iter()
+        .map(|row| row.cells.iter().cloned().map(CellState::new).collect())
+        .collect();
+    let row_count = rows.len();
+    let col_count = columns.len();
+
+    let index: HashMap<(String, String), CellState> = rows
+        .iter()
+        .zip(&cells)
+        .flat_map(|(row, states)| {
+            columns.iter().zip(states).map(|(column, state)| {
+                (
+                    (row.student_id.clone(), column.assessment_id.clone()),
+                    *state,
+                )
+            })
+        })
+        .collect();
+    let index = StoredValue::new(index);
+    let student_ids = StoredValue::new(
+        rows.iter()
+            .map(|row| row.student_id.clone())
+            .collect::<Vec<_>>(),
+    );
+    let columns = StoredValue::new(columns);
+    let cells = StoredValue::new(cells);
+    let ids = StoredValue::new((class_subject_id, term_id));
+    let sync = expect_context::<SyncContext>();
+    let admin = matches!(
+        expect_context::<SessionUser>().user_type,
+        UserType::SuperAdmin | UserType::Admin
+    );
+    let unlock_error = RwSignal::new(None::<String>);
+
+    // Offline edits go to the outbox and are replayed against `updated_at` on sync
+    let queue = move |class_subject_id: String, edits: Vec<ScoreEdit>| async move {
+        let recorded_at = Datetime::now();
+        let offline_edits = edits
+            .iter()
+            .map(|edit| OfflineScoreDto {
+                assessment_id: edit.assessment_id.clone(),
+                student_id: edit.student_id.clone(),
+                score: edit.score,
+                recorded_at,
+            })
+            .collect();
+        let status = match sync
+            .queue(QueuedWrite::Scores {
+                class_subject_id,
+                edits: offline_edits,
+            })
+            .await
+        {
+            Ok(()) => CellStatus::Queued,
+            Err(err) => CellStatus::Failed(err),
+        };
+        index.with_value(|index| {
+            for edit in edits {
+                if let Some(cell) = index.get(&(edit.student_id, edit.assessment_id)) {
+                    cell.status.set(status.clone());
+                }
+            }
+        });
+    };
+
+    let save = move |edits: Vec<ScoreEdit>| {
+        if edits.is_empty() {
+            return;
+        }
+        let (class_subject_id, term_id) = ids.get_value();
+        spawn_local(async move {
+            match save_scores(class_subject_id.clone(), term_id, edits.clone()).await {
+                Ok(results) => index.with_value(|index| {
+                    for result in results {
+                        if let Some(cell) = index.get(&(result.student_id, result.assessment_id)) {
+                            cell.apply(result.outcome);
+                        }
+                    }
+                }),
+                Err(err) if offline::is_network_error(&err) => queue(class_subject_id, edits).await,
+                Err(err) => index.with_value(|index| {
+                    for edit in edits {
+                        if let Some(cell) = index.get(&(edit.student_id, edit.assessment_id)) {
+                            cell.status.set(CellStatus::Failed(err.to_string()));
+                        }
+                    }
+                }),
+            }
+        });
+    };
+    let edit_at = move |row: usize, col: usize| {
+        let state = cells.with_value(|cells| cells[row][col]);
+        let student_id = student_ids.with_value(|ids| ids[row].clone());
+        columns.with_value(|columns| state.edit(&student_id, &columns[col]))
+    };
+    let verified = move |row: usize, col: usize| {
+        cells.with_value(|cells| cells[row][col].stored.get().verified)
+    };
+    let locked = move |row: usize, col: usize| published || verified(row, col);
+    // Admins may still change a verified cell, but only as a logged override
+    let editable = move |row: usize, col: usize| !published && (admin || !verified(row, col));
+
+    let override_at = move |row: usize, col: usize| {
+        let Some(edit) = edit_at(row, col) else {
+            return;
+        };
+        let state = cells.with_value(|cells| cells[row][col]);
+        let reason = window()
+            .prompt_with_message("Reason for changing a verified score")
+            .ok()
+            .flatten();
+        let Some(reason) = reason else {
+            state
+                .text
+                .set(score_text(state.stored.get_untracked().score));
+            state.status.set(CellStatus::Idle);
+            return;
+        };
+        let reason = Some(reason.trim().to_string()).filter(|reason| !reason.is_empty());
+        spawn_local(async move {
+            match override_score(edit.student_id, edit.assessment_id, edit.score, reason).await {
+                Ok(cell) => state.apply(SaveOutcome::Saved(cell)),
+                Err(err) => state.status.set(CellStatus::Failed(err.to_string())),
+            }
+        });
+    };
+    let unlock = move |col: usize| {
+        let assessment_id = columns.with_value(|columns| columns[col].assessment_id.clone());
+        spawn_local(async move {
+            match unlock_scores(assessment_id).await {
+                Ok(_) => {
+                    unlock_error.set(None);
+                    cells.with_value(|cells| {
+                        for row in cells {
+                            row[col].stored.update(|cell| cell.verified = false);
+                        }
+                    });
+                }
+                Err(err) => unlock_error.set(Some(err.to_string())),
+            }
+        });
+    };
+
+    // Spreadsheet copies arrive as tab-separated columns and newline-separated rows
+    let paste = move |row: usize, col: usize, text: String| {
+        let mut edits = Vec::new();
+        for (dr, line) in text.lines().enumerate() {
+            for (dc, value) in line.split('\t').enumerate() {
+                let (r, c) = (row + dr, col + dc);
+                if r >= row_count || c >= col_count || locked(r, c) {
+                    continue;
+                }
+                cells.with_value(|cells| cells[r][c].text.set(value.trim().to_string()));
+                edits.extend(edit_at(r, c));
+            }
+        }
+        save(edits);
+    };
+
+    let header = columns.with_value(|columns| {
+        columns
+            .iter()
+            .enumerate()
+            .map(|(c, column)| {
+                let any_verified = move || (0..row_count).any(|r| verified(r, c));
+                view! {
+                    <th>
+                        {column.label.clone()}
+                        <small>{format!("/{}", score_text(Some(column.max_score)))}</small>
+                        <Show when=move || admin && !published && any_verified()>
+                            <button
+                                type="button"
+                                class="unlock"
+                                title="Let teachers edit this column's verified scores again"
+                                on:click=move |_| unlock(c)
+                            >
+                                "Unlock"
+                            </button>
+                        </Show>
+                    </th>
+                }
+            })
+            .collect_view()
+    });
+    let body = rows
+        .into_iter()
+        .enumerate()
+        .map(|(r, row)| {
+            let inputs = (0..col_count)
+                .map(|c| {
+                    let state = cells.with_value(|cells| cells[r][c]);
+                    let title = move || match state.status.get() {
+                        CellStatus::Invalid(m) | CellStatus::Conflict(m) | CellStatus::Failed(m) => m,
+                        CellStatus::Queued => "Saved on this device; syncs when you reconnect".into(),
+                        _ if locked(r, c) && editable(r, c) => {
+                            "Verified; changing it is logged as an override".into()
+                        }
+                        _ if locked(r, c) => "Verified; ask an admin to unlock".into(),
+                        _ => String::new(),
+                    };
+                    view! {
+                        <td
+                            class="score-cell"
+                            class:locked=move || locked(r, c)
+                            class:saving=move || state.status.get() == CellStatus::Saving
+                            class:saved=move || state.status.get() == CellStatus::Saved
+                            class:queued=move || state.status.get() == CellStatus::Queued
+                            class:invalid=move || {
+                                matches!(state.status.get(), CellStatus::Invalid(_) | CellStatus::Failed(_))
+                            }
+                            class:conflict=move || matches!(state.status.get(), CellStatus::Conflict(_))
+                            title=title
+                        >
+                            <input
+                                id=cell_id(r, c)
+                                type="text"
+                                inputmode="decimal"
+                                autocomplete="off"
+                                disabled=move || !editable(r, c)
+                                prop:value=move || state.text.get()
+                                on:input=move |ev| state.text.set(event_target_value(&ev))
+                                on:change=move |_| {
+                                    if verified(r, c) {
+                                        override_at(r, c);
+                                    } else {
+                                        save(edit_at(r, c).into_iter().collect());
+                                    }
+                                }
+                                on:keydown=move |ev| {
+                                    let target = match ev.key().as_str() {
+                                        "ArrowDown" | "Enter" => (r + 1 < row_count).then(|| (r + 1, c)),
+                                        "ArrowUp" => r.checked_sub(1).map(|r| (r, c)),
+                                        "ArrowRight" => (c + 1 < col_count).then(|| (r, c + 1)),
+                                        "ArrowLeft" => c.checked_sub(1).map(|c| (r, c)),
+                                        _ => return,
+                                    };
+                                    ev.prevent_default();
+                                    if let Some((row, col)) = target {
+                                        focus_cell(row, col);
+                                    }
+                                }
+                                on:paste=move |ev| {
+                                    let text = ev
+                                        .clipboard_data()
+                                        .and_then(|data| data.get_data("text").ok())
+                                        .unwrap_or_default();
+                                    if text.contains(['\t', '\n']) {
+                                        ev.prevent_default();
+                                        paste(r, c, text);
+                                    }
+                                }
+                            />
+                        </td>
+                    }
+                })
+                .collect_view();
+            view! {
+                <tr>
+                    <th scope="row">
+                        {row.name}
+                        <small>{row.admission_number}</small>
+                    </th>
+                    {inputs}
+                </tr>
+            }
+        })
+        .collect_view();
+
+    view! {
+        {published
+            .then(|| {
+                view! {
+                    <p class="notice">"Report cards for this term are published, so scores are read-only."</p>
+                }
+            })}
+        {move || unlock_error.get().map(|message| view! { <p class="error">{message}</p> })}
+        <p class="hint">
+            "Scores save as you leave each cell. Use the arrow keys or Enter to move, "
+            "and paste a block copied from a spreadsheet into the top-left cell."
+        </p>
+        <div class="score-grid">
+            <table>
+                <thead>
+                    <tr>
+                        <th>"Student"</th>
+                        {header}
+                    </tr>
+                </thead>
+                <tbody>{body}</tbody>
+            </table>
+        </div>
+    }
+}